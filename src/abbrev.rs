@@ -0,0 +1,27 @@
+// Abbreviations: short words that expand as you type ([abbreviations] in
+// config.toml)
+//
+//   [abbreviations]
+//   btw = "by the way"
+//   ddate = "{{date}}"
+//
+// An abbreviation expands when the character after it isn't part of a
+// word - a space, punctuation or Enter - so "btw," becomes "by the way,".
+// Ctrl-V before that character types it without expanding.
+
+use chrono::Local;
+use std::collections::BTreeMap;
+
+// What 'word' expands to, with {{date}}, {{iso_date}} and {{time}} filled
+// in. Expansions stay on one line, so newlines become spaces.
+pub fn expand(abbreviations: &BTreeMap<String, String>, word: &str) -> Option<String> {
+    let expansion = abbreviations.get(word)?;
+    let now = Local::now();
+    Some(
+        expansion
+            .replace("{{date}}", &now.format("%A, %B %d, %Y").to_string())
+            .replace("{{iso_date}}", &now.format("%Y-%m-%d").to_string())
+            .replace("{{time}}", &now.format("%H:%M").to_string())
+            .replace('\n', " "),
+    )
+}
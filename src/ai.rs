@@ -2,11 +2,57 @@ use chrono::{DateTime, Local, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
 use crate::config::Config;
-use reqwest::blocking::Client;
+use crate::hooks;
+use crate::mood::{self, MoodReading};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::StatusCode;
 use std::error::Error;
 
+// What river summarize --week asks for
+const SUMMARY_INSTRUCTIONS: &str = "Here are someone's journal entries from the past week. \
+Write a short weekly review of them in markdown, addressed to the writer, with three sections: \
+\"## Themes\" (what kept coming up), \"## Decisions\" (choices made or committed to) and \
+\"## Open questions\" (things still unresolved). Use bullet points, keep each to a sentence, \
+and don't add a title or anything outside those sections. Leave a section empty rather than inventing things.";
+
+// What :title asks for
+const TITLE_INSTRUCTIONS: &str = "Give this journal entry a title: one line of at most ten words \
+that says what it's about. Reply with the title only.";
+
+// What mood_tracking = "ai" asks for
+const MOOD_INSTRUCTIONS: &str = "Read this journal entry and reply with only a JSON object: \
+{\"mood\": one of \"rough\", \"low\", \"okay\", \"good\" or \"great\", \
+\"themes\": up to three one or two word themes, lowercase}.";
+
+// Tries per request before giving up
+const MAX_ATTEMPTS: u32 = 3;
+// Longest we'll wait between tries, whatever Retry-After says
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+// After a failure the editor doesn't try again at startup for this long
+const FAILURE_BACKOFF_HOURS: i64 = 6;
+
+// The request sent to the model unless ai_prompt_template names a file.
+// {{...}} placeholders are filled in by fill_template.
+const DEFAULT_TEMPLATE: &str = "Based on these recent journal entries, generate {{count}} unique daily prompts. Each prompt should be:
+- Personalized based on themes you notice
+- Encouraging deeper reflection
+- Different from each other
+- About 10-20 words
+
+Recent entries:
+{{notes_summary}}
+
+Return a JSON array with exactly {{count}} objects, each having:
+- \"date\": \"YYYY-MM-DD\" (starting from {{start}})
+- \"prompt\": \"The prompt text\"
+- \"theme\": \"Brief theme (1-3 words)\"
+- \"context\": \"Optional brief explanation\"";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PromptCache {
     #[serde(with = "chrono::serde::ts_seconds")]
@@ -21,19 +67,27 @@ pub struct DailyPrompt {
     pub context: Option<String>,
 }
 
+// Both Anthropic's and OpenAI's chat APIs take a list of these
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String, // "user" or "assistant"
+    pub content: String,
+}
+
+impl Message {
+    pub fn user(content: &str) -> Self {
+        Message { role: "user".to_string(), content: content.to_string() }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicRequest {
     model: String,
     max_tokens: u32,
+    temperature: f32,
     messages: Vec<Message>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Message {
-    role: String,
-    content: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicResponse {
     content: Vec<Content>,
@@ -44,50 +98,282 @@ struct Content {
     text: String,
 }
 
-pub struct PromptGenerator {
+// OpenAI's chat completions format, which OpenRouter, Azure and most
+// self-hosted servers also speak
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiRequest {
+    // Azure chooses the model by deployment, so it's left out there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    max_tokens: u32,
+    temperature: f32,
+    messages: Vec<Message>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+// Something that can reply to a conversation. Send lets a provider move
+// to the background thread that generates prompts.
+pub trait Provider: Send {
+    fn complete(&self, messages: &[Message]) -> Result<String, Box<dyn Error>>;
+}
+
+struct Anthropic {
+    client: Client,
     api_key: String,
+    base_url: String,
+    model: String,
+    max_tokens: u32,
+    temperature: f32,
+}
+
+impl Provider for Anthropic {
+    fn complete(&self, messages: &[Message]) -> Result<String, Box<dyn Error>> {
+        let request = AnthropicRequest {
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            messages: messages.to_vec(),
+        };
+        
+        let response = send_with_retry(|| {
+            self.client
+                .post(format!("{}/messages", self.base_url))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01")
+                .header("content-type", "application/json")
+                .json(&request)
+        })?;
+        
+        let api_response: AnthropicResponse = response.json()?;
+        let content = api_response.content.into_iter().next().ok_or("No response content")?;
+        Ok(content.text)
+    }
+}
+
+struct OpenAi {
+    client: Client,
+    api_key: String,
+    url: String,           // Full chat completions URL
+    model: Option<String>, // None for Azure
+    azure: bool,           // Azure wants an api-key header instead of a bearer token
+    max_tokens: u32,
+    temperature: f32,
+}
+
+impl Provider for OpenAi {
+    fn complete(&self, messages: &[Message]) -> Result<String, Box<dyn Error>> {
+        let request = OpenAiRequest {
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            messages: messages.to_vec(),
+        };
+        
+        let response = send_with_retry(|| {
+            let builder = self.client.post(&self.url).json(&request);
+            if self.azure {
+                builder.header("api-key", &self.api_key)
+            } else {
+                builder.bearer_auth(&self.api_key)
+            }
+        })?;
+        
+        let api_response: OpenAiResponse = response.json()?;
+        let choice = api_response.choices.into_iter().next().ok_or("No response content")?;
+        Ok(choice.message.content)
+    }
+}
+
+// Send a request, trying again with exponential backoff (1s, then 2s) when
+// the API is rate limiting (429), having trouble (5xx) or can't be reached.
+// A Retry-After header from the server wins over our own delay.
+// 'request' builds the request afresh for each try, since sending uses it up.
+fn send_with_retry(request: impl Fn() -> RequestBuilder) -> Result<Response, Box<dyn Error>> {
+    let mut delay = Duration::from_secs(1);
+    let mut attempt = 1;
+    loop {
+        let last_try = attempt == MAX_ATTEMPTS;
+        match request().send() {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                if !retryable || last_try {
+                    let error_text = response.text().unwrap_or_default();
+                    return Err(format!("API request failed ({}): {}", status, error_text.trim()).into());
+                }
+                thread::sleep(retry_after(&response).unwrap_or(delay).min(MAX_RETRY_WAIT));
+            }
+            Err(e) if (e.is_timeout() || e.is_connect()) && !last_try => thread::sleep(delay),
+            Err(e) => return Err(e.into()),
+        }
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+// Retry-After in seconds (the HTTP date form isn't worth supporting here)
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get("retry-after")?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+// The provider picked by ai_provider, with ai_base_url and ai_model
+// filled in from each provider's defaults when they're left empty
+pub fn provider_from_config(config: &Config) -> Result<Box<dyn Provider>, Box<dyn Error>> {
+    let api_key = |var: &str| find_api_key(config, var);
+    let base_url = |default: &str| {
+        let url = if config.ai_base_url.is_empty() { default } else { &config.ai_base_url };
+        url.trim_end_matches('/').to_string()
+    };
+    let model = |default: &str| {
+        if config.ai_model.is_empty() { default.to_string() } else { config.ai_model.clone() }
+    };
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.ai_timeout_seconds))
+        .build()?;
+    
+    match config.ai_provider.as_str() {
+        "anthropic" => Ok(Box::new(Anthropic {
+            client,
+            api_key: api_key("ANTHROPIC_API_KEY")?,
+            base_url: base_url("https://api.anthropic.com/v1"),
+            model: model("claude-3-haiku-20240307"),
+            max_tokens: config.ai_max_tokens,
+            temperature: config.ai_temperature,
+        })),
+        "openai" => Ok(Box::new(OpenAi {
+            client,
+            api_key: api_key("OPENAI_API_KEY")?,
+            url: format!("{}/chat/completions", base_url("https://api.openai.com/v1")),
+            model: Some(model("gpt-4o-mini")),
+            azure: false,
+            max_tokens: config.ai_max_tokens,
+            temperature: config.ai_temperature,
+        })),
+        "azure" => {
+            if config.ai_base_url.is_empty() {
+                return Err("ai_base_url must be set to the Azure deployment URL".into());
+            }
+            Ok(Box::new(OpenAi {
+                client,
+                api_key: api_key("AZURE_OPENAI_API_KEY")?,
+                url: config.ai_base_url.clone(),
+                model: None,
+                azure: true,
+                max_tokens: config.ai_max_tokens,
+                temperature: config.ai_temperature,
+            }))
+        }
+        other => Err(format!("Unknown ai_provider \"{}\" (expected anthropic, openai or azure)", other).into()),
+    }
+}
+
+// The API key, from the first of these that has one:
+// 1. the provider's environment variable (ANTHROPIC_API_KEY, ...)
+// 2. the output of api_key_command
+// 3. the api_key file in the config directory
+fn find_api_key(config: &Config, var: &str) -> Result<String, Box<dyn Error>> {
+    if let Ok(key) = std::env::var(var) {
+        if !key.trim().is_empty() {
+            return Ok(key.trim().to_string());
+        }
+    }
+    
+    if !config.api_key_command.is_empty() {
+        let output = hooks::shell_command(&config.api_key_command).output()
+            .map_err(|e| format!("Couldn't run api_key_command: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(format!("api_key_command failed ({}) {}", output.status, stderr).trim_end().into());
+        }
+        let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if key.is_empty() {
+            return Err("api_key_command printed nothing".into());
+        }
+        return Ok(key);
+    }
+    
+    let path = api_key_path();
+    if path.exists() {
+        check_key_permissions(&path)?;
+        let key = fs::read_to_string(&path)?.trim().to_string();
+        if !key.is_empty() {
+            return Ok(key);
+        }
+    }
+    
+    Err(format!("No API key: set {}, api_key_command, or put the key in {}", var, path.display()).into())
+}
+
+fn api_key_path() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("api_key");
+    path
+}
+
+// A key file others can read is as good as published, so refuse to use it
+#[cfg(unix)]
+fn check_key_permissions(path: &Path) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(format!("{} can be read by other users; run chmod 600 on it", path.display()).into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_key_permissions(_path: &Path) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+pub struct PromptGenerator {
+    provider: Box<dyn Provider>,
+    system_prompt: String,
+    template: String,
     cache_path: PathBuf,
     notes_dir: PathBuf,
 }
 
 impl PromptGenerator {
     pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
-        // Get API key from environment variable
-        let api_key = std::env::var("ANTHROPIC_API_KEY")
-            .map_err(|_| "ANTHROPIC_API_KEY environment variable not set")?;
-        
+        let provider = provider_from_config(config)?;
         let cache_path = Self::get_cache_path(config);
         let notes_dir = PathBuf::from(&config.daily_notes_dir);
+        let template = if config.ai_prompt_template.is_empty() {
+            DEFAULT_TEMPLATE.to_string()
+        } else {
+            fs::read_to_string(&config.ai_prompt_template)
+                .map_err(|e| format!("Couldn't read {}: {}", config.ai_prompt_template, e))?
+        };
         
         Ok(PromptGenerator {
-            api_key,
+            provider,
+            system_prompt: config.ai_system_prompt.clone(),
+            template,
             cache_path,
             notes_dir,
         })
     }
     
-    fn get_cache_path(_config: &Config) -> PathBuf {
+    pub fn get_cache_path(_config: &Config) -> PathBuf {
         let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
         path.push("river");
         path.push("prompt_cache.json");
         path
     }
     
-    pub fn load_cached_prompt(&self, date: &NaiveDate) -> Option<DailyPrompt> {
-        // Try to load from cache
-        if let Ok(contents) = fs::read_to_string(&self.cache_path) {
-            if let Ok(cache) = serde_json::from_str::<PromptCache>(&contents) {
-                // Check if cache is less than 7 days old
-                let age = Utc::now().signed_duration_since(cache.generated_at);
-                if age.num_days() < 7 {
-                    let date_str = date.format("%Y-%m-%d").to_string();
-                    return cache.prompts.get(&date_str).cloned();
-                }
-            }
-        }
-        None
-    }
-    
     pub fn generate_prompts(&self) -> Result<(), Box<dyn Error>> {
         println!("Analyzing recent notes...");
         
@@ -101,24 +387,122 @@ impl PromptGenerator {
         
         println!("Found {} recent notes. Generating personalized prompts...", recent_notes.len());
         
-        // Analyze notes and generate prompts
-        let prompts = self.analyze_and_generate(recent_notes)?;
+        // Analyze notes and generate prompts, starting tomorrow
+        let tomorrow = Local::now().date_naive() + chrono::Duration::days(1);
+        let prompts = self.analyze_and_generate(recent_notes, tomorrow, 7)?;
         
         // Save to cache
-        let cache = PromptCache {
+        self.save_cache(&PromptCache {
             generated_at: Utc::now(),
             prompts,
-        };
+        })?;
+        
+        println!("Successfully generated prompts for the next 7 days!");
+        Ok(())
+    }
+    
+    // A markdown review of the last seven days of notes
+    pub fn summarize_week(&self) -> Result<String, Box<dyn Error>> {
+        let notes = self.collect_recent_notes(7)?;
+        if notes.is_empty() {
+            return Err("No notes from the last 7 days to summarize".into());
+        }
         
+        // collect_recent_notes starts from today; oldest first reads better
+        let entries = notes.iter().rev()
+            .map(|(date, content)| format!("Entry for {}:\n{}", date, content.trim()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let reply = self.provider.complete(&[Message::user(&format!("{}\n\n{}", SUMMARY_INSTRUCTIONS, entries))])?;
+        Ok(reply.trim().to_string())
+    }
+    
+    // Replace just today's prompt, keeping the rest of the cache
+    pub fn regenerate_today(&self) -> Result<DailyPrompt, Box<dyn Error>> {
+        let recent_notes = self.collect_recent_notes(30)?;
+        if recent_notes.is_empty() {
+            return Err("No recent notes to base a prompt on".into());
+        }
+        
+        let today = Local::now().date_naive();
+        let generated = self.analyze_and_generate(recent_notes, today, 1)?;
+        let prompt = generated.into_values().next().ok_or("No prompt in response")?;
+        
+        let mut cache = load_cache(&self.cache_path).unwrap_or(PromptCache {
+            generated_at: Utc::now(),
+            prompts: HashMap::new(),
+        });
+        cache.generated_at = Utc::now();
+        cache.prompts.insert(today.format("%Y-%m-%d").to_string(), prompt.clone());
+        self.save_cache(&cache)?;
+        Ok(prompt)
+    }
+    
+    // regenerate_today on a background thread, so the editor isn't frozen
+    // while the request is out
+    pub fn spawn_regenerate_today(self) -> Receiver<Result<DailyPrompt, String>> {
+        in_background(move || {
+            let result = self.regenerate_today().map_err(|e| e.to_string());
+            if let Err(e) = &result {
+                // Remember the failure so the next launches leave the API alone
+                let _ = fs::write(failure_path(&self.cache_path), format!("{}\n{}\n", Utc::now().to_rfc3339(), e));
+            }
+            result
+        })
+    }
+    
+    // A one-line summary of a note, to use as its title
+    pub fn title(&self, text: &str) -> Result<String, Box<dyn Error>> {
+        let request = format!("{}\n\n{}", TITLE_INSTRUCTIONS, text);
+        let reply = self.provider.complete(&[Message::user(&request)])?;
+        // Models like to add quotes or a trailing full stop
+        let title = reply.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+        let title = title.trim_matches(|c| c == '"' || c == '\'' || c == '*').trim_end_matches('.');
+        if title.is_empty() {
+            return Err("The reply was empty".into());
+        }
+        Ok(title.to_string())
+    }
+    
+    // The mood and themes of a note
+    pub fn mood(&self, text: &str) -> Result<MoodReading, Box<dyn Error>> {
+        let request = format!("{}\n\n{}", MOOD_INSTRUCTIONS, text);
+        let reply = self.provider.complete(&[Message::user(&request)])?;
+        
+        // Like parse_prompts: take the first '{' that starts valid JSON
+        let object = reply
+            .match_indices('{')
+            .find_map(|(i, _)| {
+                let mut values = serde_json::Deserializer::from_str(&reply[i..]).into_iter::<serde_json::Value>();
+                values.next()?.ok()
+            })
+            .ok_or("no JSON object in the reply")?;
+        
+        let mood = object.get("mood").and_then(|m| m.as_str()).unwrap_or_default().to_lowercase();
+        if mood::rank(&mood).is_none() {
+            return Err(format!("unexpected mood \"{}\"", mood).into());
+        }
+        let themes = object.get("themes").and_then(|t| t.as_array()).map(|themes| {
+            themes.iter().filter_map(|t| t.as_str()).map(|t| t.trim().to_lowercase()).take(3).collect()
+        });
+        Ok(MoodReading { mood, themes: themes.unwrap_or_default() })
+    }
+    
+    // title on a background thread
+    pub fn spawn_title(self, text: String) -> Receiver<Result<String, String>> {
+        in_background(move || self.title(&text).map_err(|e| e.to_string()))
+    }
+    
+    fn save_cache(&self, cache: &PromptCache) -> Result<(), Box<dyn Error>> {
         // Ensure directory exists
         if let Some(parent) = self.cache_path.parent() {
             fs::create_dir_all(parent)?;
         }
         
-        let json = serde_json::to_string_pretty(&cache)?;
+        let json = serde_json::to_string_pretty(cache)?;
         fs::write(&self.cache_path, json)?;
-        
-        println!("Successfully generated prompts for the next 7 days!");
+        // It worked, so any earlier failure no longer matters
+        let _ = fs::remove_file(failure_path(&self.cache_path));
         Ok(())
     }
     
@@ -132,7 +516,7 @@ impl PromptGenerator {
             let filepath = self.notes_dir.join(&filename);
             
             if filepath.exists() {
-                if let Ok(content) = fs::read_to_string(&filepath) {
+                if let Ok(content) = crate::crypto::read_to_string(&filepath) {
                     // Skip if file is mostly empty (just header)
                     let lines: Vec<&str> = content.lines().collect();
                     if lines.len() > 2 && lines[2..].join("").trim().len() > 50 {
@@ -145,7 +529,8 @@ impl PromptGenerator {
         Ok(notes)
     }
     
-    fn analyze_and_generate(&self, notes: Vec<(String, String)>) -> Result<HashMap<String, DailyPrompt>, Box<dyn Error>> {
+    // Ask for 'count' prompts for the days starting at 'start'
+    fn analyze_and_generate(&self, notes: Vec<(String, String)>, start: NaiveDate, count: usize) -> Result<HashMap<String, DailyPrompt>, Box<dyn Error>> {
         // Combine recent notes for analysis
         let notes_summary = notes.iter()
             .map(|(date, content)| {
@@ -159,83 +544,154 @@ impl PromptGenerator {
             .collect::<Vec<_>>()
             .join("\n");
         
-        // Create prompt for Claude
-        let system_prompt = "You are helping generate personalized daily journal prompts based on someone's recent journal entries. Analyze the themes, emotions, and patterns in their writing to create thoughtful, relevant prompts that encourage deeper reflection and personal growth.";
-        
-        let user_prompt = format!(
-            "Based on these recent journal entries, generate 7 unique daily prompts for the next week. Each prompt should be:\n\
-            - Personalized based on themes you notice\n\
-            - Encouraging deeper reflection\n\
-            - Different from each other\n\
-            - About 10-20 words\n\n\
-            Recent entries:\n{}\n\n\
-            Return a JSON array with exactly 7 objects, each having:\n\
-            - \"date\": \"YYYY-MM-DD\" (starting from tomorrow)\n\
-            - \"prompt\": \"The prompt text\"\n\
-            - \"theme\": \"Brief theme (1-3 words)\"\n\
-            - \"context\": \"Optional brief explanation\"",
-            notes_summary
-        );
-        
-        // Call Anthropic API
-        let client = Client::new();
-        let request = AnthropicRequest {
-            model: "claude-3-haiku-20240307".to_string(),
-            max_tokens: 1000,
-            messages: vec![
-                Message {
-                    role: "user".to_string(),
-                    content: format!("{}\n\n{}", system_prompt, user_prompt),
-                },
-            ],
-        };
-        
-        let response = client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()?;
-        
-        if !response.status().is_success() {
-            let error_text = response.text().unwrap_or_else(|_| "Unknown error".to_string());
-            eprintln!("API Error Response: {}", error_text);
-            return Err(format!("API request failed: {}", error_text).into());
-        }
+        let user_prompt = fill_template(&self.template, &[
+            ("notes_summary", notes_summary),
+            ("count", count.to_string()),
+            ("start", start.format("%Y-%m-%d").to_string()),
+        ]);
         
-        let api_response: AnthropicResponse = response.json()?;
-        let json_str = api_response.content.get(0)
-            .ok_or("No response content")?
-            .text.clone();
+        let mut messages = vec![Message::user(&format!("{}\n\n{}", self.system_prompt, user_prompt))];
+        let reply = self.provider.complete(&messages)?;
+        let parsed = match parse_prompts(&reply) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                // One more go, showing the model what it sent
+                messages.push(Message { role: "assistant".to_string(), content: reply });
+                messages.push(Message::user(&format!(
+                    "That reply couldn't be used ({}). Reply with only the JSON array, no other text.", e
+                )));
+                parse_prompts(&self.provider.complete(&messages)?)?
+            }
+        };
         
-        // Parse the JSON response
-        let prompt_array: Vec<serde_json::Value> = serde_json::from_str(&json_str)?;
         let mut prompts = HashMap::new();
-        
-        for (i, prompt_obj) in prompt_array.iter().enumerate() {
-            let date = Local::now().date_naive() + chrono::Duration::days((i + 1) as i64);
-            let date_str = date.format("%Y-%m-%d").to_string();
-            
-            let prompt = DailyPrompt {
-                prompt: prompt_obj["prompt"].as_str().unwrap_or("What are you grateful for today?").to_string(),
-                theme: prompt_obj["theme"].as_str().unwrap_or("reflection").to_string(),
-                context: prompt_obj["context"].as_str().map(|s| s.to_string()),
-            };
-            
-            prompts.insert(date_str, prompt);
+        for (i, prompt) in parsed.into_iter().take(count).enumerate() {
+            let date = start + chrono::Duration::days(i as i64);
+            prompts.insert(date.format("%Y-%m-%d").to_string(), prompt);
         }
         
         Ok(prompts)
     }
 }
 
-// Public function to get prompt for a specific date
-pub fn get_ai_prompt(config: &Config, date: &NaiveDate) -> Option<String> {
-    if let Ok(generator) = PromptGenerator::new(config) {
-        if let Some(daily_prompt) = generator.load_cached_prompt(date) {
-            return Some(daily_prompt.prompt);
+// The prompts in a model's reply. Models often wrap the JSON in ```json
+// fences or a sentence of explanation, so rather than parsing the whole
+// reply this tries each '[' in turn and takes the first JSON array that
+// has prompts in it. Entries without a prompt are dropped.
+fn parse_prompts(reply: &str) -> Result<Vec<DailyPrompt>, String> {
+    let mut found_array = false;
+    for (i, _) in reply.match_indices('[') {
+        // A streaming deserializer stops at the end of the first value
+        // instead of complaining about whatever text follows it
+        let mut values = serde_json::Deserializer::from_str(&reply[i..]).into_iter::<serde_json::Value>();
+        let Some(Ok(serde_json::Value::Array(array))) = values.next() else {
+            continue;
+        };
+        found_array = true;
+        
+        let prompts: Vec<DailyPrompt> = array.iter().filter_map(prompt_from_json).collect();
+        if !prompts.is_empty() {
+            return Ok(prompts);
         }
     }
+    
+    if found_array {
+        Err("the JSON array has no entries with a \"prompt\"".to_string())
+    } else {
+        Err("no JSON array in the reply".to_string())
+    }
+}
+
+// One array entry: "prompt" must be a non-empty string, "theme" and
+// "context" are optional
+fn prompt_from_json(entry: &serde_json::Value) -> Option<DailyPrompt> {
+    let prompt = entry.get("prompt")?.as_str()?.trim();
+    if prompt.is_empty() {
+        return None;
+    }
+    Some(DailyPrompt {
+        prompt: prompt.to_string(),
+        theme: entry.get("theme").and_then(|t| t.as_str()).unwrap_or("reflection").to_string(),
+        context: entry.get("context").and_then(|c| c.as_str()).map(|c| c.to_string()),
+    })
+}
+
+// Run a job on its own thread. The result arrives on the returned channel;
+// errors are Strings because Box<dyn Error> can't be sent between threads.
+fn in_background<T: Send + 'static>(job: impl FnOnce() -> Result<T, String> + Send + 'static) -> Receiver<Result<T, String>> {
+    let (sender, receiver) = mpsc::channel();
+    // 'move' gives the thread ownership of the job and the sender
+    thread::spawn(move || {
+        // The editor may have quit already, in which case nobody's listening
+        let _ = sender.send(job());
+    });
+    receiver
+}
+
+// Where the time and reason of the last failed generation are kept
+fn failure_path(cache_path: &Path) -> PathBuf {
+    cache_path.with_file_name("prompt_failure")
+}
+
+// True if background generation failed in the last few hours, in which
+// case the editor sticks to the built-in prompts rather than trying again
+pub fn failed_recently(config: &Config) -> bool {
+    let path = failure_path(&PromptGenerator::get_cache_path(config));
+    let Ok(contents) = fs::read_to_string(path) else {
+        return false;
+    };
+    let failed_at = contents.lines().next().and_then(|line| DateTime::parse_from_rfc3339(line).ok());
+    match failed_at {
+        Some(failed_at) => Utc::now().signed_duration_since(failed_at).num_hours() < FAILURE_BACKOFF_HOURS,
+        None => false,
+    }
+}
+
+// Replace each {{name}} in the template with its value
+fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in values {
+        text = text.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    text
+}
+
+fn load_cache(cache_path: &Path) -> Option<PromptCache> {
+    let contents = fs::read_to_string(cache_path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+// Reading the cache doesn't need an API key - only generating does
+fn load_cached_prompt(cache_path: &Path, date: &NaiveDate) -> Option<DailyPrompt> {
+    let cache = load_cache(cache_path)?;
+    // Check if cache is less than 7 days old
+    let age = Utc::now().signed_duration_since(cache.generated_at);
+    if age.num_days() < 7 {
+        let date_str = date.format("%Y-%m-%d").to_string();
+        return cache.prompts.get(&date_str).cloned();
+    }
     None
+}
+
+// The cached prompt for a date, with its theme and context
+pub fn cached_prompt(config: &Config, date: &NaiveDate) -> Option<DailyPrompt> {
+    load_cached_prompt(&PromptGenerator::get_cache_path(config), date)
+}
+
+// Delete the prompt cache, and forget any recent failure so the editor
+// tries again straight away. Returns false if there wasn't a cache.
+pub fn clear_cache(config: &Config) -> std::io::Result<bool> {
+    let path = PromptGenerator::get_cache_path(config);
+    let _ = fs::remove_file(failure_path(&path));
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+// Public function to get prompt for a specific date
+pub fn get_ai_prompt(config: &Config, date: &NaiveDate) -> Option<String> {
+    let cache_path = PromptGenerator::get_cache_path(config);
+    load_cached_prompt(&cache_path, date).map(|daily_prompt| daily_prompt.prompt)
 }
\ No newline at end of file
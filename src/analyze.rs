@@ -0,0 +1,121 @@
+// Word frequency report for spotting verbal tics (river analyze / :analyze)
+//
+// Two things are reported:
+// - the most frequent words once common "stopwords" (the, and, of...) are
+//   left out
+// - words used again within a few words of their last use, which is where
+//   repetition is most noticeable to a reader
+
+use std::collections::HashMap;
+
+use crate::markdown;
+
+// How many of the most frequent words to list
+const TOP_WORDS: usize = 15;
+// A word counts as repeated if it comes back within this many words
+const REPEAT_WINDOW: usize = 30;
+// Short words repeat naturally, so they're never flagged
+const MIN_REPEAT_LENGTH: usize = 4;
+
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "again", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "but", "by", "can", "could", "did", "do", "does", "doing",
+    "don", "down", "each", "for", "from", "get", "got", "had", "has", "have", "having", "he", "her",
+    "here", "hers", "him", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just", "ll", "me",
+    "more", "most", "my", "no", "not", "now", "of", "off", "on", "once", "one", "only", "or", "other",
+    "our", "out", "over", "re", "s", "same", "she", "should", "so", "some", "t", "than", "that", "the",
+    "their", "them", "then", "there", "these", "they", "this", "those", "through", "to", "too", "up",
+    "ve", "very", "was", "we", "were", "what", "when", "where", "which", "while", "who", "why", "will",
+    "with", "would", "you", "your",
+];
+
+// A word that came back too soon: where the second use is, and how many
+// words separated the two
+#[derive(Debug, Clone)]
+pub struct Repeat {
+    pub word: String,
+    pub location: String, // "2024-05-01 line 12"
+    pub gap: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub total_words: usize,
+    pub unique_words: usize,
+    pub top: Vec<(String, usize)>,
+    pub repeats: Vec<Repeat>,
+}
+
+// Analyze some documents, each given as (label, markdown text). The label
+// names the document in repeat locations.
+pub fn analyze(documents: &[(String, String)]) -> Report {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut report = Report::default();
+
+    for (label, text) in documents {
+        // Word index of each word's last use, reset per document
+        let mut last_seen: HashMap<String, usize> = HashMap::new();
+        let mut index = 0;
+
+        for (line_number, line) in markdown::strip_markup(text).lines().enumerate() {
+            for word in line.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+                let word = word.to_lowercase();
+                report.total_words += 1;
+                index += 1;
+                if STOPWORDS.contains(&word.as_str()) || word.chars().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                *counts.entry(word.clone()).or_insert(0) += 1;
+
+                if let Some(previous) = last_seen.insert(word.clone(), index) {
+                    let gap = index - previous - 1;
+                    if gap < REPEAT_WINDOW && word.chars().count() >= MIN_REPEAT_LENGTH {
+                        report.repeats.push(Repeat {
+                            word,
+                            location: format!("{} line {}", label, line_number + 1),
+                            gap,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    report.unique_words = counts.len();
+    let mut top: Vec<(String, usize)> = counts.into_iter().collect();
+    // Most frequent first, alphabetical among equals so output is stable
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(TOP_WORDS);
+    report.top = top;
+    report
+}
+
+// The report as lines of text, for printing or showing in a buffer
+pub fn format_report(report: &Report) -> Vec<String> {
+    let mut lines = vec![
+        format!("{} words, {} distinct (excluding common words)", report.total_words, report.unique_words),
+        String::new(),
+        "Most used words:".to_string(),
+    ];
+    let width = report.top.iter().map(|(word, _)| word.chars().count()).max().unwrap_or(0);
+    for (word, count) in &report.top {
+        // {:<w$} left-aligns in a width given at runtime
+        lines.push(format!("  {:<w$}  {}", word, count, w = width));
+    }
+
+    lines.push(String::new());
+    if report.repeats.is_empty() {
+        lines.push("No words repeated close together.".to_string());
+    } else {
+        lines.push(format!("Repeated within {} words:", REPEAT_WINDOW));
+        for repeat in &report.repeats {
+            let gap = match repeat.gap {
+                0 => "back to back".to_string(),
+                1 => "1 word apart".to_string(),
+                n => format!("{} words apart", n),
+            };
+            lines.push(format!("  {} - \"{}\" {}", repeat.location, repeat.word, gap));
+        }
+    }
+    lines
+}
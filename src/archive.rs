@@ -0,0 +1,129 @@
+// river archive --before 2023-01-01: old notes out of the way
+//
+// Daily notes dated before the cutoff move into the archive/ folder of the
+// notes directory - out of reach of the finder and of searches over the
+// folder, which only look at the top level - along with their stats files.
+// With --zip the notes go into one compressed archive/notes-before-<date>.zip
+// instead. Stats always stay as plain files in archive/, where river stats
+// still reads them, so streaks and totals don't change.
+//
+// Files are copied byte for byte, so encrypted notes stay encrypted.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const DIR: &str = "archive";
+
+pub struct Archived {
+    pub notes: usize,
+    pub stats: usize,
+    pub skipped: Vec<String>, // Already in the archive under the same name
+}
+
+// Daily notes and stats files in 'notes_dir' dated before 'before', oldest
+// first: (note files, stats files)
+fn old_files(notes_dir: &Path, before: NaiveDate) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut notes = Vec::new();
+    let mut stats = Vec::new();
+    for entry in fs::read_dir(notes_dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let (date, list) = match name.strip_prefix(".stats-").and_then(|rest| rest.strip_suffix(".toml")) {
+            Some(date) => (date, &mut stats),
+            None => match name.strip_suffix(".md") {
+                Some(date) => (date, &mut notes),
+                None => continue,
+            },
+        };
+        let old = NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok_and(|date| date < before);
+        if old && entry.path().is_file() {
+            list.push(entry.path());
+        }
+    }
+    notes.sort();
+    stats.sort();
+    Ok((notes, stats))
+}
+
+pub fn archive(notes_dir: &Path, before: NaiveDate, zip: bool) -> io::Result<Archived> {
+    let (notes, stats) = old_files(notes_dir, before)?;
+    let archive_dir = notes_dir.join(DIR);
+    let mut archived = Archived { notes: 0, stats: 0, skipped: Vec::new() };
+    if notes.is_empty() && stats.is_empty() {
+        return Ok(archived);
+    }
+    fs::create_dir_all(&archive_dir)?;
+
+    if zip && !notes.is_empty() {
+        archived.notes = zip_notes(&archive_dir.join(format!("notes-before-{}.zip", before)), &notes)?;
+    } else {
+        archived.notes = move_files(&archive_dir, &notes, &mut archived.skipped)?;
+    }
+    archived.stats = move_files(&archive_dir, &stats, &mut archived.skipped)?;
+    Ok(archived)
+}
+
+// Move 'files' into 'dir', leaving any whose name is taken there alone
+fn move_files(dir: &Path, files: &[PathBuf], skipped: &mut Vec<String>) -> io::Result<usize> {
+    let mut moved = 0;
+    for file in files {
+        let Some(name) = file.file_name() else {
+            continue;
+        };
+        let target = dir.join(name);
+        if target.exists() {
+            skipped.push(name.to_string_lossy().to_string());
+            continue;
+        }
+        fs::rename(file, &target)?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+// Write 'notes' into a new zip file, then delete them. Nothing is deleted
+// unless the whole zip was written.
+fn zip_notes(path: &Path, notes: &[PathBuf]) -> io::Result<usize> {
+    if path.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let result = (|| -> zip::result::ZipResult<()> {
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
+        for note in notes {
+            let name = note.file_name().unwrap_or_default().to_string_lossy();
+            // Unzipping gives the note back its date
+            let options = match modified(note) {
+                Some(time) => options.last_modified_time(time),
+                None => options,
+            };
+            zip.start_file(name, options)?;
+            zip.write_all(&fs::read(note)?)?;
+        }
+        zip.finish()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(path);
+        return Err(io::Error::other(e));
+    }
+
+    for note in notes {
+        fs::remove_file(note)?;
+    }
+    Ok(notes.len())
+}
+
+fn modified(path: &Path) -> Option<zip::DateTime> {
+    let time: DateTime<Local> = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?.into();
+    zip::DateTime::from_date_and_time(
+        time.year().try_into().ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
@@ -0,0 +1,98 @@
+// Insert mode word completion (Ctrl-N / Ctrl-P)
+//
+// The word before the cursor is completed from the other words in the
+// buffer, nearest first, and optionally from the most recent notes
+// (completion_notes in config.toml). Candidates show in a small menu under
+// the cursor. Ctrl-N and Ctrl-P step through them, putting each one in the
+// text as they go; stepping past either end brings back what was typed.
+
+use std::collections::HashSet;
+
+pub struct Completion {
+    pub start: usize,   // Column where the word being completed starts
+    pub prefix: String, // The word as typed
+    pub candidates: Vec<String>,
+    pub selected: Option<usize>, // None = the prefix as typed
+}
+
+impl Completion {
+    // None when nothing starts with 'prefix'
+    pub fn new(start: usize, prefix: String, candidates: Vec<String>, backwards: bool) -> Option<Self> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let selected = if backwards { candidates.len() - 1 } else { 0 };
+        Some(Completion { start, prefix, candidates, selected: Some(selected) })
+    }
+
+    pub fn next(&mut self) {
+        self.selected = match self.selected {
+            None => Some(0),
+            Some(i) if i + 1 < self.candidates.len() => Some(i + 1),
+            Some(_) => None,
+        };
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = match self.selected {
+            None => Some(self.candidates.len() - 1),
+            Some(0) => None,
+            Some(i) => Some(i - 1),
+        };
+    }
+
+    // What goes in the text: the selected candidate, or the prefix
+    pub fn current(&self) -> &str {
+        match self.selected {
+            Some(i) => &self.candidates[i],
+            None => &self.prefix,
+        }
+    }
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Where the word ending at column x of 'line' starts
+pub fn word_start(line: &[char], x: usize) -> usize {
+    let mut start = x.min(line.len());
+    while start > 0 && is_word_char(line[start - 1]) {
+        start -= 1;
+    }
+    start
+}
+
+// Words starting with 'prefix' (and longer than it), each once. Words in
+// 'lines' come first, those on lines nearest 'cursor_y' before the rest;
+// then those in 'extra' (text of other notes) in order.
+pub fn candidates(prefix: &str, lines: &[Vec<char>], cursor_y: usize, extra: &[String]) -> Vec<String> {
+    // Lines by distance from the cursor: the cursor line, then the line
+    // above, the line below, two above...
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    order.sort_by_key(|&y| (y.abs_diff(cursor_y), y > cursor_y));
+
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    let mut add = |word: String| {
+        if word.len() > prefix.len() && word.starts_with(prefix) && seen.insert(word.clone()) {
+            found.push(word);
+        }
+    };
+    for y in order {
+        let line: String = lines[y].iter().collect();
+        for word in words(&line) {
+            add(word.to_string());
+        }
+    }
+    for text in extra {
+        for word in words(text) {
+            add(word.to_string());
+        }
+    }
+    found
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !is_word_char(c)).filter(|word| !word.is_empty())
+}
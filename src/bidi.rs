@@ -0,0 +1,181 @@
+// Right-to-left text: Hebrew, Arabic and the like
+//
+// Notes are stored in the order they're typed (logical order), but a line
+// of Hebrew reads from the right, so drawing it character by character
+// left to right comes out backwards. This is the core of the Unicode
+// Bidirectional Algorithm (UAX #9) for one line at a time, without the
+// explicit embedding controls:
+//   - a line's direction is that of its first letter (P2, P3)
+//   - numbers after left-to-right text join it, others stay numbers (W7)
+//   - spaces and punctuation between two runs of the same direction go
+//     with them, otherwise with the line (N1, N2)
+//   - right-to-left runs are reversed, numbers and left-to-right words in
+//     them keep their own order (I1, I2, L2)
+// The cursor still moves through the text in logical order, as typed.
+
+use std::ops::Range;
+
+use crate::width;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Left,    // Latin, Greek, CJK... and the left-to-right mark
+    Right,   // Hebrew, Arabic, Syriac, Thaana... and the right-to-left mark
+    Number,  // 0-9 and the Arabic-Indic digits
+    Mark,    // A combining mark, which goes with the character before it
+    Neutral, // Spaces, punctuation, symbols
+}
+
+fn class(c: char) -> Class {
+    match c as u32 {
+        0x200E => Class::Left,
+        0x200F => Class::Right,
+        0x0660..=0x0669 | 0x06F0..=0x06F9 => Class::Number,
+        _ if c.is_ascii_digit() => Class::Number,
+        _ if width::char_width(c) == 0 => Class::Mark,
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFE | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF => Class::Right,
+        _ if c.is_alphabetic() => Class::Left,
+        _ => Class::Neutral,
+    }
+}
+
+// Does the line have any right-to-left text in it?
+pub fn has_rtl(line: &[char]) -> bool {
+    line.iter().any(|&c| class(c) == Class::Right)
+}
+
+// Is the line itself right-to-left - is its first letter?
+pub fn is_rtl(line: &[char]) -> bool {
+    line.iter().map(|&c| class(c)).find(|class| matches!(class, Class::Left | Class::Right)) == Some(Class::Right)
+}
+
+// The order to draw 'line' in from left to right, as indices into it,
+// each with whether it's in a right-to-left run (where brackets are
+// mirrored: "(" shows as ")")
+pub fn visual_order(line: &[char]) -> Vec<(usize, bool)> {
+    let levels = levels(line);
+    // A letter and the combining marks after it stay together, in order
+    let mut clusters: Vec<(Range<usize>, u8)> = Vec::new();
+    for (i, &c) in line.iter().enumerate() {
+        match clusters.last_mut() {
+            Some((range, _)) if class(c) == Class::Mark => range.end = i + 1,
+            _ => clusters.push((i..i + 1, levels[i])),
+        }
+    }
+
+    // L2: from the highest level down to the lowest odd one, reverse each
+    // run of clusters at that level or above
+    let highest = clusters.iter().map(|(_, level)| *level).max().unwrap_or(0);
+    let lowest_odd = clusters.iter().map(|(_, level)| *level).filter(|level| level % 2 == 1).min().unwrap_or(highest + 1);
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < clusters.len() {
+            if clusters[i].1 < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < clusters.len() && clusters[i].1 >= level {
+                i += 1;
+            }
+            clusters[start..i].reverse();
+        }
+    }
+    clusters
+        .into_iter()
+        .flat_map(|(range, level)| range.map(move |i| (i, level % 2 == 1)))
+        .collect()
+}
+
+// The embedding level of each character: even is left to right, odd
+// right to left
+fn levels(line: &[char]) -> Vec<u8> {
+    let rtl = is_rtl(line);
+    let line_class = if rtl { Class::Right } else { Class::Left };
+    let mut classes: Vec<Class> = line.iter().map(|&c| class(c)).collect();
+
+    // W1: a combining mark takes the class of what it's on
+    let mut before = line_class;
+    for class in classes.iter_mut() {
+        if *class == Class::Mark {
+            *class = before;
+        }
+        before = *class;
+    }
+
+    // W7: a number after left-to-right text is part of it
+    let mut strong = line_class;
+    for class in classes.iter_mut() {
+        match *class {
+            Class::Left | Class::Right => strong = *class,
+            Class::Number if strong == Class::Left => *class = Class::Left,
+            _ => {}
+        }
+    }
+
+    // N1, N2: neutrals between text going the same way go that way too (a
+    // number counts as right to left here); otherwise the line's way
+    let direction = |class: Class| if class == Class::Left { Class::Left } else { Class::Right };
+    let mut i = 0;
+    while i < classes.len() {
+        if classes[i] != Class::Neutral {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < classes.len() && classes[i] == Class::Neutral {
+            i += 1;
+        }
+        let before = if start == 0 { line_class } else { direction(classes[start - 1]) };
+        let after = if i == classes.len() { line_class } else { direction(classes[i]) };
+        let resolved = if before == after { before } else { line_class };
+        classes[start..i].fill(resolved);
+    }
+
+    // I1, I2
+    let mut levels: Vec<u8> = classes
+        .iter()
+        .map(|class| match (rtl, class) {
+            (false, Class::Right) => 1,
+            (false, Class::Number) => 2,
+            (false, _) => 0,
+            (true, Class::Right) => 1,
+            (true, _) => 2,
+        })
+        .collect();
+
+    // L1: tabs, and spaces before them or at the end of the line, are at
+    // the line's own level
+    let base = rtl as u8;
+    let mut trailing = true;
+    for i in (0..line.len()).rev() {
+        if line[i] == '\t' {
+            trailing = true;
+        }
+        if trailing && line[i].is_whitespace() {
+            levels[i] = base;
+        } else {
+            trailing = false;
+        }
+    }
+    levels
+}
+
+// A bracket drawn right to left faces the other way
+pub fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        '‹' => '›',
+        '›' => '‹',
+        _ => c,
+    }
+}
@@ -0,0 +1,142 @@
+// A Buffer is one open file: its text plus the per-file editing state
+// (cursor, scroll position, unsaved changes).
+//
+// The Editor keeps a Vec<Buffer> and an index of the active one. While a
+// buffer is active its contents live directly in the Editor's fields
+// (buffer, cursor_x, ...) so the editing code doesn't need to change;
+// switching buffers moves the state out into this struct and back.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::crypto;
+use crate::lines::Lines;
+
+use crate::vim::Pos;
+
+#[derive(Debug, Default)]
+pub struct Buffer {
+    pub lines: Lines,
+    pub filename: Option<String>,
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub offset_x: usize,
+    pub offset_y: usize,
+    pub needs_save: bool,
+    pub show_prompt: bool,
+    pub read_only: bool,
+    pub marks: HashMap<char, Pos>,
+    pub line_endings: LineEndings,
+    pub large: bool,
+}
+
+impl Buffer {
+    // An empty, unnamed buffer with a single empty line
+    pub fn new() -> Self {
+        Buffer {
+            lines: Lines::from(vec![Vec::new()]),
+            ..Default::default() // Struct update syntax - remaining fields from Default
+        }
+    }
+
+    // Name shown by :ls and in messages
+    pub fn display_name(&self) -> &str {
+        // as_deref turns Option<String> into Option<&str>
+        self.filename.as_deref().unwrap_or("[No Name]")
+    }
+}
+
+// How the file's lines were ended when it was read, so saving writes them
+// back the same way. A new file gets the platform's own ending ("\r\n" on
+// Windows, "\n" elsewhere) and a final newline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineEndings {
+    pub crlf: bool,          // "\r\n" (Windows) rather than "\n"
+    pub final_newline: bool, // The last line ends with one too
+}
+
+impl Default for LineEndings {
+    fn default() -> Self {
+        LineEndings { crlf: cfg!(windows), final_newline: true }
+    }
+}
+
+impl LineEndings {
+    // Judged by the first line ending; an empty or one-line file has none
+    // to go on, so it keeps the defaults for style
+    pub fn detect(content: &str) -> Self {
+        let crlf = match content.find('\n') {
+            Some(i) => content[..i].ends_with('\r'),
+            None => LineEndings::default().crlf,
+        };
+        LineEndings { crlf, final_newline: content.ends_with('\n') }
+    }
+
+    // line_endings ("auto", "lf", "crlf") and final_newline ("auto",
+    // "always", "never") from config.toml win over what was detected
+    pub fn with_overrides(self, line_endings: &str, final_newline: &str) -> Self {
+        LineEndings {
+            crlf: match line_endings {
+                "lf" => false,
+                "crlf" => true,
+                _ => self.crlf,
+            },
+            final_newline: match final_newline {
+                "always" => true,
+                "never" => false,
+                _ => self.final_newline,
+            },
+        }
+    }
+
+    // The file's text from its lines
+    pub fn join(&self, lines: &[String]) -> String {
+        let ending = if self.crlf { "\r\n" } else { "\n" };
+        let mut text = lines.join(ending);
+        // An empty file stays empty rather than becoming a lone newline
+        if self.final_newline && !text.is_empty() {
+            text.push_str(ending);
+        }
+        text
+    }
+}
+
+// Read a file into lines. Plain text is read a line at a time, so a big
+// file is never held twice over (as one String and as lines). An
+// encrypted file has to be decrypted whole first.
+pub fn read_lines(path: &Path) -> io::Result<(Vec<Vec<char>>, LineEndings)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if crypto::is_encrypted(reader.fill_buf()?) {
+        let content = crypto::read_to_string(path)?;
+        let lines = content.lines().map(|line| line.chars().collect()).collect();
+        return Ok((lines, LineEndings::detect(&content)));
+    }
+
+    let mut lines = Vec::new();
+    let mut endings = LineEndings { final_newline: false, ..LineEndings::default() };
+    let mut bytes = Vec::new();
+    loop {
+        bytes.clear();
+        if reader.read_until(b'\n', &mut bytes)? == 0 {
+            break;
+        }
+        // Same as str::lines: "\n" or "\r\n" ends a line
+        endings.final_newline = bytes.ends_with(b"\n");
+        if endings.final_newline {
+            bytes.pop();
+            let crlf = bytes.ends_with(b"\r");
+            if crlf {
+                bytes.pop();
+            }
+            // The first line ending is the one that counts
+            if lines.is_empty() {
+                endings.crlf = crlf;
+            }
+        }
+        let line = std::str::from_utf8(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        lines.push(line.chars().collect());
+    }
+    Ok((lines, endings))
+}
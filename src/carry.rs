@@ -0,0 +1,48 @@
+// Carrying unfinished todos over to a new day (carry_over_todos in
+// config.toml)
+//
+// When today's note is created, the "- [ ]" tasks still open in the last
+// note before it are copied into a "Carried over" section, and marked
+// "- [>]" where they were - migrated, as in a bullet journal - so each
+// task is only open in one place.
+
+use crate::buffer::LineEndings;
+use crate::markdown;
+
+pub struct CarryOver {
+    pub section: String,  // "## Carried over" and the tasks, for the new note
+    pub previous: String, // The old note with its tasks marked migrated
+}
+
+// None if 'previous' has no open tasks
+pub fn carry_over(previous: &str) -> Option<CarryOver> {
+    let mut lines: Vec<Vec<char>> = previous.lines().map(|line| line.chars().collect()).collect();
+    let mut tasks: Vec<Vec<char>> = Vec::new();
+    let mut in_fence = false;
+    for line in &mut lines {
+        if markdown::is_fence(line) {
+            in_fence = !in_fence;
+        } else if let (false, Some(i)) = (in_fence, markdown::open_task(line)) {
+            tasks.push(line.clone());
+            line[i] = '>';
+        }
+    }
+    if tasks.is_empty() {
+        return None;
+    }
+
+    // Subtasks keep their place under each other, but the least indented
+    // task starts at the margin
+    let indent = tasks.iter().map(|task| markdown::list_item(task).map_or(0, |item| item.indent)).min().unwrap_or(0);
+    let mut section = String::from("## Carried over\n\n");
+    for task in &tasks {
+        section.extend(&task[indent..]);
+        section.push('\n');
+    }
+    section.push('\n');
+
+    // Saved with the line endings it had
+    let lines: Vec<String> = lines.iter().map(|line| line.iter().collect()).collect();
+    let previous = LineEndings::detect(previous).join(&lines);
+    Some(CarryOver { section, previous })
+}
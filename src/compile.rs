@@ -0,0 +1,125 @@
+// A month or year of daily notes as one markdown document (river compile)
+//
+// The document opens with totals and a table of contents linking to each
+// day. Every note then follows under a "## Wednesday, May 1, 2024" header,
+// with its own headings pushed two levels down so they sit under the day.
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::front_matter;
+
+// One day's note and what it took to write
+pub struct Entry {
+    pub date: NaiveDate,
+    pub text: String,
+    pub words: usize,
+    pub typing_seconds: u64,
+}
+
+// A heading for the whole document: "May 2024" for a calendar month,
+// "2024" for a year, otherwise the two dates
+pub fn title(from: NaiveDate, to: NaiveDate) -> String {
+    let next_day = to.succ_opt().unwrap_or(to);
+    if from.day() == 1 && next_day.day() == 1 && from.year() == to.year() {
+        if from.month() == 1 && to.month() == 12 {
+            return from.year().to_string();
+        }
+        if from.month() == to.month() {
+            return from.format("%B %Y").to_string();
+        }
+    }
+    format!("{} to {}", from.format("%B %-d, %Y"), to.format("%B %-d, %Y"))
+}
+
+pub fn compile(title: &str, entries: &[Entry]) -> String {
+    let words: usize = entries.iter().map(|entry| entry.words).sum();
+    let seconds: u64 = entries.iter().map(|entry| entry.typing_seconds).sum();
+
+    let mut out = format!("# Journal: {}\n\n", title);
+    out.push_str(&format!(
+        "{} {}, {} words, {} of writing\n\n",
+        entries.len(),
+        if entries.len() == 1 { "entry" } else { "entries" },
+        words,
+        duration(seconds)
+    ));
+
+    out.push_str("## Contents\n\n");
+    for entry in entries {
+        let heading = day_heading(entry.date);
+        out.push_str(&format!("- [{}](#{}) - {} words\n", heading, anchor(&heading), entry.words));
+    }
+
+    for entry in entries {
+        out.push_str(&format!("\n## {}\n\n", day_heading(entry.date)));
+        out.push_str(&format!("*{} words, {} of writing*\n\n", entry.words, duration(entry.typing_seconds)));
+        out.push_str(&body(&entry.text, entry.date));
+    }
+    out
+}
+
+fn day_heading(date: NaiveDate) -> String {
+    date.format("%A, %B %-d, %Y").to_string()
+}
+
+// The note without front matter or the date heading new daily notes start
+// with (the day header replaces it), and with headings demoted
+fn body(text: &str, date: NaiveDate) -> String {
+    let text = front_matter::strip(text);
+    let mut lines: Vec<&str> = text.lines().collect();
+
+    let first = lines.iter().position(|line| !line.trim().is_empty());
+    if let Some(first) = first {
+        let is_date = lines[first]
+            .strip_prefix("# ")
+            .and_then(|heading| NaiveDate::parse_from_str(heading.trim(), "%A, %B %d, %Y").ok())
+            == Some(date);
+        if is_date {
+            lines.drain(..=first);
+        }
+    }
+
+    let mut out = String::new();
+    let mut in_code = false;
+    for line in lines.iter().skip_while(|line| line.trim().is_empty()) {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if !in_code && (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            // Markdown stops at six levels
+            out.push_str(&"#".repeat((level + 2).min(6)));
+            out.push_str(&trimmed[level..]);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    // Exactly one newline at the end, whatever the note had
+    let trimmed_len = out.trim_end().len();
+    out.truncate(trimmed_len);
+    out.push('\n');
+    out
+}
+
+// The link target most markdown renderers give a heading: lowercase, with
+// punctuation dropped and spaces turned into hyphens
+fn anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+// "45 min" or "3h 20m"
+fn duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    if minutes < 60 {
+        format!("{} min", minutes)
+    } else {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    }
+}
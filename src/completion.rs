@@ -0,0 +1,135 @@
+// This module holds the pieces behind command-line editing:
+// - History: remembers previously entered commands and searches
+// - Tab completion for command names and file paths
+// Keeping this separate from main.rs means the Editor only has to
+// forward keys here and copy the result back into its command buffer.
+
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+
+// Every command that run_command understands, in the order they
+// should be offered when completing an empty or partial command name
+pub const COMMANDS: &[&str] = &[
+    "analyze", "b", "bn", "bp", "cclose", "close", "cn", "copen", "count", "cp", "define", "diff", "e", "find", "fold", "goal", "grammar", "grep", "help", "link", "ls", "meta", "now", "only", "outline", "prompt", "q", "r", "read", "readability", "reload-config", "scratch", "set", "snippet", "spell", "syn", "sp", "split", "title", "toggle", "vault", "view", "vs", "vsplit", "w", "wq",
+];
+
+// Commands whose argument is a file path (gets path completion)
+const PATH_COMMANDS: &[&str] = &["diff", "e", "r", "read", "sp", "split", "view", "vs", "vsplit", "w"];
+
+// A simple list of previously entered lines with a cursor for Up/Down
+// navigation. The 'draft' is whatever was typed before browsing started,
+// so pressing Down past the newest entry gives it back.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    index: Option<usize>, // None = not browsing
+    draft: String,
+}
+
+impl History {
+    // Record a line, skipping blanks and immediate duplicates
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim();
+        if !line.is_empty() && self.entries.last().map(|l| l.as_str()) != Some(line) {
+            self.entries.push(line.to_string());
+        }
+        self.reset();
+    }
+
+    // Stop browsing (called whenever the user edits the line)
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.draft.clear();
+    }
+
+    // Step back to an older entry. Returns the text to show, if any.
+    pub fn older(&mut self, current: &str) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let next = match self.index {
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => 0,
+            Some(i) => i - 1,
+        };
+        self.index = Some(next);
+        Some(self.entries[next].clone())
+    }
+
+    // Step forward to a newer entry, ending at the saved draft
+    pub fn newer(&mut self) -> Option<String> {
+        let i = self.index?;
+        if i + 1 < self.entries.len() {
+            self.index = Some(i + 1);
+            Some(self.entries[i + 1].clone())
+        } else {
+            self.index = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+}
+
+// Returns every full command line that completes 'line'.
+// "b" -> ["b", "bn", "bp"], "e ~/no" -> ["e ~/notes/"], etc.
+// 'user_commands' are the ones from [commands] in config.toml.
+pub fn complete(line: &str, user_commands: &[&String]) -> Vec<String> {
+    // split_once splits at the first space: ("e", "~/notes")
+    match line.split_once(' ') {
+        None => {
+            let mut names: Vec<String> = COMMANDS
+                .iter()
+                .copied()
+                .chain(user_commands.iter().map(|name| name.as_str()))
+                .filter(|cmd| cmd.starts_with(line))
+                .map(|cmd| cmd.to_string())
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+        }
+        Some((cmd, arg)) if PATH_COMMANDS.contains(&cmd) => complete_path(arg)
+            .into_iter()
+            .map(|path| format!("{} {}", cmd, path))
+            .collect(),
+        Some(_) => Vec::new(),
+    }
+}
+
+// Completes a partial path against the filesystem. The returned strings
+// keep the user's spelling (e.g. a leading '~') and directories get a
+// trailing '/' so the next Tab descends into them.
+pub fn complete_path(partial: &str) -> Vec<String> {
+    // Split "dir/part" into the directory to list and the name prefix
+    // (Windows paths can use "\" as well)
+    let (dir_part, name_prefix) = match partial.rfind(std::path::is_separator) {
+        Some(i) => (&partial[..=i], &partial[i + 1..]),
+        None => ("", partial),
+    };
+
+    let expanded = config::expand_tilde(dir_part);
+    let dir = if expanded.is_empty() { Path::new(".") } else { Path::new(&expanded) };
+
+    let mut matches: Vec<String> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Hide dotfiles (like the .stats files) unless asked for
+                if !name.starts_with(name_prefix) || (name.starts_with('.') && !name_prefix.starts_with('.')) {
+                    return None;
+                }
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                Some(format!("{}{}{}", dir_part, name, if is_dir { "/" } else { "" }))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    matches.sort();
+    matches
+}
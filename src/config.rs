@@ -6,8 +6,35 @@
 // - File I/O operations
 
 use serde::{Deserialize, Serialize}; // Traits for automatic serialization
+use std::collections::BTreeMap; // A map that keeps its keys sorted
 use std::fs; // File system operations
-use std::path::PathBuf; // Owned path type (like String vs &str)
+use std::path::{Path, PathBuf}; // Owned path type (like String vs &str)
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::i18n;
+use crate::pdf;
+use crate::theme::{self, Theme};
+
+// The profile chosen with --profile or :vault. A Mutex makes a global
+// that can change safely; Config::load reads it.
+static ACTIVE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+// Settings given on the command line for this run (--goal 300, --set
+// key=value), as (flag, setting, value). Set by main, read by resolve().
+static FLAGS: Mutex<Vec<(String, String, String)>> = Mutex::new(Vec::new());
+
+// Short names for the environment variables used most. Every setting can
+// also be set as RIVER_ and its name in capitals (RIVER_TAB_SIZE=2).
+const ENV_ALIASES: [(&str, &str); 3] = [
+    ("RIVER_NOTES_DIR", "daily_notes_dir"),
+    ("RIVER_GOAL", "daily_word_goal"),
+    ("RIVER_VIM", "vim_bindings"),
+];
+
+// Settings read once at startup, so a change to them while river is
+// running (editing config.toml, or :set) waits for the next start
+pub const RESTART_SETTINGS: [&str; 3] = ["daily_notes_dir", "vim_bindings", "encrypt"];
 
 // Configuration struct that maps to TOML file format
 // 'pub' makes this struct visible outside the module
@@ -35,6 +62,234 @@ pub struct Config {
     
     #[serde(default = "default_use_ai_prompts")]
     pub use_ai_prompts: bool,
+    
+    #[serde(default = "default_remember_position")]
+    pub remember_position: bool,
+    
+    #[serde(default = "default_open_at_end")]
+    pub open_at_end: bool,
+    
+    #[serde(default = "default_show_line_numbers")]
+    pub show_line_numbers: bool,
+    
+    #[serde(default = "default_relative_numbers")]
+    pub relative_numbers: bool,
+    
+    #[serde(default = "default_auto_indent")]
+    pub auto_indent: bool, // New lines keep the current line's indentation
+    
+    #[serde(default = "default_continue_lists")]
+    pub continue_lists: bool, // Enter on a markdown list item starts the next one
+    
+    #[serde(default = "default_markdown_word_count")]
+    pub markdown_word_count: bool, // Leave markdown syntax out of word counts
+    
+    #[serde(default = "default_show_reading_time")]
+    pub show_reading_time: bool, // Estimated reading time in the status bar
+    
+    #[serde(default = "default_reading_wpm")]
+    pub reading_wpm: usize, // Reading speed used for reading time estimates
+    
+    #[serde(default = "default_long_sentence_words")]
+    pub long_sentence_words: usize, // Sentences longer than this count as long in :readability
+    
+    #[serde(default = "default_ai_provider")]
+    pub ai_provider: String, // "anthropic", "openai" or "azure"
+    
+    #[serde(default = "default_ai_base_url")]
+    pub ai_base_url: String, // Empty means the provider's usual endpoint
+    
+    #[serde(default = "default_ai_model")]
+    pub ai_model: String, // Empty means the provider's default model
+    
+    #[serde(default = "default_ai_max_tokens")]
+    pub ai_max_tokens: u32, // Longest reply the model may give
+    
+    #[serde(default = "default_ai_temperature")]
+    pub ai_temperature: f32, // Higher is more varied, lower more predictable
+    
+    #[serde(default = "default_ai_system_prompt")]
+    pub ai_system_prompt: String, // Sets the tone of generated prompts
+    
+    #[serde(default = "default_ai_prompt_template")]
+    pub ai_prompt_template: String, // File with the request text; empty uses the built-in one
+    
+    #[serde(default = "default_api_key_command")]
+    pub api_key_command: String, // Shell command that prints the AI API key
+    
+    #[serde(default = "default_ai_timeout_seconds")]
+    pub ai_timeout_seconds: u64, // Give up on an AI request after this long
+    
+    #[serde(default = "default_auto_title")]
+    pub auto_title: bool, // Ask the AI for a one-line title when quitting
+    
+    #[serde(default = "default_mood_tracking")]
+    pub mood_tracking: String, // "off", "local" or "ai"
+    
+    #[serde(default = "default_daily_word_goal")]
+    pub daily_word_goal: usize, // Words to write each day
+    
+    #[serde(default = "default_goal_bell")]
+    pub goal_bell: bool, // Ring the terminal bell on reaching the goal
+    
+    #[serde(default = "default_on_save")]
+    pub on_save: String, // Command run after each save
+    
+    #[serde(default = "default_on_goal_reached")]
+    pub on_goal_reached: String, // Command run when the word goal is reached
+    
+    #[serde(default = "default_on_quit")]
+    pub on_quit: String, // Command run when the editor closes
+    
+    #[serde(default = "default_pdf_page_size")]
+    pub pdf_page_size: String, // Paper size for river export pdf
+    
+    #[serde(default = "default_pdf_margin_mm")]
+    pub pdf_margin_mm: f32, // Margin on every side, in millimetres
+    
+    #[serde(default = "default_pdf_font")]
+    pub pdf_font: String, // times, helvetica or courier
+    
+    #[serde(default = "default_pdf_font_size")]
+    pub pdf_font_size: f32, // Body text size in points
+    
+    #[serde(default = "default_publish_dir")]
+    pub publish_dir: String, // Where river publish writes the site
+    
+    #[serde(default = "default_site_title")]
+    pub site_title: String, // Heading of the published site and its feed
+    
+    #[serde(default = "default_site_url")]
+    pub site_url: String, // e.g. "https://example.com/journal"
+    
+    #[serde(default = "default_daily_note_template")]
+    pub daily_note_template: String, // File new daily notes start from
+    
+    #[serde(default = "default_encrypt")]
+    pub encrypt: bool, // Keep notes and stats encrypted with age
+    
+    #[serde(default = "default_line_endings")]
+    pub line_endings: String, // "auto", "lf" or "crlf"
+    
+    #[serde(default = "default_final_newline")]
+    pub final_newline: String, // "auto", "always" or "never"
+    
+    #[serde(default = "default_expand_tabs")]
+    pub expand_tabs: bool, // Tab key inserts spaces rather than a tab
+    
+    #[serde(default = "default_large_file_kb")]
+    pub large_file_kb: u64, // Files bigger than this count words only when typing pauses
+    
+    #[serde(default = "default_bidi")]
+    pub bidi: bool, // Lay out right-to-left text in river rather than leaving it to the terminal
+    
+    #[serde(default = "default_theme")]
+    pub theme: String, // "default", "high-contrast", "deuteranopia" or "monochrome" (see theme.rs)
+    
+    #[serde(default = "default_show_session_time")]
+    pub show_session_time: bool, // Time since river opened in the status bar
+    
+    #[serde(default = "default_show_clock")]
+    pub show_clock: bool, // Current time in the status bar
+    
+    #[serde(default = "default_completion_notes")]
+    pub completion_notes: usize, // Recent notes Ctrl-N/Ctrl-P also take words from
+    
+    // [abbreviations]: btw = "by the way" and so on (see abbrev.rs)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub abbreviations: BTreeMap<String, String>,
+    
+    #[serde(default = "default_smart_typography")]
+    pub smart_typography: bool, // Curly quotes, dashes and ellipses as you type
+    
+    #[serde(default = "default_snippet_triggers")]
+    pub snippet_triggers: bool, // Tab after a snippet's name inserts it
+    
+    #[serde(default = "default_timestamp_format")]
+    pub timestamp_format: String, // strftime format :now (F5) inserts
+    
+    // [commands]: your own :commands, each one river command or a list of
+    // them run in order: gratitude = ["snippet gratitude", "now"]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub commands: BTreeMap<String, UserCommand>,
+    
+    // [keys]: "F6" = "gratitude" runs :gratitude when F6 is pressed
+    // (see keys.rs for the key names)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+    
+    #[serde(default = "default_carry_over_todos")]
+    pub carry_over_todos: bool, // Open tasks move from the last note into a new one
+    
+    #[serde(default = "default_review_days")]
+    pub review_days: Vec<String>, // Weekdays whose new note is the weekly review
+    
+    #[serde(default = "default_review_template")]
+    pub review_template: String, // Template for the review note (built-in if empty)
+    
+    #[serde(default = "default_spell_check")]
+    pub spell_check: bool, // Underline misspelled words (:spell toggles)
+    
+    #[serde(default = "default_spell_lang")]
+    pub spell_lang: Vec<String>, // Dictionaries to check against
+    
+    #[serde(default = "default_spell_file")]
+    pub spell_file: String, // Personal word list; empty means words.txt in the config dir
+    
+    #[serde(default = "default_thesaurus_file")]
+    pub thesaurus_file: String, // Thesaurus for :syn; empty looks for one
+    
+    #[serde(default = "default_dictionary_file")]
+    pub dictionary_file: String, // dictd dictionary for :define; empty uses /usr/share/dictd
+    
+    #[serde(default = "default_dictionary_online")]
+    pub dictionary_online: bool, // Look words :define can't find up online
+    
+    #[serde(default = "default_languagetool_url")]
+    pub languagetool_url: String, // LanguageTool server for grammar checks; empty is off
+    
+    #[serde(default = "default_languagetool_language")]
+    pub languagetool_language: String, // Language code sent to LanguageTool
+    
+    #[serde(default = "default_language")]
+    pub language: String, // River's own messages (see i18n.rs); empty follows LANG
+    
+    // [profiles.work], [profiles.personal], ... Left out when saving if
+    // there are none, so a fresh config file doesn't get an empty table
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    
+    // Which of them is in effect. #[serde(skip)] keeps it out of the file.
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+// A [commands] entry. #[serde(untagged)] lets the TOML be either a plain
+// string or an array of them, whichever the value happens to be.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UserCommand {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl UserCommand {
+    pub fn steps(&self) -> &[String] {
+        match self {
+            // slice::from_ref makes a one-item slice without copying
+            UserCommand::One(command) => std::slice::from_ref(command),
+            UserCommand::Many(commands) => commands,
+        }
+    }
+}
+
+// Settings a profile can change. Option means "not set here", so the
+// top-level setting applies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    pub daily_notes_dir: Option<String>,
+    pub daily_word_goal: Option<usize>,
+    pub daily_note_template: Option<String>,
 }
 
 // These functions provide default values for config fields
@@ -49,15 +304,25 @@ fn default_tab_size() -> usize {
 
 fn default_daily_notes_dir() -> String {
     // 'if let' combines pattern matching with conditional logic
-    // It's like: "if this matches Some(value), bind it to 'home'"
-    if let Some(home) = dirs::home_dir() {
-        // PathBuf::join creates a new path
+    // It's like: "if this matches Some(value), bind it to 'documents'"
+    if let Some(documents) = documents_dir() {
+        // PathBuf::join adds a path component with the platform's
+        // separator ("/", or "\" on Windows)
         // to_string_lossy() converts Path to String, replacing invalid UTF-8
         // Final .to_string() converts from Cow<str> to owned String
-        home.join("Documents/DailyNotes").to_string_lossy().to_string()
+        documents.join("DailyNotes").to_string_lossy().to_string()
     } else {
-        // String literal with .to_string() creates an owned String
-        "./DailyNotes".to_string()
+        Path::new(".").join("DailyNotes").to_string_lossy().to_string()
+    }
+}
+
+// ~/Documents. Windows users' Documents folder can be somewhere else
+// (moved into OneDrive, say), so there it's asked for by name.
+fn documents_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        dirs::document_dir()
+    } else {
+        dirs::home_dir().map(|home| home.join("Documents"))
     }
 }
 
@@ -77,6 +342,246 @@ fn default_use_ai_prompts() -> bool {
     true
 }
 
+fn default_remember_position() -> bool {
+    true
+}
+
+fn default_open_at_end() -> bool {
+    true
+}
+
+fn default_show_line_numbers() -> bool {
+    false
+}
+
+fn default_relative_numbers() -> bool {
+    false
+}
+
+fn default_auto_indent() -> bool {
+    true
+}
+
+fn default_continue_lists() -> bool {
+    true
+}
+
+fn default_markdown_word_count() -> bool {
+    true
+}
+
+fn default_show_reading_time() -> bool {
+    true
+}
+
+fn default_reading_wpm() -> usize {
+    230
+}
+
+fn default_long_sentence_words() -> usize {
+    25
+}
+
+fn default_ai_provider() -> String {
+    "anthropic".to_string()
+}
+
+fn default_ai_base_url() -> String {
+    String::new()
+}
+
+fn default_ai_model() -> String {
+    String::new()
+}
+
+fn default_ai_max_tokens() -> u32 {
+    1000
+}
+
+fn default_ai_temperature() -> f32 {
+    1.0
+}
+
+fn default_ai_system_prompt() -> String {
+    "You are helping generate personalized daily journal prompts based on someone's recent journal entries. Analyze the themes, emotions, and patterns in their writing to create thoughtful, relevant prompts that encourage deeper reflection and personal growth.".to_string()
+}
+
+fn default_ai_prompt_template() -> String {
+    String::new()
+}
+
+fn default_api_key_command() -> String {
+    String::new()
+}
+
+fn default_ai_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_auto_title() -> bool {
+    false
+}
+
+fn default_mood_tracking() -> String {
+    "off".to_string()
+}
+
+fn default_daily_word_goal() -> usize {
+    500
+}
+
+fn default_goal_bell() -> bool {
+    false
+}
+
+fn default_on_save() -> String {
+    String::new()
+}
+
+fn default_on_goal_reached() -> String {
+    String::new()
+}
+
+fn default_on_quit() -> String {
+    String::new()
+}
+
+fn default_pdf_page_size() -> String {
+    "a4".to_string()
+}
+
+fn default_pdf_margin_mm() -> f32 {
+    20.0
+}
+
+fn default_pdf_font() -> String {
+    "times".to_string()
+}
+
+fn default_pdf_font_size() -> f32 {
+    11.0
+}
+
+fn default_publish_dir() -> String {
+    if let Some(documents) = documents_dir() {
+        documents.join("river-site").to_string_lossy().to_string()
+    } else {
+        Path::new(".").join("river-site").to_string_lossy().to_string()
+    }
+}
+
+fn default_site_title() -> String {
+    "Journal".to_string()
+}
+
+fn default_site_url() -> String {
+    String::new()
+}
+
+fn default_daily_note_template() -> String {
+    String::new()
+}
+
+fn default_encrypt() -> bool {
+    false
+}
+
+fn default_line_endings() -> String {
+    "auto".to_string()
+}
+
+fn default_final_newline() -> String {
+    "auto".to_string()
+}
+
+fn default_expand_tabs() -> bool {
+    true
+}
+
+fn default_large_file_kb() -> u64 {
+    1024
+}
+
+fn default_bidi() -> bool {
+    true
+}
+
+fn default_theme() -> String {
+    "default".to_string()
+}
+
+fn default_show_session_time() -> bool {
+    false
+}
+
+fn default_show_clock() -> bool {
+    false
+}
+
+fn default_completion_notes() -> usize {
+    0
+}
+
+fn default_smart_typography() -> bool {
+    false
+}
+
+fn default_snippet_triggers() -> bool {
+    false
+}
+
+fn default_timestamp_format() -> String {
+    "[%H:%M]".to_string()
+}
+
+fn default_carry_over_todos() -> bool {
+    false
+}
+
+fn default_review_days() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_review_template() -> String {
+    String::new()
+}
+
+fn default_spell_check() -> bool {
+    false
+}
+
+fn default_spell_lang() -> Vec<String> {
+    vec!["en_US".to_string()]
+}
+
+fn default_spell_file() -> String {
+    String::new()
+}
+
+fn default_thesaurus_file() -> String {
+    String::new()
+}
+
+fn default_dictionary_file() -> String {
+    String::new()
+}
+
+fn default_dictionary_online() -> bool {
+    false
+}
+
+fn default_languagetool_url() -> String {
+    String::new()
+}
+
+fn default_languagetool_language() -> String {
+    "auto".to_string()
+}
+
+fn default_language() -> String {
+    String::new()
+}
+
 // Implementing the Default trait allows Config::default() to be called
 // This is useful for creating instances with sensible defaults
 impl Default for Config {
@@ -90,6 +595,70 @@ impl Default for Config {
             show_prompts: default_show_prompts(),
             prompt_style: default_prompt_style(),
             use_ai_prompts: default_use_ai_prompts(),
+            remember_position: default_remember_position(),
+            open_at_end: default_open_at_end(),
+            show_line_numbers: default_show_line_numbers(),
+            relative_numbers: default_relative_numbers(),
+            auto_indent: default_auto_indent(),
+            continue_lists: default_continue_lists(),
+            markdown_word_count: default_markdown_word_count(),
+            show_reading_time: default_show_reading_time(),
+            reading_wpm: default_reading_wpm(),
+            long_sentence_words: default_long_sentence_words(),
+            ai_provider: default_ai_provider(),
+            ai_base_url: default_ai_base_url(),
+            ai_model: default_ai_model(),
+            ai_max_tokens: default_ai_max_tokens(),
+            ai_temperature: default_ai_temperature(),
+            ai_system_prompt: default_ai_system_prompt(),
+            ai_prompt_template: default_ai_prompt_template(),
+            api_key_command: default_api_key_command(),
+            ai_timeout_seconds: default_ai_timeout_seconds(),
+            auto_title: default_auto_title(),
+            mood_tracking: default_mood_tracking(),
+            daily_word_goal: default_daily_word_goal(),
+            goal_bell: default_goal_bell(),
+            on_save: default_on_save(),
+            on_goal_reached: default_on_goal_reached(),
+            on_quit: default_on_quit(),
+            pdf_page_size: default_pdf_page_size(),
+            pdf_margin_mm: default_pdf_margin_mm(),
+            pdf_font: default_pdf_font(),
+            pdf_font_size: default_pdf_font_size(),
+            publish_dir: default_publish_dir(),
+            site_title: default_site_title(),
+            site_url: default_site_url(),
+            daily_note_template: default_daily_note_template(),
+            abbreviations: BTreeMap::new(),
+            commands: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+            profile: None,
+            encrypt: default_encrypt(),
+            line_endings: default_line_endings(),
+            final_newline: default_final_newline(),
+            expand_tabs: default_expand_tabs(),
+            large_file_kb: default_large_file_kb(),
+            show_session_time: default_show_session_time(),
+            show_clock: default_show_clock(),
+            bidi: default_bidi(),
+            theme: default_theme(),
+            completion_notes: default_completion_notes(),
+            smart_typography: default_smart_typography(),
+            snippet_triggers: default_snippet_triggers(),
+            timestamp_format: default_timestamp_format(),
+            carry_over_todos: default_carry_over_todos(),
+            review_days: default_review_days(),
+            review_template: default_review_template(),
+            spell_check: default_spell_check(),
+            spell_lang: default_spell_lang(),
+            spell_file: default_spell_file(),
+            thesaurus_file: default_thesaurus_file(),
+            dictionary_file: default_dictionary_file(),
+            dictionary_online: default_dictionary_online(),
+            languagetool_url: default_languagetool_url(),
+            languagetool_language: default_languagetool_language(),
+            language: default_language(),
         }
     }
 }
@@ -102,38 +671,368 @@ impl Config {
         let config_path = Self::config_path();
         
         // Try to read the config file
-        // Ok(contents) means success, Err(_) means failure
-        if let Ok(contents) = fs::read_to_string(&config_path) {
-            // Parse TOML into Config struct
-            // unwrap_or_else takes a closure |e| { ... } that runs on error
-            // Closures are anonymous functions that can capture variables
-            let mut config: Config = toml::from_str(&contents).unwrap_or_else(|e| {
-                // eprintln! prints to stderr (error output)
-                eprintln!("Error parsing config file: {}", e);
-                Self::default() // Return default config on parse error
-            });
-            
-            // Expand tilde (~) to home directory path
-            // This is a common Unix convention
-            if config.daily_notes_dir.starts_with("~") {
-                if let Some(home) = dirs::home_dir() {
-                    // replacen replaces first N occurrences (1 in this case)
-                    // & borrows the string instead of moving it
-                    config.daily_notes_dir = config.daily_notes_dir.replacen("~", &home.to_string_lossy(), 1);
+        // Ok(contents) means success, Err(e) means failure
+        match fs::read_to_string(&config_path) {
+            // Problems in the values were reported by main() before anything
+            // got this far; the settings are used as they are
+            Ok(contents) => match Self::parse(&contents) {
+                Ok((config, _)) => config,
+                Err(e) => {
+                    // Only river config edit/check get here with a file that
+                    // doesn't parse. The file is left alone to be fixed.
+                    eprintln!("Error parsing config file: {}", e);
+                    Self::default().resolve()
                 }
-            }
-            
-            config
-        } else {
+            },
             // Create default config file if it doesn't exist
-            let default_config = Self::default();
-            // Pattern match on Result - we only care about errors here
-            if let Err(e) = default_config.save() {
-                eprintln!("Error creating default config file: {}", e);
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let default_config = Self::default();
+                // Pattern match on Result - we only care about errors here
+                if let Err(e) = default_config.save() {
+                    eprintln!("Error creating default config file: {}", e);
+                }
+                // Saved without the environment and flags, which go on now
+                default_config.resolve()
+            }
+            // There but unreadable: never overwrite it with the defaults
+            Err(e) => {
+                eprintln!("Can't read {}: {}", config_path.display(), e);
+                Self::default().resolve()
+            }
+        }
+    }
+
+    // Read config.toml again while river is running. Unlike load(), a
+    // mistake in the file is an error to show rather than a reason to fall
+    // back to the defaults.
+    pub fn reload() -> Result<Self, String> {
+        let contents = fs::read_to_string(Self::config_path()).map_err(|e| e.to_string())?;
+        match Self::parse(&contents)? {
+            (config, problems) if problems.is_empty() => Ok(config),
+            (_, problems) => Err(problems.join("; ")),
+        }
+    }
+
+    // When config.toml last changed, to notice edits made while river runs
+    pub fn modified() -> Option<SystemTime> {
+        fs::metadata(Self::config_path()).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    // Everything wrong with config.toml (and RIVER_* variables and flags),
+    // one line each - nothing when all's well
+    pub fn check() -> Vec<String> {
+        match fs::read_to_string(Self::config_path()) {
+            Ok(contents) => match Self::parse(&contents) {
+                Ok((_, problems)) => problems,
+                Err(e) => vec![e],
+            },
+            // No file yet: the defaults, but RIVER_* and flags still count
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::parse("").map(|(_, problems)| problems).unwrap_or_default(),
+            Err(e) => vec![format!("Can't read {}: {}", Self::config_path().display(), e)],
+        }
+    }
+
+    // The settings in config.toml's text, with anything wrong in them as
+    // "line 4: ...". Err when the file isn't valid TOML at all.
+    fn parse(contents: &str) -> Result<(Self, Vec<String>), String> {
+        // toml_edit remembers where each setting is, for the line numbers
+        let document = toml_edit::ImDocument::parse(contents).map_err(|e| {
+            // "invalid string\nexpected `\"`" on one line
+            let message = e.message().trim().replace('\n', ", ");
+            match e.span() {
+                Some(span) => format!("line {}: {}", line_at(contents, span.start), message),
+                None => message,
+            }
+        })?;
+        let config: Config = toml::from_str(contents).map_err(|e| {
+            // serde's messages don't say which setting; the one whose value
+            // the mistake is in does
+            let offset = e.span().map_or(0, |span| span.start);
+            let key = document
+                .as_table()
+                .iter()
+                .find(|(key, _)| document.as_table().get(key).and_then(|item| item.span()).is_some_and(|span| span.contains(&offset)))
+                .map_or(String::new(), |(key, _)| format!("{}: ", key));
+            format!("line {}: {}{}", line_at(contents, offset), key, e.message())
+        })?;
+        let line_of = |key: &str| {
+            document.as_table().get_key_value(key).and_then(|(key, _)| key.span()).map(|span| line_at(contents, span.start))
+        };
+
+        // (line, what's wrong), so they can be listed in order
+        let mut problems: Vec<(Option<usize>, String)> = Vec::new();
+        // serde skips settings it doesn't know, so a misspelled one would
+        // otherwise be ignored without a word
+        let known = toml::Value::try_from(Self::default()).map_err(|e| e.to_string())?;
+        let known: Vec<&str> = known.as_table().into_iter().flat_map(|table| table.keys()).map(String::as_str).chain(TABLES).collect();
+        for (key, _) in document.as_table().iter() {
+            if !known.contains(&key) {
+                let closest = known.iter().min_by_key(|name| distance(key, name)).filter(|name| distance(key, name) <= 3);
+                problems.push((line_of(key), match closest {
+                    Some(name) => format!("there's no setting called {} - did you mean {}?", key, name),
+                    None => format!("there's no setting called {}", key),
+                }));
+            }
+        }
+        // Overrides that don't work are left out by resolve(), so they're
+        // checked one at a time here, named by where they came from
+        for (from, key, text) in Self::overrides() {
+            if let Err(e) = config.parse_setting(&key, &text).and_then(|value| config.with(&key, value)) {
+                problems.push((None, format!("{}: {}", from, e)));
+            }
+        }
+        let config = config.resolve();
+        for (key, problem) in config.problems() {
+            problems.push((line_of(key), format!("{}: {}", key, problem)));
+        }
+        // Ones from the defaults have no line, and go last
+        problems.sort_by_key(|(line, _)| line.unwrap_or(usize::MAX));
+        let problems = problems
+            .into_iter()
+            .map(|(line, problem)| match line {
+                Some(line) => format!("line {}: {}", line, problem),
+                None => problem,
+            })
+            .collect();
+        Ok((config, problems))
+    }
+
+    // Values that are the right type but still can't work
+    fn problems(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        let choices: [(&'static str, &str, &[&str]); 5] = [
+            ("prompt_style", &self.prompt_style, &["ghost", "none", "command_only"]),
+            ("mood_tracking", &self.mood_tracking, &["off", "local", "ai"]),
+            ("ai_provider", &self.ai_provider, &["anthropic", "openai", "azure"]),
+            ("line_endings", &self.line_endings, &["auto", "lf", "crlf"]),
+            ("final_newline", &self.final_newline, &["auto", "always", "never"]),
+        ];
+        for (key, value, allowed) in choices {
+            if !allowed.contains(&value) {
+                problems.push((key, format!("\"{}\" isn't one of {}", value, allowed.join(", "))));
+            }
+        }
+        if pdf::page_size(&self.pdf_page_size).is_none() {
+            problems.push(("pdf_page_size", format!("\"{}\" isn't one of a4, a5, letter, legal", self.pdf_page_size)));
+        }
+        if pdf::Font::from_name(&self.pdf_font).is_none() {
+            problems.push(("pdf_font", format!("\"{}\" isn't one of times, helvetica, courier", self.pdf_font)));
+        }
+        if theme::named(&self.theme).is_none() {
+            problems.push(("theme", format!("\"{}\" isn't one of default, high-contrast, deuteranopia, monochrome", self.theme)));
+        }
+        if let Some(problem) = i18n::problem(&self.language) {
+            problems.push(("language", problem));
+        }
+        if self.ai_provider == "azure" && self.ai_base_url.is_empty() {
+            problems.push(("ai_base_url", "ai_provider = \"azure\" needs the deployment URL here".to_string()));
+        }
+
+        let at_least_one: [(&'static str, u64); 3] = [
+            ("tab_size", self.tab_size as u64),
+            ("typing_timeout_seconds", self.typing_timeout_seconds),
+            ("reading_wpm", self.reading_wpm as u64),
+        ];
+        for (key, value) in at_least_one {
+            if value == 0 {
+                problems.push((key, "must be at least 1".to_string()));
+            }
+        }
+        if self.pdf_font_size <= 0.0 {
+            problems.push(("pdf_font_size", "must be more than 0".to_string()));
+        }
+
+        for (key, dir) in [("daily_notes_dir", &self.daily_notes_dir), ("publish_dir", &self.publish_dir)] {
+            if let Some(problem) = dir_problem(Path::new(dir)) {
+                problems.push((key, problem));
+            }
+        }
+        let files = [
+            ("daily_note_template", &self.daily_note_template),
+            ("review_template", &self.review_template),
+            ("ai_prompt_template", &self.ai_prompt_template),
+            ("thesaurus_file", &self.thesaurus_file),
+        ];
+        for (key, file) in files {
+            if !file.is_empty() && !Path::new(file).is_file() {
+                problems.push((key, format!("no such file: {}", file)));
+            }
+        }
+        // The .index file or the name without an extension
+        let dictionary = Path::new(&self.dictionary_file);
+        if !self.dictionary_file.is_empty() && !dictionary.is_file() && !dictionary.with_extension("index").is_file() {
+            problems.push(("dictionary_file", format!("no such file: {}", self.dictionary_file)));
+        }
+
+        for (key, url) in [("languagetool_url", &self.languagetool_url), ("ai_base_url", &self.ai_base_url)] {
+            if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+                problems.push((key, format!("\"{}\" should start with http:// or https://", url)));
+            }
+        }
+        // chrono panics formatting with a bad format, so it's checked up front
+        if chrono::format::StrftimeItems::new(&self.timestamp_format).any(|item| item == chrono::format::Item::Error) {
+            problems.push(("timestamp_format", format!("\"{}\" isn't a valid strftime format", self.timestamp_format)));
+        }
+        const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+        for day in &self.review_days {
+            let day = day.trim().to_lowercase();
+            if day.len() < 3 || !WEEKDAYS.iter().any(|weekday| weekday.starts_with(&day)) {
+                problems.push(("review_days", format!("\"{}\" isn't a day of the week", day)));
+            }
+        }
+        problems
+    }
+
+    // The colours to draw in. A theme river doesn't know is reported by
+    // check() and drawn in the default one meanwhile.
+    pub fn colors(&self) -> &'static Theme {
+        theme::named(&self.theme).unwrap_or(&theme::DEFAULT)
+    }
+
+    // One setting's value, by its name in config.toml. Going through
+    // toml::Value means every field can be looked up without a big match.
+    pub fn get(&self, key: &str) -> Option<toml::Value> {
+        toml::Value::try_from(self).ok()?.get(key).cloned()
+    }
+
+    // Turn 'text' (as typed after :set or river config set) into a value
+    // of the type 'key' has: "true", "500", "en_US, de_DE" for a list
+    pub fn parse_setting(&self, key: &str, text: &str) -> Result<toml::Value, String> {
+        use toml::Value;
+        let text = text.trim();
+        let Some(current) = self.get(key) else {
+            return Err(format!("No setting called {}", key));
+        };
+        match current {
+            Value::Boolean(_) => match text {
+                "true" | "on" | "yes" => Ok(Value::Boolean(true)),
+                "false" | "off" | "no" => Ok(Value::Boolean(false)),
+                _ => Err(format!("{} is true or false", key)),
+            },
+            Value::Integer(_) => text.parse().map(Value::Integer).map_err(|_| format!("{} is a whole number", key)),
+            Value::Float(_) => text.parse().map(Value::Float).map_err(|_| format!("{} is a number", key)),
+            Value::Array(_) => Ok(Value::Array(
+                text.split(',')
+                    .map(|item| item.trim().trim_matches('"'))
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            )),
+            Value::Table(_) => Err(format!("[{}] is a table - change it with river config edit", key)),
+            // A string; quotes around it are optional
+            _ => Ok(Value::String(text.trim_matches('"').to_string())),
+        }
+    }
+
+    // A copy with one setting changed. Serde checks the value the same
+    // way it would in the file, so a negative goal is refused here too.
+    pub fn with(&self, key: &str, value: toml::Value) -> Result<Self, String> {
+        let mut table = toml::Value::try_from(self).map_err(|e| e.to_string())?;
+        if let Some(table) = table.as_table_mut() {
+            table.insert(key.to_string(), value);
+        }
+        let mut config: Config = table.try_into().map_err(|e| format!("{}: {}", key, e.message()))?;
+        config.profile = self.profile.clone(); // Not part of the file, so lost on the way
+        let config = config.expand_paths();
+        match config.problems().into_iter().find(|(problem_key, _)| *problem_key == key) {
+            Some((_, problem)) => Err(format!("{}: {}", key, problem)),
+            None => Ok(config),
+        }
+    }
+
+    // river config set: change one setting in config.toml. toml_edit
+    // rewrites just that line, so comments and the order of the file stay.
+    pub fn set_in_file(key: &str, text: &str) -> Result<(), String> {
+        let path = Self::config_path();
+        if !path.exists() {
+            Self::default().save().map_err(|e| e.to_string())?;
+        }
+        let contents = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        // The file's own settings, without a profile laid over them
+        let (current, _) = Self::parse(&contents).map_err(|e| format!("config.toml {}", e))?;
+        let value = current.parse_setting(key, text)?;
+        current.with(key, value.clone())?;
+
+        let mut document: toml_edit::DocumentMut = contents.parse().map_err(|e: toml_edit::TomlError| e.message().to_string())?;
+        // toml::Value prints as TOML, which toml_edit reads back
+        let mut new_value: toml_edit::Value = value.to_string().parse().map_err(|e: toml_edit::TomlError| e.message().to_string())?;
+        match document.get_mut(key).and_then(toml_edit::Item::as_value_mut) {
+            Some(old) => {
+                // Keep a comment at the end of the line
+                *new_value.decor_mut() = old.decor().clone();
+                *old = new_value;
             }
-            default_config // Return the config (moved ownership)
+            None => document[key] = toml_edit::Item::Value(new_value),
+        }
+        fs::write(&path, document.to_string()).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+    }
+
+    // Use 'value' for 'key' in this run, over config.toml and the
+    // environment. 'flag' is what was typed, for error messages.
+    pub fn set_flag(flag: &str, key: &str, value: &str) {
+        if let Ok(mut flags) = FLAGS.lock() {
+            flags.push((flag.to_string(), key.to_string(), value.to_string()));
         }
     }
+
+    // The settings changed from outside config.toml, as (where from,
+    // setting, value): RIVER_* variables, then command-line flags, so a
+    // flag wins over a variable
+    fn overrides() -> Vec<(String, String, String)> {
+        let known = Self::default();
+        let mut found: Vec<(String, String, String)> = std::env::vars()
+            .filter_map(|(name, value)| {
+                let key = match ENV_ALIASES.iter().find(|(alias, _)| *alias == name) {
+                    Some((_, key)) => key.to_string(),
+                    None => name.strip_prefix("RIVER_")?.to_lowercase(),
+                };
+                // Others (RIVER_PASSPHRASE, the hooks' RIVER_FILE) aren't settings
+                known.get(&key).is_some().then_some((name, key, value))
+            })
+            .collect();
+        // The environment comes in no particular order
+        found.sort();
+        found.extend(FLAGS.lock().map(|flags| flags.clone()).unwrap_or_default());
+        found
+    }
+
+    // Finish off a config read from the file. Each layer goes over the one
+    // before: the file, the active profile, the environment, the command
+    // line. Then ~ in paths.
+    fn resolve(mut self) -> Self {
+        // Lay the active profile's settings over the top-level ones
+        let active = ACTIVE_PROFILE.lock().map(|name| name.clone()).unwrap_or(None);
+        if let Some(name) = active {
+            if let Some(profile) = self.profiles.get(&name).cloned() {
+                // Option::unwrap_or keeps the current value when the profile has none
+                self.daily_notes_dir = profile.daily_notes_dir.unwrap_or(self.daily_notes_dir);
+                self.daily_word_goal = profile.daily_word_goal.unwrap_or(self.daily_word_goal);
+                self.daily_note_template = profile.daily_note_template.unwrap_or(self.daily_note_template);
+                self.profile = Some(name);
+            }
+        }
+        // A value that doesn't work is left out here and reported by parse()
+        for (_, key, text) in Self::overrides() {
+            if let Ok(changed) = self.parse_setting(&key, &text).and_then(|value| self.with(&key, value)) {
+                self = changed;
+            }
+        }
+        self.expand_paths()
+    }
+    
+    fn expand_paths(mut self) -> Self {
+        // Expand tilde (~) to home directory path
+        // This is a common Unix convention
+        self.daily_notes_dir = expand_tilde(&self.daily_notes_dir);
+        self.ai_prompt_template = expand_tilde(&self.ai_prompt_template);
+        self.publish_dir = expand_tilde(&self.publish_dir);
+        self.daily_note_template = expand_tilde(&self.daily_note_template);
+        self.review_template = expand_tilde(&self.review_template);
+        self.spell_file = expand_tilde(&self.spell_file);
+        self.thesaurus_file = expand_tilde(&self.thesaurus_file);
+        self.dictionary_file = expand_tilde(&self.dictionary_file);
+        self
+    }
     
     // Save config to file
     // &self - immutable borrow (we only read the config)
@@ -157,9 +1056,16 @@ impl Config {
         Ok(()) // Success - return unit type wrapped in Ok
     }
     
-    // Private associated function (no 'pub')
+    // Use a profile's settings from now on (None for the top-level ones).
+    // Takes effect at the next Config::load.
+    pub fn select_profile(name: Option<String>) {
+        if let Ok(mut active) = ACTIVE_PROFILE.lock() {
+            *active = name;
+        }
+    }
+    
     // Returns the platform-specific config file path
-    fn config_path() -> PathBuf {
+    pub fn config_path() -> PathBuf {
         // dirs::config_dir() returns:
         // - Linux: ~/.config
         // - macOS: ~/Library/Application Support
@@ -170,4 +1076,77 @@ impl Config {
         path.push("config.toml"); // Add filename
         path // Return the PathBuf (implicit return)
     }
-}
\ No newline at end of file
+}
+
+// The [tables] a config.toml can have besides the plain settings
+const TABLES: [&str; 4] = ["abbreviations", "commands", "keys", "profiles"];
+
+// Which line of 'text' the byte at 'offset' is on, counting from 1
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+// What's wrong with using 'dir' for notes, if anything. It needn't exist
+// yet, as long as it can be made: the nearest folder above it that does
+// exist has to be one that can be written to.
+fn dir_problem(dir: &Path) -> Option<String> {
+    if dir.exists() {
+        return (!dir.is_dir()).then(|| format!("{} isn't a directory", dir.display()));
+    }
+    let parent = dir.ancestors().skip(1).find(|parent| parent.exists())?;
+    if !parent.is_dir() {
+        return Some(format!("{} can't be created: {} isn't a directory", dir.display(), parent.display()));
+    }
+    let read_only = parent.metadata().map(|metadata| metadata.permissions().readonly()).unwrap_or(false);
+    read_only.then(|| format!("{} can't be created: {} is read-only", dir.display(), parent.display()))
+}
+
+// How many letters have to change to turn 'a' into 'b' (Levenshtein
+// distance), for suggesting the setting a misspelled name was meant to be
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // One row of the usual table at a time
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substituted.min(previous + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Expands a leading tilde (~), or %USERPROFILE% as Windows writes it, to
+// the user's home directory. Used for config paths and for paths typed in
+// command mode.
+pub fn expand_tilde(path: &str) -> String {
+    const PROFILE: &str = "%USERPROFILE%";
+    // get() rather than [..] - slicing through the middle of a multi-byte
+    // character would panic
+    let rest = match path.strip_prefix('~') {
+        Some(rest) => rest,
+        None if path.get(..PROFILE.len()).is_some_and(|start| start.eq_ignore_ascii_case(PROFILE)) => &path[PROFILE.len()..],
+        None => return path.to_string(),
+    };
+    // "~notes" is a file called that, not something in the home directory
+    if !rest.is_empty() && !rest.starts_with(std::path::is_separator) {
+        return path.to_string();
+    }
+    match dirs::home_dir() {
+        Some(home) => format!("{}{}", home.to_string_lossy(), rest),
+        None => path.to_string(),
+    }
+}
+
+// Where River keeps its own state (sessions, caches) as opposed to settings:
+// - Linux: ~/.local/share/river
+// - macOS: ~/Library/Application Support/river
+// - Windows: %APPDATA%\river
+pub fn data_dir() -> PathBuf {
+    let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path
+}
@@ -0,0 +1,140 @@
+// Encryption at rest (encrypt = true in config.toml)
+//
+// Notes and stats files are encrypted with age (https://age-encryption.org).
+// The first time, river makes a key pair and keeps it in
+// ~/.config/river/identity.age, itself encrypted with your passphrase. At
+// startup it asks for the passphrase once (or reads RIVER_PASSPHRASE) to
+// unlock the key. Only that step is slow: after it, reading and writing a
+// note takes no longer than without encryption.
+//
+// Encrypted files are recognised by age's header, so a journal can hold a
+// mix. Plaintext notes still open, and are encrypted the next time they're
+// saved (or all at once with river encrypt).
+
+use age::secrecy::{ExposeSecret, SecretString};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Every age file starts with this line
+const HEADER: &[u8] = b"age-encryption.org/v1\n";
+
+// Set by unlock(); while it's empty files are written as plain text
+static KEY: OnceLock<age::x25519::Identity> = OnceLock::new();
+
+// ~/.config/river/identity.age (or the platform's config directory). Shared
+// by every profile, so one passphrase opens them all.
+pub fn identity_path() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("identity.age");
+    path
+}
+
+pub fn has_identity() -> bool {
+    identity_path().exists()
+}
+
+// Open the key with the passphrase, making it first if there isn't one
+pub fn unlock(passphrase: &str) -> Result<(), String> {
+    let passphrase = SecretString::from(passphrase.to_string());
+    let path = identity_path();
+    let key = if path.exists() {
+        let sealed = fs::read(&path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        let opened = age::decrypt(&age::scrypt::Identity::new(passphrase), &sealed)
+            .map_err(|_| "Wrong passphrase".to_string())?;
+        String::from_utf8(opened)
+            .ok()
+            .and_then(|text| text.trim().parse::<age::x25519::Identity>().ok())
+            .ok_or_else(|| format!("{} doesn't hold an age key", path.display()))?
+    } else {
+        let key = age::x25519::Identity::generate();
+        let sealed = age::encrypt(&age::scrypt::Recipient::new(passphrase), key.to_string().expose_secret().as_bytes())
+            .map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, sealed).map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+        key
+    };
+    // A second unlock in the same run has nothing to do
+    let _ = KEY.set(key);
+    Ok(())
+}
+
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(HEADER)
+}
+
+// fs::read_to_string, decrypting if the file is encrypted
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let bytes = fs::read(path.as_ref())?;
+    let bytes = if is_encrypted(&bytes) {
+        let key = KEY.get().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is encrypted; set encrypt = true to open it", path.as_ref().display()))
+        })?;
+        age::decrypt(key, &bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// fs::write, encrypting once the key is unlocked
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    match KEY.get() {
+        Some(key) if !is_setting(path.as_ref()) => {
+            let sealed = age::encrypt(&key.to_public(), contents.as_ref()).map_err(io::Error::other)?;
+            fs::write(path, sealed)
+        }
+        _ => fs::write(path, contents),
+    }
+}
+
+// Files in the config directory (config.toml, opened with river config
+// edit, say) have to stay readable before the key is unlocked
+fn is_setting(path: &Path) -> bool {
+    let Some(dir) = identity_path().parent().and_then(|dir| dir.canonicalize().ok()) else {
+        return false;
+    };
+    path.canonicalize().ok().and_then(|path| path.parent().map(|parent| parent == dir)).unwrap_or(false)
+}
+
+// Read a passphrase from the terminal without echoing it. RIVER_PASSPHRASE
+// takes its place for scripts and cron jobs.
+pub fn ask_passphrase(prompt: &str) -> io::Result<String> {
+    if let Ok(passphrase) = std::env::var("RIVER_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    let mut stderr = io::stderr();
+    write!(stderr, "{}", prompt)?;
+    stderr.flush()?;
+
+    terminal::enable_raw_mode()?;
+    let mut passphrase = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"))
+                }
+                KeyCode::Char(c) => passphrase.push(c),
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                KeyCode::Esc => break Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled")),
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    // Raw mode swallowed the newline Enter would have printed
+    writeln!(stderr)?;
+    result.map(|_| passphrase)
+}
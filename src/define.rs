@@ -0,0 +1,169 @@
+// :define - what the word under the cursor means (K in vim mode)
+//
+// Definitions come from dictd dictionary files on disk: the .index file
+// lists each headword with where its entry sits in the .dict file (or the
+// compressed .dict.dz). Debian's dict-gcide, dict-wn and friends install
+// them in /usr/share/dictd; dictionary_file in config.toml picks one
+// elsewhere. With dictionary_online = true, a word none of them has is
+// looked up at dictionaryapi.dev instead.
+
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::i18n::t;
+
+const ONLINE_URL: &str = "https://api.dictionaryapi.dev/api/v2/entries/en/";
+
+// What the popup shows
+pub struct Definition {
+    pub word: String,
+    pub source: String,     // Which dictionary it came from
+    pub lines: Vec<String>, // The entry, wrapped when it's drawn
+    pub top: usize,         // First line showing
+}
+
+impl Definition {
+    // The lines from 'top' on, wrapped at spaces to fit 'width' columns
+    pub fn wrapped(&self, width: usize) -> Vec<String> {
+        let mut rows = Vec::new();
+        for line in &self.lines[self.top.min(self.lines.len())..] {
+            // Carry the line's indent onto the rows it wraps to
+            let indent = line.chars().take_while(|c| c.is_whitespace()).count().min(width / 2);
+            let mut row = String::new();
+            for word in line.split_whitespace() {
+                let len = row.chars().count();
+                if len > indent && len + 1 + word.chars().count() > width {
+                    rows.push(std::mem::take(&mut row));
+                }
+                if row.is_empty() {
+                    row = " ".repeat(indent);
+                } else if row.chars().count() > indent {
+                    row.push(' ');
+                }
+                row.push_str(word);
+            }
+            rows.push(row.chars().take(width).collect());
+        }
+        rows
+    }
+
+    pub fn scroll(&mut self, by: isize) {
+        let last = self.lines.len().saturating_sub(1) as isize;
+        self.top = (self.top as isize + by).clamp(0, last) as usize;
+    }
+}
+
+pub fn lookup(config: &Config, word: &str) -> Result<Definition, String> {
+    let indexes = index_files(config);
+    for index in &indexes {
+        if let Some(entry) = dictd_entry(index, word)? {
+            let source = index.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            return Ok(Definition { word: word.to_string(), source, lines: entry.lines().map(str::to_string).collect(), top: 0 });
+        }
+    }
+    if config.dictionary_online {
+        return online(word);
+    }
+    if indexes.is_empty() {
+        return Err(t!("No dictionary found - install dict-gcide or dict-wn, or set dictionary_file"));
+    }
+    Err(t!("\"{word}\" isn't in the dictionary", word = word))
+}
+
+// The .index files to look in, in order
+fn index_files(config: &Config) -> Vec<PathBuf> {
+    if !config.dictionary_file.is_empty() {
+        // Either the .index itself or the name without an extension
+        let path = PathBuf::from(&config.dictionary_file);
+        return vec![if path.extension().is_some_and(|ext| ext == "index") { path } else { path.with_extension("index") }];
+    }
+    let mut found: Vec<PathBuf> = fs::read_dir("/usr/share/dictd")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "index"))
+        .collect();
+    found.sort();
+    found
+}
+
+// The entry for 'word' in the dictionary whose index is 'index'
+fn dictd_entry(index: &Path, word: &str) -> Result<Option<String>, String> {
+    let text = fs::read_to_string(index).map_err(|e| t!("Couldn't read {path}: {error}", path = index.display(), error = e))?;
+    let key = word.to_lowercase();
+    // "headword<TAB>offset<TAB>length", the numbers in dictd's base 64
+    let place = text.lines().find_map(|line| {
+        let mut fields = line.split('\t');
+        let head = fields.next()?;
+        if head.to_lowercase() != key {
+            return None;
+        }
+        Some((base64_number(fields.next()?)?, base64_number(fields.next()?)?))
+    });
+    let Some((offset, length)) = place else {
+        return Ok(None);
+    };
+
+    let data = read_data(index)?;
+    let Some(bytes) = data.get(offset..offset + length) else {
+        return Err(t!("{file} doesn't match its index", file = index.with_extension("dict").display()));
+    };
+    Ok(Some(String::from_utf8_lossy(bytes).trim_end().to_string()))
+}
+
+// The whole .dict file, decompressing a .dict.dz (dictzip is gzip with
+// extras a plain gzip reader skips)
+fn read_data(index: &Path) -> Result<Vec<u8>, String> {
+    let plain = index.with_extension("dict");
+    if plain.exists() {
+        return fs::read(&plain).map_err(|e| t!("Couldn't read {path}: {error}", path = plain.display(), error = e));
+    }
+    let compressed = index.with_extension("dict.dz");
+    let file = fs::File::open(&compressed).map_err(|e| t!("Couldn't read {path}: {error}", path = compressed.display(), error = e))?;
+    let mut data = Vec::new();
+    GzDecoder::new(file).read_to_end(&mut data).map_err(|e| t!("Couldn't read {path}: {error}", path = compressed.display(), error = e))?;
+    Ok(data)
+}
+
+// dictd writes offsets as numbers in base 64, most significant digit first
+fn base64_number(text: &str) -> Option<usize> {
+    const DIGITS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    text.chars().try_fold(0usize, |n, c| Some(n * 64 + DIGITS.find(c)?))
+}
+
+// dictionaryapi.dev: a list of entries, each with meanings by part of speech
+fn online(word: &str) -> Result<Definition, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(format!("{}{}", ONLINE_URL, word)).send().map_err(|e| t!("Lookup failed: {error}", error = e))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(t!("\"{word}\" isn't in the dictionary", word = word));
+    }
+    let entries: serde_json::Value = response.json().map_err(|e| t!("Lookup failed: {error}", error = e))?;
+
+    let mut lines = Vec::new();
+    let meanings = entries.as_array().into_iter().flatten().flat_map(|entry| entry["meanings"].as_array().into_iter().flatten());
+    for meaning in meanings {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(meaning["partOfSpeech"].as_str().unwrap_or("").to_string());
+        for (i, definition) in meaning["definitions"].as_array().into_iter().flatten().enumerate() {
+            lines.push(format!("  {}. {}", i + 1, definition["definition"].as_str().unwrap_or("")));
+            if let Some(example) = definition["example"].as_str() {
+                lines.push(format!("     \"{}\"", example));
+            }
+        }
+    }
+    if lines.is_empty() {
+        return Err(t!("\"{word}\" isn't in the dictionary", word = word));
+    }
+    Ok(Definition { word: word.to_string(), source: "dictionaryapi.dev".to_string(), lines, top: 0 })
+}
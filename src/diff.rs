@@ -0,0 +1,135 @@
+// Line-by-line differences between two versions of a note (:diff)
+//
+// Uses Myers' algorithm - the one behind git diff - which finds the fewest
+// lines to add and remove to turn one text into the other. The view shows
+// the whole note with added lines green ("+") and removed lines red ("-").
+
+pub enum Change {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+impl Change {
+    pub fn is_change(&self) -> bool {
+        !matches!(self, Change::Same(_))
+    }
+}
+
+// Every line of 'old' and 'new' in order, marked with what happened to it
+pub fn diff(old: &[String], new: &[String]) -> Vec<Change> {
+    // Lines shared at the start and end don't need searching through
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut changes: Vec<Change> = old[..prefix].iter().cloned().map(Change::Same).collect();
+    changes.extend(middle(a, b));
+    changes.extend(old[old.len() - suffix..].iter().cloned().map(Change::Same));
+    changes
+}
+
+// Myers' search: v[k] is the furthest x reached on diagonal k (x - y = k)
+// with d edits. Each round's v is kept so the path can be walked back.
+fn middle(a: &[String], b: &[String]) -> Vec<Change> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    if max == 0 {
+        return Vec::new();
+    }
+    let index = |k: isize| (k + max) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            // Come down from diagonal k + 1 (an added line) or across from
+            // k - 1 (a removed one), whichever got further
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back from the end, one edit per round
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) { k + 1 } else { k - 1 };
+        let previous_x = v[index(previous_k)];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            changes.push(Change::Same(a[x as usize - 1].clone()));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == previous_x {
+                changes.push(Change::Added(b[y as usize - 1].clone()));
+            } else {
+                changes.push(Change::Removed(a[x as usize - 1].clone()));
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    changes.reverse();
+    changes
+}
+
+// The diff overlay's contents and where it's scrolled to
+pub struct DiffView {
+    pub label: String, // What the note is compared with
+    pub changes: Vec<Change>,
+    pub top: usize, // Index into changes of the first line shown
+}
+
+impl DiffView {
+    // Opens at the first change
+    pub fn new(label: String, changes: Vec<Change>) -> Self {
+        let top = changes.iter().position(Change::is_change).unwrap_or(0).saturating_sub(2);
+        DiffView { label, changes, top }
+    }
+
+    pub fn counts(&self) -> (usize, usize) {
+        let added = self.changes.iter().filter(|change| matches!(change, Change::Added(_))).count();
+        let removed = self.changes.iter().filter(|change| matches!(change, Change::Removed(_))).count();
+        (added, removed)
+    }
+
+    pub fn scroll(&mut self, lines: isize) {
+        let last = self.changes.len().saturating_sub(1);
+        self.top = self.top.saturating_add_signed(lines).min(last);
+    }
+
+    // The start of the next (or previous) run of changed lines, with a
+    // couple of lines above it for context
+    pub fn next_change(&mut self, forward: bool) {
+        let at = self.top + 2;
+        let starts = (0..self.changes.len())
+            .filter(|&i| self.changes[i].is_change() && (i == 0 || !self.changes[i - 1].is_change()));
+        let target = if forward {
+            starts.filter(|&i| i > at).min()
+        } else {
+            starts.filter(|&i| i < at).max()
+        };
+        if let Some(i) = target {
+            self.top = i.saturating_sub(2);
+        }
+    }
+}
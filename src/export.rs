@@ -0,0 +1,185 @@
+// Daily notes in the formats other journaling apps import (river export
+// dayone / river export jrnl), the reverse of import.rs.
+//
+// A note with "## 08:30 Title" sections, as river import writes them,
+// becomes one entry per section; any other note is a single entry. A
+// trailing line of hashtags becomes the entry's tags.
+
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+
+use crate::front_matter;
+use crate::import::Entry;
+
+// The entries in one daily note
+pub fn entries_from_note(date: NaiveDate, text: &str) -> Vec<Entry> {
+    let text = front_matter::strip(text);
+    let mut lines: Vec<&str> = text.lines().skip_while(|line| line.trim().is_empty()).collect();
+    // The date heading new notes start with says nothing the entry's date doesn't
+    if lines.first().is_some_and(|line| {
+        line.strip_prefix("# ").and_then(|h| NaiveDate::parse_from_str(h.trim(), "%A, %B %d, %Y").ok()) == Some(date)
+    }) {
+        lines.remove(0);
+    }
+
+    // Split at "## HH:MM" headings; whatever comes before the first one is
+    // an entry of its own, timed at the start of the day
+    let mut entries = Vec::new();
+    let mut current = Entry { time: date.and_time(NaiveTime::MIN), title: String::new(), body: String::new(), tags: Vec::new() };
+    for line in lines {
+        if let Some((time, title)) = section_heading(line) {
+            entries.push(current);
+            current = Entry { time: date.and_time(time), title, body: String::new(), tags: Vec::new() };
+        } else {
+            current.body.push_str(line);
+            current.body.push('\n');
+        }
+    }
+    entries.push(current);
+
+    for entry in &mut entries {
+        take_tags(entry);
+    }
+    entries.retain(|entry| !entry.title.is_empty() || !entry.body.trim().is_empty());
+    entries
+}
+
+// "## 08:30 Title" or just "## 08:30"
+fn section_heading(line: &str) -> Option<(NaiveTime, String)> {
+    let rest = line.strip_prefix("## ")?;
+    let (time, title) = rest.split_once(' ').unwrap_or((rest, ""));
+    let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+    Some((time, title.trim().to_string()))
+}
+
+// Move a last line made only of hashtags ("#nature #walks") into tags
+fn take_tags(entry: &mut Entry) {
+    let body = entry.body.trim_end();
+    let (rest, last) = body.rsplit_once('\n').unwrap_or(("", body));
+    let words: Vec<&str> = last.split_whitespace().collect();
+    let all_tags = !words.is_empty()
+        && words.iter().all(|word| word.len() > 1 && word.starts_with('#') && !word[1..].starts_with('#'));
+    if all_tags {
+        entry.tags = words.iter().map(|word| word[1..].to_string()).collect();
+        entry.body = rest.to_string();
+    }
+    entry.body = entry.body.trim().to_string();
+}
+
+// Typing stats for the day an entry was written, stored alongside it in
+// the Day One export under "river". Day One ignores fields it doesn't know.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayStats {
+    pub typing_seconds: u64,
+    pub word_count: u64,
+}
+
+#[derive(Serialize)]
+struct DayOneJournal {
+    metadata: DayOneMetadata,
+    entries: Vec<DayOneEntry>,
+}
+
+#[derive(Serialize)]
+struct DayOneMetadata {
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DayOneEntry {
+    uuid: String,
+    creation_date: String,
+    text: String,
+    tags: Vec<String>,
+    starred: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    river: Option<DayStats>,
+}
+
+// A Day One "Export > JSON" style zip: Journal.json holding every entry.
+// Each day's stats go on its first entry.
+pub fn dayone_zip(days: &[(Vec<Entry>, DayStats)]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for (day, stats) in days {
+        for (i, entry) in day.iter().enumerate() {
+            let mut text = String::new();
+            if !entry.title.is_empty() {
+                text.push_str(&format!("# {}\n", entry.title));
+            }
+            text.push_str(&entry.body);
+            entries.push(DayOneEntry {
+                uuid: uuid(entry),
+                creation_date: utc(entry.time),
+                text,
+                tags: entry.tags.clone(),
+                starred: false,
+                river: (i == 0).then_some(*stats),
+            });
+        }
+    }
+    let journal = DayOneJournal { metadata: DayOneMetadata { version: "1.0" }, entries };
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    let mut zip = zip::ZipWriter::new(&mut buffer);
+    zip.start_file("Journal.json", zip::write::SimpleFileOptions::default())?;
+    zip.write_all(serde_json::to_string_pretty(&journal)?.as_bytes())?;
+    zip.finish()?;
+    Ok(buffer.into_inner())
+}
+
+// A jrnl journal file, which `jrnl --import` reads. jrnl uses the first
+// sentence as the title, so an untitled entry's first line stands in.
+pub fn jrnl_text(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        let (title, body) = if entry.title.is_empty() {
+            let (first, rest) = entry.body.split_once('\n').unwrap_or((&entry.body, ""));
+            (first.to_string(), rest.trim_start().to_string())
+        } else {
+            (entry.title.clone(), entry.body.clone())
+        };
+        out.push_str(&format!("[{}] {}\n", entry.time.format("%Y-%m-%d %H:%M"), title));
+        if !body.is_empty() {
+            out.push_str(&body);
+            out.push('\n');
+        }
+        if !entry.tags.is_empty() {
+            let tags: Vec<String> = entry.tags.iter().map(|tag| format!("@{}", tag)).collect();
+            out.push_str(&tags.join(" "));
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Local time as Day One's UTC timestamp
+fn utc(time: NaiveDateTime) -> String {
+    let utc = match Local.from_local_datetime(&time).earliest() {
+        Some(local) => local.with_timezone(&Utc),
+        // A time skipped by a clock change; near enough
+        None => Utc.from_utc_datetime(&time),
+    };
+    utc.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+// Day One wants 32 hex digits. Deriving them from the entry (with FNV-1a,
+// which unlike Rust's own hasher never changes) means exporting twice gives
+// the same ids, so re-importing updates rather than duplicates.
+fn uuid(entry: &Entry) -> String {
+    let key = format!("{}\n{}\n{}", entry.time, entry.title, entry.body);
+    let mut id = String::new();
+    for salt in [0xcbf29ce484222325u64, 0x84222325cbf29ce4] {
+        let mut hash = salt;
+        for byte in key.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        id.push_str(&format!("{:016X}", hash));
+    }
+    id
+}
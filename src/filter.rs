@@ -0,0 +1,87 @@
+// Running text through shell commands, as in vim:
+//
+//   :!cmd          run cmd and show what it printed
+//   :%!fmt -w 72   replace the whole note with what fmt makes of it
+//   :5,9!sort      the same for lines 5 to 9 (. is the cursor line, $ the last)
+//   :r !date       insert what the command prints below the cursor line
+//
+// In vim mode !{motion} starts a filter over the lines the motion covers
+// (!ip for the paragraph, !! for the line), with the range filled in.
+
+use std::io::Write;
+use std::process::Stdio;
+
+use crate::hooks::shell_command;
+use crate::i18n::t;
+
+// Split "5,9!sort" into the lines it covers (0-based, inclusive) and the
+// command. None if 'cmd' isn't a filter with a range.
+pub fn parse(cmd: &str, current: usize, last: usize) -> Option<(usize, usize, &str)> {
+    let (text, command) = cmd.split_once('!')?;
+    let (first, end) = range(text, current, last)?;
+    Some((first, end, command.trim()))
+}
+
+// A range of lines in front of a command - %, 5, 5,9, .,$ - as the first
+// and last line it covers (0-based, in order). None for anything else,
+// including no range at all.
+pub fn range(text: &str, current: usize, last: usize) -> Option<(usize, usize)> {
+    let (first, end) = match text.trim() {
+        "" => return None,
+        "%" => (0, last),
+        text => match text.split_once(',') {
+            Some((from, to)) => (address(from, current, last)?, address(to, current, last)?),
+            None => {
+                let line = address(text, current, last)?;
+                (line, line)
+            }
+        },
+    };
+    Some((first.min(end), first.max(end)))
+}
+
+// One end of a range: a line number, . or $
+fn address(text: &str, current: usize, last: usize) -> Option<usize> {
+    match text.trim() {
+        "." => Some(current),
+        "$" => Some(last),
+        number => Some(number.parse::<usize>().ok()?.saturating_sub(1).min(last)),
+    }
+}
+
+// Run 'command' with 'input' on its standard input and return what it
+// printed. A command that fails gives its error output instead.
+pub fn run(command: &str, input: &str) -> Result<String, String> {
+    if command.is_empty() {
+        return Err(t!("No command given"));
+    }
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| t!("Couldn't run {command}: {error}", command = command, error = e))?;
+
+    // Written from another thread: a command that prints as it reads would
+    // otherwise fill its output pipe and stop, waiting on us
+    let mut stdin = child.stdin.take();
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            // A command that doesn't read its input may have closed it
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+
+    // Reads what it prints and its errors together, so neither can fill up
+    let output = child.wait_with_output().map_err(|e| format!("{}: {}", command, e))?;
+    let _ = writer.join();
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        let message = errors.lines().next().unwrap_or("").trim().to_string();
+        Err(if message.is_empty() { t!("{command} failed ({status})", command = command, status = output.status) } else { message })
+    }
+}
@@ -0,0 +1,138 @@
+// Fuzzy finder overlay for jumping between notes (:find / Ctrl-P)
+//
+// Notes are matched on their filename (usually the date), the heading on
+// their first line and their :title if they have one, so typing "oct rain" finds the entry whose
+// header mentions October and whose text starts with a rainy-day title.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::titles::TitleStore;
+
+#[derive(Debug, Clone)]
+pub struct NoteEntry {
+    pub path: PathBuf,
+    pub name: String,    // File name without the .md extension
+    pub heading: String, // First line with the leading '#'s removed
+    pub title: Option<String>, // One-line summary from :title
+}
+
+impl NoteEntry {
+    // The text the query is matched against
+    fn haystack(&self) -> String {
+        format!("{} {} {}", self.name, self.heading, self.title.as_deref().unwrap_or(""))
+    }
+
+    // What to show next to the name: the title if there is one
+    pub fn label(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.heading)
+    }
+}
+
+pub struct Finder {
+    pub query: String,
+    entries: Vec<NoteEntry>,
+    pub matches: Vec<usize>, // Indices into entries, best match first
+    pub selected: usize,
+}
+
+impl Finder {
+    pub fn new(notes_dir: &Path) -> Self {
+        let mut finder = Finder {
+            query: String::new(),
+            entries: scan_notes(notes_dir),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        finder.update();
+        finder
+    }
+
+    // Re-filter after the query changes
+    pub fn update(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| fuzzy_score(&self.query, &entry.haystack()).map(|score| (score, i)))
+            .collect();
+        // Highest score first; ties keep the newest note on top
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn entry(&self, match_index: usize) -> Option<&NoteEntry> {
+        self.matches.get(match_index).map(|&i| &self.entries[i])
+    }
+
+    pub fn selected_entry(&self) -> Option<&NoteEntry> {
+        self.entry(self.selected)
+    }
+}
+
+// Every markdown file in the notes directory, newest name first
+pub fn scan_notes(dir: &Path) -> Vec<NoteEntry> {
+    let titles = TitleStore::load();
+    let mut entries: Vec<NoteEntry> = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|ext| ext == "md").unwrap_or(false))
+            .map(|path| {
+                let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                let heading = first_heading(&path);
+                let title = titles.get(&path).map(|t| t.title.clone());
+                NoteEntry { path, name, heading, title }
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    // Daily notes are named YYYY-MM-DD, so reverse name order is newest first
+    entries.sort_by(|a, b| b.name.cmp(&a.name));
+    entries
+}
+
+fn first_heading(path: &Path) -> String {
+    crate::crypto::read_to_string(path)
+        .ok()
+        .and_then(|content| content.lines().next().map(|line| line.trim_start_matches('#').trim().to_string()))
+        .unwrap_or_default()
+}
+
+// Scores 'text' against 'query' as a case-insensitive subsequence match.
+// Returns None when some query character can't be found in order.
+// Consecutive matches and matches at the start of a word score higher,
+// so "mtg" ranks "my tiny garden" (three word starts) above "meeting".
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0i64;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+
+    // Spaces in the query just separate terms
+    for qc in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (pos..text.len()).find(|&i| text[i] == qc)?;
+        score += 1;
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 5; // Consecutive run
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3; // Start of a word
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+
+    // Slightly prefer shorter texts when everything else is equal
+    Some(score * 100 - text.len() as i64)
+}
@@ -0,0 +1,93 @@
+// Folded sections: a heading whose body is hidden, so the heading stands
+// in for the whole section on screen (za in vim mode, :fold otherwise)
+//
+// A section runs from its heading to just before the next heading of the
+// same or a higher level, so folding "## Morning" hides its "### Coffee"
+// subsection too. Which headings are folded is kept with the text (see
+// lines.rs); a FoldMap works out from that which lines are hidden, and
+// converts between buffer lines and the rows they take on screen.
+
+use crate::markdown;
+
+pub struct FoldMap {
+    // (heading, last line) of each closed section, in order and not
+    // overlapping: a fold inside another closed one is left out
+    ranges: Vec<(usize, usize)>,
+    len: usize, // Lines in the buffer
+}
+
+impl FoldMap {
+    pub fn new(lines: &[Vec<char>], folds: &[usize]) -> Self {
+        let headings = markdown::headings(lines);
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &fold in folds {
+            // Hidden by an earlier fold already
+            if ranges.last().is_some_and(|&(_, last)| fold <= last) {
+                continue;
+            }
+            // Text edited since it was folded may no longer be a heading
+            if let Some(last) = section_end(&headings, fold, lines.len()) {
+                if last > fold {
+                    ranges.push((fold, last));
+                }
+            }
+        }
+        FoldMap { ranges, len: lines.len() }
+    }
+
+    // The folded heading hiding line y, if it's hidden
+    pub fn hidden_by(&self, y: usize) -> Option<usize> {
+        self.ranges.iter().find(|&&(heading, last)| heading < y && y <= last).map(|&(heading, _)| heading)
+    }
+
+    // Lines hidden under the heading on line y (0 if it isn't folded)
+    pub fn hidden_under(&self, y: usize) -> usize {
+        self.ranges.iter().find(|&&(heading, _)| heading == y).map_or(0, |&(heading, last)| last - heading)
+    }
+
+    // The screen row of line y, counting from the top of the buffer
+    pub fn row(&self, y: usize) -> usize {
+        let y = self.hidden_by(y).unwrap_or(y);
+        let hidden: usize = self.ranges.iter().filter(|&&(_, last)| last < y).map(|&(heading, last)| last - heading).sum();
+        y - hidden
+    }
+
+    // The line shown 'count' rows below line y (stopping at the last one)
+    pub fn down(&self, y: usize, count: usize) -> usize {
+        let mut y = self.hidden_by(y).unwrap_or(y);
+        for _ in 0..count {
+            let next = y + self.hidden_under(y) + 1;
+            if next >= self.len {
+                break;
+            }
+            y = next;
+        }
+        y
+    }
+
+    // The line shown 'count' rows above line y (stopping at the first)
+    pub fn up(&self, y: usize, count: usize) -> usize {
+        let mut y = self.hidden_by(y).unwrap_or(y);
+        for _ in 0..count {
+            if y == 0 {
+                break;
+            }
+            y = self.hidden_by(y - 1).unwrap_or(y - 1);
+        }
+        y
+    }
+}
+
+// The last line of the section whose heading is on line y, given all the
+// note's headings; None if line y isn't a heading
+pub fn section_end(headings: &[(usize, usize)], y: usize, len: usize) -> Option<usize> {
+    let index = headings.iter().position(|&(line, _)| line == y)?;
+    let level = headings[index].1;
+    let next = headings[index + 1..].iter().find(|&&(_, other)| other <= level);
+    Some(next.map_or(len, |&(line, _)| line) - 1)
+}
+
+// The heading of the section line y is in, if it's in one
+pub fn section_heading(headings: &[(usize, usize)], y: usize) -> Option<usize> {
+    headings.iter().rev().find(|&&(line, _)| line <= y).map(|&(line, _)| line)
+}
@@ -0,0 +1,146 @@
+// Front matter: a block of metadata at the very top of a note, as YAML
+// between "---" lines or TOML between "+++" lines.
+//
+//     ---
+//     title: A long walk
+//     tags: [walks, autumn]
+//     mood: good
+//     goal: 1000
+//     ---
+//
+// Fields are read and written as single "key: value" (TOML: key = value)
+// lines, which is all River needs; anything else in the block, such as
+// nested YAML, is left exactly as it was.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn fence(self) -> &'static str {
+        match self {
+            Format::Yaml => "---",
+            Format::Toml => "+++",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrontMatter {
+    pub format: Format,
+    pub end: usize, // Index of the closing fence line
+}
+
+impl FrontMatter {
+    // True for lines that belong to the block, fences included
+    pub fn contains(&self, y: usize) -> bool {
+        y <= self.end
+    }
+}
+
+fn line_str(line: &[char]) -> String {
+    line.iter().collect::<String>().trim_end().to_string()
+}
+
+// The block at the top of 'lines', if there is one and it's closed
+pub fn find(lines: &[Vec<char>]) -> Option<FrontMatter> {
+    let format = match line_str(lines.first()?).as_str() {
+        "---" => Format::Yaml,
+        "+++" => Format::Toml,
+        _ => return None,
+    };
+    let end = lines.iter().skip(1).position(|line| line_str(line) == format.fence())? + 1;
+    Some(FrontMatter { format, end })
+}
+
+// Splits "key: value" / "key = value" for the block's format
+fn field(line: &str, format: Format) -> Option<(&str, &str)> {
+    let separator = match format {
+        Format::Yaml => ':',
+        Format::Toml => '=',
+    };
+    let (key, value) = line.split_once(separator)?;
+    // Indented lines belong to a nested value, not a field of their own
+    if key.starts_with(char::is_whitespace) || key.trim().is_empty() {
+        return None;
+    }
+    Some((key.trim(), value.trim()))
+}
+
+// A field's value with any surrounding quotes removed
+pub fn get(lines: &[Vec<char>], key: &str) -> Option<String> {
+    let block = find(lines)?;
+    lines[1..block.end].iter().find_map(|line| {
+        let line = line_str(line);
+        let (k, value) = field(&line, block.format)?;
+        (k == key).then(|| value.trim_matches(|c| c == '"' || c == '\'').to_string())
+    })
+}
+
+// Set a field, replacing it if it's already there and adding a YAML block
+// to the top of the note if there isn't one. Returns the line changed.
+pub fn set(lines: &mut Vec<Vec<char>>, key: &str, value: &str) -> usize {
+    let block = match find(lines) {
+        Some(block) => block,
+        None => {
+            lines.insert(0, "---".chars().collect());
+            lines.insert(1, "---".chars().collect());
+            FrontMatter { format: Format::Yaml, end: 1 }
+        }
+    };
+
+    let text = match block.format {
+        Format::Yaml => format!("{}: {}", key, value),
+        Format::Toml => format!("{} = {}", key, toml_value(value)),
+    };
+    let existing = (1..block.end).find(|&y| {
+        let line = line_str(&lines[y]);
+        field(&line, block.format).map(|(k, _)| k == key).unwrap_or(false)
+    });
+    match existing {
+        Some(y) => {
+            lines[y] = text.chars().collect();
+            y
+        }
+        None => {
+            lines.insert(block.end, text.chars().collect());
+            block.end
+        }
+    }
+}
+
+// Numbers, booleans, arrays and already quoted strings go into TOML as
+// they are; anything else is quoted
+fn toml_value(value: &str) -> String {
+    let bare = value.parse::<f64>().is_ok()
+        || value == "true"
+        || value == "false"
+        || value.starts_with('[')
+        || value.starts_with('"');
+    if bare {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+// The text after the front matter, for counting words
+pub fn strip(text: &str) -> &str {
+    let mut lines = text.split_inclusive('\n');
+    let fence = match lines.next().map(str::trim_end) {
+        Some("---") => "---",
+        Some("+++") => "+++",
+        _ => return text,
+    };
+    let mut offset = text.split_inclusive('\n').next().map(str::len).unwrap_or(0);
+    for line in lines {
+        offset += line.len();
+        if line.trim_end() == fence {
+            return &text[offset..];
+        }
+    }
+    // Never closed, so it's not front matter after all
+    text
+}
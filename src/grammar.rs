@@ -0,0 +1,149 @@
+// Grammar and style checking with LanguageTool
+//
+// Off unless languagetool_url in config.toml points at a LanguageTool
+// server - normally one running on this machine, started with
+//   java -cp languagetool-server.jar org.languagetool.server.HTTPServer --port 8081
+// The note is sent after each save (and on :grammar), on a background
+// thread so typing never waits for it. Front matter and code blocks are
+// sent as markup, which LanguageTool skips. The issues it finds are
+// underlined; ]g / [g in vim mode (F7 without) go to the next or previous
+// one and show what's wrong in the message line.
+
+use serde::Deserialize;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::front_matter;
+use crate::i18n::t;
+use crate::markdown;
+use crate::vim::Pos;
+
+pub struct Issue {
+    pub start: Pos,
+    pub end: Pos,               // Exclusive, on the same line as start
+    pub text: Vec<char>,        // What was flagged, to tell when it's been edited away
+    pub message: String,        // LanguageTool's explanation
+    pub replacements: Vec<String>,
+}
+
+impl Issue {
+    // Whether the flagged text is still where it was
+    pub fn is_current(&self, lines: &[Vec<char>]) -> bool {
+        lines.get(self.start.y).and_then(|line| line.get(self.start.x..self.end.x)) == Some(self.text.as_slice())
+    }
+
+    // For the message line: "Possible typo (try: their, there)"
+    pub fn describe(&self) -> String {
+        match self.replacements.as_slice() {
+            [] => self.message.clone(),
+            list => t!("{message} (try: {replacements})", message = self.message, replacements = list.iter().take(3).cloned().collect::<Vec<_>>().join(", ")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    matches: Vec<Match>,
+}
+
+#[derive(Deserialize)]
+struct Match {
+    message: String,
+    offset: usize, // In UTF-16 code units, as Java counts them
+    length: usize,
+    replacements: Vec<Replacement>,
+}
+
+#[derive(Deserialize)]
+struct Replacement {
+    value: String,
+}
+
+// Check 'lines' on another thread; the issues arrive on the receiver
+pub fn spawn_check(url: &str, language: &str, lines: Vec<Vec<char>>) -> Receiver<Result<Vec<Issue>, String>> {
+    let (sender, receiver) = mpsc::channel();
+    let url = format!("{}/v2/check", url.trim_end_matches('/'));
+    let language = language.to_string();
+    thread::spawn(move || {
+        let _ = sender.send(check(&url, &language, &lines));
+    });
+    receiver
+}
+
+fn check(url: &str, language: &str, lines: &[Vec<char>]) -> Result<Vec<Issue>, String> {
+    let data = serde_json::json!({ "annotation": annotate(lines) }).to_string();
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .post(url)
+        .form(&[("language", language), ("data", data.as_str())])
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("LanguageTool: {}", e))?;
+    let response: Response = response.json().map_err(|e| format!("LanguageTool: {}", e))?;
+
+    let places = utf16_positions(lines);
+    let issues = response
+        .matches
+        .into_iter()
+        .filter_map(|found| {
+            let start = *places.get(found.offset)?;
+            let end = *places.get(found.offset + found.length)?;
+            // Issues across a line break are rare; mark the first line's part
+            let end = if end.y == start.y { end } else { Pos::new(lines[start.y].len(), start.y) };
+            Some(Issue {
+                text: lines[start.y][start.x..end.x].to_vec(),
+                start,
+                end,
+                message: found.message,
+                replacements: found.replacements.into_iter().map(|r| r.value).collect(),
+            })
+        })
+        .filter(|issue| issue.end.x > issue.start.x)
+        .collect();
+    Ok(issues)
+}
+
+// The note as LanguageTool's annotated text: prose as "text", front matter
+// and code blocks as "markup" it doesn't check but still counts offsets in
+fn annotate(lines: &[Vec<char>]) -> Vec<serde_json::Value> {
+    let front = front_matter::find(lines);
+    let mut parts: Vec<(bool, String)> = Vec::new(); // (is markup, text)
+    let mut in_fence = false;
+    for (y, line) in lines.iter().enumerate() {
+        let fence = markdown::is_fence(line);
+        let markup = front.as_ref().is_some_and(|block| block.contains(y)) || fence || in_fence;
+        if fence {
+            in_fence = !in_fence;
+        }
+        let mut text: String = line.iter().collect();
+        if y + 1 < lines.len() {
+            text.push('\n');
+        }
+        match parts.last_mut() {
+            Some((kind, run)) if *kind == markup => run.push_str(&text),
+            _ => parts.push((markup, text)),
+        }
+    }
+    parts
+        .into_iter()
+        .map(|(markup, text)| if markup { serde_json::json!({ "markup": text }) } else { serde_json::json!({ "text": text }) })
+        .collect()
+}
+
+// Where each UTF-16 offset into the joined text falls, one past the end included
+fn utf16_positions(lines: &[Vec<char>]) -> Vec<Pos> {
+    let mut places = Vec::new();
+    for (y, line) in lines.iter().enumerate() {
+        for (x, c) in line.iter().enumerate() {
+            for _ in 0..c.len_utf16() {
+                places.push(Pos::new(x, y));
+            }
+        }
+        places.push(Pos::new(line.len(), y)); // The newline
+    }
+    places
+}
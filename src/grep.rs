@@ -0,0 +1,61 @@
+// :grep - search every note for a term, like vim's quickfix list
+//
+// Results open in a pane at the bottom of the screen: one line per match
+// with the note's name, the line number and the line itself. Enter opens
+// the note there, and :cn / :cp step through the results afterwards (:copen
+// brings the pane back). The term is plain text, matched ignoring case
+// unless it has a capital letter in it.
+
+use std::path::{Path, PathBuf};
+
+use crate::crypto;
+use crate::finder;
+
+pub struct GrepMatch {
+    pub path: PathBuf,
+    pub name: String, // The note's name, without .md
+    pub line: usize,
+    pub column: usize,
+    pub text: String, // The whole line, for the preview
+}
+
+pub struct GrepList {
+    pub term: String,
+    pub matches: Vec<GrepMatch>,
+    pub selected: usize,
+}
+
+// Every line in the notes in 'dir' with 'term' on it, newest note first
+pub fn search(dir: &Path, term: &str) -> GrepList {
+    let ignore_case = !term.chars().any(char::is_uppercase);
+    let needle: Vec<char> = if ignore_case { term.to_lowercase().chars().collect() } else { term.chars().collect() };
+    let mut matches = Vec::new();
+    for note in finder::scan_notes(dir) {
+        let Ok(content) = crypto::read_to_string(&note.path) else {
+            continue;
+        };
+        for (line, text) in content.lines().enumerate() {
+            let chars: Vec<char> = if ignore_case { text.to_lowercase().chars().collect() } else { text.chars().collect() };
+            if let Some(column) = (0..chars.len()).find(|&x| chars[x..].starts_with(&needle)) {
+                matches.push(GrepMatch { path: note.path.clone(), name: note.name.clone(), line, column, text: text.to_string() });
+            }
+        }
+    }
+    GrepList { term: term.to_string(), matches, selected: 0 }
+}
+
+impl GrepList {
+    pub fn selected_match(&self) -> Option<&GrepMatch> {
+        self.matches.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
@@ -0,0 +1,259 @@
+// :help and F1 - a cheat sheet of the keys for the mode you're in, your
+// own [keys] and [commands] from config.toml, and what each : command does.
+// ":help define" just shows that command's line.
+//
+// The tables below are written by hand; when a key or command is added,
+// it goes here too.
+
+use crate::config::Config;
+use crate::keys;
+
+// (keys, what they do). Single keys are written the way [keys] names them
+// ("Ctrl-P", "F5") so a binding that takes one over can be spotted.
+pub const NORMAL: &[(&str, &str)] = &[
+    ("h j k l", "left, down, up, right (the arrows work too)"),
+    ("w b e", "next word, previous word, end of word"),
+    (") (", "next / previous sentence"),
+    ("} {", "next / previous paragraph"),
+    ("0 ^ $", "start of line, first non-blank, end of line"),
+    ("gg G", "first line, last line (5G: line 5)"),
+    ("]] [[", "next / previous heading"),
+    ("f t F T", "to / till a character on the line; ; and , repeat"),
+    ("i a I A", "insert before, after, at the start, at the end"),
+    ("o O", "open a line below / above"),
+    ("R", "replace mode: type over the text"),
+    ("x ~", "delete a character, switch its case"),
+    ("r", "replace one character"),
+    ("J", "join lines"),
+    ("v V", "select characters / lines, then an operator (d y c > < u U ~)"),
+    ("viw vap", "select a text object: a word, a paragraph, ..."),
+    ("d c y", "delete, change, copy - then a motion (dw) or a text object (dip)"),
+    ("dd cc yy", "the same for the whole line"),
+    ("C s S", "change to the end of the line, one character, the line"),
+    ("> <", "indent, outdent (>> for one line)"),
+    ("gu gU g~", "lowercase, uppercase, switch case"),
+    ("gl", "link: turn the text into a markdown link"),
+    ("!", "filter lines through a shell command"),
+    ("p P", "paste after / before"),
+    (".", "repeat the last change"),
+    ("/ ? n N", "search forward, backward, next, previous"),
+    ("m'", "set a mark (ma), jump to it ('a, or `a for the exact spot)"),
+    ("za zR zM", "fold the heading, open all, close all"),
+    ("zt zz zb", "scroll so the line is at the top, middle, bottom"),
+    ("zg", "add the word to your spelling list"),
+    ("zs", "synonyms for the word"),
+    ("K", "look the word up in the dictionary"),
+    ("]g [g", "next / previous grammar issue"),
+    ("Ctrl-D Ctrl-U", "half a page down / up"),
+    ("Ctrl-E Ctrl-Y", "scroll a line down / up"),
+    ("Ctrl-P", "find a note"),
+    ("Ctrl-T", "tick or untick a checkbox"),
+    ("Ctrl-W", "next window"),
+    ("Ctrl-S", "save now"),
+    ("Ctrl-Q", "quit"),
+    ("F1", "this help"),
+    (":", "command line - see Commands below"),
+];
+
+pub const INSERT: &[(&str, &str)] = &[
+    ("Esc", "back to normal mode"),
+    ("Ctrl-N Ctrl-P", "complete the word (from this note and recent ones)"),
+    ("Ctrl-T", "tick or untick a checkbox"),
+    ("Ctrl-V", "type the next character as it is (no abbreviation)"),
+    ("Tab", "indent, or the next snippet field"),
+    ("F5", "insert the time"),
+    ("Ctrl-S", "save now"),
+    ("F1", "this help"),
+];
+
+pub const STANDARD: &[(&str, &str)] = &[
+    ("Arrows, Home, End", "move (Home again: start of the indent)"),
+    ("Ctrl-Left Ctrl-Right", "a word at a time (Alt-B and Alt-F too)"),
+    ("Ctrl-A Ctrl-E", "start / end of the line"),
+    ("Ctrl-W", "delete the word before the cursor"),
+    ("Ctrl-Backspace", "delete the word before (Ctrl-Delete: after)"),
+    ("Ctrl-K", "delete to the end of the line"),
+    ("Ctrl-N", "complete the word"),
+    ("Ctrl-V", "type the next character as it is (no abbreviation)"),
+    ("Tab", "indent, or the next snippet field"),
+    ("Ctrl-P", "find a note"),
+    ("Ctrl-T", "tick or untick a checkbox"),
+    ("Alt-K", "link the word to a URL"),
+    ("F5", "insert the time"),
+    ("F7", "next grammar issue"),
+    ("Shift-F7", "synonyms for the word"),
+    ("Ctrl-S", "save now"),
+    ("Ctrl-Q", "quit"),
+    ("F1", "this help"),
+];
+
+// (command as typed, what it does), in alphabetical order
+pub const COMMANDS: &[(&str, &str)] = &[
+    (":analyze", "most used words and close repeats, in a scratch buffer"),
+    (":b <n>  :bn  :bp  :ls", "go to buffer n, the next, the previous; list them"),
+    (":cn  :cp  :copen  :cclose", "step through :grep results, show or hide them"),
+    (":count", "words, characters, sentences and paragraphs (of the selection from visual mode)"),
+    (":define [word]", "what the word means (the one under the cursor without one)"),
+    (":diff [file]", "changes since the last save, or against another file"),
+    (":e <path>", "open a file"),
+    (":find", "find a note by name or title"),
+    (":fold [all|none]", "fold the heading under the cursor, or all of them"),
+    (":goal [words]", "show, or set this note's word goal"),
+    (":grammar", "check grammar now (needs languagetool_url)"),
+    (":grep <term>", "search every note"),
+    (":help [command]", "this help, or one command's line"),
+    (":link <url>", "link the word under the cursor"),
+    (":meta [key]", "the front matter, or one field (:meta set <key> <value> changes it)"),
+    (":now", "insert the time"),
+    (":only  :close", "keep only this window, close this one"),
+    (":outline", "the headings, to jump between"),
+    (":prompt", "today's writing prompt"),
+    (":q  :w [path]  :wq", "quit, save, save and quit"),
+    (":r <path>", "insert a file (or :r !command for its output)"),
+    (":readability [highlight]", "grade level and long sentences"),
+    (":reload-config", "read config.toml again"),
+    (":s/old/new/g", "replace (with ranges like :%s, and a preview)"),
+    (":scratch", "a scratch buffer that's never saved"),
+    (":set key=value", "change a setting until river quits (:set key? shows it)"),
+    (":snippet [name]", "insert a snippet, or list them"),
+    (":sp  :vs [path]", "split the window across / side by side"),
+    (":spell", "spell checking on or off"),
+    (":syn", "synonyms for the word under the cursor"),
+    (":title [text]", "give the note a title (the AI suggests one without text)"),
+    (":toggle", "tick or untick a checkbox"),
+    (":vault [name]", "list profiles, or switch to one"),
+    (":view [date|path]", "read-only: toggle it, or open a note that way"),
+    (":!command", "run a shell command (:{range}!command filters lines)"),
+];
+
+// The popup: built once when it opens, scrolled with j/k
+pub struct Help {
+    pub title: String,
+    entries: Vec<Entry>,
+    pub top: usize, // First entry showing
+}
+
+enum Entry {
+    Heading(String),
+    Item(String, String),
+    Blank,
+}
+
+impl Help {
+    // 'mode' names the mode for the title; 'table' is its keys
+    pub fn new(mode: &str, table: &[(&str, &str)], config: &Config) -> Self {
+        let mut entries = vec![Entry::Heading(format!("Keys ({})", mode))];
+        // A key in [keys] replaces river's own, so it's listed with yours instead
+        let taken = |key: &str| keys::parse(key).is_some() && config.keys.keys().any(|name| keys::parse(name) == keys::parse(key));
+        for (key, what) in table.iter().filter(|(key, _)| !taken(key)) {
+            entries.push(Entry::Item(key.to_string(), what.to_string()));
+        }
+
+        if !config.keys.is_empty() {
+            entries.push(Entry::Blank);
+            entries.push(Entry::Heading("Your keys ([keys] in config.toml)".to_string()));
+            for (key, command) in &config.keys {
+                entries.push(Entry::Item(key.clone(), format!(":{}", command.trim().trim_start_matches(':'))));
+            }
+        }
+
+        entries.push(Entry::Blank);
+        entries.push(Entry::Heading("Commands".to_string()));
+        for (command, what) in COMMANDS {
+            entries.push(Entry::Item(command.to_string(), what.to_string()));
+        }
+        if !config.commands.is_empty() {
+            entries.push(Entry::Blank);
+            entries.push(Entry::Heading("Your commands ([commands] in config.toml)".to_string()));
+            for (name, command) in &config.commands {
+                entries.push(Entry::Item(format!(":{}", name), command.steps().join(" | ")));
+            }
+        }
+        Help { title: format!("Help - {}", mode), entries, top: 0 }
+    }
+
+    // The entries from 'top' on, as rows 'width' columns wide: the keys in
+    // a column of their own, what they do wrapped beside them
+    pub fn rows(&self, width: usize) -> Vec<String> {
+        // Each section's key column fits its own keys; a key too long for
+        // it goes on a row of its own
+        let mut key_widths = Vec::with_capacity(self.entries.len());
+        for (i, entry) in self.entries.iter().enumerate() {
+            let section_width = match entry {
+                Entry::Heading(_) => self.entries[i + 1..]
+                    .iter()
+                    .take_while(|entry| !matches!(entry, Entry::Heading(_)))
+                    .filter_map(|entry| match entry {
+                        Entry::Item(key, _) => Some(key.chars().count()),
+                        _ => None,
+                    })
+                    .filter(|len| *len <= 18)
+                    .max()
+                    .unwrap_or(0)
+                    .min(width / 2),
+                _ => key_widths.last().copied().unwrap_or(0),
+            };
+            key_widths.push(section_width);
+        }
+
+        let mut rows = Vec::new();
+        let start = self.top.min(self.entries.len());
+        for (entry, &key_width) in self.entries[start..].iter().zip(&key_widths[start..]) {
+            match entry {
+                Entry::Heading(heading) => rows.push(heading.clone()),
+                Entry::Blank => rows.push(String::new()),
+                Entry::Item(key, what) => {
+                    let text_width = width.saturating_sub(key_width + 4).max(10);
+                    let mut lines = wrap(what, text_width).into_iter();
+                    let first = lines.next().unwrap_or_default();
+                    if key.chars().count() > key_width {
+                        rows.push(format!("  {}", key));
+                        rows.push(format!("  {:key_width$}  {}", "", first));
+                    } else {
+                        rows.push(format!("  {:key_width$}  {}", key, first));
+                    }
+                    for line in lines {
+                        rows.push(format!("  {:key_width$}  {}", "", line));
+                    }
+                }
+            }
+        }
+        rows
+    }
+
+    pub fn scroll(&mut self, by: isize) {
+        let last = self.entries.len().saturating_sub(1) as isize;
+        self.top = (self.top as isize + by).clamp(0, last) as usize;
+    }
+}
+
+// :help <command> - the line for one command, found by any of its names
+pub fn command(name: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches(':');
+    COMMANDS
+        .iter()
+        .find(|(command, _)| {
+            command
+                .split_whitespace()
+                .filter_map(|word| word.strip_prefix(':'))
+                .any(|word| word == name || word.split(['/', '!']).next() == Some(name))
+        })
+        .map(|(command, what)| format!("{} - {}", command, what))
+}
+
+// Words of 'text' in lines of up to 'width' characters
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
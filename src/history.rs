@@ -0,0 +1,80 @@
+// Earlier versions of each note, for :diff
+//
+// Before a save overwrites a file, the version on disk is copied into the
+// data directory (history/<the file's path>/<when it was written>), at most
+// one copy every ten minutes so autosave doesn't fill the folder. The copy
+// is byte for byte, so encrypted notes stay encrypted.
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+const GAP_MINUTES: i64 = 10;
+const KEEP: usize = 200; // Oldest copies beyond this are deleted
+const STAMP: &str = "%Y-%m-%d_%H%M%S";
+
+// Keep the version of 'filename' that's about to be overwritten
+pub fn snapshot(filename: &str) -> io::Result<()> {
+    let Some(written) = modified(Path::new(filename)) else {
+        return Ok(()); // A new file has no earlier version
+    };
+    let dir = dir(filename);
+    let mut versions = versions(&dir);
+    if versions.last().is_some_and(|(stamp, _)| (written - *stamp).num_minutes() < GAP_MINUTES) {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir)?;
+    let copy = dir.join(written.format(STAMP).to_string());
+    fs::copy(filename, &copy)?;
+    versions.push((written, copy));
+
+    for (_, path) in &versions[..versions.len().saturating_sub(KEEP)] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// The newest version of 'filename' written at or before 'time': a copy
+// from the history, or the file itself if it hasn't been saved since
+pub fn version_at(filename: &str, time: NaiveDateTime) -> Option<(NaiveDateTime, PathBuf)> {
+    let mut versions = versions(&dir(filename));
+    if let Some(written) = modified(Path::new(filename)) {
+        versions.push((written, PathBuf::from(filename)));
+    }
+    versions.into_iter().filter(|(stamp, _)| *stamp <= time).max_by_key(|(stamp, _)| *stamp)
+}
+
+// Copies kept for one file, oldest first
+fn versions(dir: &Path) -> Vec<(NaiveDateTime, PathBuf)> {
+    let mut versions: Vec<(NaiveDateTime, PathBuf)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let stamp = NaiveDateTime::parse_from_str(&entry.file_name().to_string_lossy(), STAMP).ok()?;
+            Some((stamp, entry.path()))
+        })
+        .collect();
+    versions.sort();
+    versions
+}
+
+// One folder per file, named after its full path like vim's undodir:
+// /home/me/notes/a.md keeps its copies in history/%home%me%notes%a.md
+fn dir(filename: &str) -> PathBuf {
+    let path = fs::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename));
+    let name: String = path
+        .to_string_lossy()
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':') { '%' } else { c })
+        .collect();
+    config::data_dir().join("history").join(name)
+}
+
+fn modified(path: &Path) -> Option<NaiveDateTime> {
+    let time = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    Some(DateTime::<Local>::from(time).naive_local())
+}
@@ -0,0 +1,176 @@
+// Shell commands run on editor events (on_save, on_goal_reached, on_quit
+// in the config), e.g. to tell a habit tracker the goal was met.
+//
+// A hook gets the details both as environment variables and as JSON on
+// its standard input, whichever is easier to use from the command:
+//
+//     RIVER_EVENT=goal_reached RIVER_DATE=2024-05-01 RIVER_FILE=/notes/2024-05-01.md
+//     RIVER_WORDS=512 RIVER_GOAL=500 RIVER_MINUTES=23
+//
+// Hooks run in the background with their output thrown away, so a slow or
+// noisy command can't freeze or scribble over the editor. The exception is
+// on_quit: river waits for it, since a terminal that closes along with the
+// editor would take the hook down with it. For a webhook,
+// use curl: on_goal_reached = "curl -s -d @- https://example.com/hook"
+//
+// Hook scripts do the same without touching the config: any executable in
+// ~/.config/river/hooks named after an event - note_created, note_saved,
+// goal_reached or app_quit - optionally followed by a dot or dash and
+// anything else (note_created.sh, goal_reached-habits.py). Several for one
+// event run in name order. What a note_created or goal_reached script
+// prints is added to the end of the note, so a note_created script can
+// put the weather at the top of each day.
+
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::Sender;
+
+use crate::i18n::t;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookInfo {
+    pub event: &'static str, // "save", "goal_reached" or "quit"; a script's own event name
+    pub date: String,        // YYYY-MM-DD
+    pub file: String,
+    pub words: usize,
+    pub goal: usize,
+    pub minutes: u64, // Typing time today
+}
+
+// Run a command line through the platform's shell, so pipes and quotes work
+pub fn shell_command(command: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+// Start the hook, waiting for it to finish only if 'wait' is set. An empty
+// command means no hook is set. Errors are returned for the status bar.
+pub fn run(command: &str, info: &HookInfo, wait: bool) -> Result<(), String> {
+    if command.trim().is_empty() {
+        return Ok(());
+    }
+    let mut child = with_info(shell_command(command), info)
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| t!("Couldn't run the {event} hook: {error}", event = info.event, error = e))?;
+
+    send_info(&mut child, info);
+    if wait {
+        let _ = child.wait();
+        return Ok(());
+    }
+    // Collect the exit status on another thread so it doesn't linger as a
+    // zombie process
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// The event details as RIVER_* variables, with stdin ready for the JSON
+fn with_info(mut command: Command, info: &HookInfo) -> Command {
+    command
+        .env("RIVER_EVENT", info.event)
+        .env("RIVER_DATE", &info.date)
+        .env("RIVER_FILE", &info.file)
+        .env("RIVER_WORDS", info.words.to_string())
+        .env("RIVER_GOAL", info.goal.to_string())
+        .env("RIVER_MINUTES", info.minutes.to_string())
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null());
+    command
+}
+
+fn send_info(child: &mut std::process::Child, info: &HookInfo) {
+    if let Some(mut stdin) = child.stdin.take() {
+        let json = serde_json::to_string(info).unwrap_or_default();
+        // A hook that ignores its input may have exited already, so a
+        // broken pipe here is fine
+        let _ = stdin.write_all(json.as_bytes());
+    }
+}
+
+// Text a hook script printed, for the note it was run for
+pub struct ScriptOutput {
+    pub file: String,
+    pub text: String,
+}
+
+// ~/.config/river/hooks (or the platform's config directory)
+pub fn dir() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("hooks");
+    path
+}
+
+// The scripts for 'event', in name order
+pub fn scripts(event: &str) -> Vec<PathBuf> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let rest = name.strip_prefix(event);
+            rest.is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '-'])) && is_executable(path)
+        })
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+// Windows has no execute bit; any file will do
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+// Run the scripts one after another and return what they printed, joined.
+// A script that fails is skipped; its error is returned alongside.
+pub fn run_scripts(scripts: &[PathBuf], info: &HookInfo) -> (String, Option<String>) {
+    let mut printed = String::new();
+    let mut error = None;
+    for script in scripts {
+        let child = with_info(Command::new(script), info).stdout(Stdio::piped()).spawn();
+        let output = child.and_then(|mut child| {
+            send_info(&mut child, info);
+            child.wait_with_output()
+        });
+        let name = script.file_name().unwrap_or_default().to_string_lossy();
+        match output {
+            Ok(output) if output.status.success() => printed.push_str(&String::from_utf8_lossy(&output.stdout)),
+            Ok(output) => error = Some(t!("Hook {name} failed ({status})", name = name, status = output.status)),
+            Err(e) => error = Some(t!("Couldn't run hook {name}: {error}", name = name, error = e)),
+        }
+    }
+    (printed, error)
+}
+
+// Run the scripts on another thread. What they print, if anyone wants it,
+// comes back through 'output'.
+pub fn spawn_scripts(scripts: Vec<PathBuf>, info: HookInfo, output: Option<Sender<ScriptOutput>>) {
+    std::thread::spawn(move || {
+        let (text, _) = run_scripts(&scripts, &info);
+        if let Some(output) = output {
+            let _ = output.send(ScriptOutput { file: info.file.clone(), text });
+        }
+    });
+}
@@ -0,0 +1,192 @@
+// River's own words in your language: messages, the status bar and the
+// stats screen
+//
+// The text is written in English where it's used, and t!() looks it up in
+// a catalog for the language in use - a TOML file from the English to
+// the translation:
+//   "Goal: {words} words" = "Ziel: {words} Wörter"
+// The {names} are filled in from t!("Goal: {words} words", words = 500).
+// tn!() is for text that depends on a count, like gettext's ngettext: it
+// takes the English for one and for more, and a catalog gives a table
+// for the count in {n}:
+//   "{n} line" = { one = "{n} Zeile", other = "{n} Zeilen" }
+//
+// German, French and Spanish come with river (locales/ in the source).
+// locales/<lang>.toml in the config directory adds another language, or
+// changes some of a bundled one's text; anything a catalog leaves out
+// stays in English. The language is `language` in config.toml, or when
+// that's empty whichever LC_ALL, LC_MESSAGES or LANG names.
+
+use chrono::{Datelike, NaiveDate};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::config::Config;
+
+const BUNDLED: [(&str, &str); 3] = [
+    ("de", include_str!("../locales/de.toml")),
+    ("fr", include_str!("../locales/fr.toml")),
+    ("es", include_str!("../locales/es.toml")),
+];
+
+struct Catalog {
+    language: String,
+    texts: toml::Table,
+}
+
+// Set by use_language(); until then everything is in English
+static CATALOG: RwLock<Option<Catalog>> = RwLock::new(None);
+
+// t!("text") or t!("text with {name}", name = value)
+macro_rules! t {
+    ($text:expr) => {
+        $crate::i18n::text($text, &[])
+    };
+    ($text:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::text($text, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+
+// tn!(count, "{n} line", "{n} lines"), with more {names} after if needed
+macro_rules! tn {
+    ($n:expr, $one:expr, $other:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::plural(($n) as u64, $one, $other, &[$((stringify!($name), $value.to_string())),*])
+    };
+}
+
+pub(crate) use {t, tn};
+
+// Switch to the language 'config' asks for. Called at startup and
+// whenever the settings change.
+pub fn use_language(config: &Config) {
+    let language = language(config);
+    let texts = load(&language).unwrap_or_default();
+    if let Ok(mut catalog) = CATALOG.write() {
+        *catalog = Some(Catalog { language, texts });
+    }
+}
+
+pub fn text(text: &str, args: &[(&str, String)]) -> String {
+    let found = lookup(text, |value| value.as_str().map(str::to_string));
+    fill(&found.unwrap_or_else(|| text.to_string()), args)
+}
+
+pub fn plural(n: u64, one: &str, other: &str, args: &[(&str, String)]) -> String {
+    let is_one = CATALOG.read().ok().and_then(|catalog| catalog.as_ref().map(|catalog| one_form(&catalog.language, n)));
+    let found = lookup(one, |value| match value {
+        toml::Value::String(text) => Some(text.clone()),
+        toml::Value::Table(forms) => {
+            let form = if is_one.unwrap_or(n == 1) { "one" } else { "other" };
+            forms.get(form).and_then(toml::Value::as_str).map(str::to_string)
+        }
+        _ => None,
+    });
+    let english = if n == 1 { one } else { other };
+    let mut args = args.to_vec();
+    args.push(("n", n.to_string()));
+    fill(&found.unwrap_or_else(|| english.to_string()), &args)
+}
+
+// "Oct 17", with the month's short name from the catalog, in its order
+pub fn short_date(date: NaiveDate) -> String {
+    t!("{month} {day}", month = t!(&date.format("%b").to_string()), day = date.day())
+}
+
+// "October 2026"
+pub fn month_year(date: NaiveDate) -> String {
+    t!("{month} {year}", month = t!(&date.format("%B").to_string()), year = date.year())
+}
+
+fn lookup(key: &str, pick: impl Fn(&toml::Value) -> Option<String>) -> Option<String> {
+    let catalog = CATALOG.read().ok()?;
+    catalog.as_ref()?.texts.get(key).and_then(pick)
+}
+
+fn fill(text: &str, args: &[(&str, String)]) -> String {
+    let mut text = text.to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+// Whether 'n' takes the singular: French counts 0 as one too
+fn one_form(language: &str, n: u64) -> bool {
+    match base(language) {
+        "fr" => n < 2,
+        _ => n == 1,
+    }
+}
+
+fn language(config: &Config) -> String {
+    if !config.language.is_empty() {
+        return normalize(&config.language);
+    }
+    let from_env = ["LC_ALL", "LC_MESSAGES", "LANG"].iter().find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()));
+    normalize(&from_env.unwrap_or_default())
+}
+
+// "de_DE" from "de_DE.UTF-8" or "de-DE"; "en" for C and POSIX
+fn normalize(name: &str) -> String {
+    let name = name.split(['.', '@']).next().unwrap_or_default().replace('-', "_");
+    match name.as_str() {
+        "" | "C" | "POSIX" => "en".to_string(),
+        _ => name,
+    }
+}
+
+fn base(language: &str) -> &str {
+    language.split('_').next().unwrap_or(language)
+}
+
+// The catalog for 'language': the bundled one for its base language, then
+// the user's for the base, then the user's for the exact one ("pt" then
+// "pt_BR"), each filling in or replacing the one before
+fn load(language: &str) -> Result<toml::Table, String> {
+    let mut texts = toml::Table::new();
+    let base = base(language);
+    if let Some((_, bundled)) = BUNDLED.iter().find(|(name, _)| *name == base) {
+        texts.extend(bundled.parse::<toml::Table>().map_err(|e| e.to_string())?);
+    }
+    let mut names = vec![base];
+    if language != base {
+        names.push(language);
+    }
+    for name in names {
+        let path = user_catalog(name);
+        if let Ok(contents) = fs::read_to_string(&path) {
+            let user: toml::Table = contents.parse().map_err(|e: toml::de::Error| format!("{}: {}", path.display(), e.message()))?;
+            texts.extend(user);
+        }
+    }
+    Ok(texts)
+}
+
+fn user_catalog(language: &str) -> PathBuf {
+    let config_path = Config::config_path();
+    let dir = config_path.parent().map(PathBuf::from).unwrap_or_default();
+    dir.join("locales").join(format!("{}.toml", language))
+}
+
+// What's wrong with language = "..." in config.toml, if anything: a
+// language river has no catalog for, or a catalog that can't be read
+pub fn problem(language: &str) -> Option<String> {
+    if language.is_empty() {
+        return None;
+    }
+    let name = normalize(language);
+    let base = base(&name);
+    let known = base == "en" || BUNDLED.iter().any(|(bundled, _)| *bundled == base) || user_catalog(base).is_file() || user_catalog(&name).is_file();
+    if !known {
+        let bundled: Vec<&str> = BUNDLED.iter().map(|(name, _)| *name).collect();
+        return Some(format!(
+            "no catalog for \"{}\" - river has en, {}; others go in {}",
+            language,
+            bundled.join(", "),
+            user_catalog(&name).display()
+        ));
+    }
+    load(&name).err()
+}
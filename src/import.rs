@@ -0,0 +1,282 @@
+// Bringing entries in from other journaling apps (river import)
+//
+// - Day One: the .zip its "Export > JSON" produces, holding one JSON file
+//   per journal
+// - jrnl: the plain text journal file, where each entry starts with a
+//   line like "[2024-05-01 08:30] Title" (older versions leave out the
+//   brackets)
+//
+// Each entry becomes a "## 08:30 Title" section in the daily note for its
+// date. Several entries on one day share the note, and a note that already
+// exists gets the new sections added at the end.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub time: NaiveDateTime, // Local time the entry was written
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+}
+
+impl Entry {
+    // The entry as a section of a daily note
+    fn to_markdown(&self) -> String {
+        let mut section = format!("## {}", self.time.format("%H:%M"));
+        if !self.title.is_empty() {
+            section.push(' ');
+            section.push_str(&self.title);
+        }
+        section.push_str("\n\n");
+        if !self.body.trim().is_empty() {
+            section.push_str(self.body.trim());
+            section.push('\n');
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("#{}", tag.replace(' ', "-"))).collect();
+            section.push('\n');
+            section.push_str(&tags.join(" "));
+            section.push('\n');
+        }
+        section
+    }
+}
+
+#[derive(Deserialize)]
+struct DayOneJournal {
+    entries: Vec<DayOneEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DayOneEntry {
+    creation_date: String, // UTC, e.g. "2024-05-01T07:30:00Z"
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// Every entry in a Day One JSON export
+pub fn read_dayone(path: &Path) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        // Photos and other attachments sit alongside the JSON
+        if !file.name().ends_with(".json") {
+            continue;
+        }
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let journal: DayOneJournal = serde_json::from_str(&contents)
+            .map_err(|e| format!("{} isn't a Day One journal: {}", file.name(), e))?;
+        for entry in journal.entries {
+            // Day One keeps UTC; the note goes on the day it was locally
+            let time = DateTime::parse_from_rfc3339(&entry.creation_date)
+                .map_err(|e| format!("Bad date '{}': {}", entry.creation_date, e))?
+                .with_timezone(&Local)
+                .naive_local();
+            let (title, body) = split_title(&unescape_dayone(&entry.text));
+            entries.push(Entry { time, title, body, tags: entry.tags });
+        }
+    }
+    Ok(entries)
+}
+
+// Day One's first line is the entry's title, usually as a "# " heading
+fn split_title(text: &str) -> (String, String) {
+    let text = text.trim_start();
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    match first.strip_prefix('#') {
+        Some(heading) => (heading.trim_start_matches('#').trim().to_string(), rest.to_string()),
+        None => (String::new(), text.to_string()),
+    }
+}
+
+// Day One backslash-escapes punctuation that's harmless in a journal
+// ("1\. First", "Hello\!"), and points at photos with a dayone-moment://
+// link that means nothing outside the app
+fn unescape_dayone(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek().is_some_and(|next| matches!(next, '.' | '!' | '-' | '(' | ')' | '+' | '#')) {
+            continue;
+        }
+        out.push(c);
+    }
+    out.lines()
+        .filter(|line| !line.trim_start().starts_with("![](dayone-moment:"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Every entry in a jrnl journal file
+pub fn read_jrnl(path: &Path) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in contents.lines() {
+        match jrnl_header(line) {
+            Some((time, title)) => entries.push(Entry { time, title, body: String::new(), tags: Vec::new() }),
+            None => match entries.last_mut() {
+                Some(entry) => {
+                    entry.body.push_str(line);
+                    entry.body.push('\n');
+                }
+                None if line.trim().is_empty() => {}
+                None => return Err(format!("{} doesn't look like a jrnl journal", path.display()).into()),
+            },
+        }
+    }
+    for entry in &mut entries {
+        take_jrnl_tags(entry);
+    }
+    Ok(entries)
+}
+
+// jrnl tags are "@words"; a last line of nothing else becomes the tags
+fn take_jrnl_tags(entry: &mut Entry) {
+    let body = entry.body.trim_end();
+    let (rest, last) = body.rsplit_once('\n').unwrap_or(("", body));
+    let words: Vec<&str> = last.split_whitespace().collect();
+    if !words.is_empty() && words.iter().all(|word| word.len() > 1 && word.starts_with('@')) {
+        entry.tags = words.iter().map(|word| word[1..].to_string()).collect();
+        entry.body = rest.to_string();
+    }
+}
+
+// "[2024-05-01 08:30] Title", "[2024-05-01 08:30:00 AM] Title" or
+// "2024-05-01 08:30 Title". A trailing " *" marks a starred entry, which
+// river has no use for.
+fn jrnl_header(line: &str) -> Option<(NaiveDateTime, String)> {
+    let (stamp, title) = match line.strip_prefix('[') {
+        Some(rest) => rest.split_once("] ")
+            .or_else(|| rest.strip_suffix(']').map(|stamp| (stamp, "")))?,
+        None => {
+            // The date and time are the first two words
+            let mut words = line.splitn(3, ' ');
+            let date = words.next()?;
+            let time = words.next()?;
+            let title = words.next().unwrap_or("");
+            let end = date.len() + 1 + time.len();
+            (&line[..end], title)
+        }
+    };
+    const FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %I:%M %p", "%Y-%m-%d %I:%M:%S %p"];
+    let time = FORMATS.iter().find_map(|format| NaiveDateTime::parse_from_str(stamp, format).ok())?;
+    let title = title.trim().trim_end_matches(" *").trim_end_matches('*').trim().to_string();
+    Some((time, title))
+}
+
+// What an import did
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub created: usize,   // Notes that didn't exist before
+    pub merged: usize,    // Existing notes entries were added to
+    pub skipped: usize,   // Entries already in their note
+}
+
+// Write the entries into daily notes. 'new_note' gives the opening of a
+// note that doesn't exist yet. Importing the same file twice doesn't
+// duplicate anything: entries already in their note are left out.
+pub fn write_entries(
+    notes_dir: &Path,
+    entries: &[Entry],
+    new_note: impl Fn(NaiveDate) -> String,
+) -> std::io::Result<Summary> {
+    fs::create_dir_all(notes_dir)?;
+    let mut by_date: BTreeMap<NaiveDate, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        by_date.entry(entry.time.date()).or_default().push(entry);
+    }
+
+    let mut summary = Summary::default();
+    for (date, mut day) in by_date {
+        day.sort_by_key(|entry| entry.time);
+        let path = notes_dir.join(format!("{}.md", date.format("%Y-%m-%d")));
+        // Only a note that isn't there yet starts afresh: one that can't be
+        // read (encrypted while the key is locked, say) must not be written over
+        let existing = match crate::crypto::read_to_string(&path) {
+            Ok(text) => Some(text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let mut note = existing.clone().unwrap_or_else(|| new_note(date));
+
+        let mut added = 0;
+        for entry in day {
+            let section = entry.to_markdown();
+            if note.contains(section.trim()) {
+                summary.skipped += 1;
+                continue;
+            }
+            // A blank line between what's there and the new section
+            let trimmed_len = note.trim_end().len();
+            note.truncate(trimmed_len);
+            if !note.is_empty() {
+                note.push_str("\n\n");
+            }
+            note.push_str(&section);
+            added += 1;
+        }
+
+        if added > 0 {
+            crate::crypto::write(&path, note)?;
+            if existing.is_some() {
+                summary.merged += 1;
+            } else {
+                summary.created += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time: &str) -> Entry {
+        Entry {
+            time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap(),
+            title: "Walk".to_string(),
+            body: "Down by the river.".to_string(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn importing_over_an_unreadable_note_fails_and_leaves_it_alone() {
+        let dir = std::env::temp_dir().join(format!("river-import-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("2024-05-01.md");
+        let original = b"\xff\xfe not utf-8".to_vec();
+        fs::write(&path, &original).unwrap();
+
+        let result = write_entries(&dir, &[entry("2024-05-01 09:30")], |_| String::new());
+        let after = fs::read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(after, original);
+    }
+
+    #[test]
+    fn importing_creates_a_missing_note() {
+        let dir = std::env::temp_dir().join(format!("river-import-test-new-{}", std::process::id()));
+        let result = write_entries(&dir, &[entry("2024-05-02 09:30")], |_| "# Thursday\n".to_string());
+        let note = fs::read_to_string(dir.join("2024-05-02.md")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result.unwrap().created, 1);
+        assert!(note.starts_with("# Thursday\n\n## 09:30 Walk"));
+    }
+}
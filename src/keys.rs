@@ -0,0 +1,64 @@
+// Key names for [keys] in config.toml: "F6", "ctrl-g", "alt-t",
+// "ctrl-alt-n". Letters are matched ignoring case; a key river already
+// uses is taken over by the binding.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// The key and modifiers a name stands for, or None if it isn't one
+pub fn parse(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let name = name.trim().to_lowercase();
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name.as_str();
+    loop {
+        if let Some(after) = rest.strip_prefix("ctrl-") {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("alt-") {
+            modifiers |= KeyModifiers::ALT;
+            rest = after;
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        (Some('f'), Some(_)) => KeyCode::F(rest[1..].parse().ok().filter(|n| (1..=12).contains(n))?),
+        _ => return None,
+    };
+    // A bare letter would stop it being typed
+    if modifiers.is_empty() && matches!(code, KeyCode::Char(_)) {
+        return None;
+    }
+    Some((code, modifiers))
+}
+
+// Does the key pressed match the name?
+pub fn matches(name: &str, key: &KeyEvent) -> bool {
+    let Some((code, modifiers)) = parse(name) else {
+        return false;
+    };
+    let pressed = match key.code {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        other => other,
+    };
+    // Shift only matters as part of the character
+    pressed == code && key.modifiers - KeyModifiers::SHIFT == modifiers
+}
+
+// The character a key types, if it types one rather than being a
+// shortcut. Windows reports AltGr as Ctrl+Alt, so Ctrl+Alt with anything
+// but a letter or digit is a character from the keyboard layout - "@" or
+// "{" on a German keyboard, "ę" on a Polish one.
+pub fn typed(key: &KeyEvent) -> Option<char> {
+    let KeyCode::Char(c) = key.code else {
+        return None;
+    };
+    let altgr = KeyModifiers::CONTROL | KeyModifiers::ALT;
+    if !key.modifiers.intersects(altgr) || (key.modifiers.contains(altgr) && !c.is_ascii_alphanumeric()) {
+        Some(c)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,257 @@
+// The text of a buffer: its lines, plus a word count for each line that
+// is kept until the line changes, and which headings are folded.
+//
+// Counting the whole note on every keystroke gets slow for long notes, so
+// each line's count is remembered and only lines that were edited are
+// counted again. Reading goes through Deref to a plain slice of lines;
+// changing a line goes through the methods below, which forget the counts
+// of the lines they touch. That way nothing can edit the text without the
+// cache hearing about it.
+//
+// Folds (see fold.rs) are kept here for the same reason: they're line
+// numbers, and inserting or removing lines above a folded heading has to
+// move its fold along with it.
+
+use std::cell::RefCell;
+use std::ops::{Bound, Deref, Index, IndexMut, Range, RangeBounds, RangeFrom, RangeInclusive, RangeTo};
+
+use crate::front_matter;
+use crate::text_stats;
+
+#[derive(Debug, Default, Clone)]
+pub struct Lines {
+    lines: Vec<Vec<char>>,
+    // RefCell lets word_count(&self) fill in counts
+    counts: RefCell<Counts>,
+    folds: Vec<usize>, // Lines of the folded headings, in order
+}
+
+// One entry per line; None means not counted since it last changed
+#[derive(Debug, Default, Clone)]
+struct Counts {
+    words: Vec<Option<usize>>,
+    total: usize,    // Sum of the known counts
+    unknown: usize,  // Lines with no count
+    markdown: bool,  // The markdown_word_count they were made with
+}
+
+impl Counts {
+    fn new(len: usize, markdown: bool) -> Self {
+        Counts { words: vec![None; len], total: 0, unknown: len, markdown }
+    }
+
+    // Take a line's count out of the totals, as when it's removed
+    fn drop_count(&mut self, count: Option<usize>) {
+        match count {
+            Some(words) => self.total -= words,
+            None => self.unknown -= 1,
+        }
+    }
+}
+
+impl Lines {
+    // Words in the text, front matter left out (see text_stats::count_words).
+    // After an edit only the changed lines are counted; the rest is a sum
+    // kept up to date as lines change.
+    pub fn word_count(&self, markdown: bool) -> usize {
+        let mut counts = self.counts.borrow_mut();
+        // Edits through vec_mut can change the length, and counting
+        // markdown differently changes every count; start over then
+        if counts.words.len() != self.lines.len() || counts.markdown != markdown {
+            *counts = Counts::new(self.lines.len(), markdown);
+        }
+        if counts.unknown > 0 {
+            let mut added = 0;
+            for (count, line) in counts.words.iter_mut().zip(&self.lines) {
+                if count.is_none() {
+                    let words = text_stats::count_line_words(line, markdown);
+                    *count = Some(words);
+                    added += words;
+                }
+            }
+            counts.total += added;
+            counts.unknown = 0;
+        }
+        // Front matter is only a few lines at the top
+        let front_matter: usize = match front_matter::find(&self.lines) {
+            Some(fm) => counts.words[..=fm.end].iter().flatten().sum(),
+            None => 0,
+        };
+        counts.total - front_matter
+    }
+
+    // Any kind of range (a..b, a..=b, ...) as a..b
+    fn range(&self, range: impl RangeBounds<usize>) -> Range<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.lines.len(),
+        };
+        start..end
+    }
+
+    // These lines changed, so their counts are out of date
+    fn forget(&mut self, range: Range<usize>) {
+        let counts = self.counts.get_mut();
+        let end = range.end.min(counts.words.len());
+        for y in range.start.min(end)..end {
+            if let Some(words) = counts.words[y].take() {
+                counts.total -= words;
+                counts.unknown += 1;
+            }
+        }
+    }
+
+    pub fn insert(&mut self, y: usize, line: Vec<char>) {
+        self.lines.insert(y, line);
+        self.shift_folds(y, 0, 1);
+        let counts = self.counts.get_mut();
+        if y <= counts.words.len() {
+            counts.words.insert(y, None);
+            counts.unknown += 1;
+        }
+    }
+
+    pub fn remove(&mut self, y: usize) -> Vec<char> {
+        let counts = self.counts.get_mut();
+        if y < counts.words.len() {
+            let count = counts.words.remove(y);
+            counts.drop_count(count);
+        }
+        self.shift_folds(y, 1, 0);
+        self.lines.remove(y)
+    }
+
+    pub fn push(&mut self, line: Vec<char>) {
+        self.insert(self.lines.len(), line);
+    }
+
+    // Replace lines[range] with 'replacement'
+    pub fn splice(&mut self, range: impl RangeBounds<usize>, replacement: impl IntoIterator<Item = Vec<char>>) {
+        let range = self.range(range);
+        let before = self.lines.len();
+        self.lines.splice(range.clone(), replacement);
+        let added = self.lines.len() + range.len() - before;
+        self.shift_folds(range.start, range.len(), added);
+        let counts = self.counts.get_mut();
+        if range.end <= counts.words.len() {
+            let removed: Vec<Option<usize>> = counts.words.splice(range, std::iter::repeat_n(None, added)).collect();
+            for count in removed {
+                counts.drop_count(count);
+            }
+            counts.unknown += added;
+        }
+    }
+
+    // Remove lines[range]
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) {
+        self.splice(range, std::iter::empty());
+    }
+
+    // The lines as a Vec, for code that needs one (front_matter::set).
+    // Every count is forgotten, since anything could change - and so are
+    // the folds, as there's no telling where their headings went.
+    pub fn vec_mut(&mut self) -> &mut Vec<Vec<char>> {
+        *self.counts.get_mut() = Counts::default();
+        self.folds.clear();
+        &mut self.lines
+    }
+
+    pub fn folds(&self) -> &[usize] {
+        &self.folds
+    }
+
+    pub fn is_folded(&self, y: usize) -> bool {
+        self.folds.binary_search(&y).is_ok()
+    }
+
+    pub fn fold(&mut self, y: usize) {
+        if let Err(i) = self.folds.binary_search(&y) {
+            self.folds.insert(i, y);
+        }
+    }
+
+    pub fn unfold(&mut self, y: usize) {
+        self.folds.retain(|&fold| fold != y);
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+    }
+
+    // 'removed' lines starting at y were replaced by 'added' new ones.
+    // Folds on the removed lines go; those below move up or down.
+    fn shift_folds(&mut self, y: usize, removed: usize, added: usize) {
+        self.folds.retain(|&fold| fold < y || fold >= y + removed);
+        for fold in self.folds.iter_mut().filter(|fold| **fold >= y) {
+            *fold = *fold + added - removed;
+        }
+    }
+}
+
+// Reading: &lines[..], lines.iter(), lines.len() and so on
+impl Deref for Lines {
+    type Target = [Vec<char>];
+
+    fn deref(&self) -> &[Vec<char>] {
+        &self.lines
+    }
+}
+
+impl From<Vec<Vec<char>>> for Lines {
+    fn from(lines: Vec<Vec<char>>) -> Self {
+        Lines { counts: RefCell::new(Counts::new(lines.len(), false)), lines, folds: Vec::new() }
+    }
+}
+
+impl FromIterator<Vec<char>> for Lines {
+    fn from_iter<I: IntoIterator<Item = Vec<char>>>(iter: I) -> Self {
+        Lines::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Index<usize> for Lines {
+    type Output = Vec<char>;
+
+    fn index(&self, y: usize) -> &Vec<char> {
+        &self.lines[y]
+    }
+}
+
+// lines[y] used mutably: that line's count is out of date
+impl IndexMut<usize> for Lines {
+    fn index_mut(&mut self, y: usize) -> &mut Vec<char> {
+        self.forget(y..y + 1);
+        &mut self.lines[y]
+    }
+}
+
+// The same for lines[a..b], lines[a..=b], lines[a..] and lines[..b]
+macro_rules! index_range {
+    ($range:ty) => {
+        impl Index<$range> for Lines {
+            type Output = [Vec<char>];
+
+            fn index(&self, range: $range) -> &[Vec<char>] {
+                &self.lines[range]
+            }
+        }
+
+        impl IndexMut<$range> for Lines {
+            fn index_mut(&mut self, range: $range) -> &mut [Vec<char>] {
+                self.forget(self.range(range.clone()));
+                &mut self.lines[range]
+            }
+        }
+    };
+}
+
+index_range!(Range<usize>);
+index_range!(RangeInclusive<usize>);
+index_range!(RangeFrom<usize>);
+index_range!(RangeTo<usize>);
@@ -0,0 +1,35 @@
+// Markdown links: [text](url) around existing text
+//
+// :link wraps the word under the cursor, gl{motion} (vim) or Alt-K
+// (standard mode) the text they cover. The URL is whatever was copied
+// last - yanked in river or on the system clipboard - if it looks like
+// one; otherwise river asks for it.
+
+use std::process::{Command, Stdio};
+
+// Good enough to tell a copied URL from a copied sentence
+pub fn is_url(text: &str) -> bool {
+    let schemes = ["http://", "https://", "mailto:", "file://", "www."];
+    !text.is_empty() && !text.contains(char::is_whitespace) && schemes.iter().any(|scheme| text.starts_with(scheme))
+}
+
+// The system clipboard's text, read with whichever of the usual tools is
+// installed. None if there isn't one (or nothing is copied).
+pub fn system_clipboard() -> Option<String> {
+    let tools: &[&[&str]] = if cfg!(target_os = "macos") {
+        &[&["pbpaste"]]
+    } else if cfg!(windows) {
+        &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]]
+    } else {
+        &[&["wl-paste", "--no-newline"], &["xclip", "-selection", "clipboard", "-o"], &["xsel", "--clipboard", "--output"]]
+    };
+    tools.iter().find_map(|tool| {
+        let output = Command::new(tool[0])
+            .args(&tool[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    })
+}
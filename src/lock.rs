@@ -0,0 +1,91 @@
+// Lock files, so two rivers don't edit the same note at once
+//
+// Editing 2024-05-01.md creates .2024-05-01.md.lock next to it holding the
+// process id. A second river that finds the lock opens the note read-only
+// instead of letting the two autosaves overwrite each other. The lock is
+// removed when the editor closes, including after SIGTERM/SIGHUP (see
+// Editor::run) or a panic. One left behind by a river that was killed
+// outright names a process that's gone, and is taken over.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    // Lock 'file'. Err(Some(pid)) means another river has it.
+    pub fn acquire(file: &Path) -> Result<Lock, Option<u32>> {
+        let path = lock_path(file);
+        for _ in 0..2 {
+            // create_new fails if the file exists, so only one process can win
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut lock_file) => {
+                    let _ = write!(lock_file, "{}", std::process::id());
+                    return Ok(Lock { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let holder = fs::read_to_string(&path).ok().and_then(|pid| pid.trim().parse::<u32>().ok());
+                    match holder {
+                        Some(pid) if pid != std::process::id() && is_running(pid) => return Err(Some(pid)),
+                        // Stale (or unreadable): clear it and try again
+                        _ => {
+                            let _ = fs::remove_file(&path);
+                        }
+                    }
+                }
+                // A directory we can't write to - nothing to protect against
+                Err(_) => return Err(None),
+            }
+        }
+        Err(None)
+    }
+
+    // Take the lock even though another river holds it (:view!)
+    pub fn take_over(file: &Path) -> Option<Lock> {
+        let _ = fs::remove_file(lock_path(file));
+        Lock::acquire(file).ok()
+    }
+}
+
+// Drop runs when the Lock goes out of scope, which is when the buffer
+// holding it closes or the editor exits
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_path(file: &Path) -> PathBuf {
+    let name = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    file.with_file_name(format!(".{}.lock", name))
+}
+
+// Signal 0 checks the process exists without disturbing it. EPERM means
+// it exists but belongs to someone else.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // SAFETY: kill with signal 0 sends nothing
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// tasklist, which comes with Windows, lists the process if there is one:
+// "river.exe","1234",... in CSV. If it can't be asked, the lock is live.
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    let filter = format!("PID eq {}", pid);
+    match std::process::Command::new("tasklist").args(["/FI", &filter, "/FO", "CSV", "/NH"]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid)),
+        Err(_) => true,
+    }
+}
+
+// No cheap check elsewhere, so assume the lock is live
+#[cfg(not(any(unix, windows)))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
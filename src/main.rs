@@ -2,9 +2,9 @@
 // 'use' brings items into scope, similar to 'import' in other languages
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Attribute, Color, Print, ResetColor, SetAttribute},
     terminal::{
         self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
         LeaveAlternateScreen,
@@ -14,17 +14,100 @@ use crossterm::{
 // 'std' is Rust's standard library, always available
 // 'self' in imports refers to the module itself (for functions)
 use std::io::{self, Write};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use std::path::{Path, PathBuf}; // Path manipulation types
 use std::fs; // File system operations
-use chrono::{Local, Datelike}; // External crate for date/time handling
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use chrono::{Local, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike}; // External crate for date/time handling
 use serde::{Deserialize, Serialize}; // Serialization traits
 
 // Module declaration - tells Rust to look for config.rs or config/mod.rs
 mod config;
+mod abbrev;
+mod analyze;
+mod archive;
+mod autocomplete;
+mod bidi;
 mod ai;
+mod compile;
+mod completion;
+mod crypto;
+mod define;
+mod buffer;
+mod carry;
+mod window;
+mod export;
+mod diff;
+mod filter;
+mod finder;
+mod fold;
+mod grep;
+mod help;
+mod front_matter;
+mod grammar;
+mod history;
+mod hooks;
+mod i18n;
+mod import;
+mod keys;
+mod lines;
+mod link;
+mod lock;
+mod markdown;
+mod mood;
+mod outline;
+mod pdf;
+mod prompts;
+mod publish;
+mod remind;
+mod saver;
+mod script;
+mod search;
+mod session;
+mod snippet;
+mod spell;
+mod stats;
+mod substitute;
+mod term;
+mod text_stats;
+mod theme;
+mod thesaurus;
+mod titles;
+mod typography;
+mod tracker;
+mod tutor;
+mod vim;
+mod width;
 // Bring Config struct into scope from our config module
 use config::Config;
+use buffer::{Buffer, LineEndings};
+use window::{Divider, Layout, Placement, Rect, SplitDirection, Window};
+use define::Definition;
+use help::Help;
+use i18n::{t, tn};
+use diff::{Change, DiffView};
+use finder::Finder;
+use fold::FoldMap;
+use grammar::Issue;
+use grep::GrepList;
+use outline::Outline;
+use autocomplete::Completion;
+use lines::Lines;
+use lock::Lock;
+use search::Offset;
+use saver::{Saver, Written};
+use session::{FileSession, SessionStore};
+use spell::Spell;
+use term::{Background, Foreground, Standout};
+use substitute::Substitute;
+use thesaurus::Synonyms;
+use vim::{Action, CharFind, FoldCommand, Motion, MotionKind, Operator, Parse, Pos, ScrollTo, Selection, Span, TextObject};
+use completion::History;
+use titles::{NoteTitle, TitleStore};
+use tracker::SessionTracker;
 
 // Enums in Rust are algebraic data types - they can only be one variant at a time
 // #[derive(...)] automatically implements common traits:
@@ -32,41 +115,77 @@ use config::Config;
 // - Clone: allows .clone() to create copies
 // - Copy: allows implicit copying (for small, stack-allocated types)
 // - PartialEq: allows == comparison
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum Mode {
     Normal,  // Vim normal mode
     Insert,  // Text insertion mode
     Command, // Command line mode (for :commands and /search)
+    Replace, // Typing overwrites existing text (R)
 }
 
 // Structs are like classes in other languages, but without inheritance
 // Serialize/Deserialize traits enable conversion to/from formats like JSON/TOML
-#[derive(Debug, Serialize, Deserialize)]
+// Default can be derived here because every field's default is zero
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 struct DailyStats {
     // #[serde(default)] uses Default::default() if field is missing during deserialization
     #[serde(default)]
     typing_seconds: u64, // u64 is an unsigned 64-bit integer
     #[serde(default)]
     word_count: u64, // Total words written today
+    #[serde(default)]
+    goal: u64, // Word goal that applied (0 in stats from before goals were saved)
+    #[serde(default)]
+    goal_reached_at: Option<String>, // "HH:MM" when the goal was first reached
+    #[serde(default)]
+    goal_minutes: Option<u64>, // Minutes of typing it took to get there
+    #[serde(default)]
+    mood: Option<String>, // One of mood::MOODS, if mood_tracking is on
+    #[serde(default)]
+    themes: Vec<String>,
+    #[serde(default)]
+    hours: Vec<u64>, // Keystrokes in each hour of the day, 0-23
 }
 
-// 'impl' blocks add methods to types
-// Default trait provides a default value for a type
-impl Default for DailyStats {
-    // 'Self' is an alias for the type we're implementing on (DailyStats)
-    fn default() -> Self {
-        DailyStats {
-            typing_seconds: 0,
-            word_count: 0,
-        }
-    }
+// Today's AI prompt being generated on a background thread
+struct PromptJob {
+    receiver: Receiver<Result<ai::DailyPrompt, String>>,
+    started: Instant,
+    frame: usize, // Spinner frame last drawn
+}
+
+// A note's title being asked for on a background thread (:title)
+struct TitleJob {
+    filename: String,
+    words: usize, // Word count when asked
+    receiver: Receiver<Result<String, String>>,
+}
+
+// A LanguageTool check running on a background thread
+struct GrammarJob {
+    filename: Option<String>, // The note it's checking
+    asked: bool,              // :grammar, rather than a save
+    receiver: Receiver<Result<Vec<Issue>, String>>,
+}
+
+// How long typing has to pause before a large file's words are recounted
+const WORD_COUNT_DELAY: Duration = Duration::from_millis(500);
+
+// A :s in progress: what it replaces, where it stops, and the match the
+// cursor is on (see substitute.rs)
+struct Substitution {
+    sub: Substitute,
+    last: usize,           // Last line of the range
+    at: Pos,               // The match being asked about
+    replaced: usize,       // Replacements made so far
+    lines: Vec<usize>,     // Lines changed, to report how many
 }
 
 // Main editor struct - holds all state for the text editor
 struct Editor {
     // Vec<T> is a growable array (like ArrayList in Java or vector in C++)
     // Vec<Vec<char>> represents lines of text, where each line is a vector of characters
-    buffer: Vec<Vec<char>>,
+    buffer: Lines,
     
     // usize is the pointer-sized unsigned integer type (32/64 bit depending on architecture)
     cursor_x: usize,          // Current cursor column
@@ -83,28 +202,100 @@ struct Editor {
     // Option<T> represents an optional value - either Some(T) or None
     // This is Rust's null-safety mechanism
     filename: Option<String>,
+    read_only: bool,          // Viewing only: no edits, autosave or typing time
+    tutor: bool,              // river tutor: practice, so no typing time, stats or hooks
+    locks: HashMap<String, Lock>, // Lock files of the files open for editing (see lock.rs)
+    line_endings: LineEndings, // How the active file's lines end on disk
+    large_file: bool,         // Bigger than large_file_kb, so the word count waits for pauses
+    word_count: usize,        // Last word count in a large file...
+    word_count_stale: bool,   // ...and whether edits have changed it since
+    last_key: Instant,        // When the last key was pressed
+    session_start: Instant,   // When this run of the editor began, for the session timer
+    
+    // All open files. The active one's state lives in the fields above
+    // and is moved in/out of this list when switching (see buffer.rs)
+    buffers: Vec<Buffer>,
+    active_buffer: usize,
+    
+    // Split windows - each shows a buffer with its own cursor and scroll
+    // position. Like buffers, the active window's state lives in the
+    // cursor/offset fields above while it has focus (see window.rs)
+    windows: Vec<Window>,
+    active_window: usize,
+    layout: Layout,
     
     mode: Mode,               // Current editor mode (enum defined above)
     
     // String is a heap-allocated, growable UTF-8 string
     // (different from &str which is a string slice/reference)
     command_buffer: String,
+    command_prompt: char,          // ':' for commands, '/' or '?' for searches
+    command_history: History,      // Previously entered :commands
+    search_history: History,       // Previously entered search patterns
+    completions: Vec<String>,      // Candidates from the last Tab press
+    completion_index: usize,       // Which candidate is currently shown
+    last_search: Option<(String, Offset, bool)>, // (pattern, offset, forward) for n/N
+    status_message: Option<String>, // One-off message shown below the status bar
+    finder: Option<Finder>,        // Note finder overlay, when open
+    outline: Option<Outline>,      // Heading panel (:outline), when open
+    diff_view: Option<DiffView>,   // Differences from an earlier version (:diff), when open
+    grep: Option<GrepList>,        // Results of the last :grep, for :cn/:cp
+    grep_pane: bool,               // Whether they're showing at the bottom
+    completion: Option<Completion>, // Ctrl-N/Ctrl-P word menu, when open
+    synonyms: Option<Synonyms>,     // :syn popup, when open
+    definition: Option<Definition>, // :define popup, when open
+    help: Option<Help>,             // :help / F1 popup, when open
+    sessions: SessionStore,        // Remembered cursor positions per file
+    pending_keys: String,          // Normal mode keys of an unfinished command (see vim.rs)
+    count: Option<usize>,          // Number typed before a normal mode command
+    marks: HashMap<char, Pos>,     // m{a-z} marks in the active buffer
+    visual: Option<Selection>,     // The v/V selection, while in visual mode
+    command_keys: Vec<KeyEvent>,   // Keys of the normal mode command being typed
+    recording: Option<Vec<KeyEvent>>, // Keys of a change still in insert mode
+    last_change: Vec<KeyEvent>,    // Keys '.' replays
+    replaying: bool,               // Set while '.' replays, so it isn't re-recorded
+    literal_next: bool,            // Ctrl-V: the next character doesn't expand an abbreviation
+    pending_link: Option<(Pos, Pos)>, // Text gl/Alt-K will link once the URL is typed in
+    user_command_depth: usize,     // [commands] running inside each other, to stop loops
+    scratch_return: Option<String>, // File :scratch came from, for going back to it
+    substitution: Option<Substitution>, // A :s///c waiting for y/n at each match
+    // Tab stops of the snippet being filled in, as (lines below, characters
+    // after) - counted from the end so typing at one stop doesn't move the rest
+    snippet_stops: Vec<(usize, usize)>,
+    highlight_long_sentences: bool, // Toggled by :readability highlight
+    spell: Option<Spell>,           // Spell checking, when it's on (:spell)
+    last_find: Option<CharFind>,   // Last f/t/F/T search, for ; and ,
     
     clipboard: Vec<Vec<char>>, // For copy/paste operations
+    clipboard_linewise: bool,  // Whole lines (yy) rather than characters (yw)
+    replaced: Vec<Option<char>>, // Characters overwritten in replace mode, for backspace
     config: Config,           // User configuration
     needs_save: bool,
     
     // Instant represents a point in time for measuring durations
     last_save: Instant,
-    typing_session_start: Option<Instant>,
     
-    // Duration represents a span of time
-    accumulated_typing_time: Duration,
-    last_typing_activity: Instant,
+    // Today's typing time (see tracker.rs)
+    typing: SessionTracker,
+    hours: Vec<u64>, // Today's keystrokes by hour, for the chart in river stats
     
     // Prompt-related fields
     current_prompt: Option<String>,
     should_show_prompt: bool,
+    prompt_job: Option<PromptJob>, // Set while today's AI prompt is being generated
+    title_job: Option<TitleJob>,   // Set while :title waits for the AI
+    grammar_job: Option<GrammarJob>, // Set while LanguageTool checks the note
+    grammar: (Option<String>, Vec<Issue>), // The last check's issues, with the note they're in
+    grammar_failing: bool,         // The last check couldn't reach the server
+    saver: Saver,                  // Writes autosaves and stats on another thread
+    last_stats_problem: Option<String>, // Shown already, so not again until it changes
+    config_modified: Option<SystemTime>, // config.toml's time when last read, to spot edits
+    session_settings: Vec<(String, toml::Value)>, // Changed with :set, kept over a reload
+    // What goal_reached hook scripts print comes back here (see hooks.rs)
+    script_sender: Sender<hooks::ScriptOutput>,
+    script_output: Receiver<hooks::ScriptOutput>,
+    goal_met: bool,                // The active note is at or past its word goal
+    celebrate_until: Option<Instant>, // Status bar flashes until then
 }
 
 // Implementation block for Editor methods
@@ -129,11 +320,16 @@ impl Editor {
         
         // Self:: refers to the type itself (for associated functions)
         // &config passes a reference (borrow) instead of moving ownership
-        let accumulated_time = Self::load_typing_time(&config)?;
+        let (typing_time, stats_problem) = Self::load_typing_time(&config);
+        let typing = SessionTracker::new(Duration::from_secs(config.typing_timeout_seconds), typing_time);
+        let hours = Self::load_hours(&config);
+        let spell = config.spell_check.then(|| Spell::new(&config));
+        // A channel: hook script threads hold senders, the editor the receiver
+        let (script_sender, script_output) = mpsc::channel();
         
         // Ok() wraps the value in Result::Ok variant
         Ok(Editor {
-            buffer: vec![Vec::new()],
+            buffer: Lines::from(vec![Vec::new()]),
             cursor_x: 0,
             cursor_y: 0,
             offset_y: 0,
@@ -142,17 +338,79 @@ impl Editor {
             terminal_width: width,
             dirty: false,
             filename: None,
+            read_only: false,
+            tutor: false,
+            locks: HashMap::new(),
+            line_endings: LineEndings::default(),
+            large_file: false,
+            word_count: 0,
+            word_count_stale: false,
+            last_key: Instant::now(),
+            session_start: Instant::now(),
+            buffers: vec![Buffer::new()],
+            active_buffer: 0,
+            windows: vec![Window::default()],
+            active_window: 0,
+            layout: Layout::Window(0),
             mode,
             command_buffer: String::new(),
+            command_prompt: ':',
+            command_history: History::default(),
+            search_history: History::default(),
+            completions: Vec::new(),
+            completion_index: 0,
+            last_search: None,
+            status_message: stats_problem.clone(),
+            finder: None,
+            outline: None,
+            diff_view: None,
+            grep: None,
+            grep_pane: false,
+            completion: None,
+            synonyms: None,
+            definition: None,
+            help: None,
+            sessions: SessionStore::load(),
+            pending_keys: String::new(),
+            count: None,
+            marks: HashMap::new(),
+            visual: None,
+            command_keys: Vec::new(),
+            recording: None,
+            last_change: Vec::new(),
+            replaying: false,
+            literal_next: false,
+            pending_link: None,
+            user_command_depth: 0,
+            scratch_return: None,
+            substitution: None,
+            snippet_stops: Vec::new(),
+            highlight_long_sentences: false,
+            spell,
+            last_find: None,
             clipboard: Vec::new(),
+            clipboard_linewise: true,
+            replaced: Vec::new(),
             config,
             needs_save: false,
             last_save: Instant::now(),
-            typing_session_start: None,
-            accumulated_typing_time: accumulated_time,
-            last_typing_activity: Instant::now(),
+            typing,
+            hours,
             current_prompt: None,
             should_show_prompt: false,
+            prompt_job: None,
+            title_job: None,
+            grammar_job: None,
+            grammar: (None, Vec::new()),
+            grammar_failing: false,
+            saver: Saver::new(),
+            last_stats_problem: stats_problem,
+            config_modified: Config::modified(),
+            session_settings: Vec::new(),
+            script_sender,
+            script_output,
+            goal_met: false,
+            celebrate_until: None,
         })
     }
 
@@ -160,12 +418,30 @@ impl Editor {
     // &mut self - mutable borrow of self (can modify the struct)
     // () is the unit type - like void in other languages
     fn run(&mut self) -> io::Result<()> {
+        // Before raw mode, in case api_key_command needs to ask for a passphrase
+        self.start_prompt_job();
         self.enter_raw_mode()?;
         
         let mut last_typing_save = Instant::now();
+        let mut last_config_check = Instant::now();
+        // The clock and session timer only change once a minute
+        let mut shown_minute = self.status_minute();
+        let mut shown_idle = self.typing.is_idle(Instant::now());
+        
+        // SIGTERM (kill, shutdown) and SIGHUP (terminal closed) end the loop
+        // the way :q does, so the note is saved and its lock removed.
+        // Arc lets the signal handler and this loop share the flag.
+        let terminated = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGHUP] {
+            let _ = signal_hook::flag::register(signal, Arc::clone(&terminated));
+        }
         
         // 'loop' creates an infinite loop (like while(true))
         loop {
+            if terminated.load(Ordering::Relaxed) {
+                break;
+            }
             self.render()?;
             
             // Auto-save logic: save after 1 second of inactivity
@@ -174,38 +450,83 @@ impl Editor {
                 self.auto_save()?;
             }
             
-            // Update accumulated typing time if actively typing
-            // 'if let' is pattern matching - only runs if pattern matches
-            // Extracts the value from Some(session_start), skips if None
-            if let Some(session_start) = self.typing_session_start {
-                let typing_timeout = Duration::from_secs(self.config.typing_timeout_seconds);
-                if self.last_typing_activity.elapsed() <= typing_timeout {
-                    self.accumulated_typing_time = self.accumulated_typing_time + 
-                        self.last_typing_activity.duration_since(session_start);
-                    self.typing_session_start = Some(self.last_typing_activity);
-                } else {
-                    // Session ended, clear it
-                    self.typing_session_start = None;
-                }
+            // The typing timeout passed: show "idle" in the status bar
+            let idle = self.typing.is_idle(Instant::now());
+            if idle != shown_idle {
+                shown_idle = idle;
+                self.dirty = true;
             }
             
-            // Save typing time every 10 seconds
-            if last_typing_save.elapsed() > Duration::from_secs(10) {
-                let _ = self.save_typing_time();
+            // Save typing time every 10 seconds (not while just reading).
+            // The stats file is read back first to keep what else is in
+            // it, so not while the save thread is writing.
+            if !self.read_only && !self.tutor && last_typing_save.elapsed() > Duration::from_secs(10) && !self.saver.busy() {
+                match self.stats_write() {
+                    Ok(write) => self.saver.write(write),
+                    Err(e) => self.stats_problem(t!("Couldn't save today's stats: {error}", error = e)),
+                }
                 last_typing_save = Instant::now();
             }
             
+            self.poll_prompt_job();
+            self.poll_title_job();
+            self.poll_grammar_job();
+            self.poll_saves();
+            
+            // config.toml edited in another window: pick up the changes
+            if last_config_check.elapsed() > Duration::from_secs(1) {
+                if Config::modified() != self.config_modified {
+                    self.reload_config();
+                }
+                last_config_check = Instant::now();
+            }
+            self.poll_script_output();
+            
+            if (self.config.show_clock || self.config.show_session_time) && self.status_minute() != shown_minute {
+                shown_minute = self.status_minute();
+                self.dirty = true;
+            }
+            
+            // The goal celebration is over
+            if self.celebrate_until.is_some_and(|until| Instant::now() >= until) {
+                self.celebrate_until = None;
+                self.dirty = true;
+            }
+            
             // Poll for events with 16ms timeout (roughly 60 FPS)
             if event::poll(Duration::from_millis(16))? {
                 // Pattern match on event type
-                if let Event::Key(key_event) = event::read()? {
-                    // If handle_key_event returns true, exit the loop
-                    if self.handle_key_event(key_event)? {
-                        break; // 'break' exits the innermost loop
+                let quit = match event::read()? {
+                    // Windows reports a key being let go as well as pressed
+                    // (and held down); letting go does nothing
+                    Event::Key(key_event) if key_event.kind != KeyEventKind::Release => {
+                        self.last_key = Instant::now();
+                        self.handle_key_event(key_event)?
                     }
+                    Event::Paste(text) => {
+                        self.last_key = Instant::now();
+                        self.handle_paste(&text);
+                        false
+                    }
+                    _ => false,
+                };
+                // If handle_key_event returns true, exit the loop
+                if quit {
+                    break; // 'break' exits the innermost loop
+                }
+                if self.needs_save {
+                    self.word_count_stale = true;
+                    self.check_goal();
                 }
             }
             
+            // Typing in a large file has paused: time to bring the word
+            // count up to date
+            if self.large_file && self.word_count_stale && self.last_key.elapsed() >= WORD_COUNT_DELAY {
+                self.dirty = true;
+                self.check_goal();
+            }
+            
             if let Ok((width, height)) = terminal::size() {
                 if width != self.terminal_width || height != self.terminal_height {
                     self.terminal_width = width;
@@ -215,13 +536,23 @@ impl Editor {
             }
         }
         
-        // Save before exiting
+        // Save before exiting, and let an autosave still going finish
         if self.needs_save {
-            self.auto_save()?;
+            self.save_file()?;
         }
-        let _ = self.save_typing_time();
+        self.finish_saves();
+        let stats_problem = if self.read_only || self.tutor { None } else { self.save_typing_time() };
+        self.remember_position();
+        let _ = self.sessions.save();
         
         self.leave_raw_mode()?;
+        // Too late for the message line
+        if let Some(problem) = stats_problem {
+            eprintln!("{}", problem);
+        }
+        self.run_hook("quit", &self.config.on_quit.clone());
+        self.title_on_quit();
+        self.track_mood_on_quit();
         Ok(())
     }
 
@@ -234,11 +565,15 @@ impl Editor {
             Hide,
             Clear(ClearType::All)
         )?;
+        // The old Windows console can't tell a paste from typing; there a
+        // paste arrives as keys, which is no reason not to start
+        let _ = execute!(io::stdout(), EnableBracketedPaste);
         self.dirty = true;
         Ok(())
     }
 
     fn leave_raw_mode(&mut self) -> io::Result<()> {
+        let _ = execute!(io::stdout(), DisableBracketedPaste);
         execute!(
             io::stdout(),
             Show,
@@ -251,45 +586,236 @@ impl Editor {
 
     // Dispatch key events based on current mode
     fn handle_key_event(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        // Messages only live until the next keypress
+        if self.status_message.take().is_some() {
+            self.dirty = true;
+        }
+
+        // Overlays grab every key until they're closed
+        if self.finder.is_some() {
+            return self.handle_finder_key(key_event);
+        }
+        if self.outline.is_some() {
+            return self.handle_outline_key(key_event);
+        }
+        if self.diff_view.is_some() {
+            self.handle_diff_key(key_event);
+            return Ok(false);
+        }
+        if self.substitution.is_some() {
+            self.handle_substitute_key(key_event);
+            return Ok(false);
+        }
+        if self.grep_pane && self.grep.is_some() {
+            return self.handle_grep_key(key_event);
+        }
+        if self.synonyms.is_some() {
+            self.handle_synonyms_key(key_event);
+            return Ok(false);
+        }
+        if self.definition.is_some() {
+            self.handle_definition_key(key_event);
+            return Ok(false);
+        }
+        if self.help.is_some() {
+            self.handle_help_key(key_event);
+            return Ok(false);
+        }
+        // Bindings from config.toml come before river's own keys, except on
+        // the command line and partway through a normal mode command
+        if self.mode != Mode::Command && self.pending_keys.is_empty() {
+            if let Some(result) = self.run_key_binding(&key_event) {
+                return result;
+            }
+        }
+        // Ctrl-S saves straight away in any mode, for anyone who'd rather
+        // not leave it to autosave
+        if key_event.code == KeyCode::Char('s') && key_event.modifiers.contains(KeyModifiers::CONTROL) && self.mode != Mode::Command {
+            self.pending_keys.clear();
+            self.count = None;
+            self.save_now();
+            return Ok(false);
+        }
+        if key_event.code == KeyCode::F(1) && self.mode != Mode::Command {
+            self.pending_keys.clear();
+            self.count = None;
+            self.open_help();
+            return Ok(false);
+        }
+
         if self.config.vim_bindings {
-            // 'match' is exhaustive pattern matching - must handle all variants
-            // Similar to switch/case but more powerful
-            match self.mode {
-                Mode::Normal => self.handle_normal_mode(key_event),
-                Mode::Insert => self.handle_vim_insert_mode(key_event),
-                Mode::Command => self.handle_command_mode(key_event),
+            // Visual mode's keys aren't kept for '.'
+            let was_normal = self.mode == Mode::Normal && self.visual.is_none();
+            if was_normal {
+                self.command_keys.push(key_event);
             }
+            let quit = self.handle_vim_key(key_event)?;
+            self.record_change(key_event, was_normal);
+            Ok(quit)
+        } else if self.mode == Mode::Command {
+            // Only when something asks a question there (Alt-K's URL)
+            self.handle_command_mode(key_event)
         } else {
             self.handle_standard_mode(key_event)
         }
     }
 
+    // Bracketed paste: the terminal sends pasted text in one piece, so it
+    // goes in as it is rather than key by key, which would expand
+    // abbreviations, auto-indent each line and hard-wrap. The finder,
+    // outline and command line take its first line.
+    fn handle_paste(&mut self, text: &str) {
+        if self.status_message.take().is_some() {
+            self.dirty = true;
+        }
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let first_line = text.lines().next().unwrap_or_default();
+        if let Some(finder) = self.finder.as_mut() {
+            finder.query.push_str(first_line);
+            finder.update();
+        } else if let Some(outline) = self.outline.as_mut() {
+            outline.query.push_str(first_line);
+            outline.update();
+        } else if self.diff_view.is_some()
+            || self.substitution.is_some()
+            || (self.grep_pane && self.grep.is_some())
+            || self.synonyms.is_some()
+            || self.definition.is_some()
+            || self.help.is_some()
+        {
+            // Popups that only take keys; nothing to paste into
+        } else if self.mode == Mode::Command {
+            self.active_history().reset();
+            self.command_buffer.push_str(first_line);
+        } else {
+            self.paste_text(&text);
+        }
+        self.dirty = true;
+    }
+
+    // Put pasted text in the buffer at the cursor. In normal mode the cursor
+    // ends on its last character, as after p.
+    fn paste_text(&mut self, text: &str) {
+        if text.is_empty() || !self.ensure_writable() {
+            return;
+        }
+        self.completion = None;
+        self.pending_keys.clear();
+        self.count = None;
+        let lines: Vec<Vec<char>> = text.split('\n').map(|line| line.chars().collect()).collect();
+        let end = self.insert_text(self.cursor_pos(), &lines);
+        self.cursor_y = end.y;
+        self.cursor_x = if self.mode == Mode::Normal { end.x.saturating_sub(1) } else { end.x };
+        self.mark_modified();
+    }
+
+    fn handle_vim_key(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        // 'match' is exhaustive pattern matching - must handle all variants
+        // Similar to switch/case but more powerful
+        match self.mode {
+            Mode::Normal => self.handle_normal_mode(key_event),
+            Mode::Insert => self.handle_vim_insert_mode(key_event),
+            Mode::Command => self.handle_command_mode(key_event),
+            Mode::Replace => self.handle_replace_mode(key_event),
+        }
+    }
+
+    // Remember the keys of the last change so '.' can replay them. A change
+    // is a normal mode command that edits text (see vim::is_change) plus,
+    // if it entered insert or replace mode, everything typed up to Esc.
+    fn record_change(&mut self, key_event: KeyEvent, was_normal: bool) {
+        if self.replaying {
+            return;
+        }
+        if was_normal {
+            // Wait until the command is complete ("d" alone isn't yet)
+            if !self.pending_keys.is_empty() || self.count.is_some() {
+                return;
+            }
+            let keys = std::mem::take(&mut self.command_keys);
+            let chars: String = keys.iter().filter_map(|k| match k.code {
+                KeyCode::Char(c) if !k.modifiers.contains(KeyModifiers::CONTROL) => Some(c),
+                _ => None,
+            }).collect();
+            if !vim::is_change(&chars) {
+                return;
+            }
+            if matches!(self.mode, Mode::Insert | Mode::Replace) {
+                self.recording = Some(keys);
+            } else {
+                self.last_change = keys;
+            }
+        } else if let Some(keys) = self.recording.as_mut() {
+            keys.push(key_event);
+            if self.mode == Mode::Normal {
+                self.last_change = self.recording.take().unwrap_or_default();
+            }
+        }
+    }
+
+    // . - replay the last change at the cursor
+    fn repeat_change(&mut self) -> io::Result<()> {
+        self.replaying = true;
+        for key_event in self.last_change.clone() {
+            self.handle_vim_key(key_event)?;
+        }
+        self.replaying = false;
+        Ok(())
+    }
+
     fn handle_standard_mode(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        if self.handle_completion_key(key_event) {
+            return Ok(false);
+        }
         // Pattern matching on enum variants with destructuring
         // KeyCode is an enum with many variants (Char, Enter, etc.)
         match key_event.code {
             // Match guards: 'if' after pattern adds extra condition
             // KeyModifiers is a bitflag, contains() checks if flag is set
             KeyCode::Char('q') if key_event.modifiers.contains(KeyModifiers::CONTROL) => return Ok(true),
+            // Ctrl-P is the finder here; it only completes once the menu is open
+            KeyCode::Char('p') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.open_finder(),
+            KeyCode::Char('n') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.start_completion(false),
+            KeyCode::Char('v') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.literal_next = true,
+            KeyCode::F(5) => self.insert_timestamp(),
+            // The thesaurus key in word processors
+            KeyCode::F(7) if key_event.modifiers.contains(KeyModifiers::SHIFT) => self.show_synonyms(),
+            KeyCode::F(7) => self.next_grammar_issue(true),
+            KeyCode::Char('t') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.toggle_checkbox(),
+            KeyCode::Char('k') if key_event.modifiers.contains(KeyModifiers::ALT) => self.link_word(),
+            // The readline keys shells and emacs use
+            KeyCode::Char('a') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.move_home(),
+            KeyCode::Char('e') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.move_end(),
+            KeyCode::Char('b') if key_event.modifiers.contains(KeyModifiers::ALT) => self.word_left(),
+            KeyCode::Char('f') if key_event.modifiers.contains(KeyModifiers::ALT) => self.word_right(),
+            KeyCode::Char('w') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.delete_word_back(),
+            KeyCode::Char('k') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.kill_line(),
+            // And a word at a time, as in GUI editors. Some terminals send
+            // Ctrl-Backspace as Alt-Backspace.
+            KeyCode::Left if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.word_left(),
+            KeyCode::Right if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.word_right(),
+            KeyCode::Backspace if key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => self.delete_word(false),
+            // Ctrl-H is what terminals set up with stty erase ^H send for
+            // Backspace, and what many send for Ctrl-Backspace - so it
+            // deletes one character, and Ctrl-W is there for a word
+            KeyCode::Char('h') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.backspace(),
+            KeyCode::Delete if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.delete_word(true),
             KeyCode::Left => self.move_left(),
             KeyCode::Right => self.move_right(),
             KeyCode::Up => self.move_up(),
             KeyCode::Down => self.move_down(),
-            KeyCode::Home => self.move_home(),
+            KeyCode::Home => self.home_toggle(true),
             KeyCode::End => self.move_end(),
             KeyCode::PageUp => self.page_up(),
             KeyCode::PageDown => self.page_down(),
             KeyCode::Backspace => self.backspace(),
             KeyCode::Delete => self.delete(),
             KeyCode::Enter => self.insert_newline(),
-            KeyCode::Tab => self.insert_tab(),
-            // Pattern binding: 'c' captures the character inside Char variant
-            KeyCode::Char(c) => {
-                // Bitwise OR combines flags, intersects() checks if ANY are set
-                // ! is logical NOT
-                if !key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
-                    self.insert_char(c);
-                }
+            KeyCode::Tab => self.tab_key(),
+            // Pattern binding: 'c' captures the character inside Char variant.
+            // keys::typed() also lets through what AltGr types
+            KeyCode::Char(c) if keys::typed(&key_event).is_some() => {
+                self.insert_char(c);
             }
             // _ is wildcard pattern - matches anything not handled above
             _ => {}
@@ -298,19 +824,80 @@ impl Editor {
     }
 
     fn handle_normal_mode(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        // A number typed before a command is its count ("3J"). 0 on its own
+        // is still the start-of-line motion.
+        if let KeyCode::Char(c @ '0'..='9') = key_event.code {
+            if self.pending_keys.is_empty() && (c != '0' || self.count.is_some()) {
+                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                return Ok(false);
+            }
+        }
+
+        if let Some(selection) = self.visual {
+            self.handle_visual_key(key_event, selection);
+            return Ok(false);
+        }
+
+        // Multi-key commands (f{char}, ...) collect keys until they're complete
+        if let KeyCode::Char(c) = key_event.code {
+            let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL) && keys::typed(&key_event).is_none();
+            if !ctrl && (!self.pending_keys.is_empty() || vim::starts_sequence(c)) {
+                self.pending_keys.push(c);
+                match vim::parse(&self.pending_keys) {
+                    Parse::Incomplete => {}
+                    Parse::Invalid => {
+                        self.pending_keys.clear();
+                        self.count = None;
+                    }
+                    // "5gg" goes to line 5; other sequences don't take a count
+                    Parse::Complete(Action::Move(Motion::FirstLine)) => {
+                        self.pending_keys.clear();
+                        let line = self.count.take().unwrap_or(1);
+                        self.go_to_line(line);
+                    }
+                    Parse::Complete(action) => {
+                        self.pending_keys.clear();
+                        self.count = None;
+                        self.run_action(action);
+                    }
+                }
+                return Ok(false);
+            }
+        }
+        // Any other key (Esc, arrows...) abandons a half-typed command
+        if !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            self.count = None;
+            return Ok(false);
+        }
+        // take() leaves None behind, so the count only applies to this key
+        let count = self.count.take();
+
         match key_event.code {
             KeyCode::Char('q') if key_event.modifiers.contains(KeyModifiers::CONTROL) => return Ok(true),
-            KeyCode::Char(':') => {
-                self.mode = Mode::Command;
-                self.command_buffer.clear();
-                self.dirty = true;
+            KeyCode::Char('w') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.next_window()?,
+            KeyCode::Char('p') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.open_finder(),
+            KeyCode::Char('t') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.toggle_checkbox(),
+            KeyCode::Char('d') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.scroll_half_page(true),
+            KeyCode::Char('u') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.scroll_half_page(false),
+            KeyCode::Char('e') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.scroll_view(true),
+            KeyCode::Char('y') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.scroll_view(false),
+            KeyCode::Char(':') => self.enter_command_mode(':'),
+            KeyCode::Char('/') => self.enter_command_mode('/'),
+            KeyCode::Char('?') => self.enter_command_mode('?'),
+            KeyCode::Char('n') => self.repeat_search(false),
+            KeyCode::Char('N') => self.repeat_search(true),
+            // Keys that would start editing are refused up front in read-only buffers
+            KeyCode::Char('i' | 'I' | 'a' | 'A' | 'o' | 'O' | 'R') if self.read_only => {
+                self.ensure_writable();
             }
             KeyCode::Char('i') => {
                 self.mode = Mode::Insert;
                 self.dirty = true;
             }
             KeyCode::Char('I') => {
-                self.move_home();
+                self.cursor_x = leading_whitespace(self.current_line());
                 self.mode = Mode::Insert;
                 self.dirty = true;
             }
@@ -322,54 +909,63 @@ impl Editor {
                 self.dirty = true;
             }
             KeyCode::Char('A') => {
-                self.move_end();
+                // Switch mode first so move_end goes past the last character
                 self.mode = Mode::Insert;
+                self.move_end();
                 self.dirty = true;
             }
             KeyCode::Char('o') => {
+                self.mode = Mode::Insert;
                 self.move_end();
                 self.insert_newline();
-                self.mode = Mode::Insert;
                 self.dirty = true;
             }
             KeyCode::Char('O') => {
-                self.move_home();
-                self.buffer.insert(self.cursor_y, Vec::new());
+                let indent = if self.config.auto_indent { self.indent_of(self.cursor_y) } else { Vec::new() };
+                self.cursor_x = indent.len();
+                self.buffer.insert(self.cursor_y, indent);
                 self.dirty = true;
                 self.needs_save = true;
                 self.last_save = Instant::now();
                 self.mode = Mode::Insert;
             }
+            KeyCode::Char('R') => {
+                self.replaced.clear();
+                self.mode = Mode::Replace;
+                self.dirty = true;
+            }
+            KeyCode::Char('v') => self.start_visual(false),
+            KeyCode::Char('V') => self.start_visual(true),
+            // Shorthands for common changes: C = c$, s = cl, S = cc
+            KeyCode::Char('C') => self.operate(Operator::Change, Motion::LineEnd),
+            KeyCode::Char('s') => self.operate(Operator::Change, Motion::Right),
+            KeyCode::Char('S') => self.operate_lines(Operator::Change, self.cursor_y, self.cursor_y),
             KeyCode::Char('h') | KeyCode::Left => self.move_left(),
             KeyCode::Char('j') | KeyCode::Down => self.move_down(),
             KeyCode::Char('k') | KeyCode::Up => self.move_up(),
             KeyCode::Char('l') | KeyCode::Right => self.move_right(),
-            KeyCode::Char('0') | KeyCode::Home => self.move_home(),
+            // Each goes to its own end of the indent first, then the other
+            KeyCode::Char('0') => self.home_toggle(false),
+            KeyCode::Char('^') | KeyCode::Home => self.home_toggle(true),
             KeyCode::Char('$') | KeyCode::End => self.move_end(),
-            KeyCode::Char('g') => {
-                self.cursor_y = 0;
-                self.cursor_x = 0;
-                self.dirty = true;
-            }
-            KeyCode::Char('G') => {
-                self.cursor_y = self.buffer.len() - 1;
-                self.cursor_x = 0;
-                self.dirty = true;
-            }
-            KeyCode::Char('w') => self.move_word_forward(),
-            KeyCode::Char('b') => self.move_word_backward(),
-            KeyCode::Char('e') => self.move_word_end(),
+            // "5G" goes to line 5, G alone to the last
+            KeyCode::Char('G') => self.go_to_line(count.unwrap_or(self.buffer.len())),
+            KeyCode::Char('w') => self.apply_motion(Motion::WordForward),
+            KeyCode::Char('b') => self.apply_motion(Motion::WordBackward),
+            KeyCode::Char('e') => self.apply_motion(Motion::WordEnd),
+            KeyCode::Char(')') => self.apply_motion(Motion::SentenceForward),
+            KeyCode::Char('(') => self.apply_motion(Motion::SentenceBackward),
+            KeyCode::Char('}') => self.apply_motion(Motion::ParagraphForward),
+            KeyCode::Char('{') => self.apply_motion(Motion::ParagraphBackward),
+            KeyCode::Char('.') => self.repeat_change()?,
+            KeyCode::Char(';') => self.repeat_find(false),
+            KeyCode::Char(',') => self.repeat_find(true),
             KeyCode::Char('x') => self.delete_char(),
-            KeyCode::Char('d') => {
-                if self.last_key_was('d') {
-                    self.delete_line();
-                }
-            }
-            KeyCode::Char('y') => {
-                if self.last_key_was('y') {
-                    self.yank_line();
-                }
-            }
+            KeyCode::Char('~') => self.toggle_case_char(),
+            // "J" joins two lines; "3J" joins three
+            KeyCode::Char('J') => self.join_lines(count.unwrap_or(2).max(2)),
+            // Vim's K looks the word up in the manual; here, the dictionary
+            KeyCode::Char('K') => self.define(""),
             KeyCode::Char('p') => self.paste_after(),
             KeyCode::Char('P') => self.paste_before(),
             KeyCode::PageUp => self.page_up(),
@@ -379,10 +975,86 @@ impl Editor {
         Ok(false)
     }
 
+    // v selects characters and V whole lines, from the cursor to wherever
+    // it moves next
+    fn start_visual(&mut self, linewise: bool) {
+        self.visual = Some(Selection { anchor: self.cursor_pos(), linewise });
+        self.dirty = true;
+    }
+
+    // In visual mode motions move the cursor end of the selection, an
+    // operator acts on it and leaves visual mode, and Esc drops it.
+    // v or V switches between characters and lines (or leaves, pressed
+    // again); o jumps to the other end. : keeps the selection for the
+    // command (:count counts just the selection).
+    fn handle_visual_key(&mut self, key_event: KeyEvent, selection: Selection) {
+        self.dirty = true;
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL) && keys::typed(&key_event).is_none();
+        let c = match key_event.code {
+            KeyCode::Char(c) if !ctrl => c,
+            code => {
+                self.pending_keys.clear();
+                self.count = None;
+                match code {
+                    KeyCode::Esc => self.visual = None,
+                    KeyCode::Left => self.move_left(),
+                    KeyCode::Right => self.move_right(),
+                    KeyCode::Up => self.move_up(),
+                    KeyCode::Down => self.move_down(),
+                    KeyCode::Home => self.home_toggle(true),
+                    KeyCode::End => self.move_end(),
+                    KeyCode::PageUp => self.page_up(),
+                    KeyCode::PageDown => self.page_down(),
+                    _ => {}
+                }
+                return;
+            }
+        };
+        if self.pending_keys.is_empty() {
+            match c {
+                'v' | 'V' if selection.linewise == (c == 'V') => self.visual = None,
+                'v' | 'V' => self.visual = Some(Selection { linewise: c == 'V', ..selection }),
+                ':' => self.enter_command_mode(':'),
+                'o' => {
+                    let cursor = self.cursor_pos();
+                    self.cursor_y = selection.anchor.y;
+                    self.cursor_x = selection.anchor.x;
+                    self.visual = Some(Selection { anchor: cursor, ..selection });
+                    self.clamp_cursor();
+                }
+                _ => {}
+            }
+            if matches!(c, 'v' | 'V' | 'o' | ':') {
+                self.count = None;
+                return;
+            }
+        }
+
+        self.pending_keys.push(c);
+        let count = self.count.take();
+        match vim::parse_visual(&self.pending_keys) {
+            // Keep the count for the rest of the command
+            Parse::Incomplete => {
+                self.count = count;
+                return;
+            }
+            Parse::Complete(Action::Move(Motion::FirstLine)) => self.go_to_line(count.unwrap_or(1)),
+            Parse::Complete(Action::Move(Motion::LastLine)) => self.go_to_line(count.unwrap_or(self.buffer.len())),
+            Parse::Complete(Action::Move(motion)) => self.apply_motion(motion),
+            Parse::Complete(action @ (Action::OperateSelection(_) | Action::SelectObject(_))) => self.run_action(action),
+            Parse::Complete(_) | Parse::Invalid => {}
+        }
+        self.pending_keys.clear();
+    }
+
     fn handle_vim_insert_mode(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        if self.handle_completion_key(key_event) {
+            return Ok(false);
+        }
         match key_event.code {
             KeyCode::Esc => {
                 self.mode = Mode::Normal;
+                self.snippet_stops.clear();
                 if self.cursor_x > 0 && self.cursor_x == self.current_line().len() {
                     self.cursor_x -= 1;
                 }
@@ -392,58 +1064,167 @@ impl Editor {
             KeyCode::Right => self.move_right(),
             KeyCode::Up => self.move_up(),
             KeyCode::Down => self.move_down(),
-            KeyCode::Home => self.move_home(),
+            KeyCode::Home => self.home_toggle(true),
             KeyCode::End => self.move_end(),
             KeyCode::PageUp => self.page_up(),
             KeyCode::PageDown => self.page_down(),
             KeyCode::Backspace => self.backspace(),
             KeyCode::Delete => self.delete(),
             KeyCode::Enter => self.insert_newline(),
-            KeyCode::Tab => self.insert_tab(),
-            KeyCode::Char(c) => {
-                if !key_event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
-                    self.insert_char(c);
-                }
+            KeyCode::Tab => self.tab_key(),
+            KeyCode::Char('t') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.toggle_checkbox(),
+            KeyCode::Char('n') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.start_completion(false),
+            KeyCode::Char('v') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.literal_next = true,
+            KeyCode::F(5) => self.insert_timestamp(),
+            KeyCode::Char('p') if key_event.modifiers.contains(KeyModifiers::CONTROL) => self.start_completion(true),
+            KeyCode::Char(c) if keys::typed(&key_event).is_some() => {
+                self.insert_char(c);
             }
             _ => {}
         }
         Ok(false)
     }
 
-    fn handle_command_mode(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+    // Like insert mode, except typed characters overwrite the text under
+    // the cursor. Backspace puts back what was overwritten.
+    fn handle_replace_mode(&mut self, key_event: KeyEvent) -> io::Result<bool> {
         match key_event.code {
             KeyCode::Esc => {
-                if self.config.vim_bindings {
-                    self.mode = Mode::Normal;
-                } else {
-                    self.mode = Mode::Insert;
-                }
-                self.command_buffer.clear();
+                self.mode = Mode::Normal;
+                self.clamp_cursor();
                 self.dirty = true;
             }
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Up => self.move_up(),
+            KeyCode::Down => self.move_down(),
+            KeyCode::Backspace => self.undo_overwrite(),
             KeyCode::Enter => {
-                let result = self.execute_command();
-                if self.config.vim_bindings {
-                    self.mode = Mode::Normal;
+                self.replaced.clear();
+                self.insert_newline();
+            }
+            KeyCode::Char(c) if keys::typed(&key_event).is_some() => {
+                self.overwrite_char(c);
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    // Type over the character under the cursor (or append at the end of the line)
+    fn overwrite_char(&mut self, c: char) {
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+
+        let line = &mut self.buffer[self.cursor_y];
+        if self.cursor_x < line.len() {
+            // std::mem::replace swaps in the new value and hands back the old one
+            let old = std::mem::replace(&mut line[self.cursor_x], c);
+            self.replaced.push(Some(old));
+        } else {
+            line.push(c);
+            self.replaced.push(None);
+        }
+        self.cursor_x += 1;
+        self.mark_modified();
+    }
+
+    fn undo_overwrite(&mut self) {
+        if self.cursor_x == 0 {
+            return;
+        }
+        self.cursor_x -= 1;
+        match self.replaced.pop() {
+            Some(Some(old)) => self.buffer[self.cursor_y][self.cursor_x] = old,
+            Some(None) => {
+                self.buffer[self.cursor_y].remove(self.cursor_x);
+            }
+            None => {} // Before where replacing started: just move back
+        }
+        self.mark_modified();
+    }
+
+    fn enter_command_mode(&mut self, prompt: char) {
+        self.mode = Mode::Command;
+        self.command_prompt = prompt;
+        self.command_buffer.clear();
+        self.completions.clear();
+        self.dirty = true;
+    }
+
+    fn leave_command_mode(&mut self) {
+        // A command can pick the mode itself (:snippet goes to insert mode)
+        if self.mode == Mode::Command {
+            self.mode = if self.config.vim_bindings { Mode::Normal } else { Mode::Insert };
+        }
+        // Asked for a URL and got none (or :link has used it)
+        self.pending_link = None;
+        // A selection kept for the command (: in visual mode) ends with it
+        self.visual = None;
+        self.command_buffer.clear();
+        self.completions.clear();
+        self.command_history.reset();
+        self.search_history.reset();
+        self.dirty = true;
+    }
+
+    // Commands and searches keep separate histories, like vim
+    fn active_history(&mut self) -> &mut History {
+        if self.command_prompt == ':' {
+            &mut self.command_history
+        } else {
+            &mut self.search_history
+        }
+    }
+
+    fn handle_command_mode(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        // Any key other than Tab ends a completion cycle
+        if key_event.code != KeyCode::Tab {
+            self.completions.clear();
+        }
+
+        match key_event.code {
+            KeyCode::Esc => self.leave_command_mode(),
+            KeyCode::Enter => {
+                let line = self.command_buffer.clone();
+                self.active_history().push(&line);
+                let result = if self.command_prompt == ':' {
+                    self.execute_command()
                 } else {
-                    self.mode = Mode::Insert;
-                }
-                self.command_buffer.clear();
-                self.dirty = true;
+                    let forward = self.command_prompt == '/';
+                    let (pattern, offset) = search::parse(&line, self.command_prompt);
+                    self.search(&pattern, offset, forward);
+                    Ok(false)
+                };
+                self.leave_command_mode();
                 return result;
             }
+            KeyCode::Up => {
+                let current = self.command_buffer.clone();
+                if let Some(line) = self.active_history().older(&current) {
+                    self.command_buffer = line;
+                    self.dirty = true;
+                }
+            }
+            KeyCode::Down => {
+                if let Some(line) = self.active_history().newer() {
+                    self.command_buffer = line;
+                    self.dirty = true;
+                }
+            }
+            KeyCode::Tab if self.command_prompt == ':' => self.complete_command(),
             KeyCode::Backspace => {
+                self.active_history().reset();
                 self.command_buffer.pop();
                 if self.command_buffer.is_empty() {
-                    if self.config.vim_bindings {
-                        self.mode = Mode::Normal;
-                    } else {
-                        self.mode = Mode::Insert;
-                    }
+                    self.leave_command_mode();
                 }
                 self.dirty = true;
             }
             KeyCode::Char(c) => {
+                self.active_history().reset();
                 self.command_buffer.push(c);
                 self.dirty = true;
             }
@@ -452,1046 +1233,5109 @@ impl Editor {
         Ok(false)
     }
 
-    fn execute_command(&mut self) -> io::Result<bool> {
-        let cmd = self.command_buffer.trim();
-        
-        if self.config.vim_bindings {
-            match cmd {
-                "q" => return Ok(true),
-                "prompt" => {
-                    // Show today's prompt in the command area
-                    self.command_buffer = format!("Today's prompt: {}", self.get_daily_prompt());
-                    self.dirty = true;
-                    // Don't exit command mode so user can see the prompt
-                    return Ok(false);
-                }
-                _ => {}
-            }
-        } else if cmd == "prompt" {
-            // Also support :prompt in non-vim mode
-            self.command_buffer = format!("Today's prompt: {}", self.get_daily_prompt());
+    // Ctrl-N/Ctrl-P: open the completion menu for the word before the cursor
+    fn start_completion(&mut self, backwards: bool) {
+        if !self.ensure_writable() {
+            return;
+        }
+        let start = autocomplete::word_start(self.current_line(), self.cursor_x);
+        let prefix: String = self.current_line()[start..self.cursor_x].iter().collect();
+        if prefix.is_empty() {
+            self.status_message = Some(t!("Type the start of a word to complete it"));
             self.dirty = true;
-            return Ok(false);
+            return;
         }
-        
-        Ok(false)
-    }
-
-    fn last_key_was(&self, _c: char) -> bool {
-        // Simplified for now - in a real implementation, we'd track the last key
-        true
-    }
-
-    // Movement methods - note they take &mut self to modify cursor position
-    fn move_left(&mut self) {
-        if self.cursor_x > 0 {
-            self.cursor_x -= 1; // -= is compound assignment
-        } else if self.cursor_y > 0 && (self.mode == Mode::Insert || !self.config.vim_bindings) {
-            self.cursor_y -= 1;
-            // Method calls use . notation
-            self.cursor_x = self.current_line().len();
+        let other_notes = self.recent_notes_text();
+        let candidates = autocomplete::candidates(&prefix, &self.buffer, self.cursor_y, &other_notes);
+        self.completion = Completion::new(start, prefix, candidates, backwards);
+        if self.completion.is_some() {
+            self.apply_completion();
+        } else {
+            self.status_message = Some(t!("No completions"));
         }
         self.dirty = true;
     }
 
-    fn move_right(&mut self) {
-        let line_len = self.current_line().len();
-        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
-            line_len - 1
-        } else {
-            line_len
-        };
-        
-        if self.cursor_x < max_x {
-            self.cursor_x += 1;
-        } else if self.cursor_y < self.buffer.len() - 1 && (self.mode == Mode::Insert || !self.config.vim_bindings) {
-            self.cursor_y += 1;
-            self.cursor_x = 0;
+    // The text of the newest completion_notes notes, other than this one
+    fn recent_notes_text(&self) -> Vec<String> {
+        if self.config.completion_notes == 0 {
+            return Vec::new();
         }
-        self.dirty = true;
+        let mut notes: Vec<PathBuf> = fs::read_dir(&self.config.daily_notes_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .filter(|path| Some(path.to_string_lossy().as_ref()) != self.filename.as_deref())
+            .collect();
+        // Daily notes are named by date, so the newest sort last
+        notes.sort();
+        notes.iter()
+            .rev()
+            .take(self.config.completion_notes)
+            .filter_map(|path| crypto::read_to_string(path).ok())
+            .collect()
     }
 
-    fn move_up(&mut self) {
-        if self.cursor_y > 0 {
-            self.cursor_y -= 1;
-            let line_len = self.current_line().len();
-            let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
-                line_len - 1
-            } else {
-                line_len
-            };
-            self.cursor_x = self.cursor_x.min(max_x);
-            self.dirty = true;
-        }
+    // Put the selected completion (or the word as typed) in the text
+    fn apply_completion(&mut self) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
+        let word: Vec<char> = completion.current().chars().collect();
+        let start = completion.start;
+        self.track_typing();
+        let end = self.cursor_x;
+        self.buffer[self.cursor_y].splice(start..end, word.iter().copied());
+        self.cursor_x = start + word.len();
+        self.mark_modified();
     }
 
-    fn move_down(&mut self) {
-        if self.cursor_y < self.buffer.len() - 1 {
-            self.cursor_y += 1;
-            let line_len = self.current_line().len();
-            let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
-                line_len - 1
-            } else {
-                line_len
-            };
-            self.cursor_x = self.cursor_x.min(max_x);
-            self.dirty = true;
+    // Keys while the completion menu is open. Any key it doesn't use
+    // keeps the word in the text, closes the menu and returns false, so
+    // the key is then handled as usual.
+    fn handle_completion_key(&mut self, key_event: KeyEvent) -> bool {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(completion) = self.completion.as_mut() else {
+            return false;
+        };
+        match key_event.code {
+            KeyCode::Char('n') if ctrl => completion.next(),
+            KeyCode::Down => completion.next(),
+            KeyCode::Char('p') if ctrl => completion.previous(),
+            KeyCode::Up => completion.previous(),
+            // Esc and Ctrl-E go back to the word as typed
+            KeyCode::Esc => completion.selected = None,
+            KeyCode::Char('e') if ctrl => completion.selected = None,
+            // Enter and Ctrl-Y keep the word without starting a new line
+            KeyCode::Enter => {}
+            KeyCode::Char('y') if ctrl => {}
+            _ => {
+                self.completion = None;
+                self.dirty = true;
+                return false;
+            }
         }
+        self.apply_completion();
+        if matches!(key_event.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('e') | KeyCode::Char('y')) {
+            self.completion = None;
+        }
+        true
     }
 
-    fn move_home(&mut self) {
-        self.cursor_x = 0;
+    // :syn - open the synonyms popup for the word under the cursor
+    fn show_synonyms(&mut self) {
         self.dirty = true;
+        let Some((start, end)) = self.word_at_cursor() else {
+            self.status_message = Some(t!("No word under the cursor"));
+            return;
+        };
+        let Some(path) = thesaurus::find_file(&self.config) else {
+            self.status_message = Some(t!("No thesaurus found - set thesaurus_file in config.toml"));
+            return;
+        };
+        let word: String = self.current_line()[start.x..end.x].iter().collect();
+        match thesaurus::lookup(&path, &word) {
+            Ok(list) if list.is_empty() => self.status_message = Some(t!("No synonyms for \"{word}\"", word = word)),
+            Ok(list) => self.synonyms = Some(Synonyms { start, end, list, selected: 0 }),
+            Err(e) => self.status_message = Some(t!("Couldn't read {path}: {error}", path = path.display(), error = e)),
+        }
     }
 
-    fn move_end(&mut self) {
-        let line_len = self.current_line().len();
-        self.cursor_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
-            line_len - 1
+    // :define [word] - the dictionary entry for 'word', or the word under
+    // the cursor
+    fn define(&mut self, word: &str) {
+        self.dirty = true;
+        let word = if word.is_empty() {
+            match self.word_at_cursor() {
+                Some((start, end)) => self.current_line()[start.x..end.x].iter().collect(),
+                None => {
+                    self.status_message = Some(t!("No word under the cursor"));
+                    return;
+                }
+            }
         } else {
-            line_len
+            word.to_string()
         };
-        self.dirty = true;
+        match define::lookup(&self.config, &word) {
+            Ok(definition) => self.definition = Some(definition),
+            Err(e) => self.status_message = Some(e),
+        }
     }
 
-    fn move_word_forward(&mut self) {
-        let line = self.current_line();
-        let mut x = self.cursor_x;
-        
-        // Skip current word
-        while x < line.len() && line[x].is_alphanumeric() {
-            x += 1;
-        }
-        // Skip spaces
-        while x < line.len() && !line[x].is_alphanumeric() {
-            x += 1;
-        }
-        
-        if x < line.len() {
-            self.cursor_x = x;
-        } else if self.cursor_y < self.buffer.len() - 1 {
-            self.cursor_y += 1;
-            self.cursor_x = 0;
+    // The definition popup only scrolls; any key it doesn't use closes it
+    fn handle_definition_key(&mut self, key_event: KeyEvent) {
+        let page = self.text_area().height as isize / 2;
+        let Some(definition) = self.definition.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => definition.scroll(1),
+            KeyCode::Up | KeyCode::Char('k') => definition.scroll(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => definition.scroll(page),
+            KeyCode::PageUp => definition.scroll(-page),
+            _ => self.definition = None,
         }
         self.dirty = true;
     }
 
-    fn move_word_backward(&mut self) {
-        if self.cursor_x == 0 {
-            if self.cursor_y > 0 {
-                self.cursor_y -= 1;
-                self.cursor_x = self.current_line().len();
-                if self.cursor_x > 0 {
-                    self.cursor_x -= 1;
-                }
-            }
-            return;
-        }
-        
-        let line = self.current_line();
-        let mut x = self.cursor_x - 1;
-        
-        // Skip spaces
-        while x > 0 && !line[x].is_alphanumeric() {
-            x -= 1;
-        }
-        // Skip word
-        while x > 0 && line[x - 1].is_alphanumeric() {
-            x -= 1;
-        }
-        
-        self.cursor_x = x;
+    // The cheat sheet for the mode being used. Opened from the command
+    // line, that's the mode it goes back to.
+    fn open_help(&mut self) {
+        let (mode, table) = match self.mode {
+            _ if !self.config.vim_bindings => ("without vim bindings", help::STANDARD),
+            Mode::Insert | Mode::Replace => ("insert mode", help::INSERT),
+            Mode::Normal | Mode::Command => ("normal mode", help::NORMAL),
+        };
+        self.help = Some(Help::new(mode, table, &self.config));
         self.dirty = true;
     }
 
-    fn move_word_end(&mut self) {
-        let line = self.current_line();
-        let mut x = self.cursor_x;
-        
-        if x < line.len() - 1 {
-            x += 1;
-            // Skip to end of current word
-            while x < line.len() - 1 && line[x].is_alphanumeric() {
-                x += 1;
-            }
-            self.cursor_x = x;
-        } else if self.cursor_y < self.buffer.len() - 1 {
-            self.cursor_y += 1;
-            self.cursor_x = 0;
+    // Scrolls like the :define popup; any other key closes it
+    fn handle_help_key(&mut self, key_event: KeyEvent) {
+        let page = self.text_area().height as isize / 2;
+        let Some(help) = self.help.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => help.scroll(1),
+            KeyCode::Up | KeyCode::Char('k') => help.scroll(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => help.scroll(page),
+            KeyCode::PageUp => help.scroll(-page),
+            _ => self.help = None,
         }
         self.dirty = true;
     }
 
-    fn delete_char(&mut self) {
-        self.track_typing(); // Track typing activity
-        
-        if self.cursor_x < self.current_line().len() {
-            self.buffer[self.cursor_y].remove(self.cursor_x);
-            if self.cursor_x > 0 && self.cursor_x == self.current_line().len() && self.config.vim_bindings {
-                self.cursor_x -= 1;
+    // j/k (or the arrows) choose a synonym, Enter puts it in place of the
+    // word, and any other key closes the popup
+    fn handle_synonyms_key(&mut self, key_event: KeyEvent) {
+        self.dirty = true;
+        let Some(synonyms) = self.synonyms.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => synonyms.select_next(),
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::BackTab => synonyms.select_previous(),
+            KeyCode::Enter => {
+                let Some(synonyms) = self.synonyms.take() else {
+                    return;
+                };
+                if !self.ensure_writable() {
+                    return;
+                }
+                let word: Vec<char> = synonyms.list[synonyms.selected].chars().collect();
+                self.buffer[synonyms.start.y].splice(synonyms.start.x..synonyms.end.x, word);
+                self.cursor_y = synonyms.start.y;
+                self.cursor_x = synonyms.start.x;
+                self.mark_modified();
             }
-            self.dirty = true;
-            self.needs_save = true;
-            self.last_save = Instant::now();
+            _ => self.synonyms = None,
         }
     }
 
-    fn delete_line(&mut self) {
-        self.track_typing(); // Track typing activity
-        
-        self.clipboard = vec![self.buffer[self.cursor_y].clone()];
-        if self.buffer.len() > 1 {
-            self.buffer.remove(self.cursor_y);
-            if self.cursor_y >= self.buffer.len() {
-                self.cursor_y = self.buffer.len() - 1;
-            }
-        } else {
-            self.buffer[0].clear();
+    // The diff view only scrolls; any key it doesn't use closes it
+    fn handle_diff_key(&mut self, key_event: KeyEvent) {
+        let page = self.text_area().height.saturating_sub(2) as isize;
+        let Some(view) = self.diff_view.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => view.scroll(1),
+            KeyCode::Up | KeyCode::Char('k') => view.scroll(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => view.scroll(page),
+            KeyCode::PageUp => view.scroll(-page),
+            KeyCode::Home | KeyCode::Char('g') => view.top = 0,
+            KeyCode::End | KeyCode::Char('G') => view.top = view.changes.len().saturating_sub(page as usize),
+            KeyCode::Char('n') => view.next_change(true),
+            KeyCode::Char('N') => view.next_change(false),
+            _ => self.diff_view = None,
         }
-        self.cursor_x = 0;
         self.dirty = true;
-        self.needs_save = true;
-        self.last_save = Instant::now();
-    }
-
-    fn yank_line(&mut self) {
-        self.clipboard = vec![self.buffer[self.cursor_y].clone()];
     }
 
-    fn paste_after(&mut self) {
-        if !self.clipboard.is_empty() {
-            self.track_typing(); // Track typing activity
-            
-            for (i, line) in self.clipboard.iter().enumerate() {
-                self.buffer.insert(self.cursor_y + 1 + i, line.clone());
+    // The :grep pane: move through the results, Enter opens one
+    fn handle_grep_key(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(grep) = self.grep.as_mut() else {
+            return Ok(false);
+        };
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.grep_pane = false,
+            KeyCode::Enter => {
+                self.grep_pane = false;
+                self.open_grep_match()?;
             }
-            self.cursor_y += 1;
-            self.cursor_x = 0;
-            self.dirty = true;
-            self.needs_save = true;
-            self.last_save = Instant::now();
+            KeyCode::Down | KeyCode::Char('j') => grep.select_next(),
+            KeyCode::Char('n') if ctrl => grep.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => grep.select_previous(),
+            KeyCode::Char('p') if ctrl => grep.select_previous(),
+            _ => {}
         }
+        self.dirty = true;
+        Ok(false)
     }
 
-    fn paste_before(&mut self) {
-        if !self.clipboard.is_empty() {
-            self.track_typing(); // Track typing activity
-            
-            for (i, line) in self.clipboard.iter().enumerate() {
-                self.buffer.insert(self.cursor_y + i, line.clone());
+    fn handle_outline_key(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(outline) = self.outline.as_mut() else {
+            return Ok(false);
+        };
+
+        match key_event.code {
+            KeyCode::Esc => self.outline = None,
+            KeyCode::Enter => {
+                if let Some(heading) = outline.selected_heading() {
+                    self.cursor_y = heading.line;
+                    self.cursor_x = 0;
+                }
+                self.outline = None;
             }
-            self.cursor_x = 0;
-            self.dirty = true;
-            self.needs_save = true;
-            self.last_save = Instant::now();
+            KeyCode::Down => outline.select_next(),
+            KeyCode::Char('n') if ctrl => outline.select_next(),
+            KeyCode::Up => outline.select_previous(),
+            KeyCode::Char('p') if ctrl => outline.select_previous(),
+            KeyCode::Backspace => {
+                outline.query.pop();
+                outline.update();
+            }
+            KeyCode::Char(c) if keys::typed(&key_event).is_some() => {
+                outline.query.push(c);
+                outline.update();
+            }
+            _ => {}
         }
+        self.dirty = true;
+        Ok(false)
     }
 
-
-    fn page_up(&mut self) {
-        let page_size = (self.terminal_height - 2) as usize;
-        self.cursor_y = self.cursor_y.saturating_sub(page_size);
-        let line_len = self.current_line().len();
-        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
-            line_len - 1
-        } else {
-            line_len
-        };
-        self.cursor_x = self.cursor_x.min(max_x);
+    fn open_finder(&mut self) {
+        self.finder = Some(Finder::new(Path::new(&self.config.daily_notes_dir)));
         self.dirty = true;
     }
 
-    fn page_down(&mut self) {
-        let page_size = (self.terminal_height - 2) as usize;
-        self.cursor_y = (self.cursor_y + page_size).min(self.buffer.len() - 1);
-        let line_len = self.current_line().len();
-        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
-            line_len - 1
-        } else {
-            line_len
+    fn handle_finder_key(&mut self, key_event: KeyEvent) -> io::Result<bool> {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        // as_mut() gives Option<&mut Finder> without taking it out
+        let Some(finder) = self.finder.as_mut() else {
+            return Ok(false);
         };
-        self.cursor_x = self.cursor_x.min(max_x);
-        self.dirty = true;
-    }
 
-    fn insert_char(&mut self, c: char) {
-        // Track typing activity
-        self.track_typing();
-        
-        // &mut creates a mutable reference - can modify the line
-        let line = &mut self.buffer[self.cursor_y];
-        line.insert(self.cursor_x, c);
-        self.cursor_x += 1;
-        
-        // Auto line wrap when reaching terminal width (with some margin)
-        let wrap_width = (self.terminal_width - 5) as usize; // Leave some margin
-        if self.cursor_x >= wrap_width && c != ' ' {
-            // Find last space to break at word boundary
-            let mut break_pos = self.cursor_x;
-            for i in (0..self.cursor_x).rev() {
-                if line[i] == ' ' {
-                    break_pos = i + 1;
-                    break;
+        match key_event.code {
+            KeyCode::Esc => self.finder = None,
+            KeyCode::Enter => {
+                let path = finder.selected_entry().map(|entry| entry.path.to_string_lossy().to_string());
+                self.finder = None;
+                if let Some(path) = path {
+                    self.open_file(&path, false)?;
                 }
             }
-            
-            // If no space found or space is too far back, just break at current position
-            if break_pos == self.cursor_x || self.cursor_x - break_pos > 20 {
-                break_pos = self.cursor_x;
+            KeyCode::Down => finder.select_next(),
+            KeyCode::Char('n') if ctrl => finder.select_next(),
+            KeyCode::Up => finder.select_previous(),
+            KeyCode::Char('p') if ctrl => finder.select_previous(),
+            KeyCode::Backspace => {
+                finder.query.pop();
+                finder.update();
             }
-            
-            // Move text after break position to new line
-            let new_line: Vec<char> = line.drain(break_pos..).collect();
-            self.buffer.insert(self.cursor_y + 1, new_line);
-            
-            // Update cursor position
-            self.cursor_y += 1;
-            self.cursor_x = self.cursor_x - break_pos;
+            KeyCode::Char(c) if keys::typed(&key_event).is_some() => {
+                finder.query.push(c);
+                finder.update();
+            }
+            _ => {}
         }
-        
         self.dirty = true;
-        self.needs_save = true;
-        self.last_save = Instant::now(); // Reset the timer on each change
+        Ok(false)
     }
 
-    fn insert_tab(&mut self) {
-        for _ in 0..self.config.tab_size {
-            self.insert_char(' ');
+    // Tab cycles through every candidate for the text typed so far
+    fn complete_command(&mut self) {
+        if self.completions.is_empty() {
+            let scripts = script::names();
+            let user_commands: Vec<&String> = self.config.commands.keys().chain(&scripts).collect();
+            self.completions = completion::complete(&self.command_buffer, &user_commands);
+            self.completion_index = 0;
+        } else {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+        }
+
+        if let Some(candidate) = self.completions.get(self.completion_index) {
+            self.command_buffer = candidate.clone();
+            self.dirty = true;
         }
     }
 
-    fn insert_newline(&mut self) {
-        self.track_typing(); // Track typing activity
-        
-        let current_line = &mut self.buffer[self.cursor_y];
-        let new_line: Vec<char> = current_line.drain(self.cursor_x..).collect();
-        self.buffer.insert(self.cursor_y + 1, new_line);
-        self.cursor_y += 1;
-        self.cursor_x = 0;
-        self.dirty = true;
-        self.needs_save = true;
-        self.last_save = Instant::now();
+    fn execute_command(&mut self) -> io::Result<bool> {
+        // to_string() makes an owned copy so self can be mutated below
+        let cmd = self.command_buffer.trim().to_string();
+        self.run_command(&cmd)
     }
 
-    fn backspace(&mut self) {
-        self.track_typing(); // Track typing activity
-        
-        if self.cursor_x > 0 {
-            self.buffer[self.cursor_y].remove(self.cursor_x - 1);
-            self.cursor_x -= 1;
-            self.dirty = true;
-            self.needs_save = true;
-            self.last_save = Instant::now();
-        } else if self.cursor_y > 0 {
-            let current_line = self.buffer.remove(self.cursor_y);
-            self.cursor_y -= 1;
-            self.cursor_x = self.buffer[self.cursor_y].len();
-            self.buffer[self.cursor_y].extend(current_line);
-            self.dirty = true;
-            self.needs_save = true;
-            self.last_save = Instant::now();
+    // Run one command line (without the ':'), typed or from [commands]
+    fn run_command(&mut self, cmd: &str) -> io::Result<bool> {
+        // :!cmd and :{range}!cmd run shell commands (see filter.rs)
+        if let Some(command) = cmd.strip_prefix('!') {
+            self.shell_command(command.trim());
+            return Ok(false);
+        }
+        if let Some((first, last, command)) = filter::parse(cmd, self.cursor_y, self.buffer.len() - 1) {
+            self.filter_lines(first, last, command);
+            return Ok(false);
+        }
+        let last_search = self.last_search.as_ref().map(|(pattern, _, _)| pattern.as_str());
+        match substitute::parse(cmd, self.cursor_y, self.buffer.len() - 1, last_search) {
+            Some(Ok((first, last, sub))) => {
+                self.substitute(first, last, sub);
+                return Ok(false);
+            }
+            Some(Err(e)) => {
+                self.status_message = Some(e);
+                return Ok(false);
+            }
+            None => {}
         }
-    }
 
-    fn delete(&mut self) {
-        self.track_typing(); // Track typing activity
-        
-        let line_len = self.current_line().len();
-        if self.cursor_x < line_len {
-            self.buffer[self.cursor_y].remove(self.cursor_x);
+        // Split "w notes.md" into the command name and its argument
+        let (name, arg) = match cmd.split_once(' ') {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (cmd, ""),
+        };
+
+        match name {
+            // With several windows open, :q closes just the current one
+            "q" if self.windows.len() > 1 => self.close_window()?,
+            "q" if self.config.vim_bindings => return Ok(true),
+            "sp" | "split" | "vs" | "vsplit" => {
+                let direction = if name.starts_with('v') {
+                    SplitDirection::Vertical
+                } else {
+                    SplitDirection::Horizontal
+                };
+                self.split_window(direction, &config::expand_tilde(arg))?;
+            }
+            "close" => self.close_window()?,
+            "find" => self.open_finder(),
+            "fold" => match arg {
+                "" => self.toggle_fold(),
+                "all" => self.fold_all(true),
+                "none" => self.fold_all(false),
+                _ => self.status_message = Some(t!("Usage: :fold [all|none]")),
+            },
+            "diff" => self.diff_command(arg),
+            "grep" => self.grep_command(arg)?,
+            "cn" | "cnext" => self.step_grep(true)?,
+            "cp" | "cprevious" | "cN" => self.step_grep(false)?,
+            "copen" | "cw" => {
+                if self.grep.is_some() {
+                    self.grep_pane = true;
+                } else {
+                    self.status_message = Some(t!("No results - search with :grep <term>"));
+                }
+            }
+            "cclose" => self.grep_pane = false,
+            "link" => self.link_command(arg),
+            "r" | "read" => self.read_command(arg),
+            "outline" => {
+                self.outline = Some(Outline::new(&self.buffer, self.cursor_y));
+            }
+            "scratch" => self.toggle_scratch()?,
+            "syn" => self.show_synonyms(),
+            "define" => self.define(arg),
+            "help" if arg.is_empty() => self.open_help(),
+            "help" => {
+                self.status_message = Some(help::command(arg).unwrap_or_else(|| t!("No help for {command}", command = arg)));
+            }
+            "grammar" => self.check_grammar(true),
+            "reload-config" => self.reload_config(),
+            "set" => self.set_command(arg),
+            "spell" => {
+                self.spell = match self.spell {
+                    Some(_) => None,
+                    None => Some(Spell::new(&self.config)),
+                };
+                self.status_message = Some(if self.spell.is_some() { t!("Spell checking on") } else { t!("Spell checking off") });
+            }
+            "only" => self.only_window(),
+            "w" | "wq" if self.read_only => {
+                self.status_message = Some(t!("Read-only buffer - use :view to allow editing"));
+            }
+            "w" | "wq" => {
+                if !arg.is_empty() {
+                    self.filename = Some(config::expand_tilde(arg));
+                }
+                self.save_file()?;
+                if let Some(filename) = &self.filename {
+                    self.status_message = Some(t!("\"{file}\" written", file = filename));
+                }
+                if name == "wq" {
+                    return Ok(true);
+                }
+            }
+            "bn" | "bp" => {
+                let count = self.buffers.len();
+                let next = if name == "bn" {
+                    (self.active_buffer + 1) % count
+                } else {
+                    (self.active_buffer + count - 1) % count
+                };
+                self.switch_buffer(next)?;
+            }
+            "b" => match arg.parse::<usize>() {
+                // Buffers are numbered from 1 in :ls, like vim
+                Ok(n) if n >= 1 && n <= self.buffers.len() => self.switch_buffer(n - 1)?,
+                _ => self.status_message = Some(t!("No such buffer: {buffer}", buffer = arg)),
+            },
+            "ls" => self.list_buffers(),
+            "e" => {
+                if arg.is_empty() {
+                    self.status_message = Some(t!("Usage: :e <path>"));
+                } else {
+                    self.open_file(&config::expand_tilde(arg), false)?;
+                }
+            }
+            "view" if arg.is_empty() => {
+                // Toggle read-only on the current buffer
+                self.read_only = !self.read_only;
+                self.status_message = Some(if self.read_only { t!("Read-only") } else { t!("Editing") });
+                // lock_file puts it back to read-only if another river has it
+                self.lock_file();
+            }
+            "view!" => {
+                // Edit even though another river has the file open
+                if let Some(filename) = self.filename.clone() {
+                    if let Some(lock) = Lock::take_over(Path::new(&filename)) {
+                        self.locks.insert(filename, lock);
+                    }
+                }
+                self.read_only = false;
+                self.status_message = Some(t!("Editing"));
+            }
+            "view" => {
+                let path = resolve_note_path(&self.config, arg);
+                self.open_file(&path.to_string_lossy(), true)?;
+            }
+            // Of the selection, when : was pressed in visual mode
+            "count" => {
+                let text = self.selection_text();
+                let stats = text_stats::analyze(text.as_deref().unwrap_or(&self.buffer_text()), self.config.markdown_word_count);
+                let counts = t!(
+                    "{words} words, {characters} characters ({letters} without spaces), {sentences} sentences, {paragraphs} paragraphs, ~{minutes} min read",
+                    words = stats.words,
+                    characters = stats.characters,
+                    letters = stats.characters_no_spaces,
+                    sentences = stats.sentences,
+                    paragraphs = stats.paragraphs,
+                    minutes = text_stats::reading_minutes(stats.words, self.config.reading_wpm)
+                );
+                self.status_message = Some(match text {
+                    Some(_) => t!("Selection: {counts}", counts = counts),
+                    None => counts,
+                });
+            }
+            "readability" => {
+                if arg == "highlight" {
+                    self.highlight_long_sentences = !self.highlight_long_sentences;
+                }
+                let long_words = self.config.long_sentence_words;
+                let r = text_stats::readability(&self.buffer_text(), self.config.markdown_word_count, long_words);
+                self.status_message = Some(t!(
+                    "Grade level {grade} · {per_sentence} words per sentence · {percent}% of sentences over {long} words ({count})",
+                    grade = format!("{:.1}", r.grade),
+                    per_sentence = format!("{:.1}", r.words_per_sentence),
+                    percent = format!("{:.0}", r.long_sentence_percent),
+                    long = long_words,
+                    count = r.long_sentences
+                ));
+            }
+            "analyze" => {
+                let label = self.filename.clone().unwrap_or_else(|| "buffer".to_string());
+                let report = analyze::analyze(&[(label, self.buffer_text())]);
+                self.open_scratch(analyze::format_report(&report))?;
+            }
+            "toggle" => self.toggle_checkbox(),
+            "meta" => self.meta_command(arg),
+            // :goal shows the word goal, :goal 1000 sets one for this note
+            "goal" if arg.is_empty() => {
+                self.status_message = Some(t!("Goal: {words} words", words = self.word_goal()));
+            }
+            "goal" => match arg.parse::<usize>() {
+                Ok(goal) if goal > 0 => self.meta_command(&format!("set goal {}", goal)),
+                _ => self.status_message = Some(t!("Usage: :goal <words>")),
+            },
+            "title" => self.title_command(arg),
+            "vault" => self.vault_command(arg)?,
+            "now" => self.insert_timestamp(),
+            "snippet" if arg.is_empty() => {
+                let names = snippet::names();
+                self.status_message = Some(if names.is_empty() {
+                    t!("No snippets yet - add files to {dir}", dir = snippet::dir().display())
+                } else {
+                    t!("Snippets: {names}", names = names.join(", "))
+                });
+            }
+            "snippet" => match snippet::load(arg) {
+                Ok(snippet) => self.insert_snippet(snippet),
+                Err(e) => self.status_message = Some(e),
+            },
+            "prompt" => {
+                let prompt = self.get_daily_prompt();
+                // Bring the ghost prompt back if there's still room for it,
+                // otherwise show the prompt below the status bar
+                if self.has_empty_body() && self.buffer.len() > 1 && self.config.prompt_style == "ghost" {
+                    self.should_show_prompt = true;
+                } else {
+                    self.status_message = Some(t!("Today's prompt: {prompt}", prompt = prompt));
+                }
+                self.current_prompt = Some(prompt);
+            }
+            name if self.config.commands.contains_key(name) => return self.run_user_command(name),
+            name if script::exists(name) => return self.run_script(name),
+            _ => {}
+        }
+
+        Ok(false)
+    }
+
+    // Jump to the next occurrence of 'pattern', wrapping around the buffer,
+    // and say which match it is (see search.rs for offsets)
+    fn search(&mut self, pattern: &str, offset: Offset, forward: bool) {
+        if pattern.is_empty() {
+            return;
+        }
+        self.last_search = Some((pattern.to_string(), offset, forward));
+
+        let matches = search::find_all(&self.buffer, pattern);
+        if matches.is_empty() {
+            self.status_message = Some(t!("Pattern not found: {pattern}", pattern = pattern));
+            return;
+        }
+        let len = pattern.chars().count();
+        let cursor = self.cursor_pos();
+        let last = matches.len() - 1;
+        // Count on from the match the cursor was left at, offset and all,
+        // so n after /term/e doesn't find the same one again
+        let current = matches.iter().position(|&start| search::target(&self.buffer, start, len, offset) == cursor);
+        let (index, wrapped) = match (current, forward) {
+            (Some(i), true) if i < last => (i + 1, false),
+            (Some(i), false) if i > 0 => (i - 1, false),
+            (Some(_), true) => (0, true),
+            (Some(_), false) => (last, true),
+            (None, true) => matches.iter().position(|&start| start > cursor).map_or((0, true), |i| (i, false)),
+            (None, false) => matches.iter().rposition(|&start| start < cursor).map_or((last, true), |i| (i, false)),
+        };
+
+        let target = search::target(&self.buffer, matches[index], len, offset);
+        self.cursor_y = target.y;
+        self.cursor_x = target.x;
+        self.dirty = true;
+        let count = t!("match {number} of {count}", number = index + 1, count = matches.len());
+        self.status_message = Some(match (wrapped, forward) {
+            (false, _) => count,
+            (true, true) => t!("search hit BOTTOM, continuing at TOP - {count}", count = count),
+            (true, false) => t!("search hit TOP, continuing at BOTTOM - {count}", count = count),
+        });
+    }
+
+    // n repeats the last search, N repeats it in the opposite direction
+    fn repeat_search(&mut self, reverse: bool) {
+        if let Some((pattern, offset, forward)) = self.last_search.clone() {
+            self.search(&pattern, offset, forward != reverse);
+        }
+    }
+
+    // :[range]s/old/new/[gci] - replace everything at once, or with c go
+    // from match to match asking about each
+    fn substitute(&mut self, first: usize, last: usize, sub: Substitute) {
+        if !self.ensure_writable() {
+            return;
+        }
+        let Some(at) = sub.find(&self.buffer, Pos::new(0, first), last) else {
+            let pattern: String = sub.pattern.iter().collect();
+            self.status_message = Some(t!("Pattern not found: {pattern}", pattern = pattern));
+            return;
+        };
+        let confirm = sub.confirm;
+        self.substitution = Some(Substitution { sub, last, at, replaced: 0, lines: Vec::new() });
+        if confirm {
+            self.show_substitution();
+        } else {
+            while self.substitute_step(true) {}
+        }
+    }
+
+    // Replace the current match (or skip it) and move on to the next one.
+    // Returns false once there are no more, having reported what was done.
+    fn substitute_step(&mut self, replace: bool) -> bool {
+        let Some(state) = self.substitution.as_mut() else {
+            return false;
+        };
+        let at = state.at;
+        if replace {
+            let end = at.x + state.sub.pattern.len();
+            self.buffer[at.y].splice(at.x..end, state.sub.replacement.iter().copied());
+            state.replaced += 1;
+            if state.lines.last() != Some(&at.y) {
+                state.lines.push(at.y);
+            }
+        }
+        let next = state.sub.find(&self.buffer, state.sub.after(at, replace), state.last);
+        if let Some(next) = next {
+            state.at = next;
+        }
+        if replace {
+            self.mark_modified();
+        }
+        if next.is_none() {
+            self.finish_substitution();
+        }
+        next.is_some()
+    }
+
+    fn finish_substitution(&mut self) {
+        let Some(state) = self.substitution.take() else {
+            return;
+        };
+        self.status_message = Some(match state.replaced {
+            0 => t!("No substitutions"),
+            n => tn!(n, "{n} substitution on {lines}", "{n} substitutions on {lines}", lines = tn!(state.lines.len(), "{n} line", "{n} lines")),
+        });
+        self.clamp_cursor();
+        self.dirty = true;
+    }
+
+    // Put the cursor on the match being asked about, and ask
+    fn show_substitution(&mut self) {
+        let Some(state) = &self.substitution else {
+            return;
+        };
+        self.cursor_y = state.at.y;
+        self.cursor_x = state.at.x;
+        let replacement: String = state.sub.replacement.iter().collect();
+        self.status_message = Some(t!("replace with \"{replacement}\" (y/n/a/q/l)?", replacement = replacement));
+        self.dirty = true;
+    }
+
+    // y replace, n skip, a replace the rest, l replace this one and stop,
+    // q (or Esc) stop
+    fn handle_substitute_key(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char('y') => {
+                if self.substitute_step(true) {
+                    self.show_substitution();
+                }
+            }
+            KeyCode::Char('n') => {
+                if self.substitute_step(false) {
+                    self.show_substitution();
+                }
+            }
+            KeyCode::Char('a') => while self.substitute_step(true) {},
+            KeyCode::Char('l') => {
+                if self.substitute_step(true) {
+                    self.finish_substitution();
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.finish_substitution(),
+            // Anything else asks again
+            _ => self.show_substitution(),
+        }
+    }
+
+    // Carry out a completed multi-key command
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Move(motion) => self.apply_motion(motion),
+            Action::Operate(op, motion) => self.operate(op, motion),
+            Action::OperateLine(op) => self.operate_lines(op, self.cursor_y, self.cursor_y),
+            Action::OperateObject(op, object) => {
+                if let Some(span) = vim::select_object(&self.buffer, self.cursor_pos(), object) {
+                    self.operate_span(op, span);
+                }
+            }
+            Action::OperateSelection(op) => {
+                if let Some(selection) = self.visual.take() {
+                    self.operate_span(op, selection.span(self.cursor_pos(), &self.buffer));
+                }
+            }
+            Action::SelectObject(object) => self.select_object(object),
+            Action::ReplaceChar(c) => self.replace_char(c),
+            Action::Scroll(to) => self.scroll_cursor_to(to),
+            Action::Fold(FoldCommand::Toggle) => self.toggle_fold(),
+            Action::Fold(FoldCommand::OpenAll) => self.fold_all(false),
+            Action::Fold(FoldCommand::CloseAll) => self.fold_all(true),
+            Action::SetMark(name) => {
+                self.marks.insert(name, self.cursor_pos());
+            }
+            Action::JumpToMark(name, exact) => self.jump_to_mark(name, exact),
+            Action::AddWord => self.add_word(),
+            Action::Synonyms => self.show_synonyms(),
+            Action::GrammarIssue(forward) => self.next_grammar_issue(forward),
+        }
+    }
+
+    // zg: add the word under the cursor to the personal word list
+    fn add_word(&mut self) {
+        let Some((start, end)) = self.word_at_cursor() else {
+            return;
+        };
+        let word: String = self.current_line()[start.x..end.x].iter().collect();
+        // The list is kept even with spell checking off
+        let spell = self.spell.get_or_insert_with(|| Spell::new(&self.config));
+        self.status_message = Some(match spell.add_word(&self.config, &word) {
+            Ok(()) => t!("\"{word}\" added to {file}", word = word, file = spell::personal_file(&self.config).display()),
+            Err(e) => t!("Couldn't add \"{word}\": {error}", word = word, error = e),
+        });
+        self.dirty = true;
+    }
+
+    // gg and G: the start of a line, counting from 1 (past the end is the
+    // last line)
+    fn go_to_line(&mut self, line: usize) {
+        self.cursor_y = line.clamp(1, self.buffer.len()) - 1;
+        self.cursor_x = 0;
+        self.dirty = true;
+    }
+
+    fn cursor_pos(&self) -> Pos {
+        Pos::new(self.cursor_x, self.cursor_y)
+    }
+
+    // Where a motion would take the cursor, or None if it can't move.
+    // Targets may sit one past the end of a line; apply_motion clamps them.
+    fn motion_target(&self, motion: Motion) -> Option<Pos> {
+        let pos = self.cursor_pos();
+        let line_len = self.current_line().len();
+        let target = match motion {
+            Motion::Left => Pos::new(pos.x.checked_sub(1)?, pos.y),
+            Motion::Right => Pos::new((pos.x + 1).min(line_len), pos.y),
+            Motion::Up => Pos::new(pos.x, pos.y.checked_sub(1)?),
+            Motion::Down if pos.y + 1 < self.buffer.len() => Pos::new(pos.x, pos.y + 1),
+            Motion::Down => return None,
+            Motion::LineStart => Pos::new(0, pos.y),
+            Motion::FirstNonBlank => Pos::new(leading_whitespace(self.current_line()), pos.y),
+            Motion::LineEnd => Pos::new(line_len.saturating_sub(1), pos.y),
+            Motion::WordForward => vim::word_forward(&self.buffer, pos),
+            Motion::WordBackward => vim::word_backward(&self.buffer, pos),
+            Motion::WordEnd => vim::word_end(&self.buffer, pos),
+            Motion::FirstLine => Pos::new(0, 0),
+            Motion::LastLine => Pos::new(0, self.buffer.len() - 1),
+            Motion::SentenceForward => vim::sentence_forward(&self.buffer, pos),
+            Motion::SentenceBackward => vim::sentence_backward(&self.buffer, pos),
+            Motion::ParagraphForward => vim::paragraph_forward(&self.buffer, pos),
+            Motion::ParagraphBackward => vim::paragraph_backward(&self.buffer, pos),
+            Motion::HeadingForward => vim::heading_forward(&self.buffer, pos),
+            Motion::HeadingBackward => vim::heading_backward(&self.buffer, pos),
+            Motion::FindChar(find) => Pos::new(vim::find_char(self.current_line(), pos.x, find, false)?, pos.y),
+        };
+        Some(target)
+    }
+
+    fn apply_motion(&mut self, motion: Motion) {
+        if let Motion::FindChar(find) = motion {
+            self.last_find = Some(find);
+        }
+        if let Some(target) = self.motion_target(motion) {
+            self.cursor_y = target.y;
+            self.cursor_x = target.x;
+            self.clamp_cursor();
+            self.dirty = true;
+        }
+    }
+
+    // Keep the cursor on a character in normal mode (it can only sit past
+    // the end of a line while inserting)
+    fn clamp_cursor(&mut self) {
+        self.cursor_y = self.cursor_y.min(self.buffer.len() - 1);
+        let line_len = self.current_line().len();
+        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+            line_len - 1
+        } else {
+            line_len
+        };
+        self.cursor_x = self.cursor_x.min(max_x);
+    }
+
+    // d{motion}, y{motion} and c{motion}
+    fn operate(&mut self, op: Operator, motion: Motion) {
+        let Some(mut target) = self.motion_target(motion) else {
+            return;
+        };
+        if let Motion::FindChar(find) = motion {
+            self.last_find = Some(find);
+        }
+        let cursor = self.cursor_pos();
+
+        // "cw" on a word changes just the rest of that word, like "ce"
+        let line = self.current_line();
+        if op == Operator::Change && motion == Motion::WordForward && line.get(cursor.x).is_some_and(|c| !c.is_whitespace()) {
+            let word = line[cursor.x].is_alphanumeric();
+            let mut x = cursor.x;
+            while x + 1 < line.len() && !line[x + 1].is_whitespace() && line[x + 1].is_alphanumeric() == word {
+                x += 1;
+            }
+            return self.operate_range(op, cursor, Pos::new(x + 1, cursor.y));
+        }
+
+        match motion.kind() {
+            MotionKind::Linewise => self.operate_lines(op, cursor.y.min(target.y), cursor.y.max(target.y)),
+            kind => {
+                // Like vim, "dw" on the last word of a line stops at the line end
+                // instead of joining the next line on
+                if motion == Motion::WordForward && target.y > cursor.y {
+                    target = Pos::new(self.current_line().len(), cursor.y);
+                }
+                let start = cursor.min(target);
+                let mut end = cursor.max(target);
+                if kind == MotionKind::Inclusive {
+                    end.x = (end.x + 1).min(self.buffer[end.y].len());
+                }
+                self.operate_range(op, start, end);
+            }
+        }
+    }
+
+    // :toggle / Ctrl-T - tick or untick the task on the current line
+    fn toggle_checkbox(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        if markdown::toggle_checkbox(&mut self.buffer[self.cursor_y]) {
+            self.mark_modified();
+        } else {
+            self.status_message = Some(t!("No checkbox on this line"));
+            self.dirty = true;
+        }
+    }
+
+    // Join 'lines' lines starting at the cursor into one, with the
+    // whitespace where they meet collapsed to a single space
+    fn join_lines(&mut self, lines: usize) {
+        if self.cursor_y + 1 >= self.buffer.len() || !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+
+        let y = self.cursor_y;
+        for _ in 1..lines {
+            if y + 1 >= self.buffer.len() {
+                break;
+            }
+            let next = self.buffer.remove(y + 1);
+            let line = &mut self.buffer[y];
+            while line.last().is_some_and(|c| c.is_whitespace()) {
+                line.pop();
+            }
+            let rest: Vec<char> = next.into_iter().skip_while(|c| c.is_whitespace()).collect();
+            // The cursor ends up where the last two lines met, as in vim
+            self.cursor_x = line.len();
+            // No space is added before a closing bracket or onto an empty line
+            if !line.is_empty() && !rest.is_empty() && rest[0] != ')' {
+                line.push(' ');
+            }
+            line.extend(rest);
+        }
+        self.clamp_cursor();
+        self.mark_modified();
+    }
+
+    // 'a jumps to the start of the marked line, `a to the exact spot
+    fn jump_to_mark(&mut self, name: char, exact: bool) {
+        let Some(&pos) = self.marks.get(&name) else {
+            self.status_message = Some(t!("Mark '{mark}' not set", mark = name));
+            self.dirty = true;
+            return;
+        };
+        // The text may have changed since the mark was set
+        self.cursor_y = pos.y.min(self.buffer.len() - 1);
+        self.cursor_x = if exact { pos.x } else { leading_whitespace(self.current_line()) };
+        self.clamp_cursor();
+        self.dirty = true;
+    }
+
+    // r{char} - swap the character under the cursor without leaving normal mode
+    fn replace_char(&mut self, c: char) {
+        if self.cursor_x >= self.current_line().len() || !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        self.buffer[self.cursor_y][self.cursor_x] = c;
+        self.mark_modified();
+    }
+
+    // viw, vap, ...: the text object at the cursor becomes the selection,
+    // by lines for a paragraph and by characters otherwise
+    fn select_object(&mut self, object: TextObject) {
+        let Some(span) = vim::select_object(&self.buffer, self.cursor_pos(), object) else {
+            return;
+        };
+        let (anchor, cursor, linewise) = match span {
+            Span::Lines(first, last) => (Pos::new(0, first), Pos::new(0, last), true),
+            // The selection takes in its last character; the span stops
+            // short of it
+            Span::Chars(start, end) if end.x == 0 && end.y > start.y => {
+                (start, Pos::new(self.buffer[end.y - 1].len().saturating_sub(1), end.y - 1), false)
+            }
+            Span::Chars(start, end) => (start, Pos::new(end.x.saturating_sub(1), end.y), false),
+        };
+        self.visual = Some(Selection { anchor, linewise });
+        self.cursor_y = cursor.y;
+        self.cursor_x = cursor.x;
+        self.clamp_cursor();
+    }
+
+    // The text of the visual selection, if there is one
+    fn selection_text(&self) -> Option<String> {
+        let selection = self.visual?;
+        let lines = match selection.span(self.cursor_pos(), &self.buffer) {
+            Span::Chars(start, end) => self.text_range(start, end),
+            Span::Lines(first, last) => self.buffer[first..=last].to_vec(),
+        };
+        let lines: Vec<String> = lines.iter().map(|line| line.iter().collect()).collect();
+        Some(lines.join("\n"))
+    }
+
+    // Apply an operator to a text object or a visual selection
+    fn operate_span(&mut self, op: Operator, span: Span) {
+        match span {
+            Span::Chars(start, end) => self.operate_range(op, start, end),
+            Span::Lines(first, last) => self.operate_lines(op, first, last),
+        }
+    }
+
+    // Apply an operator to the characters from 'start' up to (not including) 'end'
+    fn operate_range(&mut self, op: Operator, start: Pos, end: Pos) {
+        // Shifting and filtering always work on whole lines; a range ending
+        // at the very start of a line doesn't include that line
+        if matches!(op, Operator::Indent | Operator::Outdent | Operator::Filter) {
+            let last = if end.x == 0 && end.y > start.y { end.y - 1 } else { end.y };
+            return self.operate_lines(op, start.y, last);
+        }
+        if op == Operator::Link {
+            return self.link_range(start, end);
+        }
+        if op != Operator::Yank && !self.ensure_writable() {
+            return;
+        }
+        if let Some(convert) = case_conversion(op) {
+            self.track_typing(); // Track typing activity
+            self.convert_case(start, end, convert);
+            self.cursor_y = start.y;
+            self.cursor_x = start.x;
+            self.clamp_cursor();
+            return;
+        }
+        self.clipboard = self.text_range(start, end);
+        self.clipboard_linewise = false;
+
+        if op != Operator::Yank {
+            self.track_typing(); // Track typing activity
+            self.delete_range(start, end);
+            self.mark_modified();
+        }
+        self.cursor_y = start.y;
+        self.cursor_x = start.x;
+        if op == Operator::Change {
+            self.mode = Mode::Insert;
+        }
+        self.clamp_cursor();
+        self.dirty = true;
+    }
+
+    // Apply an operator to whole lines first..=last (dd, yy, cc, dj, ...)
+    fn operate_lines(&mut self, op: Operator, first: usize, last: usize) {
+        if op != Operator::Yank && !self.ensure_writable() {
+            return;
+        }
+        if matches!(op, Operator::Yank | Operator::Delete | Operator::Change) {
+            self.clipboard = self.buffer[first..=last].to_vec();
+            self.clipboard_linewise = true;
+        }
+
+        match op {
+            Operator::Yank => self.cursor_y = first,
+            Operator::Delete => {
+                self.track_typing(); // Track typing activity
+                // drain removes a range of elements from the Vec
+                self.buffer.drain(first..=last);
+                if self.buffer.is_empty() {
+                    self.buffer.push(Vec::new());
+                }
+                self.cursor_y = first;
+                self.mark_modified();
+            }
+            Operator::Change => {
+                self.track_typing(); // Track typing activity
+                // The lines are replaced by a single empty one to type into
+                self.buffer.splice(first..=last, [Vec::new()]);
+                self.cursor_y = first;
+                self.mode = Mode::Insert;
+                self.mark_modified();
+            }
+            Operator::Indent | Operator::Outdent => {
+                self.track_typing(); // Track typing activity
+                self.shift_lines(first, last, op == Operator::Indent);
+                self.cursor_y = first;
+                self.mark_modified();
+                // Land on the first non-blank character, as vim does
+                self.cursor_x = leading_whitespace(&self.buffer[first]);
+                self.clamp_cursor();
+                return;
+            }
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                let end = Pos::new(self.buffer[last].len(), last);
+                return self.operate_range(op, Pos::new(0, first), end);
+            }
+            // Ask for the command, with the lines filled in as the range
+            Operator::Filter => {
+                self.enter_command_mode(':');
+                self.command_buffer = if first == last {
+                    ".!".to_string()
+                } else {
+                    format!("{},{}!", first + 1, last + 1)
+                };
+                return;
+            }
+            // The lines' text, leaving the indent outside the brackets
+            Operator::Link => {
+                let start = Pos::new(leading_whitespace(&self.buffer[first]), first);
+                return self.link_range(start, Pos::new(self.buffer[last].len(), last));
+            }
+        }
+        self.cursor_x = 0;
+        self.clamp_cursor();
+        self.dirty = true;
+    }
+
+    // A command from [commands] in config.toml: its steps in order, as if
+    // each were typed after ':'
+    fn run_user_command(&mut self, name: &str) -> io::Result<bool> {
+        // A command that (through others) runs itself would never end
+        if self.user_command_depth >= 10 {
+            self.status_message = Some(t!(":{command} runs too many commands inside each other", command = name));
+            return Ok(false);
+        }
+        let steps = self.config.commands[name].steps().to_vec();
+        self.user_command_depth += 1;
+        let mut quit = Ok(false);
+        for step in steps {
+            quit = self.run_command(step.trim().trim_start_matches(':'));
+            if !matches!(quit, Ok(false)) {
+                break;
+            }
+        }
+        self.user_command_depth -= 1;
+        quit
+    }
+
+    // A script from the scripts folder (see script.rs). It works on a copy
+    // of the note, which replaces the note if the script changed it; then
+    // the commands it asked for run, as [commands] steps do.
+    fn run_script(&mut self, name: &str) -> io::Result<bool> {
+        if self.user_command_depth >= 10 {
+            self.status_message = Some(t!(":{command} runs too many commands inside each other", command = name));
+            return Ok(false);
+        }
+        let lines: Vec<String> = self.buffer.iter().map(|line| line.iter().collect()).collect();
+        let note = script::Note { lines: lines.clone(), cursor: (self.cursor_y, self.cursor_x), ..Default::default() };
+        let note = match script::run(name, note, Path::new(&self.config.daily_notes_dir)) {
+            Ok(note) => note,
+            Err(e) => {
+                self.status_message = Some(e);
+                return Ok(false);
+            }
+        };
+        if note.lines != lines {
+            if !self.ensure_writable() {
+                return Ok(false);
+            }
+            self.track_typing(); // Track typing activity
+            self.buffer.splice(.., note.lines.iter().map(|line| line.chars().collect()));
+            self.mark_modified();
+        }
+        (self.cursor_y, self.cursor_x) = note.cursor;
+        self.clamp_cursor();
+        if note.message.is_some() {
+            self.status_message = note.message;
+        }
+        self.dirty = true;
+
+        self.user_command_depth += 1;
+        let mut quit = Ok(false);
+        for command in note.commands {
+            quit = self.run_command(&command);
+            if !matches!(quit, Ok(false)) {
+                break;
+            }
+        }
+        self.user_command_depth -= 1;
+        quit
+    }
+
+    // A key bound in [keys] runs its command. Returns None for keys with
+    // no binding, to be handled as usual.
+    fn run_key_binding(&mut self, key_event: &KeyEvent) -> Option<io::Result<bool>> {
+        let command = self.config.keys
+            .iter()
+            .find(|(name, _)| keys::matches(name, key_event))
+            .map(|(_, command)| command.trim().trim_start_matches(':').to_string())?;
+        self.dirty = true;
+        Some(self.run_command(&command))
+    }
+
+    // :!cmd - run a command and show what it printed
+    fn shell_command(&mut self, command: &str) {
+        self.status_message = Some(match filter::run(command, "") {
+            Ok(output) if output.trim().is_empty() => t!("{command} finished", command = command),
+            Ok(output) => output.trim_end().lines().collect::<Vec<_>>().join(" | "),
+            Err(e) => e,
+        });
+    }
+
+    // :{range}!cmd - replace lines first..=last with what 'command' makes
+    // of them
+    fn filter_lines(&mut self, first: usize, last: usize, command: &str) {
+        if !self.ensure_writable() {
+            return;
+        }
+        let input: String = self.buffer[first..=last]
+            .iter()
+            .map(|line| line.iter().collect::<String>() + "\n")
+            .collect();
+        match filter::run(command, &input) {
+            Ok(output) => {
+                self.track_typing(); // Track typing activity
+                let lines: Vec<Vec<char>> = output.lines().map(|line| line.chars().collect()).collect();
+                let count = lines.len();
+                self.buffer.splice(first..=last, lines);
+                if self.buffer.is_empty() {
+                    self.buffer.push(Vec::new());
+                }
+                self.cursor_y = first;
+                self.cursor_x = 0;
+                self.clamp_cursor();
+                self.mark_modified();
+                self.status_message = Some(tn!(last - first + 1, "{n} line filtered through {command} ({count} now)", "{n} lines filtered through {command} ({count} now)", command = command, count = count));
+            }
+            Err(e) => self.status_message = Some(e),
+        }
+    }
+
+    // :r !cmd inserts what a command prints below the cursor line, and
+    // :r <path> a file
+    fn read_command(&mut self, arg: &str) {
+        if !self.ensure_writable() {
+            return;
+        }
+        let text = match arg.strip_prefix('!') {
+            Some(command) => filter::run(command.trim(), ""),
+            None if arg.is_empty() => Err(t!("Usage: :r <file> or :r !<command>")),
+            None => {
+                let path = config::expand_tilde(arg);
+                buffer::read_lines(Path::new(&path))
+                    .map(|(lines, _)| lines.iter().map(|line| line.iter().collect::<String>() + "\n").collect())
+                    .map_err(|e| t!("Can't read {path}: {error}", path = path, error = e))
+            }
+        };
+        match text {
+            Ok(text) if text.is_empty() => self.status_message = Some(t!("Nothing to insert")),
+            Ok(text) => {
+                self.track_typing(); // Track typing activity
+                let lines: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+                let below = self.cursor_y + 1;
+                self.buffer.splice(below..below, lines);
+                self.cursor_y = below;
+                self.cursor_x = 0;
+                self.clamp_cursor();
+                self.mark_modified();
+            }
+            Err(e) => self.status_message = Some(e),
+        }
+    }
+
+    // :grep <term> - every line in the notes with 'term' on it, in a pane
+    // (see grep.rs)
+    fn grep_command(&mut self, term: &str) -> io::Result<()> {
+        if term.is_empty() {
+            self.status_message = Some(t!("Usage: :grep <term>"));
+            return Ok(());
+        }
+        // The search reads the files, so they should have what's on screen
+        if self.needs_save {
+            self.save_file()?;
+        }
+        let results = grep::search(Path::new(&self.config.daily_notes_dir), term);
+        if results.matches.is_empty() {
+            self.status_message = Some(t!("No notes mention {term}", term = term));
+            return Ok(());
+        }
+        self.grep = Some(results);
+        self.grep_pane = true;
+        Ok(())
+    }
+
+    // :cn / :cp - open the next or previous result
+    fn step_grep(&mut self, forward: bool) -> io::Result<()> {
+        let Some(grep) = self.grep.as_mut() else {
+            self.status_message = Some(t!("No results - search with :grep <term>"));
+            return Ok(());
+        };
+        let at_end = if forward { grep.selected + 1 >= grep.matches.len() } else { grep.selected == 0 };
+        if at_end {
+            self.status_message = Some(if forward { t!("No more results") } else { t!("No earlier results") });
+            return Ok(());
+        }
+        if forward {
+            grep.select_next();
+        } else {
+            grep.select_previous();
+        }
+        self.open_grep_match()
+    }
+
+    // Open the note of the selected result at the line it was found on
+    fn open_grep_match(&mut self) -> io::Result<()> {
+        let Some(grep) = &self.grep else {
+            return Ok(());
+        };
+        let Some(found) = grep.selected_match() else {
+            return Ok(());
+        };
+        let (path, line, column) = (found.path.to_string_lossy().to_string(), found.line, found.column);
+        let position = t!("({number} of {count}) {name}:{line}", number = grep.selected + 1, count = grep.matches.len(), name = found.name, line = line + 1);
+        self.open_file(&path, false)?;
+        // open_file leaves the old note open if this one can't be read
+        if self.filename.as_deref() == Some(path.as_str()) {
+            self.cursor_y = line;
+            self.cursor_x = column;
+            self.clamp_cursor();
+            self.status_message = Some(position);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // :diff compares the buffer with another version of it:
+    //   :diff                   the file as last saved
+    //   :diff yesterday         how it stood at the end of yesterday (for a
+    //                           daily note, the note of the day before)
+    //   :diff 2024-05-01 14:00  how it stood then (a date alone means the end
+    //                           of that day, a time alone means today)
+    //   :diff other.md          another note
+    fn diff_command(&mut self, arg: &str) {
+        let Some(filename) = self.filename.clone() else {
+            self.status_message = Some(t!("No file to compare"));
+            return;
+        };
+        let (label, path) = match self.diff_target(&filename, arg) {
+            Ok(target) => target,
+            Err(e) => {
+                self.status_message = Some(e);
+                return;
+            }
+        };
+        let old: Vec<String> = match buffer::read_lines(&path) {
+            Ok((lines, _)) => lines.iter().map(|line| line.iter().collect()).collect(),
+            Err(e) => {
+                self.status_message = Some(t!("Can't read {path}: {error}", path = path.display(), error = e));
+                return;
+            }
+        };
+        let new: Vec<String> = self.buffer.iter().map(|line| line.iter().collect()).collect();
+        let changes = diff::diff(&old, &new);
+        if !changes.iter().any(Change::is_change) {
+            self.status_message = Some(t!("No differences from {version}", version = label));
+            return;
+        }
+        self.diff_view = Some(DiffView::new(label, changes));
+    }
+
+    // What :diff's argument names: a label for the view and the file with
+    // that version in it
+    fn diff_target(&self, filename: &str, arg: &str) -> Result<(String, PathBuf), String> {
+        let today = Local::now().date_naive();
+        if arg.is_empty() {
+            return Ok((t!("the saved file"), PathBuf::from(filename)));
+        }
+        if arg == "yesterday" {
+            // A daily note is compared with the day before's, since there was
+            // no version of it yesterday
+            let path = Path::new(filename);
+            let date = path
+                .file_stem()
+                .and_then(|stem| NaiveDate::parse_from_str(&stem.to_string_lossy(), "%Y-%m-%d").ok());
+            if let Some(date) = date {
+                let previous = date - chrono::Duration::days(1);
+                let note = path.with_file_name(format!("{}.md", previous.format("%Y-%m-%d")));
+                return if note.exists() {
+                    Ok((t!("{date}'s note", date = i18n::short_date(previous)), note))
+                } else {
+                    Err(t!("No note for {date}", date = previous.format("%Y-%m-%d")))
+                };
+            }
+            return self.diff_version(filename, today.and_time(NaiveTime::MIN), "yesterday");
+        }
+        if let Some(time) = parse_timestamp(arg, today) {
+            return self.diff_version(filename, time, arg);
+        }
+        let path = PathBuf::from(config::expand_tilde(arg));
+        if path.exists() {
+            Ok((arg.to_string(), path))
+        } else {
+            Err(t!("Not a file or a time: {arg}", arg = arg))
+        }
+    }
+
+    fn diff_version(&self, filename: &str, time: NaiveDateTime, asked: &str) -> Result<(String, PathBuf), String> {
+        match history::version_at(filename, time) {
+            Some((written, path)) => Ok((t!("the version saved {date} {time}", date = i18n::short_date(written.date()), time = written.format("%H:%M")), path)),
+            None => Err(t!("No saved version from {time}", time = asked)),
+        }
+    }
+
+    // The word under the cursor - or, while typing, the one just finished -
+    // as a range for link_range
+    fn word_at_cursor(&self) -> Option<(Pos, Pos)> {
+        let line = self.current_line();
+        let mut x = self.cursor_x.min(line.len());
+        if (x == line.len() || !autocomplete::is_word_char(line[x])) && x > 0 && autocomplete::is_word_char(line[x - 1]) {
+            x -= 1;
+        }
+        if x == line.len() || !autocomplete::is_word_char(line[x]) {
+            return None;
+        }
+        let start = autocomplete::word_start(line, x);
+        let end = x + line[x..].iter().take_while(|c| autocomplete::is_word_char(**c)).count();
+        Some((Pos::new(start, self.cursor_y), Pos::new(end, self.cursor_y)))
+    }
+
+    // A URL copied last, in river or elsewhere
+    fn copied_url(&self) -> Option<String> {
+        let yanked = match self.clipboard.as_slice() {
+            [line] => Some(line.iter().collect::<String>()),
+            _ => None,
+        };
+        [yanked, link::system_clipboard()]
+            .into_iter()
+            .flatten()
+            .map(|text| text.trim().to_string())
+            .find(|text| link::is_url(text))
+    }
+
+    // Alt-K - link the word at the cursor (or an empty [] to type into)
+    fn link_word(&mut self) {
+        let (start, end) = self.word_at_cursor().unwrap_or((self.cursor_pos(), self.cursor_pos()));
+        self.link_range(start, end);
+    }
+
+    // gl{motion} / Alt-K: link the text from 'start' up to 'end' to the
+    // copied URL, or ask for the URL on the command line (see :link)
+    fn link_range(&mut self, start: Pos, end: Pos) {
+        if !self.ensure_writable() {
+            return;
+        }
+        match self.copied_url() {
+            Some(url) => self.insert_link(start, end, &url),
+            None => {
+                self.enter_command_mode(':');
+                self.command_buffer = "link ".to_string();
+                self.pending_link = Some((start, end));
+            }
+        }
+    }
+
+    // :link [url] - link what gl/Alt-K asked about, or the word under the
+    // cursor. Without a URL, the copied one.
+    fn link_command(&mut self, arg: &str) {
+        if !self.ensure_writable() {
+            return;
+        }
+        let url = if arg.is_empty() { self.copied_url() } else { Some(arg.to_string()) };
+        let Some(url) = url else {
+            self.status_message = Some(t!("No URL copied - use :link <url>"));
+            return;
+        };
+        let (start, end) = self.pending_link
+            .take()
+            .or_else(|| self.word_at_cursor())
+            .unwrap_or((self.cursor_pos(), self.cursor_pos()));
+        self.insert_link(start, end, &url);
+    }
+
+    // Wrap the text between two positions in [](url). With no text the
+    // cursor goes between the brackets to type some.
+    fn insert_link(&mut self, start: Pos, end: Pos, url: &str) {
+        self.track_typing(); // Track typing activity
+        let close: Vec<char> = format!("]({})", url).chars().collect();
+        let mut after = self.insert_text(end, &[close]);
+        self.insert_text(start, &[vec!['[']]);
+        if after.y == start.y {
+            after.x += 1;
+        }
+        self.mark_modified();
+
+        if start == end {
+            self.cursor_y = start.y;
+            self.cursor_x = start.x + 1;
+            self.mode = Mode::Insert;
+        } else if self.mode == Mode::Insert || !self.config.vim_bindings {
+            self.cursor_y = after.y;
+            self.cursor_x = after.x;
+        } else {
+            // On the closing ')' in normal mode
+            self.cursor_y = after.y;
+            self.cursor_x = after.x - 1;
+        }
+        self.status_message = Some(t!("Linked to {url}", url = url));
+        self.dirty = true;
+    }
+
+    // Rewrite the case of the characters between two positions
+    fn convert_case(&mut self, start: Pos, end: Pos, convert: fn(char) -> char) {
+        for y in start.y..=end.y {
+            let line = &mut self.buffer[y];
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { line.len() };
+            for c in &mut line[from..to] {
+                *c = convert(*c);
+            }
+        }
+        self.mark_modified();
+    }
+
+    // ~ - flip the case of the character under the cursor and step past it
+    fn toggle_case_char(&mut self) {
+        if self.cursor_x >= self.current_line().len() || !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        let cursor = self.cursor_pos();
+        self.convert_case(cursor, Pos::new(cursor.x + 1, cursor.y), toggle_case);
+        self.cursor_x += 1;
+        self.clamp_cursor();
+    }
+
+    // >> and << - shift lines right or left by one tab_size worth of spaces.
+    // Blank lines are left alone so they don't pick up trailing spaces.
+    fn shift_lines(&mut self, first: usize, last: usize, right: bool) {
+        let width = self.config.tab_size;
+        for line in &mut self.buffer[first..=last] {
+            if right {
+                if !line.is_empty() {
+                    line.splice(0..0, std::iter::repeat_n(' ', width));
+                }
+            } else if line.first() == Some(&'\t') {
+                line.remove(0);
+            } else {
+                let spaces = line.iter().take(width).take_while(|&&c| c == ' ').count();
+                line.drain(..spaces);
+            }
+        }
+    }
+
+    // Copy of the text between two positions, one Vec per line
+    fn text_range(&self, start: Pos, end: Pos) -> Vec<Vec<char>> {
+        if start.y == end.y {
+            return vec![self.buffer[start.y][start.x..end.x].to_vec()];
+        }
+        let mut lines = vec![self.buffer[start.y][start.x..].to_vec()];
+        lines.extend(self.buffer[start.y + 1..end.y].iter().cloned());
+        lines.push(self.buffer[end.y][..end.x].to_vec());
+        lines
+    }
+
+    fn delete_range(&mut self, start: Pos, end: Pos) {
+        if start.y == end.y {
+            self.buffer[start.y].drain(start.x..end.x);
+            return;
+        }
+        // Join what's left of the first and last lines, dropping everything between
+        let tail = self.buffer[end.y].split_off(end.x);
+        self.buffer[start.y].truncate(start.x);
+        self.buffer[start.y].extend(tail);
+        self.buffer.drain(start.y + 1..=end.y);
+    }
+
+    // Insert text (as produced by text_range) at 'pos', returning the
+    // position just after it
+    fn insert_text(&mut self, pos: Pos, text: &[Vec<char>]) -> Pos {
+        let tail = self.buffer[pos.y].split_off(pos.x);
+        let mut y = pos.y;
+        self.buffer[y].extend(text[0].iter().copied());
+        for line in &text[1..] {
+            y += 1;
+            self.buffer.insert(y, line.clone());
+        }
+        let end = Pos::new(self.buffer[y].len(), y);
+        self.buffer[y].extend(tail);
+        end
+    }
+
+    // Record that the text changed so it gets redrawn and auto-saved
+    fn mark_modified(&mut self) {
+        self.dirty = true;
+        self.needs_save = true;
+        self.last_save = Instant::now();
+    }
+
+    // Move to a character on the current line; the cursor stays put if
+    // the character isn't there
+    fn find_char(&mut self, find: CharFind, repeat: bool) {
+        if let Some(x) = vim::find_char(self.current_line(), self.cursor_x, find, repeat) {
+            self.cursor_x = x;
+            self.dirty = true;
+        }
+    }
+
+    // ; repeats the last f/t/F/T, and , repeats it the other way
+    fn repeat_find(&mut self, reverse: bool) {
+        if let Some(find) = self.last_find {
+            let kind = if reverse { find.kind.reversed() } else { find.kind };
+            self.find_char(CharFind { kind, ch: find.ch }, true);
+        }
+    }
+
+    // Every editing operation checks this first. Returns false (and says
+    // why) when the buffer is read-only.
+    fn ensure_writable(&mut self) -> bool {
+        if self.read_only {
+            self.status_message = Some(t!("Read-only buffer - use :view to allow editing"));
+            self.dirty = true;
+            return false;
+        }
+        true
+    }
+
+    // Movement methods - note they take &mut self to modify cursor position
+    fn move_left(&mut self) {
+        if self.cursor_x > 0 {
+            self.cursor_x -= 1; // -= is compound assignment
+            // An accent typed as a combining mark moves with its letter
+            while self.cursor_x > 0 && width::char_width(self.current_line()[self.cursor_x]) == 0 {
+                self.cursor_x -= 1;
+            }
+        } else if self.cursor_y > 0 && (self.mode == Mode::Insert || !self.config.vim_bindings) {
+            self.cursor_y -= 1;
+            // Method calls use . notation
+            self.cursor_x = self.current_line().len();
+        }
+        self.dirty = true;
+    }
+
+    fn move_right(&mut self) {
+        let line_len = self.current_line().len();
+        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+            line_len - 1
+        } else {
+            line_len
+        };
+        
+        if self.cursor_x < max_x {
+            self.cursor_x += 1;
+            while self.cursor_x < max_x && width::char_width(self.current_line()[self.cursor_x]) == 0 {
+                self.cursor_x += 1;
+            }
+        } else if self.cursor_y < self.buffer.len() - 1 && (self.mode == Mode::Insert || !self.config.vim_bindings) {
+            self.cursor_y += 1;
+            self.cursor_x = 0;
+        }
+        self.dirty = true;
+    }
+
+    fn move_up(&mut self) {
+        if self.cursor_y > 0 {
+            // Stay in the same screen column, which differs from the
+            // character index once tabs are involved
+            let column = self.cursor_column();
+            // Folded sections are stepped over as one line
+            self.cursor_y = self.fold_map().up(self.cursor_y, 1);
+            let line_len = self.current_line().len();
+            let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+                line_len - 1
+            } else {
+                line_len
+            };
+            self.cursor_x = self.char_at_screen_column(column).min(max_x);
+            self.dirty = true;
+        }
+    }
+
+    fn move_down(&mut self) {
+        let below = self.fold_map().down(self.cursor_y, 1);
+        if below > self.cursor_y {
+            // Stay in the same screen column, which differs from the
+            // character index once tabs are involved
+            let column = self.cursor_column();
+            self.cursor_y = below;
+            let line_len = self.current_line().len();
+            let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+                line_len - 1
+            } else {
+                line_len
+            };
+            self.cursor_x = self.char_at_screen_column(column).min(max_x);
+            self.dirty = true;
+        }
+    }
+
+    fn move_home(&mut self) {
+        self.cursor_x = 0;
+        self.dirty = true;
+    }
+
+    // Home (and ^) go to the first character after the indent, or to
+    // column 0 if the cursor is already there; 0 tries column 0 first. On
+    // an indented list item that's the marker, then the margin.
+    fn home_toggle(&mut self, text_first: bool) {
+        let line_len = self.current_line().len();
+        let last = if self.mode == Mode::Normal && self.config.vim_bindings { line_len.saturating_sub(1) } else { line_len };
+        let text = leading_whitespace(self.current_line()).min(last);
+        let (first, second) = if text_first { (text, 0) } else { (0, text) };
+        self.cursor_x = if self.cursor_x == first { second } else { first };
+        self.dirty = true;
+    }
+
+    fn move_end(&mut self) {
+        let line_len = self.current_line().len();
+        self.cursor_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+            line_len - 1
+        } else {
+            line_len
+        };
+        self.dirty = true;
+    }
+
+    fn delete_char(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        
+        if self.cursor_x < self.current_line().len() {
+            self.buffer[self.cursor_y].remove(self.cursor_x);
+            if self.cursor_x > 0 && self.cursor_x == self.current_line().len() && self.config.vim_bindings {
+                self.cursor_x -= 1;
+            }
+            self.dirty = true;
+            self.needs_save = true;
+            self.last_save = Instant::now();
+        }
+    }
+
+    fn paste_after(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        if !self.clipboard.is_empty() {
+            self.track_typing(); // Track typing activity
+            
+            if self.clipboard_linewise {
+                for (i, line) in self.clipboard.clone().into_iter().enumerate() {
+                    self.buffer.insert(self.cursor_y + 1 + i, line);
+                }
+                self.cursor_y += 1;
+                self.cursor_x = 0;
+            } else {
+                // Characters go after the cursor, which ends on the last one pasted
+                let x = (self.cursor_x + 1).min(self.current_line().len());
+                self.paste_chars(Pos::new(x, self.cursor_y));
+            }
+            self.mark_modified();
+        }
+    }
+
+    fn paste_before(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        if !self.clipboard.is_empty() {
+            self.track_typing(); // Track typing activity
+            
+            if self.clipboard_linewise {
+                for (i, line) in self.clipboard.clone().into_iter().enumerate() {
+                    self.buffer.insert(self.cursor_y + i, line);
+                }
+                self.cursor_x = 0;
+            } else {
+                self.paste_chars(self.cursor_pos());
+            }
+            self.mark_modified();
+        }
+    }
+
+    fn paste_chars(&mut self, pos: Pos) {
+        let text = self.clipboard.clone();
+        let end = self.insert_text(pos, &text);
+        self.cursor_y = end.y;
+        self.cursor_x = end.x.saturating_sub(1);
+    }
+
+
+    fn page_up(&mut self) {
+        let page_size = self.active_rect().height as usize;
+        self.cursor_y = self.fold_map().up(self.cursor_y, page_size);
+        let line_len = self.current_line().len();
+        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+            line_len - 1
+        } else {
+            line_len
+        };
+        self.cursor_x = self.cursor_x.min(max_x);
+        self.dirty = true;
+    }
+
+    fn page_down(&mut self) {
+        let page_size = self.active_rect().height as usize;
+        self.cursor_y = self.fold_map().down(self.cursor_y, page_size);
+        let line_len = self.current_line().len();
+        let max_x = if self.mode == Mode::Normal && line_len > 0 && self.config.vim_bindings {
+            line_len - 1
+        } else {
+            line_len
+        };
+        self.cursor_x = self.cursor_x.min(max_x);
+        self.dirty = true;
+    }
+
+    fn visible_height(&self) -> usize {
+        // .max(1) keeps the math sane when a split leaves no room
+        (self.active_rect().height as usize).max(1)
+    }
+
+    // Ctrl-D / Ctrl-U - scroll half a window, taking the cursor along
+    fn scroll_half_page(&mut self, down: bool) {
+        let half = (self.visible_height() / 2).max(1);
+        let map = self.fold_map();
+        if down {
+            self.offset_y = map.down(self.offset_y, half);
+            self.cursor_y = map.down(self.cursor_y, half);
+        } else {
+            self.offset_y = map.up(self.offset_y, half);
+            self.cursor_y = map.up(self.cursor_y, half);
+        }
+        self.clamp_cursor();
+        self.dirty = true;
+    }
+
+    // Ctrl-E / Ctrl-Y - scroll the view one line; the cursor only moves
+    // when it would otherwise go off screen
+    fn scroll_view(&mut self, down: bool) {
+        let height = self.visible_height();
+        let map = self.fold_map();
+        if down {
+            self.offset_y = map.down(self.offset_y, 1);
+            self.cursor_y = self.cursor_y.max(self.offset_y);
+        } else {
+            self.offset_y = map.up(self.offset_y, 1);
+            self.cursor_y = self.cursor_y.min(map.down(self.offset_y, height - 1));
+        }
+        self.clamp_cursor();
+        self.dirty = true;
+    }
+
+    // zt / zz / zb - scroll so the cursor line is at the top, middle or
+    // bottom of the window
+    fn scroll_cursor_to(&mut self, to: ScrollTo) {
+        let height = self.visible_height();
+        let map = self.fold_map();
+        self.offset_y = match to {
+            ScrollTo::Top => self.cursor_y,
+            ScrollTo::Center => map.up(self.cursor_y, height / 2),
+            ScrollTo::Bottom => map.up(self.cursor_y, height - 1),
+        };
+        self.dirty = true;
+    }
+
+    fn fold_map(&self) -> FoldMap {
+        FoldMap::new(&self.buffer, self.buffer.folds())
+    }
+
+    // za / :fold - fold the section the cursor is in, leaving the cursor on
+    // its heading, or open it again if it's folded
+    fn toggle_fold(&mut self) {
+        let headings = markdown::headings(&self.buffer);
+        let Some(heading) = fold::section_heading(&headings, self.cursor_y) else {
+            self.status_message = Some(t!("No heading to fold"));
+            return;
+        };
+        if self.buffer.is_folded(heading) {
+            self.buffer.unfold(heading);
+        } else if fold::section_end(&headings, heading, self.buffer.len()) > Some(heading) {
+            self.buffer.fold(heading);
+            self.cursor_y = heading;
+            self.clamp_cursor();
+        } else {
+            self.status_message = Some(t!("Nothing under this heading to fold"));
+        }
+        self.dirty = true;
+    }
+
+    // zM / :fold all folds every section; zR / :fold none opens them
+    fn fold_all(&mut self, fold: bool) {
+        self.buffer.unfold_all();
+        if fold {
+            let headings = markdown::headings(&self.buffer);
+            for &(heading, _) in &headings {
+                if fold::section_end(&headings, heading, self.buffer.len()) > Some(heading) {
+                    self.buffer.fold(heading);
+                }
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn insert_char(&mut self, mut c: char) {
+        if !self.ensure_writable() {
+            return;
+        }
+        if !autocomplete::is_word_char(c) {
+            self.expand_abbreviation();
+        }
+        if self.config.smart_typography && !markdown::in_code(&self.buffer, self.cursor_y, self.cursor_x) {
+            let before = &self.current_line()[..self.cursor_x];
+            if let Some((replace, typographic)) = typography::substitute(before, c) {
+                // Take out what the new character replaces ("--" before "-")
+                self.cursor_x -= replace;
+                let end = self.cursor_x + replace;
+                self.buffer[self.cursor_y].drain(self.cursor_x..end);
+                c = typographic;
+            }
+        }
+        
+        // Track typing activity
+        self.track_typing();
+        
+        // Auto line wrap when reaching terminal width (with some margin),
+        // except in front matter where a field has to stay on one line
+        let wrap_width = self.text_width().saturating_sub(5); // Leave some margin
+        let in_front_matter = front_matter::find(&self.buffer).is_some_and(|fm| fm.contains(self.cursor_y));
+        
+        // &mut creates a mutable reference - can modify the line
+        let tab_size = self.config.tab_size;
+        let line = &mut self.buffer[self.cursor_y];
+        line.insert(self.cursor_x, c);
+        self.cursor_x += 1;
+        // In columns, so a line of wide CJK characters wraps where it meets the edge
+        if display_column(line, self.cursor_x, tab_size) >= wrap_width && c != ' ' && !in_front_matter {
+            // Find last space to break at word boundary
+            let mut break_pos = self.cursor_x;
+            for i in (0..self.cursor_x).rev() {
+                if line[i] == ' ' {
+                    break_pos = i + 1;
+                    break;
+                }
+            }
+            
+            // If no space found or space is too far back, just break at current position
+            if break_pos == self.cursor_x || self.cursor_x - break_pos > 20 {
+                break_pos = self.cursor_x;
+            }
+            
+            // Move text after break position to new line
+            let new_line: Vec<char> = line.drain(break_pos..).collect();
+            self.buffer.insert(self.cursor_y + 1, new_line);
+            
+            // Update cursor position
+            self.cursor_y += 1;
+            self.cursor_x -= break_pos;
+        }
+        
+        self.dirty = true;
+        self.needs_save = true;
+        self.last_save = Instant::now(); // Reset the timer on each change
+    }
+
+    // The word before the cursor is an abbreviation and the character
+    // being typed ends it: replace it with its expansion
+    fn expand_abbreviation(&mut self) {
+        if std::mem::take(&mut self.literal_next) || self.config.abbreviations.is_empty() {
+            return;
+        }
+        let start = autocomplete::word_start(self.current_line(), self.cursor_x);
+        let word: String = self.current_line()[start..self.cursor_x].iter().collect();
+        let Some(expansion) = abbrev::expand(&self.config.abbreviations, &word) else {
+            return;
+        };
+        let end = self.cursor_x;
+        self.buffer[self.cursor_y].splice(start..end, expansion.chars());
+        self.cursor_x = start + expansion.chars().count();
+    }
+
+    // :now / F5 - the time (timestamp_format) at the cursor
+    fn insert_timestamp(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        // A format chrono can't read would panic when formatted
+        let format = &self.config.timestamp_format;
+        if chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+            self.status_message = Some(t!("timestamp_format \"{format}\" isn't a valid strftime format", format = format));
+            self.dirty = true;
+            return;
+        }
+        let stamp: Vec<char> = Local::now().format(format).to_string().chars().collect();
+        self.track_typing();
+        // In normal mode it goes after the character under the cursor, like 'a'
+        // (:now is typed from normal mode, so command mode counts too)
+        let normal = self.config.vim_bindings
+            && matches!(self.mode, Mode::Normal | Mode::Command)
+            && !self.current_line().is_empty();
+        let x = if normal { self.cursor_x + 1 } else { self.cursor_x };
+        let end = self.insert_text(Pos::new(x, self.cursor_y), &[stamp]);
+        self.cursor_x = if normal { end.x.saturating_sub(1) } else { end.x };
+        self.mark_modified();
+    }
+
+    // Tab in insert mode: the next tab stop of a snippet, a snippet whose
+    // name is the word before the cursor (snippet_triggers), or a tab
+    fn tab_key(&mut self) {
+        if !self.snippet_stops.is_empty() {
+            self.next_snippet_stop();
+            return;
+        }
+        if self.config.snippet_triggers {
+            let start = autocomplete::word_start(self.current_line(), self.cursor_x);
+            let name: String = self.current_line()[start..self.cursor_x].iter().collect();
+            if !name.is_empty() && snippet::names().contains(&name) {
+                if let Ok(snippet) = snippet::load(&name) {
+                    let end = self.cursor_x;
+                    self.buffer[self.cursor_y].drain(start..end);
+                    self.cursor_x = start;
+                    self.insert_snippet(snippet);
+                    return;
+                }
+            }
+        }
+        self.insert_tab();
+    }
+
+    // Put a snippet in at the cursor and go to its first tab stop
+    fn insert_snippet(&mut self, snippet: snippet::Snippet) {
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing();
+        let (y, x) = (self.cursor_y, self.cursor_x);
+        let end = self.insert_text(Pos::new(x, y), &snippet.lines);
+
+        // Where the stops are now, counted from the end
+        self.snippet_stops = snippet.stops
+            .iter()
+            .map(|&(stop_y, stop_x)| {
+                let stop_x = if stop_y == 0 { x + stop_x } else { stop_x };
+                let line_len = self.buffer[y + stop_y].len();
+                (self.buffer.len() - 1 - (y + stop_y), line_len - stop_x)
+            })
+            .collect();
+        // With no stops the cursor goes after the snippet
+        self.cursor_y = end.y;
+        self.cursor_x = end.x;
+        if self.config.vim_bindings {
+            self.mode = Mode::Insert;
+        }
+        self.next_snippet_stop();
+        self.mark_modified();
+    }
+
+    fn next_snippet_stop(&mut self) {
+        if self.snippet_stops.is_empty() {
+            return;
+        }
+        let (lines_below, chars_after) = self.snippet_stops.remove(0);
+        self.cursor_y = self.buffer.len().saturating_sub(1 + lines_below);
+        self.cursor_x = self.current_line().len().saturating_sub(chars_after);
+        self.dirty = true;
+    }
+
+    fn insert_tab(&mut self) {
+        if !self.config.expand_tabs {
+            self.insert_char('\t');
+            return;
+        }
+        for _ in 0..self.config.tab_size {
+            self.insert_char(' ');
+        }
+    }
+
+    fn insert_newline(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        self.expand_abbreviation();
+        self.track_typing(); // Track typing activity
+        
+        // Inside a markdown list item the new line gets the next marker;
+        // otherwise, with auto_indent, it starts at the same indentation
+        let item = markdown::list_item(&self.buffer[self.cursor_y])
+            .filter(|item| self.config.continue_lists && self.cursor_x >= item.content_start);
+        let prefix = match item {
+            // Enter on an empty item ends the list instead of adding another
+            Some(item) if item.content_start == self.current_line().len() => {
+                self.buffer[self.cursor_y].clear();
+                self.cursor_x = 0;
+                self.mark_modified();
+                return;
+            }
+            Some(item) => item.next_prefix(self.current_line()),
+            None if self.config.auto_indent => self.indent_of(self.cursor_y),
+            None => Vec::new(),
+        };
+
+        let current_line = &mut self.buffer[self.cursor_y];
+        let mut new_line: Vec<char> = current_line.drain(self.cursor_x..).collect();
+        self.cursor_x = prefix.len();
+        new_line.splice(0..0, prefix);
+        self.buffer.insert(self.cursor_y + 1, new_line);
+        self.cursor_y += 1;
+        self.dirty = true;
+        self.needs_save = true;
+        self.last_save = Instant::now();
+    }
+
+    // The leading whitespace of a line, capped at the cursor when splitting
+    // the cursor's own line so Enter inside the indent doesn't double it
+    fn indent_of(&self, y: usize) -> Vec<char> {
+        let line = &self.buffer[y];
+        let mut len = leading_whitespace(line);
+        if y == self.cursor_y {
+            len = len.min(self.cursor_x);
+        }
+        line[..len].to_vec()
+    }
+
+    fn backspace(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        
+        if self.cursor_x > 0 {
+            self.buffer[self.cursor_y].remove(self.cursor_x - 1);
+            self.cursor_x -= 1;
+            self.dirty = true;
+            self.needs_save = true;
+            self.last_save = Instant::now();
+        } else if self.cursor_y > 0 {
+            let current_line = self.buffer.remove(self.cursor_y);
+            self.cursor_y -= 1;
+            self.cursor_x = self.buffer[self.cursor_y].len();
+            self.buffer[self.cursor_y].extend(current_line);
+            self.dirty = true;
+            self.needs_save = true;
+            self.last_save = Instant::now();
+        }
+    }
+
+    // Alt-B - to the start of this word or the one before, across lines
+    fn word_left(&mut self) {
+        let (mut x, mut y) = (self.cursor_x, self.cursor_y);
+        loop {
+            let line = &self.buffer[y];
+            while x > 0 && !line[x - 1].is_alphanumeric() {
+                x -= 1;
+            }
+            if x > 0 || y == 0 {
+                break;
+            }
+            y -= 1;
+            x = self.buffer[y].len();
+        }
+        while x > 0 && self.buffer[y][x - 1].is_alphanumeric() {
+            x -= 1;
+        }
+        (self.cursor_x, self.cursor_y) = (x, y);
+        self.dirty = true;
+    }
+
+    // Alt-F - to just after the end of this word or the next, across lines
+    fn word_right(&mut self) {
+        let (mut x, mut y) = (self.cursor_x, self.cursor_y);
+        loop {
+            let line = &self.buffer[y];
+            while x < line.len() && !line[x].is_alphanumeric() {
+                x += 1;
+            }
+            if x < line.len() || y + 1 == self.buffer.len() {
+                break;
+            }
+            y += 1;
+            x = 0;
+        }
+        while x < self.buffer[y].len() && self.buffer[y][x].is_alphanumeric() {
+            x += 1;
+        }
+        (self.cursor_x, self.cursor_y) = (x, y);
+        self.dirty = true;
+    }
+
+    // Ctrl-Backspace / Ctrl-Delete - delete to where Alt-B / Alt-F would go
+    fn delete_word(&mut self, forward: bool) {
+        if !self.ensure_writable() {
+            return;
+        }
+        let from = Pos::new(self.cursor_x, self.cursor_y);
+        if forward {
+            self.word_right();
+        } else {
+            self.word_left();
+        }
+        let to = Pos::new(self.cursor_x, self.cursor_y);
+        let (start, end) = if forward { (from, to) } else { (to, from) };
+        if start == end {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        self.delete_range(start, end);
+        (self.cursor_x, self.cursor_y) = (start.x, start.y);
+        self.mark_modified();
+    }
+
+    // Ctrl-W - delete back to the start of the word, counting anything
+    // between spaces as one as readline does. At the start of a line it
+    // joins the line to the one above, like Backspace.
+    fn delete_word_back(&mut self) {
+        if self.cursor_x == 0 {
+            self.backspace();
+            return;
+        }
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        let line = self.current_line();
+        let mut x = self.cursor_x;
+        while x > 0 && line[x - 1].is_whitespace() {
+            x -= 1;
+        }
+        while x > 0 && !line[x - 1].is_whitespace() {
+            x -= 1;
+        }
+        self.buffer[self.cursor_y].drain(x..self.cursor_x);
+        self.cursor_x = x;
+        self.mark_modified();
+    }
+
+    // Ctrl-K - delete to the end of the line, or join the next line on if
+    // the cursor is already there
+    fn kill_line(&mut self) {
+        if self.cursor_x >= self.current_line().len() {
+            self.delete();
+            return;
+        }
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        self.buffer[self.cursor_y].truncate(self.cursor_x);
+        self.mark_modified();
+    }
+
+    fn delete(&mut self) {
+        if !self.ensure_writable() {
+            return;
+        }
+        self.track_typing(); // Track typing activity
+        
+        let line_len = self.current_line().len();
+        if self.cursor_x < line_len {
+            self.buffer[self.cursor_y].remove(self.cursor_x);
+            self.dirty = true;
+            self.needs_save = true;
+            self.last_save = Instant::now();
+        } else if self.cursor_y < self.buffer.len() - 1 {
+            let next_line = self.buffer.remove(self.cursor_y + 1);
+            self.buffer[self.cursor_y].extend(next_line);
+            self.dirty = true;
+            self.needs_save = true;
+            self.last_save = Instant::now();
+        }
+    }
+
+    // Returns a reference to the current line
+    // &self - immutable borrow (read-only access)
+    // &Vec<char> - returns a reference, not ownership
+    fn current_line(&self) -> &Vec<char> {
+        // & creates a reference to the value
+        &self.buffer[self.cursor_y]
+    }
+    
+    // Celebrate when an edit takes the note past its goal: flash the status
+    // bar, maybe ring the bell, and note the time in today's stats
+    fn check_goal(&mut self) {
+        let met = self.settled_word_count() >= self.word_goal();
+        let just_met = met && !self.goal_met;
+        self.goal_met = met;
+        if !just_met || self.read_only {
+            return;
+        }
+        
+        self.status_message = Some(t!("🎉 Goal reached: {words} words!", words = self.word_goal()));
+        self.run_hook("goal_reached", &self.config.on_goal_reached.clone());
+        self.celebrate_until = Some(Instant::now() + Duration::from_secs(3));
+        if self.config.goal_bell {
+            // BEL - the terminal decides whether that's a beep or a flash
+            let _ = execute!(io::stdout(), Print('\x07'));
+        }
+        
+        // Only the first time today, in today's note, counts towards
+        // "average time to goal"
+        if self.tutor || !self.is_todays_note() {
+            return;
+        }
+        // A stats write still on its way was made from the file before
+        // this one, and would wipe the goal time out if it landed after
+        self.finish_saves();
+        let stats = Self::load_daily_stats(&self.config);
+        if stats.goal_reached_at.is_some() {
+            return;
+        }
+        let stats = DailyStats {
+            goal_reached_at: Some(Local::now().format("%H:%M").to_string()),
+            goal_minutes: Some(self.get_total_typing_time().as_secs() / 60),
+            ..stats
+        };
+        let write = self.stats_write().and_then(|write| {
+            let content = toml::to_string(&stats).map_err(io::Error::other)?;
+            Ok(saver::Write { content, ..write })
+        });
+        match write {
+            Ok(write) => {
+                let written = saver::write_now(write);
+                self.saved(written);
+            }
+            Err(e) => self.stats_problem(t!("Couldn't save today's stats: {error}", error = e)),
+        }
+    }
+    
+    // Start one of the on_save/on_goal_reached/on_quit commands (see hooks.rs)
+    fn run_hook(&mut self, event: &'static str, command: &str) {
+        if self.tutor {
+            return;
+        }
+        let info = hooks::HookInfo {
+            event,
+            date: Local::now().format("%Y-%m-%d").to_string(),
+            file: self.filename.clone().unwrap_or_default(),
+            words: self.count_words(),
+            goal: self.word_goal(),
+            minutes: self.get_total_typing_time().as_secs() / 60,
+        };
+        if let Err(e) = hooks::run(command, &info, event == "quit") {
+            self.status_message = Some(e);
+        }
+
+        // Scripts in the hooks directory go by slightly longer names
+        let script_event = match event {
+            "save" => "note_saved",
+            "quit" => "app_quit",
+            other => other,
+        };
+        let scripts = hooks::scripts(script_event);
+        if scripts.is_empty() {
+            return;
+        }
+        let info = hooks::HookInfo { event: script_event, ..info };
+        match event {
+            // The terminal is back to normal by now, so errors can go there
+            "quit" => {
+                if let (_, Some(e)) = hooks::run_scripts(&scripts, &info) {
+                    eprintln!("{}", e);
+                }
+            }
+            "goal_reached" => hooks::spawn_scripts(scripts, info, Some(self.script_sender.clone())),
+            // Adding a note_saved script's output would only save again
+            _ => hooks::spawn_scripts(scripts, info, None),
+        }
+    }
+
+    // A goal_reached script printed something: add it to the end of the
+    // note, if that's still the one being edited
+    fn poll_script_output(&mut self) {
+        while let Ok(output) = self.script_output.try_recv() {
+            if output.text.trim().is_empty() {
+                continue;
+            }
+            if self.read_only || self.filename.as_deref() != Some(output.file.as_str()) {
+                self.status_message = Some(t!("A hook printed text for {file}, which isn't open", file = output.file));
+                continue;
+            }
+            let lines = output.text.trim_end().lines().map(|line| line.chars().collect());
+            self.buffer.splice(self.buffer.len().., lines);
+            self.mark_modified();
+        }
+    }
+    
+    fn word_goal(&self) -> usize {
+        note_goal(&self.buffer, &self.config)
+    }
+    
+    // Only lines edited since the last count are counted again (see lines.rs)
+    fn count_words(&self) -> usize {
+        self.buffer.word_count(self.config.markdown_word_count)
+    }
+
+    // The word count for the status bar and goal. Recounting a large file
+    // on every keystroke makes typing lag, so there it's only redone once
+    // typing pauses for WORD_COUNT_DELAY.
+    fn settled_word_count(&mut self) -> usize {
+        if !self.large_file {
+            return self.count_words();
+        }
+        if self.word_count_stale && self.last_key.elapsed() >= WORD_COUNT_DELAY {
+            self.word_count = self.count_words();
+            self.word_count_stale = false;
+        }
+        self.word_count
+    }
+
+    // The whole buffer as one string, lines joined with newlines
+    fn buffer_text(&self) -> String {
+        self.buffer
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    
+    fn get_daily_prompt(&self) -> String {
+        let today = Local::now().date_naive();
+        
+        // First try to get AI-generated prompt if enabled
+        if self.config.use_ai_prompts {
+            if let Some(ai_prompt) = ai::get_ai_prompt(&self.config, &today) {
+                return ai_prompt;
+            }
+        }
+        
+        // Fall back to the offline library (see prompts.rs)
+        prompts::for_date(&today)
+    }
+    
+    // Generate today's AI prompt in the background if it isn't cached yet.
+    // Without an API key, or for a few hours after a failed attempt, this
+    // quietly does nothing and the static prompts are used as before.
+    fn start_prompt_job(&mut self) {
+        if !self.config.use_ai_prompts || !self.config.show_prompts || self.read_only {
+            return;
+        }
+        let today = Local::now().date_naive();
+        if ai::get_ai_prompt(&self.config, &today).is_some() || ai::failed_recently(&self.config) {
+            return;
+        }
+        if let Ok(generator) = ai::PromptGenerator::new(&self.config) {
+            self.prompt_job = Some(PromptJob {
+                receiver: generator.spawn_regenerate_today(),
+                started: Instant::now(),
+                frame: 0,
+            });
+        }
+    }
+    
+    // Called every pass of the event loop: picks up the prompt once it's
+    // ready and keeps the spinner turning until then
+    fn poll_prompt_job(&mut self) {
+        let Some(job) = &mut self.prompt_job else {
+            return;
+        };
+        match job.receiver.try_recv() {
+            Err(TryRecvError::Empty) => {
+                let frame = (job.started.elapsed().as_millis() / 100) as usize % term::glyphs().spinner.len();
+                if frame != job.frame {
+                    job.frame = frame;
+                    self.dirty = true;
+                }
+                return;
+            }
+            Ok(Ok(prompt)) => {
+                // Swap it in if the static prompt is still showing
+                if self.should_show_prompt {
+                    self.current_prompt = Some(prompt.prompt);
+                }
+            }
+            // Offline, out of credits... the built-in prompt is already
+            // showing, so just carry on with it. The failure is recorded
+            // and "river prompts regenerate-today" shows the reason.
+            Ok(Err(_)) | Err(TryRecvError::Disconnected) => {}
+        }
+        self.prompt_job = None;
+        self.dirty = true;
+    }
+    
+    // :meta lists the note's front matter fields, :meta <key> shows one and
+    // :meta set <key> <value> changes or adds one
+    fn meta_command(&mut self, arg: &str) {
+        let mut words = arg.splitn(3, ' ');
+        match (words.next().unwrap_or(""), words.next(), words.next()) {
+            ("", _, _) => {
+                let fields: Vec<String> = match front_matter::find(&self.buffer) {
+                    Some(block) => self.buffer[1..block.end]
+                        .iter()
+                        .map(|line| line.iter().collect::<String>().trim().to_string())
+                        .filter(|line| !line.is_empty())
+                        .collect(),
+                    None => Vec::new(),
+                };
+                self.status_message = Some(if fields.is_empty() {
+                    t!("No front matter")
+                } else {
+                    fields.join(" · ")
+                });
+            }
+            ("set", Some(key), Some(value)) => {
+                if !self.ensure_writable() {
+                    return;
+                }
+                let new_block = front_matter::find(&self.buffer).is_none();
+                let before = self.buffer.len();
+                let y = front_matter::set(self.buffer.vec_mut(), key, value.trim());
+                // A new field pushes the text below it down; a new block
+                // goes above everything, the cursor's line included
+                if new_block || y <= self.cursor_y {
+                    self.cursor_y += self.buffer.len() - before;
+                }
+                self.mark_modified();
+            }
+            ("set", _, _) => self.status_message = Some(t!("Usage: :meta set <key> <value>")),
+            (key, _, _) => {
+                self.status_message = Some(match front_matter::get(&self.buffer, key) {
+                    Some(value) => format!("{}: {}", key, value),
+                    None => t!("No {key} in the front matter", key = key),
+                });
+            }
+        }
+    }
+    
+    // :vault lists the profiles, :vault <name> switches to one and opens
+    // its daily note (:vault default goes back to the top-level settings)
+    fn vault_command(&mut self, arg: &str) -> io::Result<()> {
+        let names: Vec<String> = self.config.profiles.keys().cloned().collect();
+        if arg.is_empty() {
+            self.status_message = Some(if names.is_empty() {
+                t!("No profiles - add [profiles.<name>] sections to config.toml")
+            } else {
+                let current = self.config.profile.as_deref().unwrap_or("default");
+                let list: Vec<String> = names
+                    .iter()
+                    .map(|name| if name == current { format!("[{}]", name) } else { name.clone() })
+                    .collect();
+                t!("Profiles: {profiles}", profiles = list.join(" "))
+            });
+            return Ok(());
+        }
+        let profile = if names.iter().any(|name| name == arg) {
+            Some(arg.to_string())
+        } else if arg == "default" {
+            None
+        } else {
+            self.status_message = Some(t!("No profile '{profile}'", profile = arg));
+            return Ok(());
+        };
+        
+        // Finish up with the current profile: its stats live in its notes dir
+        if self.needs_save {
+            self.save_file()?;
+        }
+        if !self.read_only {
+            self.save_typing_time();
+        }
+        
+        // A config.toml broken since river started would mean the
+        // defaults, and notes in the wrong place
+        Config::select_profile(profile);
+        let config = match Config::reload() {
+            Ok(config) => config,
+            Err(e) => {
+                Config::select_profile(self.config.profile.clone());
+                self.status_message = Some(t!("Can't switch - config.toml: {error}", error = e));
+                return Ok(());
+            }
+        };
+        self.config = config;
+        let (typing_time, stats_problem) = Self::load_typing_time(&self.config);
+        self.typing.reset(typing_time);
+        self.hours = Self::load_hours(&self.config);
+        self.typing.set_timeout(Duration::from_secs(self.config.typing_timeout_seconds));
+        let path = create_daily_note(&self.config)?;
+        self.open_file(&path.to_string_lossy(), false)?;
+        self.last_stats_problem = None;
+        self.status_message = Some(t!(
+            "Profile: {profile} ({dir})",
+            profile = self.config.profile.as_deref().unwrap_or("default"),
+            dir = self.config.daily_notes_dir
+        ));
+        if let Some(problem) = stats_problem {
+            self.stats_problem(problem);
+        }
+        Ok(())
+    }
+    
+    // :title asks the AI for a one-line summary of the note in the
+    // background; :title <text> sets one directly
+    fn title_command(&mut self, arg: &str) {
+        let Some(filename) = self.filename.clone() else {
+            self.status_message = Some(t!("The buffer has no file to title"));
+            return;
+        };
+        let words = self.count_words();
+        if !arg.is_empty() {
+            self.store_title(&filename, arg.to_string(), words);
+            return;
+        }
+        if words == 0 {
+            self.status_message = Some(t!("Nothing written to title yet"));
+            return;
+        }
+        match ai::PromptGenerator::new(&self.config) {
+            Ok(generator) => {
+                self.title_job = Some(TitleJob {
+                    filename,
+                    words,
+                    receiver: generator.spawn_title(self.buffer_text()),
+                });
+                self.status_message = Some(t!("Asking for a title..."));
+            }
+            Err(e) => self.status_message = Some(t!("Can't ask for a title: {error}", error = e)),
+        }
+    }
+    
+    fn poll_title_job(&mut self) {
+        let Some(job) = &self.title_job else {
+            return;
+        };
+        let result = match job.receiver.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(result) => result,
+            Err(TryRecvError::Disconnected) => Err(t!("the request stopped unexpectedly")),
+        };
+        let (filename, words) = (job.filename.clone(), job.words);
+        self.title_job = None;
+        match result {
+            Ok(title) => self.store_title(&filename, title, words),
+            Err(e) => self.status_message = Some(t!("Couldn't get a title: {error}", error = e)),
+        }
+        self.dirty = true;
+    }
+    
+    // Send the note to LanguageTool, if languagetool_url is set. 'asked'
+    // is :grammar; otherwise it's a save, and nothing is said unless
+    // something goes wrong.
+    fn check_grammar(&mut self, asked: bool) {
+        if self.config.languagetool_url.is_empty() {
+            if asked {
+                self.status_message = Some(t!("Set languagetool_url in config.toml to check grammar"));
+            }
+            return;
+        }
+        // One at a time; the next save catches up
+        if self.grammar_job.is_some() {
+            return;
+        }
+        // "lang: de_DE" in the front matter counts here too
+        let language = match spell::note_langs(&self.buffer) {
+            Some(langs) => langs[0].replace('_', "-"),
+            None => self.config.languagetool_language.clone(),
+        };
+        self.grammar_job = Some(GrammarJob {
+            filename: self.filename.clone(),
+            asked,
+            receiver: grammar::spawn_check(&self.config.languagetool_url, &language, self.buffer.to_vec()),
+        });
+        if asked {
+            self.status_message = Some(t!("Checking grammar..."));
+        }
+    }
+
+    fn poll_grammar_job(&mut self) {
+        let Some(job) = &self.grammar_job else {
+            return;
+        };
+        let result = match job.receiver.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(result) => result,
+            Err(TryRecvError::Disconnected) => Err(t!("the check stopped unexpectedly")),
+        };
+        let (filename, asked) = (job.filename.clone(), job.asked);
+        self.grammar_job = None;
+        match result {
+            Ok(issues) => {
+                if asked {
+                    let count = issues.len();
+                    self.status_message = Some(match count {
+                        0 => t!("No grammar issues"),
+                        _ => tn!(count, "{n} grammar issue - ]g/[g go through them", "{n} grammar issues - ]g/[g go through them"),
+                    });
+                }
+                self.grammar = (filename, issues);
+                self.grammar_failing = false;
+            }
+            // A server that isn't running is mentioned once, not on every save
+            Err(e) if asked || !self.grammar_failing => {
+                self.status_message = Some(e);
+                self.grammar_failing = true;
+            }
+            Err(_) => {}
+        }
+        self.dirty = true;
+    }
+
+    // The issues from the last check still standing in the active buffer,
+    // in order
+    fn grammar_issues(&self) -> Vec<&Issue> {
+        if self.grammar.0 != self.filename {
+            return Vec::new();
+        }
+        let mut issues: Vec<&Issue> = self.grammar.1.iter().filter(|issue| issue.is_current(&self.buffer)).collect();
+        issues.sort_by_key(|issue| issue.start);
+        issues
+    }
+
+    // ]g / [g (F7): move to the next or previous issue, wrapping round,
+    // and say what's wrong there
+    fn next_grammar_issue(&mut self, forward: bool) {
+        self.dirty = true;
+        let issues = self.grammar_issues();
+        let pos = self.cursor_pos();
+        let found = if forward {
+            issues.iter().find(|issue| issue.start > pos).or(issues.first())
+        } else {
+            issues.iter().rev().find(|issue| issue.start < pos).or(issues.last())
+        };
+        let Some(issue) = found else {
+            self.status_message = Some(if self.config.languagetool_url.is_empty() {
+                t!("Set languagetool_url in config.toml to check grammar")
+            } else {
+                t!("No grammar issues")
+            });
+            return;
+        };
+        let (start, message) = (issue.start, issue.describe());
+        self.cursor_y = start.y;
+        self.cursor_x = start.x;
+        self.status_message = Some(message);
+    }
+
+    fn store_title(&mut self, filename: &str, title: String, words: usize) {
+        let mut titles = TitleStore::load();
+        self.status_message = Some(t!("Title: {title}", title = title));
+        titles.set(Path::new(filename), NoteTitle { title, words });
+        if let Err(e) = titles.save() {
+            self.status_message = Some(t!("Couldn't save the title: {error}", error = e));
+        }
+    }
+    
+    // With auto_title on, title the note on the way out if it has changed
+    // since it was last titled. The terminal is back to normal by now, so
+    // progress is printed rather than drawn.
+    fn title_on_quit(&mut self) {
+        if !self.config.auto_title || self.read_only || self.tutor {
+            return;
+        }
+        let Some(filename) = self.filename.clone() else {
+            return;
+        };
+        let words = self.count_words();
+        let mut titles = TitleStore::load();
+        let unchanged = titles.get(Path::new(&filename)).map(|t| t.words) == Some(words);
+        if words == 0 || unchanged {
+            return;
+        }
+        
+        println!("Asking for a title...");
+        let result = ai::PromptGenerator::new(&self.config).and_then(|generator| generator.title(&self.buffer_text()));
+        match result {
+            Ok(title) => {
+                println!("Title: {}", title);
+                titles.set(Path::new(&filename), NoteTitle { title, words });
+                if let Err(e) = titles.save() {
+                    eprintln!("Couldn't save the title: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Couldn't get a title: {}", e),
+        }
+    }
+    
+    // Whether the open note is today's daily note, the only one whose
+    // writing goes into today's stats
+    fn is_todays_note(&self) -> bool {
+        match (&self.filename, get_daily_note_path(&self.config)) {
+            (Some(filename), Ok(today)) => fs::canonicalize(filename).ok() == fs::canonicalize(today).ok(),
+            _ => false,
+        }
+    }
+
+    // With mood_tracking on, record the mood and themes of today's note in
+    // today's stats when the session ends. Other notes are left alone since
+    // the stats are kept per day.
+    fn track_mood_on_quit(&mut self) {
+        if self.config.mood_tracking == "off" || self.read_only || self.count_words() == 0 {
+            return;
+        }
+        if !self.is_todays_note() {
+            return;
+        }
+        
+        let text = self.buffer_text();
+        let reading = if self.config.mood_tracking == "ai" {
+            match ai::PromptGenerator::new(&self.config).and_then(|generator| generator.mood(&text)) {
+                Ok(reading) => reading,
+                Err(e) => {
+                    eprintln!("Couldn't ask the AI about mood ({}), guessing locally", e);
+                    mood::classify(&text)
+                }
+            }
+        } else {
+            mood::classify(&text)
+        };
+        
+        let stats = DailyStats {
+            mood: Some(reading.mood.clone()),
+            themes: reading.themes.clone(),
+            ..Self::load_daily_stats(&self.config)
+        };
+        let saved = toml::to_string(&stats)
+            .map_err(io::Error::other)
+            .and_then(|toml_str| crypto::write(Self::get_stats_file_path(&self.config), toml_str));
+        match saved {
+            Ok(()) if reading.themes.is_empty() => println!("Mood: {}", reading.mood),
+            Ok(()) => println!("Mood: {} ({})", reading.mood, reading.themes.join(", ")),
+            Err(e) => eprintln!("Couldn't save today's mood: {}", e),
+        }
+    }
+    
+    fn should_display_prompt(&self) -> bool {
+        // Show prompt if:
+        // 1. Prompts are enabled in config
+        // 2. Prompt style is "ghost"
+        // 3. The document has a header on the first line
+        // 4. Nothing has been written below the header yet
+        
+        if !self.config.show_prompts || self.config.prompt_style != "ghost" {
+            return false;
+        }
+        
+        self.has_empty_body()
+    }
+    
+    // True for a note that is just a header (starts with #) and blank lines
+    fn has_empty_body(&self) -> bool {
+        let has_header = self.buffer.first().and_then(|line| line.first()) == Some(&'#');
+        // .all() on an empty iterator is true, so a header-only note counts
+        has_header && self.buffer[1..].iter().all(|line| line.iter().all(|c| c.is_whitespace()))
+    }
+    
+    fn get_stats_file_path(config: &Config) -> PathBuf {
+        stats_file_for(config, Local::now().date_naive())
+    }
+    
+    // Today's typing time so far. A stats file that can't be read doesn't
+    // stop river starting; it says so and counts from zero.
+    fn load_typing_time(config: &Config) -> (Duration, Option<String>) {
+        let path = Self::get_stats_file_path(config);
+        if path.exists() {
+            match crypto::read_to_string(&path) {
+                Ok(contents) => {
+                    if let Ok(stats) = toml::from_str::<DailyStats>(&contents) {
+                        return (Duration::from_secs(stats.typing_seconds), None);
+                    }
+                }
+                Err(e) => return (Duration::ZERO, Some(t!("Couldn't read today's stats ({path}): {error}", path = path.display(), error = e))),
+            }
+        }
+        (Duration::ZERO, None)
+    }
+    
+    // Today's stats as saved so far, or empty ones
+    fn load_daily_stats(config: &Config) -> DailyStats {
+        crypto::read_to_string(Self::get_stats_file_path(config))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+    
+    // Always 24 entries, whatever an older stats file held
+    fn load_hours(config: &Config) -> Vec<u64> {
+        let mut hours = Self::load_daily_stats(config).hours;
+        hours.resize(24, 0);
+        hours
+    }
+    
+    // Write today's stats now. A problem goes on the message line and is
+    // returned too, for when there's no screen left to show it on.
+    fn save_typing_time(&mut self) -> Option<String> {
+        self.finish_saves();
+        self.last_stats_problem = None;
+        match self.stats_write() {
+            Ok(write) => {
+                let written = saver::write_now(write);
+                self.saved(written);
+            }
+            Err(e) => self.stats_problem(t!("Couldn't save today's stats: {error}", error = e)),
+        }
+        self.last_stats_problem.clone()
+    }
+
+    // Today's stats, ready for the save thread. They go in the notes
+    // folder, or river's data directory if that can't be written to.
+    fn stats_write(&self) -> io::Result<saver::Write> {
+        let path = Self::get_stats_file_path(&self.config);
+        let fallback = stats::fallback_dir(&self.config).join(path.file_name().unwrap_or_default());
+        let fallback = (fallback != path).then_some(fallback);
+        Ok(saver::Write { path, content: self.typing_stats()?, snapshot: false, fallback })
+    }
+
+    // Say what's wrong with saving stats - once, not every ten seconds
+    fn stats_problem(&mut self, message: String) {
+        if self.last_stats_problem.as_ref() != Some(&message) {
+            self.status_message = Some(message.clone());
+            self.last_stats_problem = Some(message);
             self.dirty = true;
-            self.needs_save = true;
+        }
+    }
+
+    // Today's stats file as it should now read
+    fn typing_stats(&self) -> io::Result<String> {
+        // ..Self::load_daily_stats keeps the mood and themes already recorded
+        let stats = DailyStats {
+            typing_seconds: self.get_total_typing_time().as_secs(),
+            word_count: self.count_words() as u64,
+            goal: self.word_goal() as u64,
+            hours: self.hours.clone(),
+            ..Self::load_daily_stats(&self.config)
+        };
+        toml::to_string(&stats).map_err(io::Error::other)
+    }
+    
+    fn track_typing(&mut self) {
+        if !self.tutor {
+            self.typing.keystroke(Instant::now());
+            self.hours[Local::now().hour() as usize] += 1;
+        }
+        // The ghost prompt goes away as soon as writing starts
+        if self.should_show_prompt {
+            self.should_show_prompt = false;
+            self.dirty = true;
+        }
+    }
+    
+    fn get_total_typing_time(&self) -> Duration {
+        self.typing.total()
+    }
+
+    fn update_offset(&mut self) {
+        let visible_height = self.visible_height();
+
+        // A cursor that lands inside a folded section (a search, G, an
+        // edit) opens it, and any fold around that
+        let mut map = self.fold_map();
+        while let Some(heading) = map.hidden_by(self.cursor_y) {
+            self.buffer.unfold(heading);
+            map = self.fold_map();
+        }
+        
+        // Vertical scrolling, in screen rows - a folded section takes one
+        let (cursor_row, offset_row) = (map.row(self.cursor_y), map.row(self.offset_y));
+        if cursor_row < offset_row {
+            self.offset_y = self.cursor_y;
+        } else if cursor_row >= offset_row + visible_height {
+            self.offset_y = map.up(self.cursor_y, visible_height - 1);
+        }
+        
+        // Horizontal scrolling, in screen columns
+        let visible_width = self.text_width().max(1);
+        let column = self.cursor_column();
+        if column < self.offset_x {
+            self.offset_x = column;
+        } else if column >= self.offset_x + visible_width {
+            self.offset_x = column - visible_width + 1;
+        }
+    }
+
+    fn render(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        self.update_offset();
+
+        let mut stdout = io::stdout();
+        execute!(stdout, Hide)?;
+
+        let (windows, dividers) = self.arrange_windows();
+        for &(id, rect) in &windows {
+            self.render_window(id, rect)?;
+        }
+        // A checker that can't run (not installed, no dictionary) turns
+        // spell checking off rather than failing on every redraw
+        if let Some(error) = self.spell.as_ref().and_then(|spell| spell.error.borrow_mut().take()) {
+            self.status_message = Some(error);
+            self.spell = None;
+        }
+
+        for &(rect, direction) in &dividers {
+            match direction {
+                SplitDirection::Vertical => {
+                    for row in 0..rect.height {
+                        execute!(stdout, MoveTo(rect.x, rect.y + row), Foreground(self.config.colors().dim), Print(term::glyphs().vertical), ResetColor)?;
+                    }
+                }
+                SplitDirection::Horizontal => {
+                    // Label the divider with the file of the window above it
+                    let above = windows.iter()
+                        .find(|(_, r)| r.x == rect.x && r.y + r.height == rect.y)
+                        .map(|(id, _)| self.window_buffer_name(*id))
+                        .unwrap_or_default();
+                    let line = term::glyphs().horizontal;
+                    let label = width::truncate(&format!("{}{} {} ", line, line, above), rect.width as usize);
+                    let fill = (rect.width as usize).saturating_sub(width::str_width(&label));
+                    execute!(
+                        stdout,
+                        MoveTo(rect.x, rect.y),
+                        Foreground(self.config.colors().dim),
+                        Print(&label),
+                        Print(line.repeat(fill)),
+                        ResetColor
+                    )?;
+                }
+            }
+        }
+
+        self.render_status_bar()?;
+
+        let rect = self.active_rect();
+        let map = self.fold_map();
+        let mut screen_y = rect.y as usize + map.row(self.cursor_y) - map.row(self.offset_y);
+        let mut screen_x = rect.x as usize + self.gutter_width(self.buffer.len()) + self.cursor_column() - self.offset_x;
+        
+        self.render_completion(screen_x, screen_y)?;
+        self.render_synonyms(screen_x, screen_y)?;
+        
+        // Overlays draw on top of everything and own the cursor
+        if let Some((x, y)) = self.render_finder()? {
+            screen_x = x as usize;
+            screen_y = y as usize;
+        }
+        if let Some((x, y)) = self.render_outline()? {
+            screen_x = x as usize;
+            screen_y = y as usize;
+        }
+        if let Some((x, y)) = self.render_grep()? {
+            screen_x = x as usize;
+            screen_y = y as usize;
+        }
+        if let Some((x, y)) = self.render_diff()? {
+            screen_x = x as usize;
+            screen_y = y as usize;
+        }
+        if let Some((x, y)) = self.render_definition()? {
+            screen_x = x as usize;
+            screen_y = y as usize;
+        }
+        if let Some((x, y)) = self.render_help()? {
+            screen_x = x as usize;
+            screen_y = y as usize;
+        }
+        
+        execute!(
+            stdout,
+            MoveTo(screen_x as u16, screen_y as u16),
+            Show
+        )?;
+
+        stdout.flush()?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    // Screen column of the cursor within its line (tabs are wider than one)
+    fn cursor_column(&self) -> usize {
+        self.screen_column(self.cursor_x)
+    }
+
+    // Screen column of character x of the current line
+    fn screen_column(&self, x: usize) -> usize {
+        let line = self.current_line();
+        LineLayout::new(line, self.text_width(), &self.config).column(line, x, self.config.tab_size)
+    }
+
+    // The character of the current line drawn at screen column 'column'
+    fn char_at_screen_column(&self, column: usize) -> usize {
+        let line = self.current_line();
+        LineLayout::new(line, self.text_width(), &self.config).char_at(line, column, self.config.tab_size)
+    }
+
+    // Columns taken by the line-number gutter (0 when numbers are off).
+    // Wide enough for the biggest number plus a space after it.
+    fn gutter_width(&self, line_count: usize) -> usize {
+        if !self.config.show_line_numbers && !self.config.relative_numbers {
+            return 0;
+        }
+        line_count.to_string().len().max(3) + 1
+    }
+
+    // Columns of the focused window available for text
+    fn text_width(&self) -> usize {
+        (self.active_rect().width as usize).saturating_sub(self.gutter_width(self.buffer.len()))
+    }
+
+    // The number shown in the gutter for 'file_y'
+    fn line_number_label(&self, file_y: usize, cursor_y: usize) -> String {
+        if self.config.relative_numbers && file_y != cursor_y {
+            file_y.abs_diff(cursor_y).to_string()
+        } else if self.config.relative_numbers && !self.config.show_line_numbers {
+            "0".to_string()
+        } else {
+            (file_y + 1).to_string()
+        }
+    }
+
+    // Draws the finder box; returns where the cursor should sit
+    fn render_finder(&self) -> io::Result<Option<(u16, u16)>> {
+        let Some(finder) = &self.finder else {
+            return Ok(None);
+        };
+        let mut stdout = io::stdout();
+        let chosen = self.config.colors().selected;
+        let area = self.text_area();
+        let width = area.width.saturating_sub(4).min(72) as usize;
+        let x = (area.width as usize - width) as u16 / 2;
+        // One row for the query, the rest for results
+        let rows = (area.height as usize).saturating_sub(3).min(15);
+
+        let query_line = format!(" {}", t!("Find note: {query}", query = finder.query));
+        execute!(
+            stdout,
+            MoveTo(x, 1),
+            SetAttribute(Attribute::Reverse),
+            Print(width::fit(&query_line, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        // Scroll the list so the selection stays visible
+        let first = finder.selected.saturating_sub(rows.saturating_sub(1));
+        for row in 0..rows {
+            let text = match finder.entry(first + row) {
+                Some(entry) => format!(" {}  {}", entry.name, entry.label()),
+                None if row == 0 && finder.matches.is_empty() => format!(" {}", t!("No matching notes")),
+                None => String::new(),
+            };
+            execute!(stdout, MoveTo(x, 2 + row as u16))?;
+            if first + row == finder.selected && !finder.matches.is_empty() {
+                execute!(stdout, Standout(chosen))?;
+            }
+            execute!(stdout, Print(width::fit(&text, width)), ResetColor)?;
+        }
+
+        let cursor_x = x as usize + width::str_width(&query_line).min(width.saturating_sub(1));
+        Ok(Some((cursor_x as u16, 1)))
+    }
+
+    // Draws the :grep results across the bottom of the text area; returns
+    // where the cursor should sit
+    fn render_grep(&self) -> io::Result<Option<(u16, u16)>> {
+        let Some(grep) = self.grep.as_ref().filter(|_| self.grep_pane) else {
+            return Ok(None);
+        };
+        let mut stdout = io::stdout();
+        let chosen = self.config.colors().selected;
+        let area = self.text_area();
+        let width = area.width as usize;
+        // A title row and up to a third of the screen of results
+        let rows = grep.matches.len().min((area.height as usize / 3).max(3));
+        let top = area.height as usize - rows - 1;
+
+        let count = grep.matches.len();
+        let title = format!(" {}", tn!(count, "grep {term}: {n} match   Enter opens, Esc closes", "grep {term}: {n} matches   Enter opens, Esc closes", term = grep.term));
+        execute!(
+            stdout,
+            MoveTo(0, top as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(width::fit(&title, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        // Scroll the list so the selection stays visible
+        let first = grep.selected.saturating_sub(rows.saturating_sub(1));
+        for (row, found) in grep.matches[first..].iter().take(rows).enumerate() {
+            let place = format!(" {}:{} ", found.name, found.line + 1);
+            let fill = width.saturating_sub(width::str_width(&place));
+            execute!(stdout, MoveTo(0, (top + 1 + row) as u16))?;
+            if first + row == grep.selected {
+                execute!(stdout, Standout(chosen))?;
+            } else {
+                execute!(stdout, Foreground(self.config.colors().dim))?;
+            }
+            execute!(stdout, Print(&place), ResetColor)?;
+            if first + row == grep.selected {
+                execute!(stdout, Standout(chosen))?;
+            }
+            execute!(stdout, Print(width::fit(found.text.trim(), fill)), ResetColor)?;
+        }
+        Ok(Some((0, top as u16)))
+    }
+
+    // Draws the diff view over the text area, long lines wrapped so a
+    // changed paragraph can be read whole; returns where the cursor should sit
+    fn render_diff(&self) -> io::Result<Option<(u16, u16)>> {
+        let Some(view) = &self.diff_view else {
+            return Ok(None);
+        };
+        let mut stdout = io::stdout();
+        let area = self.text_area();
+        let width = area.width as usize;
+        let (added, removed) = view.counts();
+        let title = format!(" {}", t!("Diff against {version}: +{added} -{removed}   n/N next change, q close", version = view.label, added = added, removed = removed));
+        execute!(
+            stdout,
+            MoveTo(0, 0),
+            SetAttribute(Attribute::Reverse),
+            Print(width::fit(&title, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        let text_width = width.saturating_sub(2).max(1);
+        let mut row = 1;
+        for change in &view.changes[view.top..] {
+            let (sign, color, text) = match change {
+                Change::Same(text) => (' ', Color::Reset, text),
+                Change::Added(text) => ('+', self.config.colors().added, text),
+                Change::Removed(text) => ('-', self.config.colors().removed, text),
+            };
+            let chars: Vec<char> = text.replace('\t', &" ".repeat(self.config.tab_size)).chars().collect();
+            for (i, chunk) in width::chunks(&chars, text_width).into_iter().chain(chars.is_empty().then_some(&[][..])).enumerate() {
+                if row >= area.height {
+                    break;
+                }
+                let sign = if i == 0 { sign } else { ' ' };
+                let line = format!("{} {}", sign, chunk.iter().collect::<String>());
+                execute!(
+                    stdout,
+                    MoveTo(0, row),
+                    Foreground(color),
+                    Print(width::fit(&line, width)),
+                    ResetColor
+                )?;
+                row += 1;
+            }
+        }
+        for row in row..area.height {
+            execute!(stdout, MoveTo(0, row), Print(" ".repeat(width)))?;
+        }
+        Ok(Some((0, 0)))
+    }
+
+    // Draws the :define popup in the middle of the text area; returns
+    // where the cursor should sit
+    fn render_definition(&self) -> io::Result<Option<(u16, u16)>> {
+        let Some(definition) = &self.definition else {
+            return Ok(None);
+        };
+        let width = self.popup_width();
+        let title = format!("{} ({})", definition.word, definition.source);
+        self.render_popup(&title, &definition.wrapped(width - 2)).map(Some)
+    }
+
+    fn render_help(&self) -> io::Result<Option<(u16, u16)>> {
+        let Some(help) = &self.help else {
+            return Ok(None);
+        };
+        let width = self.popup_width();
+        self.render_popup(&help.title, &help.rows(width - 2)).map(Some)
+    }
+
+    fn popup_width(&self) -> usize {
+        (self.text_area().width as usize).saturating_sub(4).min(72)
+    }
+
+    // A box in the middle of the screen: a title row, 'rows' (as many as fit
+    // in two thirds of the screen) and a closing rule. Returns its corner.
+    fn render_popup(&self, title: &str, rows: &[String]) -> io::Result<(u16, u16)> {
+        let mut stdout = io::stdout();
+        let area = self.text_area();
+        let width = self.popup_width();
+        let x = (area.width as usize - width) / 2;
+        let height = rows.len().min((area.height as usize * 2 / 3).max(3));
+        let top = (area.height as usize).saturating_sub(height + 2) / 2;
+
+        let keys = if rows.len() > height { t!("j/k scroll, q closes") } else { t!("q closes") };
+        let title = format!(" {}   {}", title, keys);
+        execute!(
+            stdout,
+            MoveTo(x as u16, top as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(width::fit(&title, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        for row in 0..height {
+            let text = width::fit(rows.get(row).map_or("", String::as_str), width - 2);
+            execute!(stdout, MoveTo(x as u16, (top + 1 + row) as u16), Print(format!(" {} ", text)))?;
+        }
+        execute!(
+            stdout,
+            MoveTo(x as u16, (top + 1 + height) as u16),
+            Foreground(self.config.colors().dim),
+            Print(term::glyphs().horizontal.repeat(width)),
+            ResetColor
+        )?;
+        Ok((x as u16, top as u16))
+    }
+
+    // Draws the outline panel down the right side; returns where the
+    // cursor should sit
+    fn render_outline(&self) -> io::Result<Option<(u16, u16)>> {
+        let Some(outline) = &self.outline else {
+            return Ok(None);
+        };
+        let mut stdout = io::stdout();
+        let chosen = self.config.colors().selected;
+        let area = self.text_area();
+        let width = (area.width as usize / 3).clamp(20, 40).min(area.width as usize - 1);
+        let x = area.width - width as u16;
+        // One row for the query, the rest for headings
+        let rows = (area.height as usize).saturating_sub(1);
+
+        let query_line = format!(" {}", t!("Outline: {query}", query = outline.query));
+        execute!(
+            stdout,
+            MoveTo(x - 1, 0),
+            Foreground(self.config.colors().dim),
+            Print(term::glyphs().vertical),
+            ResetColor,
+            SetAttribute(Attribute::Reverse),
+            Print(width::fit(&query_line, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        // Scroll the list so the selection stays visible
+        let first = outline.selected.saturating_sub(rows.saturating_sub(1));
+        for row in 0..rows {
+            let text = match outline.entry(first + row) {
+                Some(heading) => format!(" {}{}", "  ".repeat(heading.level - 1), heading.text),
+                None if row == 0 && outline.matches.is_empty() => " No headings".to_string(),
+                None => String::new(),
+            };
+            execute!(
+                stdout,
+                MoveTo(x - 1, 1 + row as u16),
+                Foreground(self.config.colors().dim),
+                Print(term::glyphs().vertical),
+                ResetColor
+            )?;
+            if first + row == outline.selected && !outline.matches.is_empty() {
+                execute!(stdout, Standout(chosen))?;
+            }
+            execute!(stdout, Print(width::fit(&text, width)), ResetColor)?;
+        }
+
+        let cursor_x = x as usize + width::str_width(&query_line).min(width.saturating_sub(1));
+        Ok(Some((cursor_x as u16, 0)))
+    }
+
+    // The completion menu, lined up under the word being completed
+    fn render_completion(&self, cursor_x: usize, cursor_y: usize) -> io::Result<()> {
+        let Some(completion) = &self.completion else {
+            return Ok(());
+        };
+        let word_columns = self.cursor_column().saturating_sub(self.screen_column(completion.start));
+        self.render_menu(&completion.candidates, completion.selected, cursor_x.saturating_sub(word_columns), cursor_y)
+    }
+
+    // The synonyms popup, under the word like the completion menu
+    fn render_synonyms(&self, cursor_x: usize, cursor_y: usize) -> io::Result<()> {
+        let Some(synonyms) = &self.synonyms else {
+            return Ok(());
+        };
+        let word_columns = self.cursor_column().saturating_sub(self.screen_column(synonyms.start.x));
+        self.render_menu(&synonyms.list, Some(synonyms.selected), cursor_x.saturating_sub(word_columns), cursor_y)
+    }
+
+    // A popup list under row cursor_y (or above it near the bottom of the
+    // screen), its text lined up with column x
+    fn render_menu(&self, entries: &[String], selected: Option<usize>, x: usize, cursor_y: usize) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        let chosen = self.config.colors().selected;
+        let area = self.text_area();
+        let rows = entries.len().min(8);
+        let y = if cursor_y + 1 + rows <= area.height as usize {
+            cursor_y + 1
+        } else {
+            cursor_y.saturating_sub(rows)
+        };
+        let width = entries.iter().map(|entry| width::str_width(entry)).max().unwrap_or(0) + 2;
+        // One column left of the word, for the space before each entry
+        let x = x.saturating_sub(1).min((area.width as usize).saturating_sub(width));
+        let width = width.min(area.width as usize);
+
+        // Scroll the list so the selection stays visible
+        let first = selected.unwrap_or(0).saturating_sub(rows - 1);
+        for (row, word) in entries.iter().enumerate().skip(first).take(rows) {
+            execute!(stdout, MoveTo(x as u16, (y + row - first) as u16))?;
+            if selected == Some(row) {
+                execute!(stdout, Standout(chosen))?;
+            } else {
+                execute!(stdout, Background(self.config.colors().menu))?;
+            }
+            let text = format!(" {}", word);
+            execute!(stdout, Print(width::fit(&text, width)), ResetColor)?;
+        }
+        Ok(())
+    }
+
+    // Stretches of the active buffer drawn with a highlight (the visual
+    // selection, long sentences after ":readability highlight")
+    fn highlights(&self) -> Vec<(Pos, Pos)> {
+        let mut highlights = Vec::new();
+        // The match a :s///c is asking about
+        if let Some(state) = &self.substitution {
+            highlights.push((state.at, Pos::new(state.at.x + state.sub.pattern.len(), state.at.y)));
+        }
+        // The visual selection; whole lines run on to the start of the next
+        if let Some(selection) = self.visual {
+            highlights.push(match selection.span(self.cursor_pos(), &self.buffer) {
+                Span::Chars(start, end) => (start, end),
+                Span::Lines(first, last) => (Pos::new(0, first), Pos::new(0, last + 1)),
+            });
+        }
+        if self.highlight_long_sentences {
+            highlights.extend(vim::sentences(&self.buffer).into_iter().filter(|&(start, end)| {
+                let text: Vec<String> = self.text_range(start, end).iter().map(|l| l.iter().collect()).collect();
+                text_stats::count_words(&text.join("\n"), false) > self.config.long_sentence_words
+            }));
+        }
+        highlights
+    }
+
+    fn window_buffer_name(&self, id: usize) -> String {
+        let buffer = self.windows[id].buffer;
+        let name = if buffer == self.active_buffer {
+            self.filename.as_deref().unwrap_or("[No Name]")
+        } else {
+            self.buffers[buffer].display_name()
+        };
+        // Just the file name - full paths don't fit in a divider
+        Path::new(name).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    }
+
+    // Draw one window's text into its rectangle
+    fn render_window(&self, id: usize, rect: Rect) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        let colors = self.config.colors();
+        let window = &self.windows[id];
+        let is_active = id == self.active_window;
+        // The active buffer's text lives in self.buffer, others in their slot
+        let shows_active_buffer = window.buffer == self.active_buffer;
+        let lines = if shows_active_buffer { &self.buffer } else { &self.buffers[window.buffer].lines };
+        let (offset_x, offset_y, cursor_y) = if is_active {
+            (self.offset_x, self.offset_y, self.cursor_y)
+        } else {
+            (window.offset_x, window.offset_y.min(lines.len().saturating_sub(1)), window.cursor_y)
+        };
+        let gutter = self.gutter_width(lines.len());
+        let width = (rect.width as usize).saturating_sub(gutter);
+
+        let highlights = if shows_active_buffer { self.highlights() } else { Vec::new() };
+        let misspelled = match &self.spell {
+            // The word being typed isn't marked until it's finished
+            Some(spell) if shows_active_buffer => {
+                let typing = (self.mode == Mode::Insert).then(|| self.cursor_pos());
+                spell.misspelled(lines, &self.config, typing)
+            }
+            _ => Vec::new(),
+        };
+        let grammar: Vec<(Pos, Pos)> = if shows_active_buffer {
+            self.grammar_issues().iter().map(|issue| (issue.start, issue.end)).collect()
+        } else {
+            Vec::new()
+        };
+        // Front matter is drawn dimmed so the note itself stands out
+        let front_matter = front_matter::find(lines);
+        let folds = FoldMap::new(lines, lines.folds());
+
+        // Rows go down the buffer line by line, except that a folded
+        // section's heading is followed by the next section
+        let mut file_y = folds.hidden_by(offset_y).unwrap_or(offset_y);
+        for y in 0..rect.height as usize {
+            execute!(stdout, MoveTo(rect.x, rect.y + y as u16))?;
+            let mut printed = 0; // Columns written so far on this row
+
+            if y > 0 {
+                file_y += folds.hidden_under(file_y) + 1;
+            }
+            if gutter > 0 {
+                let label = if file_y < lines.len() { self.line_number_label(file_y, cursor_y) } else { String::new() };
+                // {:>w$} right-aligns in a width given at runtime
+                let color = if file_y == cursor_y && is_active { colors.line_number } else { colors.dim };
+                execute!(
+                    stdout,
+                    Foreground(color),
+                    Print(format!("{:>w$} ", label, w = gutter - 1)),
+                    ResetColor
+                )?;
+            }
+
+            if file_y < lines.len() {
+                let line = &lines[file_y];
+                // Split the visible text into runs drawn the same way
+                // (plain, highlighted, misspelled...), so each run is a single
+                // Print. offset_x and width count screen columns, and a tab
+                // fills the columns up to the next multiple of tab_size with
+                // spaces. Right-to-left text is drawn in its own order (see
+                // LineLayout), a line of it right-aligned.
+                let mut runs: Vec<(Mark, String)> = Vec::new();
+                let layout = LineLayout::new(line, width, &self.config);
+                if layout.start > offset_x {
+                    let pad = (layout.start - offset_x).min(width);
+                    runs.push((Mark::Plain, " ".repeat(pad)));
+                    printed += pad;
+                }
+                for (x, mirrored, column, next) in layout.cells(line, self.config.tab_size) {
+                    let c = line[x];
+                    // A combining mark takes no column, so one just past the
+                    // edge still belongs on the letter before it
+                    if column >= offset_x + width && next > column {
+                        break;
+                    }
+                    let mark = if is_highlighted(&highlights, Pos::new(x, file_y)) {
+                        Mark::Highlight
+                    } else if is_highlighted(&misspelled, Pos::new(x, file_y)) {
+                        Mark::Misspelled
+                    } else if is_highlighted(&grammar, Pos::new(x, file_y)) {
+                        Mark::Grammar
+                    } else {
+                        Mark::Plain
+                    };
+                    if next == column {
+                        // Drawn with the character it sits on, if that showed
+                        if column > offset_x {
+                            if let Some((_, run)) = runs.last_mut() {
+                                run.push(c);
+                            }
+                        }
+                        continue;
+                    }
+                    // Only the columns inside the window. A tab is spaces,
+                    // and so is a wide character cut off at either edge.
+                    let shown_columns = next.min(offset_x + width).saturating_sub(column.max(offset_x));
+                    let shown = if c != '\t' && shown_columns == next - column {
+                        if mirrored { bidi::mirror(c) } else { c }.to_string()
+                    } else {
+                        " ".repeat(shown_columns)
+                    };
+                    if !shown.is_empty() {
+                        match runs.last_mut() {
+                            Some((run_mark, run)) if *run_mark == mark => run.push_str(&shown),
+                            _ => runs.push((mark, shown)),
+                        }
+                        printed += shown_columns;
+                    }
+                }
+                for (mark, run) in runs {
+                    if mark == Mark::Highlight {
+                        execute!(stdout, Standout(colors.highlight), Print(&run), ResetColor)?;
+                    } else if matches!(mark, Mark::Misspelled | Mark::Grammar) {
+                        let color = if mark == Mark::Misspelled { colors.misspelled } else { colors.grammar };
+                        execute!(
+                            stdout,
+                            Foreground(color),
+                            SetAttribute(Attribute::Underlined),
+                            Print(&run),
+                            SetAttribute(Attribute::Reset),
+                            ResetColor
+                        )?;
+                    } else if front_matter.is_some_and(|fm| fm.contains(file_y)) {
+                        execute!(stdout, Foreground(colors.dim), Print(&run), ResetColor)?;
+                    } else {
+                        execute!(stdout, Print(&run))?;
+                    }
+                }
+
+                // A folded heading says how much is tucked away under it
+                let hidden = folds.hidden_under(file_y);
+                if hidden > 0 {
+                    let marker = format!(" {}", tn!(hidden, "··· {n} line", "··· {n} lines"));
+                    let marker = width::truncate(&marker, width.saturating_sub(printed));
+                    printed += width::str_width(&marker);
+                    execute!(stdout, Foreground(colors.dim), Print(&marker), ResetColor)?;
+                }
+                
+                // Show prompt on the appropriate empty line (typically line 1 after header)
+                if shows_active_buffer && self.should_show_prompt && line.is_empty() && file_y == 1 {
+                    if let Some(ref prompt) = self.current_prompt {
+                        let ghost = width::truncate(&format!("> {}", prompt), width);
+                        printed = width::str_width(&ghost);
+                        execute!(stdout, Foreground(colors.dim))?;
+                        execute!(stdout, Print(&ghost))?;
+                        execute!(stdout, ResetColor)?;
+                    }
+                }
+            } else {
+                execute!(stdout, Foreground(colors.dim))?;
+                execute!(stdout, Print("~"))?;
+                execute!(stdout, ResetColor)?;
+                printed = 1;
+            }
+
+            // Pad with spaces rather than clearing the line, which would
+            // also wipe any window to the right of this one
+            execute!(stdout, Print(" ".repeat(width.saturating_sub(printed))))?;
+        }
+        Ok(())
+    }
+
+    // The minute the clock shows and the session timer's minutes; when
+    // either changes the status bar needs redrawing
+    fn status_minute(&self) -> (u32, u64) {
+        (Local::now().minute(), self.session_start.elapsed().as_secs() / 60)
+    }
+
+    fn render_status_bar(&mut self) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        let y = self.terminal_height - 2;
+
+        // Clear status bar area
+        execute!(
+            stdout,
+            MoveTo(0, y),
+            Clear(ClearType::CurrentLine),
+            MoveTo(0, y + 1),
+            Clear(ClearType::CurrentLine)
+        )?;
+
+        // Calculate word count and progress
+        let word_count = self.settled_word_count();
+        let goal = self.word_goal();
+        let progress = ((word_count as f32 / goal as f32) * 100.0).min(100.0) as u32;
+        
+        // Get typing time in minutes
+        let typing_time = self.get_total_typing_time();
+        let typing_mins = typing_time.as_secs() / 60;
+        
+        // A '·' between items, or '.' where the terminal only shows ASCII
+        let glyphs = term::glyphs();
+
+        // Create fixed-width formatted strings
+        // The numbers are padded before they go into the (translated) text
+        let word_str = t!("{words} words", words = format!("{:>4}", word_count)); // Right-align in 4 chars
+        let percent_str = format!("{:>3}%", progress);      // Right-align in 3 chars
+        // Said in words as well as by the bar's colour, which not everyone
+        // can tell apart (or has)
+        let goal_str = if word_count >= goal { format!(" {}", t!("GOAL!")) } else { String::new() };
+        let time_str = t!("{minutes} min", minutes = format!("{:>3}", typing_mins)); // Right-align in 3 chars
+        // The typing clock has stopped until the next keystroke
+        let idle_str = if self.typing.is_idle(Instant::now()) { format!(" {}", t!("idle")) } else { String::new() };
+        let view_str = if self.read_only { format!(" [{}]", t!("view")) } else { String::new() };
+        let spinner_str = match &self.prompt_job {
+            Some(job) => format!(" {} {}", glyphs.spinner[job.frame], t!("prompt")),
+            None => String::new(),
+        };
+        let read_str = if self.config.show_reading_time {
+            let minutes = text_stats::reading_minutes(word_count, self.config.reading_wpm);
+            format!(" {} {}", glyphs.dot, t!("{minutes} min read", minutes = minutes))
+        } else {
+            String::new()
+        };
+        // Time since river opened, for time-boxing a session
+        let session_str = if self.config.show_session_time {
+            let minutes = self.session_start.elapsed().as_secs() / 60;
+            format!(" {} {}", glyphs.dot, t!("session {time}", time = format!("{}:{:02}", minutes / 60, minutes % 60)))
+        } else {
+            String::new()
+        };
+        let clock_str = if self.config.show_clock {
+            format!(" {} {}", glyphs.dot, Local::now().format("%H:%M"))
+        } else {
+            String::new()
+        };
+        
+        // Calculate progress bar width - use full terminal width minus the text and spacing
+        // Layout: " [progress bar] word_str percent_str goal_str · time_str idle_str read_str session_str clock_str "
+        // Columns rather than len() - the '·' and translations' accents
+        // are two bytes but one column
+        let text_width = 2 + 2 + width::str_width(&word_str) + 1 + percent_str.len() + width::str_width(&goal_str) + 3 + width::str_width(&time_str) + width::str_width(&idle_str)
+            + width::str_width(&read_str) + width::str_width(&session_str) + width::str_width(&clock_str)
+            + width::str_width(&view_str) + width::str_width(&spinner_str) + 1; // brackets, spaces
+        let bar_width = (self.terminal_width as usize).saturating_sub(text_width).max(10);
+        let filled = (bar_width as f32 * (progress as f32 / 100.0)) as usize;
+        let empty = bar_width - filled;
+        
+        // Create the full-width status line
+        // format! macro creates a String using interpolation
+        // {} are placeholders filled by subsequent arguments
+        let status = format!(" [{}{}] {} {}{} {} {}{}{}{}{}{}{}", 
+            "=".repeat(filled),    // String method repeat()
+            " ".repeat(empty),
+            word_str,
+            percent_str,
+            goal_str,
+            glyphs.dot,
+            time_str,
+            idle_str,
+            read_str,
+            session_str,
+            clock_str,
+            view_str,
+            spinner_str
+        );
+        
+        // Set color based on progress
+        let colors = self.config.colors();
+        let color = if word_count >= goal {
+            colors.progress[2]
+        } else if word_count >= goal * 3 / 4 {
+            colors.progress[1]
+        } else {
+            colors.progress[0]
+        };
+        
+        // Just after reaching the goal the whole bar lights up
+        let (color, background) = match self.celebrate_until {
+            Some(_) => colors.celebrate,
+            None => (color, Color::Reset),
+        };
+        
+        execute!(
+            stdout,
+            MoveTo(0, y),
+            Standout((color, background)),
+            Print(&status),
+            ResetColor
+        )?;
+
+        // Show command buffer if in command mode, otherwise any pending message
+        if self.mode == Mode::Command {
+            execute!(
+                stdout,
+                MoveTo(0, y + 1),
+                Print(self.command_prompt),
+                Print(&self.command_buffer)
+            )?;
+        } else if let Some(ref message) = self.status_message {
+            execute!(stdout, MoveTo(0, y + 1), Print(message))?;
+        } else if self.mode == Mode::Replace {
+            execute!(stdout, MoveTo(0, y + 1), Print(t!("-- REPLACE --")))?;
+        } else if let Some(selection) = self.visual {
+            let label = if selection.linewise { t!("-- VISUAL LINE --") } else { t!("-- VISUAL --") };
+            execute!(stdout, MoveTo(0, y + 1), Print(label))?;
+        }
+
+        Ok(())
+    }
+
+    // The active buffer as it goes on disk
+    fn file_content(&self) -> String {
+        // Iterator chain pattern - functional programming style
+        let lines: Vec<String> = self.buffer
+            .iter()                                    // Iterator over &Vec<char>
+            .map(|line| line.iter().collect::<String>()) // Transform each line to String
+            .collect();                                // Collect into Vec<String>
+        // Written back with the line endings the file had (see buffer.rs)
+        self.line_endings
+            .with_overrides(&self.config.line_endings, &self.config.final_newline)
+            .join(&lines)
+    }
+
+    // Save now, waiting for the write (:w, quitting, leaving the buffer)
+    fn save_file(&mut self) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        // An autosave still being written mustn't land after this one
+        self.finish_saves();
+        if let Some(filename) = self.filename.clone() {
+            // Losing the earlier version isn't worth failing the save over
+            let _ = history::snapshot(&filename);
+            crypto::write(&filename, self.file_content())?;
+            self.needs_save = false;
             self.last_save = Instant::now();
-        } else if self.cursor_y < self.buffer.len() - 1 {
-            let next_line = self.buffer.remove(self.cursor_y + 1);
-            self.buffer[self.cursor_y].extend(next_line);
-            self.dirty = true;
-            self.needs_save = true;
+            self.saved(Written { path: PathBuf::from(filename), result: Ok(()), moved_to: None });
+        }
+        Ok(())
+    }
+    
+    // Read config.toml again, when it changes or on :reload-config. Most
+    // settings are read as they're needed and just take effect; the few
+    // the editor keeps state for are brought up to date by apply_config.
+    // Where notes live and which set of keys is in use wait for a restart.
+    fn reload_config(&mut self) {
+        self.config_modified = Config::modified();
+        self.dirty = true;
+        let mut config = match Config::reload() {
+            Ok(config) => config,
+            Err(e) => {
+                self.status_message = Some(t!("config.toml not reloaded - {error}", error = e));
+                return;
+            }
+        };
+        let mut on_restart = Vec::new();
+        for key in config::RESTART_SETTINGS {
+            let old = self.config.get(key);
+            if config.get(key) != old {
+                if let Some(Ok(kept)) = old.map(|old| config.with(key, old)) {
+                    config = kept;
+                }
+                on_restart.push(key);
+            }
+        }
+        // :set settings last until river quits, whatever the file says
+        for (key, value) in &self.session_settings {
+            if let Ok(changed) = config.with(key, value.clone()) {
+                config = changed;
+            }
+        }
+        self.apply_config(config);
+        self.status_message = Some(if on_restart.is_empty() {
+            t!("Config reloaded")
+        } else {
+            t!("Config reloaded ({settings} will change after a restart)", settings = on_restart.join(", "))
+        });
+    }
+
+    fn apply_config(&mut self, config: Config) {
+        if config.spell_check != self.config.spell_check {
+            self.spell = config.spell_check.then(|| Spell::new(&config));
+        } else if self.spell.is_some() && config.spell_file != self.config.spell_file {
+            self.spell = Some(Spell::new(&config));
+        }
+        i18n::use_language(&config);
+        self.config = config;
+        self.typing.set_timeout(Duration::from_secs(self.config.typing_timeout_seconds));
+        self.word_count_stale = true;
+        self.goal_met = self.count_words() >= self.word_goal();
+        self.dirty = true;
+    }
+
+    // :set key=value - change a setting until river quits, leaving
+    // config.toml alone. Like vim, ":set key" turns a true/false setting
+    // on, ":set nokey" off, and ":set key?" shows the value.
+    fn set_command(&mut self, arg: &str) {
+        if arg.is_empty() {
+            let set: Vec<String> = self.session_settings.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            self.status_message = Some(if set.is_empty() {
+                t!("Usage: :set <setting>=<value>")
+            } else {
+                t!("Set for this session: {settings}", settings = set.join(", "))
+            });
+            return;
+        }
+        let is_switch = |key: &str| matches!(self.config.get(key), Some(toml::Value::Boolean(_)));
+        let (key, text) = match arg.split_once('=') {
+            Some((key, text)) => (key.trim(), text.trim()),
+            None => match arg.strip_suffix('?') {
+                Some(key) => (key, ""),
+                None if is_switch(arg) => (arg, "true"),
+                None => match arg.strip_prefix("no") {
+                    Some(key) if is_switch(key) => (key, "false"),
+                    _ => (arg, ""),
+                },
+            },
+        };
+        if text.is_empty() {
+            self.status_message = Some(match self.config.get(key) {
+                Some(value) => format!("{}={}", key, value),
+                None => t!("No setting called {key}", key = key),
+            });
+            return;
+        }
+        if config::RESTART_SETTINGS.contains(&key) {
+            self.status_message = Some(t!("{key} can only change in config.toml, after a restart", key = key));
+            return;
+        }
+        let changed = self.config.parse_setting(key, text).and_then(|value| Ok((self.config.with(key, value.clone())?, value)));
+        match changed {
+            Ok((config, value)) => {
+                self.session_settings.retain(|(set, _)| set != key);
+                self.session_settings.push((key.to_string(), value.clone()));
+                self.apply_config(config);
+                self.status_message = Some(t!("{key}={value} until river quits", key = key, value = value));
+            }
+            Err(e) => self.status_message = Some(e),
+        }
+    }
+
+    // Ctrl-S
+    fn save_now(&mut self) {
+        self.dirty = true;
+        if !self.ensure_writable() {
+            return;
+        }
+        let Some(filename) = self.filename.clone() else {
+            self.status_message = Some(t!("No file name - use :w <path>"));
+            return;
+        };
+        self.status_message = Some(match self.save_file() {
+            Ok(()) => {
+                let words = self.count_words();
+                tn!(words, "\"{file}\" written, {n} word", "\"{file}\" written, {n} words", file = filename)
+            }
+            Err(e) => t!("Couldn't save {file}: {error}", file = filename, error = e),
+        });
+    }
+
+    // The autosave after a pause in typing, handed to the save thread so
+    // a slow disk doesn't hold up the next keystroke (see saver.rs)
+    fn auto_save(&mut self) -> io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        if let Some(filename) = &self.filename {
+            let write = saver::Write { path: PathBuf::from(filename), content: self.file_content(), snapshot: true, fallback: None };
+            self.saver.write(write);
+            self.needs_save = false;
             self.last_save = Instant::now();
         }
+        Ok(())
     }
 
-    // Returns a reference to the current line
-    // &self - immutable borrow (read-only access)
-    // &Vec<char> - returns a reference, not ownership
-    fn current_line(&self) -> &Vec<char> {
-        // & creates a reference to the value
-        &self.buffer[self.cursor_y]
+    fn poll_saves(&mut self) {
+        for written in self.saver.poll() {
+            self.saved(written);
+        }
     }
-    
-    fn count_words(&self) -> usize {
-        let mut word_count = 0;
-        let mut in_word = false;
-        
-        // & creates iterator over references (doesn't consume self.buffer)
-        // Without &, 'for line in self.buffer' would try to move ownership
-        for line in &self.buffer {
-            for ch in line {
-                if ch.is_alphanumeric() {
-                    if !in_word {
-                        word_count += 1;
-                        in_word = true;
-                    }
+
+    fn finish_saves(&mut self) {
+        for written in self.saver.wait() {
+            self.saved(written);
+        }
+    }
+
+    // A write finished: the save hook and grammar check follow a note's
+    // save; a failure goes on the message line, and the note is saved
+    // again at the next pause. Anything else written is today's stats.
+    fn saved(&mut self, written: Written) {
+        let is_note = self.filename.as_deref().is_some_and(|filename| Path::new(filename) == written.path);
+        match written.result {
+            Ok(()) if is_note => {
+                self.run_hook("save", &self.config.on_save.clone());
+                self.check_grammar(false);
+            }
+            Ok(()) => match written.moved_to {
+                Some(path) => {
+                    let dir = path.parent().unwrap_or(&path).display().to_string();
+                    self.stats_problem(t!("Can't write to the notes folder - today's stats are saved in {dir}", dir = dir));
+                }
+                None => self.last_stats_problem = None,
+            },
+            Err(e) if !is_note => self.stats_problem(t!("Couldn't save today's stats: {error}", error = e)),
+            Err(e) => {
+                self.status_message = Some(t!("Couldn't save {file}: {error}", file = written.path.display(), error = e));
+                if is_note {
+                    self.needs_save = true;
+                }
+                self.dirty = true;
+            }
+        }
+    }
+
+    // Record the active file's cursor/scroll/mode in the session store
+    fn remember_position(&mut self) {
+        if let Some(filename) = &self.filename {
+            let session = FileSession {
+                cursor_x: self.cursor_x,
+                cursor_y: self.cursor_y,
+                offset_x: self.offset_x,
+                offset_y: self.offset_y,
+                // Reopening into the command line would be confusing
+                mode: Some(if matches!(self.mode, Mode::Command | Mode::Replace) { Mode::Normal } else { self.mode }),
+                marks: self.marks.clone(),
+            };
+            self.sessions.set(filename, session);
+        }
+    }
+
+    // Move the active buffer's state out of the editor fields into its slot
+    fn stash_active_buffer(&mut self) {
+        self.snippet_stops.clear();
+        self.remember_position();
+        let buf = &mut self.buffers[self.active_buffer];
+        // mem::take moves the value out and leaves Default::default() behind
+        buf.lines = std::mem::take(&mut self.buffer);
+        buf.filename = self.filename.take();
+        buf.cursor_x = self.cursor_x;
+        buf.cursor_y = self.cursor_y;
+        buf.offset_x = self.offset_x;
+        buf.offset_y = self.offset_y;
+        buf.needs_save = self.needs_save;
+        buf.show_prompt = self.should_show_prompt;
+        buf.read_only = self.read_only;
+        buf.marks = std::mem::take(&mut self.marks);
+        buf.line_endings = self.line_endings;
+        buf.large = self.large_file;
+    }
+
+    // Make buffers[index] active by moving its state into the editor fields
+    fn restore_buffer(&mut self, index: usize) {
+        self.active_buffer = index;
+        self.windows[self.active_window].buffer = index;
+        let buf = &mut self.buffers[index];
+        self.buffer = std::mem::take(&mut buf.lines);
+        if self.buffer.is_empty() {
+            self.buffer.push(Vec::new());
+        }
+        self.filename = buf.filename.clone();
+        self.cursor_x = buf.cursor_x;
+        self.cursor_y = buf.cursor_y;
+        self.offset_x = buf.offset_x;
+        self.offset_y = buf.offset_y;
+        self.needs_save = buf.needs_save;
+        self.should_show_prompt = buf.show_prompt;
+        self.read_only = buf.read_only;
+        self.marks = std::mem::take(&mut buf.marks);
+        self.visual = None;
+        self.line_endings = buf.line_endings;
+        self.large_file = buf.large;
+        self.word_count_stale = true;
+        self.goal_met = self.count_words() >= self.word_goal();
+        self.dirty = true;
+    }
+
+    fn switch_buffer(&mut self, index: usize) -> io::Result<()> {
+        if index == self.active_buffer {
+            return Ok(());
+        }
+        // Only the active buffer is autosaved, so save before leaving it
+        if self.needs_save {
+            self.save_file()?;
+        }
+        self.stash_active_buffer();
+        self.restore_buffer(index);
+        self.status_message = Some(format!("[{}] {}", index + 1, self.buffers[index].display_name()));
+        Ok(())
+    }
+
+    // :ls - one line listing every open buffer, active one marked with %
+    fn list_buffers(&mut self) {
+        let listing: Vec<String> = self.buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buf)| {
+                if i == self.active_buffer {
+                    let name = self.filename.clone().unwrap_or_else(|| t!("[No Name]"));
+                    format!("{}% {}{}", i + 1, name, if self.needs_save { " +" } else { "" })
                 } else {
-                    in_word = false;
+                    format!("{}  {}", i + 1, buf.display_name())
                 }
+            })
+            .collect();
+        self.status_message = Some(listing.join("  |  "));
+    }
+
+    // The screen area available to windows (everything above the status bar)
+    fn text_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.terminal_width,
+            height: self.terminal_height.saturating_sub(2),
+        }
+    }
+
+    // Where each window and divider goes on screen
+    fn arrange_windows(&self) -> (Vec<Placement>, Vec<Divider>) {
+        let mut windows = Vec::new();
+        let mut dividers = Vec::new();
+        self.layout.arrange(self.text_area(), &mut windows, &mut dividers);
+        (windows, dividers)
+    }
+
+    // The area of the focused window - used for scrolling and paging
+    fn active_rect(&self) -> Rect {
+        self.arrange_windows()
+            .0
+            .into_iter()
+            .find(|(id, _)| *id == self.active_window)
+            .map(|(_, rect)| rect)
+            .unwrap_or_else(|| self.text_area())
+    }
+
+    // Copy the focused window's cursor/offset out of the editor fields
+    fn stash_active_window(&mut self) {
+        self.windows[self.active_window] = Window {
+            buffer: self.active_buffer,
+            cursor_x: self.cursor_x,
+            cursor_y: self.cursor_y,
+            offset_x: self.offset_x,
+            offset_y: self.offset_y,
+        };
+    }
+
+    // Give focus to window 'id', bringing in its buffer if needed
+    fn enter_window(&mut self, id: usize) -> io::Result<()> {
+        let target = self.windows[id];
+        self.active_window = id;
+        if target.buffer != self.active_buffer {
+            if self.needs_save {
+                self.save_file()?;
+            }
+            self.stash_active_buffer();
+            self.restore_buffer(target.buffer);
+        }
+
+        // The text may have changed in another window, so clamp the cursor
+        self.cursor_y = target.cursor_y.min(self.buffer.len() - 1);
+        self.cursor_x = target.cursor_x.min(self.current_line().len());
+        self.offset_x = target.offset_x;
+        self.offset_y = target.offset_y;
+        self.dirty = true;
+        Ok(())
+    }
+
+    // Ctrl-W cycles focus through the windows in screen order
+    fn next_window(&mut self) -> io::Result<()> {
+        let order = self.layout.windows();
+        if order.len() < 2 {
+            return Ok(());
+        }
+        let pos = order.iter().position(|&id| id == self.active_window).unwrap_or(0);
+        self.stash_active_window();
+        self.enter_window(order[(pos + 1) % order.len()])
+    }
+
+    // :split / :vsplit [path] - the new window starts on the current
+    // buffer and then optionally opens another file
+    fn split_window(&mut self, direction: SplitDirection, path: &str) -> io::Result<()> {
+        self.stash_active_window();
+        let new_id = self.windows.len();
+        self.windows.push(self.windows[self.active_window]);
+        self.layout.split(self.active_window, new_id, direction);
+        self.enter_window(new_id)?;
+
+        if !path.is_empty() {
+            self.open_file(path, false)?;
+        }
+        Ok(())
+    }
+
+    fn close_window(&mut self) -> io::Result<()> {
+        if self.windows.len() < 2 {
+            self.status_message = Some(t!("Cannot close last window"));
+            return Ok(());
+        }
+        let closing = self.active_window;
+        self.windows.remove(closing);
+        self.layout.remove(closing);
+        // Indices have shifted, so focus whichever window comes first
+        let next = self.layout.windows()[0];
+        self.enter_window(next)
+    }
+
+    // :only - keep just the focused window
+    fn only_window(&mut self) {
+        self.stash_active_window();
+        self.windows = vec![self.windows[self.active_window]];
+        self.active_window = 0;
+        self.layout = Layout::Window(0);
+        self.dirty = true;
+    }
+
+    // Open a file from inside the editor (:e <path>, or :view <path> with
+    // read_only set). Files that are already open are switched to;
+    // otherwise a new buffer is added. The current buffer is saved first
+    // so nothing is lost.
+    fn open_file(&mut self, filename: &str, read_only: bool) -> io::Result<()> {
+        if self.filename.as_deref() == Some(filename) {
+            self.read_only |= read_only;
+            return Ok(());
+        }
+        if let Some(index) = self.buffers
+            .iter()
+            .position(|buf| buf.filename.as_deref() == Some(filename))
+        {
+            self.switch_buffer(index)?;
+            self.read_only |= read_only;
+            return Ok(());
+        }
+
+        if self.needs_save {
+            self.save_file()?;
+        }
+        let previous = self.active_buffer;
+        self.stash_active_buffer();
+        self.buffers.push(Buffer::new());
+        self.restore_buffer(self.buffers.len() - 1);
+        self.read_only = read_only;
+
+        if Path::new(filename).exists() {
+            // A failed read drops the new buffer and goes back
+            if let Err(e) = self.load_file(filename) {
+                self.buffers.pop();
+                self.restore_buffer(previous);
+                self.status_message = Some(t!("Can't open {file}: {error}", file = filename, error = e));
+                return Ok(());
+            }
+        } else {
+            // New file - start empty, it gets created on first save
+            self.buffer = Lines::from(vec![Vec::new()]);
+            self.filename = Some(filename.to_string());
+            self.cursor_x = 0;
+            self.cursor_y = 0;
+            self.should_show_prompt = false;
+            self.status_message = Some(t!("\"{file}\" [New]", file = filename));
+            self.lock_file();
+        }
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    // :scratch - the scratch note (scratch.md in the data directory) for
+    // thoughts that don't belong under a date; :scratch again goes back
+    fn toggle_scratch(&mut self) -> io::Result<()> {
+        let path = config::data_dir().join("scratch.md");
+        let path = path.to_string_lossy().to_string();
+        if self.filename.as_deref() == Some(path.as_str()) {
+            // Back to the note it was opened from, or today's if that's closed
+            let back = self.scratch_return.take().filter(|filename| {
+                self.buffers.iter().any(|buf| buf.filename.as_ref() == Some(filename))
+            });
+            let back = match back {
+                Some(filename) => filename,
+                None => create_daily_note(&self.config)?.to_string_lossy().to_string(),
+            };
+            return self.open_file(&back, false);
+        }
+
+        // The data directory may not exist before the first save
+        fs::create_dir_all(config::data_dir())?;
+        self.scratch_return = self.filename.clone();
+        self.open_file(&path, false)?;
+        self.should_show_prompt = false;
+        self.status_message = Some(t!("Scratch - :scratch goes back"));
+        Ok(())
+    }
+
+    // Show some generated text (a report) in a new read-only buffer with
+    // no file behind it. :bp goes back.
+    fn open_scratch(&mut self, lines: Vec<String>) -> io::Result<()> {
+        if self.needs_save {
+            self.save_file()?;
+        }
+        self.stash_active_buffer();
+        self.buffers.push(Buffer::new());
+        self.restore_buffer(self.buffers.len() - 1);
+        self.buffer = lines.iter().map(|line| line.chars().collect()).collect();
+        if self.buffer.is_empty() {
+            self.buffer.push(Vec::new());
+        }
+        self.read_only = true;
+        self.should_show_prompt = false;
+        self.dirty = true;
+        Ok(())
+    }
+
+    // Lock the active file for editing. If another river already has it,
+    // fall back to read-only rather than fight over autosaves.
+    fn lock_file(&mut self) {
+        let Some(filename) = self.filename.clone() else {
+            return;
+        };
+        if self.read_only || self.locks.contains_key(&filename) {
+            return;
+        }
+        match Lock::acquire(Path::new(&filename)) {
+            Ok(lock) => {
+                self.locks.insert(filename, lock);
             }
-            in_word = false; // Reset at end of line
+            Err(Some(pid)) => {
+                self.read_only = true;
+                self.status_message = Some(t!(
+                    "Open in another river (pid {pid}) - read-only, :view! to edit anyway",
+                    pid = pid
+                ));
+            }
+            // Couldn't write a lock file (read-only directory?) - carry on
+            Err(None) => {}
         }
-        
-        word_count
     }
-    
-    fn get_daily_prompt(&self) -> String {
-        let today = Local::now().date_naive();
+
+    fn load_file(&mut self, filename: &str) -> io::Result<()> {
+        let (lines, line_endings) = buffer::read_lines(Path::new(filename))?;
+        self.buffer = Lines::from(lines);
+        self.line_endings = line_endings;
+        let size = fs::metadata(filename).map(|metadata| metadata.len()).unwrap_or(0);
+        self.large_file = size > self.config.large_file_kb * 1024;
+        self.word_count_stale = true;
+        if self.large_file {
+            self.status_message = Some(t!("Large file - the word count updates when you pause typing"));
+        }
         
-        // First try to get AI-generated prompt if enabled
-        if self.config.use_ai_prompts {
-            if let Some(ai_prompt) = ai::get_ai_prompt(&self.config, &today) {
-                return ai_prompt;
-            }
+        if self.buffer.is_empty() {
+            self.buffer.push(Vec::new());
         }
         
-        // Fall back to static prompts
-        let prompts = vec![
-            "What moment from today do you want to remember?",
-            "What are you grateful for today?",
-            "What challenged you today and how did you handle it?",
-            "What made you smile or laugh today?",
-            "What did you learn about yourself today?",
-            "What small victory did you achieve today?",
-            "How did you grow as a person today?",
-            "What would you tell your future self about today?",
-            "What surprised you today?",
-            "What intention do you want to set for tomorrow?",
-        ];
+        self.filename = Some(filename.to_string());
+        self.lock_file();
         
-        // Use the current date as a seed for consistent daily prompts
-        let day_of_year = today.ordinal() as usize;
-        let prompt_index = day_of_year % prompts.len();
+        // Marks are always restored - they were set on purpose, unlike
+        // the cursor position which remember_position controls
+        self.marks = self.sessions.get(filename).map(|s| s.marks.clone()).unwrap_or_default();
         
-        prompts[prompt_index].to_string()
-    }
-    
-    fn should_display_prompt(&self) -> bool {
-        // Show prompt if:
-        // 1. Prompts are enabled in config
-        // 2. Prompt style is "ghost"
-        // 3. We have a current prompt set
-        // 4. The document has a header on the first line
+        // cloned() turns Option<&FileSession> into an owned Option<FileSession>
+        let remembered = if self.config.remember_position {
+            self.sessions.get(filename).cloned()
+        } else {
+            None
+        };
         
-        if !self.config.show_prompts || self.config.prompt_style != "ghost" {
-            return false;
+        if let Some(session) = remembered {
+            // Pick up where we left off, clamped in case the file changed
+            self.cursor_y = session.cursor_y.min(self.buffer.len() - 1);
+            self.cursor_x = session.cursor_x.min(self.buffer[self.cursor_y].len());
+            self.offset_y = session.offset_y.min(self.cursor_y);
+            self.offset_x = session.offset_x.min(self.cursor_x);
+            if let Some(mode) = session.mode {
+                if self.config.vim_bindings {
+                    self.mode = mode;
+                }
+            }
+        } else if self.config.open_at_end && !self.read_only {
+            // Position cursor at end of file
+            self.cursor_y = self.buffer.len() - 1;
+            self.cursor_x = self.buffer[self.cursor_y].len();
+            
+            // If the last line has content, add a new line and position cursor there
+            if !self.buffer[self.cursor_y].is_empty() {
+                self.buffer.push(Vec::new());
+                self.cursor_y += 1;
+                self.cursor_x = 0;
+            }
+        } else {
+            self.cursor_y = 0;
+            self.cursor_x = 0;
         }
         
-        // Check if first line looks like a header (starts with #)
-        if !self.buffer.is_empty() && !self.buffer[0].is_empty() && self.buffer[0][0] == '#' {
-            return true;
+        // Check if we should show a prompt
+        if self.should_display_prompt() {
+            self.current_prompt = Some(self.get_daily_prompt());
         }
+        // Always keep should_show_prompt in sync with should_display_prompt
+        self.should_show_prompt = self.should_display_prompt();
+        // Opening a note that's already past its goal is no cause to celebrate
+        self.goal_met = self.count_words() >= self.word_goal();
         
-        false
+        self.dirty = true;
+        Ok(())
     }
-    
-    fn get_stats_file_path(config: &Config) -> PathBuf {
-        let today = Local::now();
-        let date_str = today.format("%Y-%m-%d").to_string();
-        let filename = format!(".stats-{}.toml", date_str);
-        Path::new(&config.daily_notes_dir).join(filename)
+}
+
+// A note's own goal from its front matter, or daily_word_goal
+fn note_goal(lines: &[Vec<char>], config: &Config) -> usize {
+    front_matter::get(lines, "goal")
+        .and_then(|goal| goal.parse().ok())
+        .filter(|&goal| goal > 0)
+        .unwrap_or(config.daily_word_goal)
+}
+
+// How a run of text in a window is drawn
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Plain,
+    Highlight,  // Yellow background: :s///c, long sentences
+    Misspelled, // Red and underlined
+    Grammar,    // Blue and underlined: a LanguageTool issue
+}
+
+fn is_highlighted(highlights: &[(Pos, Pos)], pos: Pos) -> bool {
+    highlights.iter().any(|&(start, end)| start <= pos && pos < end)
+}
+
+// The per-character conversion behind gu, gU and g~
+fn case_conversion(op: Operator) -> Option<fn(char) -> char> {
+    match op {
+        Operator::Lowercase => Some(|c| c.to_lowercase().next().unwrap_or(c)),
+        Operator::Uppercase => Some(|c| c.to_uppercase().next().unwrap_or(c)),
+        Operator::ToggleCase => Some(toggle_case),
+        _ => None,
     }
-    
-    fn load_typing_time(config: &Config) -> io::Result<Duration> {
-        let path = Self::get_stats_file_path(config);
-        if path.exists() {
-            let contents = fs::read_to_string(&path)?;
-            if let Ok(stats) = toml::from_str::<DailyStats>(&contents) {
-                return Ok(Duration::from_secs(stats.typing_seconds));
-            }
-        }
-        Ok(Duration::from_secs(0))
+}
+
+fn toggle_case(c: char) -> char {
+    if c.is_uppercase() {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c.to_uppercase().next().unwrap_or(c)
     }
-    
-    fn save_typing_time(&self) -> io::Result<()> {
-        let path = Self::get_stats_file_path(&self.config);
-        let stats = DailyStats {
-            typing_seconds: self.get_total_typing_time().as_secs(),
-            word_count: self.count_words() as u64,
-        };
-        let toml_str = toml::to_string(&stats).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        fs::write(&path, toml_str)?;
-        Ok(())
+}
+
+// The column after character c when it starts at 'column'. A tab reaches
+// the next multiple of tab_size; everything else takes its width (see
+// width.rs): two columns for CJK, none for a combining accent.
+fn advance_column(column: usize, c: char, tab_size: usize) -> usize {
+    let tab_size = tab_size.max(1);
+    if c == '\t' {
+        column + tab_size - column % tab_size
+    } else {
+        column + width::char_width(c)
     }
-    
-    fn track_typing(&mut self) {
-        let now = Instant::now();
-        let typing_timeout = Duration::from_secs(self.config.typing_timeout_seconds);
-        
-        // If this is the first typing activity or we've been inactive
-        if self.typing_session_start.is_none() || now.duration_since(self.last_typing_activity) > typing_timeout {
-            self.typing_session_start = Some(now);
+}
+
+// Screen columns taken by line[..x]
+fn display_column(line: &[char], x: usize, tab_size: usize) -> usize {
+    line.iter().take(x).fold(0, |column, &c| advance_column(column, c, tab_size))
+}
+
+// Where a line's characters go on screen. Stored in the order they were
+// typed, right-to-left text is drawn in another (see bidi.rs), and a line
+// that starts with it is right-aligned in the window.
+struct LineLayout {
+    order: Vec<(usize, bool)>, // Indices into the line in drawing order, and whether each is mirrored
+    start: usize,              // Column the first is drawn at
+    rtl: bool,                 // A right-to-left line, which ends on the left
+}
+
+impl LineLayout {
+    fn new(line: &[char], width: usize, config: &Config) -> Self {
+        if !config.bidi || !bidi::has_rtl(line) {
+            return LineLayout { order: (0..line.len()).map(|x| (x, false)).collect(), start: 0, rtl: false };
         }
-        
-        self.last_typing_activity = now;
+        let rtl = bidi::is_rtl(line);
+        let start = if rtl { width.saturating_sub(display_column(line, line.len(), config.tab_size)) } else { 0 };
+        LineLayout { order: bidi::visual_order(line), start, rtl }
     }
-    
-    fn get_total_typing_time(&self) -> Duration {
-        let mut total = self.accumulated_typing_time;
-        
-        // Add current session time if actively typing
-        if let Some(session_start) = self.typing_session_start {
-            let typing_timeout = Duration::from_secs(self.config.typing_timeout_seconds);
-            if self.last_typing_activity.elapsed() <= typing_timeout {
-                total += self.last_typing_activity.duration_since(session_start);
+
+    // (index, mirrored, first column, column after) for each character,
+    // from left to right
+    fn cells<'a>(&'a self, line: &'a [char], tab_size: usize) -> impl Iterator<Item = (usize, bool, usize, usize)> + 'a {
+        let mut column = self.start;
+        self.order.iter().map(move |&(x, mirrored)| {
+            let from = column;
+            column = self.start + advance_column(column - self.start, line[x], tab_size);
+            (x, mirrored, from, column)
+        })
+    }
+
+    // Screen column of character x, or of the end of the line - after its
+    // last character, which for a right-to-left line is on the left
+    fn column(&self, line: &[char], x: usize, tab_size: usize) -> usize {
+        let mut end = self.start;
+        for (i, _, from, to) in self.cells(line, tab_size) {
+            if i == x {
+                return from;
             }
+            end = to;
         }
-        
-        total
+        if self.rtl { self.start.saturating_sub(1) } else { end }
     }
 
-    fn update_offset(&mut self) {
-        let visible_height = (self.terminal_height - 2) as usize;
-        
-        // Vertical scrolling
-        if self.cursor_y < self.offset_y {
-            self.offset_y = self.cursor_y;
-        } else if self.cursor_y >= self.offset_y + visible_height {
-            self.offset_y = self.cursor_y - visible_height + 1;
-        }
-        
-        // Horizontal scrolling
-        let visible_width = self.terminal_width as usize;
-        if self.cursor_x < self.offset_x {
-            self.offset_x = self.cursor_x;
-        } else if self.cursor_x >= self.offset_x + visible_width {
-            self.offset_x = self.cursor_x - visible_width + 1;
+    // The character covering screen column 'column', or the line's length
+    // past the end of the line
+    fn char_at(&self, line: &[char], column: usize, tab_size: usize) -> usize {
+        if self.rtl && column < self.start {
+            return line.len();
         }
+        self.cells(line, tab_size).find(|&(_, _, _, to)| to > column).map_or(line.len(), |(x, ..)| x)
     }
+}
 
-    fn render(&mut self) -> io::Result<()> {
-        if !self.dirty {
-            return Ok(());
-        }
+// Number of spaces/tabs at the start of a line
+fn leading_whitespace(line: &[char]) -> usize {
+    line.iter().take_while(|c| **c == ' ' || **c == '\t').count()
+}
 
-        self.update_offset();
+// Helper function to count words in a markdown file
+fn count_words_in_file(path: &Path, config: &Config) -> io::Result<usize> {
+    let content = crypto::read_to_string(path)?;
+    Ok(text_stats::count_words(&content, config.markdown_word_count))
+}
 
-        let mut stdout = io::stdout();
-        let visible_height = (self.terminal_height - 2) as usize;
+fn get_daily_note_path(config: &Config) -> io::Result<PathBuf> {
+    let today = Local::now();
+    let date_str = today.format("%Y-%m-%d").to_string();
+    let filename = format!("{}.md", date_str);
+    
+    let notes_dir = Path::new(&config.daily_notes_dir);
+    
+    // Create directory if it doesn't exist
+    if !notes_dir.exists() {
+        fs::create_dir_all(notes_dir)?;
+    }
+    
+    Ok(notes_dir.join(filename))
+}
 
-        execute!(stdout, Hide)?;
+// Turns "today", "yesterday", a YYYY-MM-DD date or a plain path into the
+// file it refers to. Dates map to daily notes in the notes directory.
+fn resolve_note_path(config: &Config, arg: &str) -> PathBuf {
+    match parse_date(arg) {
+        Some(date) => {
+            let name = format!("{}.md", date.format("%Y-%m-%d"));
+            let path = Path::new(&config.daily_notes_dir).join(&name);
+            // An archived note is still found by its date
+            let archived = Path::new(&config.daily_notes_dir).join(archive::DIR).join(&name);
+            if !path.exists() && archived.exists() { archived } else { path }
+        }
+        None => PathBuf::from(config::expand_tilde(arg)),
+    }
+}
 
-        for y in 0..visible_height {
-            execute!(stdout, MoveTo(0, y as u16))?;
-            execute!(stdout, Clear(ClearType::CurrentLine))?;
-
-            let file_y = y + self.offset_y;
-            if file_y < self.buffer.len() {
-                let line = &self.buffer[file_y];
-                // Apply horizontal scrolling
-                let visible_start = self.offset_x;
-                // 'as' performs type casting (u16 to usize)
-                // .min() returns the smaller of two values
-                let visible_end = (visible_start + self.terminal_width as usize).min(line.len());
-                
-                if visible_start < line.len() {
-                    // Range syntax [start..end] creates a slice
-                    // .iter() creates iterator over &char
-                    // .collect() builds String from iterator
-                    let line_str: String = line[visible_start..visible_end].iter().collect();
-                    execute!(stdout, Print(&line_str))?;
-                }
-                
-                // Show prompt on the appropriate empty line (typically line 1 after header)
-                if self.should_show_prompt && line.is_empty() && file_y == 1 {
-                    if let Some(ref prompt) = self.current_prompt {
-                        execute!(stdout, SetForegroundColor(Color::DarkGrey))?;
-                        execute!(stdout, Print("> "))?;
-                        execute!(stdout, Print(prompt))?;
-                        execute!(stdout, ResetColor)?;
-                    }
-                }
-            } else {
-                execute!(stdout, SetForegroundColor(Color::DarkGrey))?;
-                execute!(stdout, Print("~"))?;
-                execute!(stdout, ResetColor)?;
+// "today", "yesterday" or a YYYY-MM-DD date
+fn parse_date(arg: &str) -> Option<NaiveDate> {
+    let today = Local::now().date_naive();
+    match arg {
+        "today" => Some(today),
+        "yesterday" => Some(today - chrono::Duration::days(1)),
+        // .ok() converts Result into Option, discarding the error
+        _ => NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok(),
+    }
+}
+
+// A range of days, inclusive:
+// - nothing: the last 30 days
+// - "week", "month", "year": the last 7, 30 or 365 days
+// - "2024-05": that calendar month
+// - "2024": that calendar year
+// - "2024-05-01..2024-05-14" (either end can be today/yesterday)
+// - a single date
+fn parse_date_range(arg: Option<&str>) -> Option<(NaiveDate, NaiveDate)> {
+    let today = Local::now().date_naive();
+    let last_days = |days: i64| Some((today - chrono::Duration::days(days - 1), today));
+
+    let Some(arg) = arg else {
+        return last_days(30);
+    };
+    match arg {
+        "week" => last_days(7),
+        "month" => last_days(30),
+        "year" => last_days(365),
+        _ => {
+            if let Some((from, to)) = arg.split_once("..") {
+                return Some((parse_date(from)?, parse_date(to)?));
+            }
+            if arg.len() == 4 && arg.chars().all(|c| c.is_ascii_digit()) {
+                let year = arg.parse().ok()?;
+                return Some((NaiveDate::from_ymd_opt(year, 1, 1)?, NaiveDate::from_ymd_opt(year, 12, 31)?));
+            }
+            if let Ok(first) = NaiveDate::parse_from_str(&format!("{}-01", arg), "%Y-%m-%d") {
+                // The day before the first of next month
+                let next = if first.month() == 12 {
+                    NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)?
+                } else {
+                    NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)?
+                };
+                return Some((first, next - chrono::Duration::days(1)));
             }
+            let date = parse_date(arg)?;
+            Some((date, date))
         }
+    }
+}
 
-        self.render_status_bar()?;
+// river analyze [range] - word frequency report across daily notes
+fn analyze_notes(range: Option<&str>) -> io::Result<()> {
+    let config = Config::load();
+    let Some((from, to)) = parse_date_range(range) else {
+        eprintln!("Usage: river analyze [week|month|year|YYYY|YYYY-MM|DATE|DATE..DATE]");
+        std::process::exit(1);
+    };
 
-        let screen_y = self.cursor_y - self.offset_y;
-        let screen_x = self.cursor_x - self.offset_x;
-        execute!(
-            stdout,
-            MoveTo(screen_x as u16, screen_y as u16),
-            Show
-        )?;
+    let documents: Vec<(String, String)> = notes_in_range(&config, from, to)
+        .into_iter()
+        .map(|(date, content)| (date.format("%Y-%m-%d").to_string(), content))
+        .collect();
 
-        stdout.flush()?;
-        self.dirty = false;
-        Ok(())
+    if documents.is_empty() {
+        println!("No notes between {} and {}", from, to);
+        return Ok(());
     }
+    println!("{} notes from {} to {}\n", documents.len(), from, to);
+    for line in analyze::format_report(&analyze::analyze(&documents)) {
+        println!("{}", line);
+    }
+    Ok(())
+}
 
-    fn render_status_bar(&mut self) -> io::Result<()> {
-        let mut stdout = io::stdout();
-        let y = self.terminal_height - 2;
-
-        // Clear status bar area
-        execute!(
-            stdout,
-            MoveTo(0, y),
-            Clear(ClearType::CurrentLine),
-            MoveTo(0, y + 1),
-            Clear(ClearType::CurrentLine)
-        )?;
+// The daily notes that exist between two dates (inclusive), oldest first
+fn notes_in_range(config: &Config, from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, String)> {
+    let mut notes = Vec::new();
+    let mut date = from;
+    while date <= to {
+        // Archived notes count too, unless they were zipped
+        let path = resolve_note_path(config, &date.format("%Y-%m-%d").to_string());
+        if let Ok(content) = crypto::read_to_string(&path) {
+            notes.push((date, content));
+        }
+        date += chrono::Duration::days(1);
+    }
+    notes
+}
 
-        // Calculate word count and progress
-        let word_count = self.count_words();
-        let goal = 500;
-        let progress = ((word_count as f32 / goal as f32) * 100.0).min(100.0) as u32;
-        
-        // Get typing time in minutes
-        let typing_time = self.get_total_typing_time();
-        let typing_mins = typing_time.as_secs() / 60;
-        
-        // Create fixed-width formatted strings
-        let word_str = format!("{:>4} words", word_count);  // Right-align in 4 chars
-        let percent_str = format!("{:>3}%", progress);      // Right-align in 3 chars
-        let time_str = format!("{:>3} min", typing_mins);   // Right-align in 3 chars
-        
-        // Calculate progress bar width - use full terminal width minus the text and spacing
-        // Layout: " [progress bar] word_str percent_str · time_str "
-        let text_width = 2 + 2 + word_str.len() + 1 + percent_str.len() + 3 + time_str.len() + 1; // brackets, spaces
-        let bar_width = (self.terminal_width as usize).saturating_sub(text_width).max(10);
-        let filled = (bar_width as f32 * (progress as f32 / 100.0)) as usize;
-        let empty = bar_width - filled;
-        
-        // Create the full-width status line
-        // format! macro creates a String using interpolation
-        // {} are placeholders filled by subsequent arguments
-        let status = format!(" [{}{}] {} {} · {}", 
-            "=".repeat(filled),    // String method repeat()
-            " ".repeat(empty),
-            word_str,
-            percent_str,
-            time_str
-        );
-        
-        // Set color based on progress
-        let color = if word_count >= goal {
-            Color::Green
-        } else if word_count >= goal * 3 / 4 {
-            Color::Yellow
-        } else {
-            Color::White
-        };
-        
-        execute!(
-            stdout,
-            MoveTo(0, y),
-            SetForegroundColor(color),
-            Print(&status),
-            ResetColor
-        )?;
+// Where the typing stats for a day are kept
+fn stats_file_for(config: &Config, date: NaiveDate) -> PathBuf {
+    let filename = format!(".stats-{}.toml", date.format("%Y-%m-%d"));
+    let path = Path::new(&config.daily_notes_dir).join(&filename);
+    let archived = Path::new(&config.daily_notes_dir).join(archive::DIR).join(&filename);
+    // Saved in river's data directory when the notes folder was read-only
+    let fallback = stats::fallback_dir(config).join(&filename);
+    [archived, fallback].into_iter().find(|other| !path.exists() && other.exists()).unwrap_or(path)
+}
 
-        // Show command buffer if in command mode
-        if self.mode == Mode::Command {
-            execute!(
-                stdout,
-                MoveTo(0, y + 1),
-                Print(":"),
-                Print(&self.command_buffer)
-            )?;
+// river compile <range> [-o file] - the notes in a range as one markdown
+// document with a table of contents
+fn compile_command(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: river compile <YYYY-MM|YYYY|DATE..DATE|...> [-o file.md]";
+    let mut range = None;
+    let mut output = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = rest.next().cloned(),
+            _ if range.is_none() => range = Some(arg.as_str()),
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
         }
+    }
+    let Some((from, to)) = range.and_then(|range| parse_date_range(Some(range))) else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
 
-        Ok(())
+    let config = Config::load();
+    let entries: Vec<compile::Entry> = notes_in_range(&config, from, to)
+        .into_iter()
+        .map(|(date, text)| {
+            let typing_seconds = crypto::read_to_string(stats_file_for(&config, date))
+                .ok()
+                .and_then(|contents| toml::from_str::<DailyStats>(&contents).ok())
+                .map_or(0, |stats| stats.typing_seconds);
+            let words = text_stats::count_words(&text, config.markdown_word_count);
+            compile::Entry { date, text, words, typing_seconds }
+        })
+        .collect();
+    if entries.is_empty() {
+        println!("No notes between {} and {}", from, to);
+        return Ok(());
     }
 
-    fn save_file(&mut self) -> io::Result<()> {
-        if let Some(filename) = &self.filename {
-            // Iterator chain pattern - functional programming style
-            let content: String = self.buffer
-                .iter()                                    // Iterator over &Vec<char>
-                .map(|line| line.iter().collect::<String>()) // Transform each line to String
-                .collect::<Vec<String>>()                  // Collect into Vec<String>
-                .join("\n");                              // Join with newlines
-            
-            std::fs::write(filename, content)?;
-            self.needs_save = false;
-            self.last_save = Instant::now();
+    let document = compile::compile(&compile::title(from, to), &entries);
+    let path = output.unwrap_or_else(|| format!("journal-{}-to-{}.md", from, to));
+    fs::write(&path, document)?;
+    println!("Wrote {} ({} notes)", path, entries.len());
+    Ok(())
+}
+
+// river archive --before <date> [--zip] - see archive.rs
+fn archive_command(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: river archive --before <YYYY-MM-DD> [--zip]";
+    let mut before = None;
+    let mut zip = false;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--before" => before = rest.next().and_then(|date| parse_date(date)),
+            "--zip" => zip = true,
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
         }
-        Ok(())
     }
-    
-    fn auto_save(&mut self) -> io::Result<()> {
-        self.save_file()
+    let Some(before) = before else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+
+    let config = Config::load();
+    let archived = archive::archive(Path::new(&config.daily_notes_dir), before, zip)?;
+    for name in &archived.skipped {
+        eprintln!("Skipped {}: already in {}/", name, archive::DIR);
+    }
+    if archived.notes == 0 && archived.stats == 0 {
+        println!("Nothing dated before {} to archive", before);
+    } else if zip {
+        println!(
+            "Archived {} notes into {}/notes-before-{}.zip and {} stats files into {}/",
+            archived.notes, archive::DIR, before, archived.stats, archive::DIR
+        );
+    } else {
+        println!("Archived {} notes and {} stats files into {}/", archived.notes, archived.stats, archive::DIR);
     }
+    Ok(())
+}
 
-    fn load_file(&mut self, filename: &str) -> io::Result<()> {
-        let content = std::fs::read_to_string(filename)?;
-        self.buffer = content
-            .lines()
-            .map(|line| line.chars().collect())
-            .collect();
-        
-        if self.buffer.is_empty() {
-            self.buffer.push(Vec::new());
+// river import dayone <export.zip> / river import jrnl <journal.txt>
+fn import_command(args: &[String]) -> io::Result<()> {
+    let (Some(format), Some(file), None) = (args.first(), args.get(1), args.get(2)) else {
+        eprintln!("Usage: river import <dayone|jrnl> <file>");
+        std::process::exit(1);
+    };
+    let path = PathBuf::from(config::expand_tilde(file));
+    let entries = match format.as_str() {
+        "dayone" => import::read_dayone(&path),
+        "jrnl" => import::read_jrnl(&path),
+        _ => {
+            eprintln!("Can't import from '{}' (try dayone or jrnl)", format);
+            std::process::exit(1);
         }
-        
-        self.filename = Some(filename.to_string());
-        
-        // Position cursor at end of file
-        self.cursor_y = self.buffer.len() - 1;
-        self.cursor_x = self.buffer[self.cursor_y].len();
-        
-        // If the last line has content, add a new line and position cursor there
-        if !self.buffer[self.cursor_y].is_empty() {
-            self.buffer.push(Vec::new());
-            self.cursor_y += 1;
-            self.cursor_x = 0;
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", path.display(), e);
+            std::process::exit(1);
         }
-        
-        // Check if we should show a prompt
-        if self.should_display_prompt() {
-            self.current_prompt = Some(self.get_daily_prompt());
+    };
+
+    let config = Config::load();
+    let summary = import::write_entries(Path::new(&config.daily_notes_dir), &entries, |date| {
+        create_daily_note_content(&config, date)
+    })?;
+    println!(
+        "Imported {} entries: {} new notes, {} existing notes added to, {} entries already there",
+        entries.len() - summary.skipped,
+        summary.created,
+        summary.merged,
+        summary.skipped
+    );
+    Ok(())
+}
+
+// river publish [-o dir] - notes tagged #public (or listed in publish.txt)
+// as a static site with an RSS feed
+fn publish_command(args: &[String]) -> io::Result<()> {
+    let config = Config::load();
+    let dir = match args {
+        [] => PathBuf::from(&config.publish_dir),
+        [flag, dir] if flag == "-o" || flag == "--output" => PathBuf::from(config::expand_tilde(dir)),
+        _ => {
+            eprintln!("Usage: river publish [-o dir]");
+            std::process::exit(1);
         }
-        // Always keep should_show_prompt in sync with should_display_prompt
-        self.should_show_prompt = self.should_display_prompt();
-        
-        self.dirty = true;
-        Ok(())
+    };
+
+    let listed = publish::manifest();
+    // scan_notes lists the newest first, which is the order the site wants
+    let posts: Vec<publish::Post> = finder::scan_notes(Path::new(&config.daily_notes_dir))
+        .into_iter()
+        .filter_map(|entry| {
+            let text = crypto::read_to_string(&entry.path).ok()?;
+            if !listed.contains(&entry.name) && !publish::is_tagged(&text) {
+                return None;
+            }
+            Some(publish::post(&entry.name, &text, entry.title.as_deref()))
+        })
+        .collect();
+
+    if posts.is_empty() {
+        println!("Nothing to publish: tag notes with #public or list them in {}", publish::manifest_path().display());
+        return Ok(());
     }
+    publish::write_site(&dir, &config.site_title, &config.site_url, &posts)?;
+    println!("Published {} notes to {}", posts.len(), dir.display());
+    Ok(())
 }
 
-// Helper function to count words in a markdown file
-fn count_words_in_file(path: &Path) -> io::Result<usize> {
-    let content = fs::read_to_string(path)?;
-    let mut word_count = 0;
-    let mut in_word = false;
-    
-    for ch in content.chars() {
-        if ch.is_alphanumeric() {
-            if !in_word {
-                word_count += 1;
-                in_word = true;
+// river export <pdf|dayone|jrnl> [range] [-o file] - the notes in another
+// format: a printable PDF, or a file Day One or jrnl can import
+fn export_command(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: river export <pdf|dayone|jrnl> [week|month|year|YYYY|YYYY-MM|DATE|DATE..DATE] [-o file]";
+    let Some(format) = args.first().filter(|format| ["pdf", "dayone", "jrnl"].contains(&format.as_str())) else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+
+    // Everything after the format: an optional range and -o/--output
+    let mut range = None;
+    let mut output = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = rest.next().cloned(),
+            _ if range.is_none() => range = Some(arg.as_str()),
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
             }
-        } else {
-            in_word = false;
         }
     }
-    
-    Ok(word_count)
-}
+    let Some((from, to)) = parse_date_range(range) else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
 
-// Standalone function (not a method) - no self parameter
-fn show_stats() -> io::Result<()> {
     let config = Config::load();
-    // Path::new creates a Path from a string reference
-    let stats_dir = Path::new(&config.daily_notes_dir);
-    
-    // Collect stats data
-    // 'mut' makes variables mutable (variables are immutable by default)
-    // _ prefix indicates unused variable (suppresses warning)
-    let mut _total_typing_seconds = 0u64; // u64 literal
-    let mut total_files = 0;
-    // Type annotation with turbofish ::<> syntax
-    // Now storing date, typing_seconds, and word_count
-    let mut daily_stats: Vec<(String, u64, u64)> = Vec::new(); // Tuple in Vec
-    let mut consecutive_days = 0;
-    let today = Local::now();
-    let mut streak_broken = false;
-    
-    // Check last 30 days for streak and collect data
-    // Range 0..30 creates an iterator from 0 to 29 (exclusive end)
-    for days_ago in 0..30 {
-        let date = today - chrono::Duration::days(days_ago);
-        let date_str = date.format("%Y-%m-%d").to_string();
-        let stats_file = stats_dir.join(format!(".stats-{}.toml", date_str));
-        let note_file = stats_dir.join(format!("{}.md", date_str));
-        
-        // Check for streak (only if not already broken)
-        if !streak_broken {
-            if stats_file.exists() {
-                if let Ok(contents) = fs::read_to_string(&stats_file) {
-                    if let Ok(stats) = toml::from_str::<DailyStats>(&contents) {
-                        if stats.typing_seconds > 0 && days_ago == consecutive_days as i64 {
-                            consecutive_days += 1;
-                        } else if days_ago == consecutive_days as i64 {
-                            // Expected day in streak has no typing data
-                            streak_broken = true;
-                        }
-                    } else if days_ago == consecutive_days as i64 {
-                        // Can't parse stats for expected day in streak
-                        streak_broken = true;
-                    }
-                } else if days_ago == consecutive_days as i64 {
-                    // Can't read file for expected day in streak
-                    streak_broken = true;
+    let notes = notes_in_range(&config, from, to);
+    if notes.is_empty() {
+        println!("No notes between {} and {}", from, to);
+        return Ok(());
+    }
+    let extension = match format.as_str() {
+        "pdf" => "pdf",
+        "dayone" => "zip",
+        _ => "txt",
+    };
+    let path = output.unwrap_or_else(|| format!("journal-{}-to-{}.{}", from, to, extension));
+
+    match format.as_str() {
+        "pdf" => {
+            let setup = match pdf::PageSetup::new(
+                &config.pdf_page_size,
+                config.pdf_margin_mm,
+                &config.pdf_font,
+                config.pdf_font_size,
+            ) {
+                Ok(setup) => setup,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
-            } else if days_ago == consecutive_days as i64 {
-                // No stats file for expected day in streak
-                streak_broken = true;
-            }
+            };
+            // Notes without a heading of their own get the date as one
+            let entries: Vec<(String, String)> = notes
+                .into_iter()
+                .map(|(date, content)| (date.format("%A, %B %-d, %Y").to_string(), content))
+                .collect();
+            let title = format!("Journal {} to {}", from, to);
+            let (bytes, pages) = pdf::render(&title, &entries, setup);
+            fs::write(&path, bytes)?;
+            println!("Wrote {} ({} notes, {} pages)", path, entries.len(), pages);
         }
-        
-        // Collect stats data (regardless of streak status)
-        if stats_file.exists() {
-            if let Ok(contents) = fs::read_to_string(&stats_file) {
-                if let Ok(mut stats) = toml::from_str::<DailyStats>(&contents) {
-                    if stats.typing_seconds > 0 {
-                        // If word_count is 0 (historical data), try to get it from the note file
-                        if stats.word_count == 0 && note_file.exists() {
-                            if let Ok(word_count) = count_words_in_file(&note_file) {
-                                stats.word_count = word_count as u64;
-                            }
-                        }
-                        daily_stats.push((date_str.clone(), stats.typing_seconds, stats.word_count));
-                        _total_typing_seconds += stats.typing_seconds;
-                    }
+        "dayone" => {
+            let days: Vec<(Vec<import::Entry>, export::DayStats)> = notes
+                .iter()
+                .map(|(date, content)| {
+                    let stats = crypto::read_to_string(stats_file_for(&config, *date))
+                        .ok()
+                        .and_then(|contents| toml::from_str::<DailyStats>(&contents).ok())
+                        .unwrap_or_default();
+                    let stats = export::DayStats { typing_seconds: stats.typing_seconds, word_count: stats.word_count };
+                    (export::entries_from_note(*date, content), stats)
+                })
+                .collect();
+            let bytes = match export::dayone_zip(&days) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    eprintln!("Couldn't build the Day One export: {}", e);
+                    std::process::exit(1);
                 }
-            }
+            };
+            fs::write(&path, bytes)?;
+            let count: usize = days.iter().map(|(entries, _)| entries.len()).sum();
+            println!("Wrote {} ({} entries from {} notes)", path, count, days.len());
         }
-        
-        if note_file.exists() {
-            total_files += 1;
-        }
-    }
-    
-    // Calculate weekly average (last 7 days)
-    // Iterator adapter chain - common Rust pattern
-    let weekly_typing: u64 = daily_stats.iter()
-        .take(7)                    // Take first 7 elements
-        .map(|(_, secs, _)| secs)   // Destructure tuple, ignore first and third elements
-        .sum();                     // Sum all values (requires type annotation)
-    let weekly_avg = weekly_typing / 7;
-    
-    // Clear screen and display stats
-    execute!(
-        io::stdout(),
-        EnterAlternateScreen,
-        Clear(ClearType::All),
-        Hide
-    )?;
-    
-    let mut stdout = io::stdout();
-    
-    // Header
-    execute!(
-        stdout,
-        MoveTo(2, 1),
-        SetForegroundColor(Color::Cyan),
-        Print("River Writing Statistics"),
-        ResetColor
-    )?;
-    
-    // Today's stats
-    let today_str = today.format("%Y-%m-%d").to_string();
-    let today_typing = daily_stats.iter()
-        .find(|(date, _, _)| date == &today_str)
-        .map(|(_, secs, _)| *secs)
-        .unwrap_or(0);
-    
-    execute!(
-        stdout,
-        MoveTo(2, 3),
-        Print("Today:"),
-        MoveTo(20, 3),
-        SetForegroundColor(Color::Green),
-        Print(format!("{} min", today_typing / 60)),
-        ResetColor
-    )?;
-    
-    // Streak
-    execute!(
-        stdout,
-        MoveTo(2, 4),
-        Print("Current Streak:"),
-        MoveTo(20, 4),
-        SetForegroundColor(if consecutive_days > 0 { Color::Yellow } else { Color::DarkGrey }),
-        Print(format!("{} days", consecutive_days)),
-        ResetColor
-    )?;
-    
-    // Weekly average
-    execute!(
-        stdout,
-        MoveTo(2, 5),
-        Print("Weekly Average:"),
-        MoveTo(20, 5),
-        SetForegroundColor(Color::Blue),
-        Print(format!("{} min/day", weekly_avg / 60)),
-        ResetColor
-    )?;
-    
-    // Total files
-    execute!(
-        stdout,
-        MoveTo(2, 6),
-        Print("Total Notes:"),
-        MoveTo(20, 6),
-        SetForegroundColor(Color::Magenta),
-        Print(format!("{}", total_files)),
-        ResetColor
-    )?;
-    
-    // Last 7 days chart
-    execute!(
-        stdout,
-        MoveTo(2, 8),
-        SetForegroundColor(Color::Cyan),
-        Print("Last 7 Days:"),
-        ResetColor
-    )?;
-    
-    // Create a map of date strings to (typing_seconds, word_count) for quick lookup
-    let stats_map: std::collections::HashMap<String, (u64, u64)> = daily_stats.iter()
-        .map(|(date, secs, words)| (date.clone(), (*secs, *words)))
-        .collect();
-    
-    // Find max minutes for scaling (only from days that have data)
-    let max_mins = stats_map.values()
-        .map(|(secs, _)| secs / 60)
-        .max()
-        .unwrap_or(1)
-        .max(1);
-    
-    // Display all 7 days, including those without data
-    for i in 0..7 {
-        let date = today - chrono::Duration::days(i as i64);
-        let date_str = date.format("%Y-%m-%d").to_string();
-        let day_str = date.format("%a").to_string();
-        
-        // Get typing minutes and words for this day (0 if no data)
-        let (mins, words) = stats_map.get(&date_str)
-            .map(|(secs, words)| (secs / 60, *words))
-            .unwrap_or((0, 0));
-        
-        let bar_width = if mins > 0 && max_mins > 0 { 
-            (mins * 20 / max_mins).min(20)  // Reduced to 20 to make room for text
-        } else { 
-            0 
-        };
-        
-        execute!(
-            stdout,
-            MoveTo(2, 10 + i as u16),
-            Print(format!("{:>3}", day_str)),
-            MoveTo(6, 10 + i as u16),
-        )?;
-        
-        if mins > 0 {
-            // Green bars for days with typing data
-            execute!(
-                stdout,
-                SetForegroundColor(Color::Green),
-                Print("█".repeat(bar_width as usize)),
-                SetForegroundColor(Color::DarkGrey),
-                Print("░".repeat((20 - bar_width) as usize)),
-                ResetColor
-            )?;
-        } else {
-            // Red indicator for days with no typing data
-            execute!(
-                stdout,
-                SetForegroundColor(Color::Red),
-                Print("▬"),
-                SetForegroundColor(Color::DarkGrey),
-                Print("░".repeat(19)),
-                ResetColor
-            )?;
+        _ => {
+            let entries: Vec<import::Entry> = notes
+                .iter()
+                .flat_map(|(date, content)| export::entries_from_note(*date, content))
+                .collect();
+            fs::write(&path, export::jrnl_text(&entries))?;
+            println!("Wrote {} ({} entries from {} notes)", path, entries.len(), notes.len());
         }
-        
-        // Display both time and words in a compact format
-        execute!(
-            stdout,
-            MoveTo(28, 10 + i as u16),
-            SetForegroundColor(Color::Cyan),
-            Print(format!("{:>3} min", mins)),
-            SetForegroundColor(Color::DarkGrey),
-            Print(" │ "),
-            SetForegroundColor(Color::Magenta),
-            Print(format!("{:>4} words", words)),
-            ResetColor
-        )?;
     }
-    
-    // Footer
-    execute!(
-        stdout,
-        MoveTo(2, 20),
-        SetForegroundColor(Color::DarkGrey),
-        Print("Press any key to exit"),
-        ResetColor
-    )?;
-    
-    stdout.flush()?;
-    
-    // Wait for key press
-    event::read()?;
-    
-    // Clean up
-    execute!(
-        stdout,
-        Show,
-        LeaveAlternateScreen
-    )?;
-    
     Ok(())
 }
 
-fn get_daily_note_path(config: &Config) -> io::Result<PathBuf> {
-    let today = Local::now();
-    let date_str = today.format("%Y-%m-%d").to_string();
-    let filename = format!("{}.md", date_str);
-    
-    let notes_dir = Path::new(&config.daily_notes_dir);
-    
-    // Create directory if it doesn't exist
-    if !notes_dir.exists() {
-        fs::create_dir_all(&notes_dir)?;
+// Today's note, created from the template (or just a date heading) if
+// it doesn't exist yet. note_created hook scripts can add to it.
+fn create_daily_note(config: &Config) -> io::Result<PathBuf> {
+    let path = get_daily_note_path(config)?;
+    if !path.exists() {
+        let today = Local::now().date_naive();
+        let mut content = if is_review_day(config, today) {
+            create_review_note_content(config, today)
+        } else {
+            create_daily_note_content(config, today)
+        };
+        // Today's note matters more than yesterday's tasks, so a problem
+        // with those is only reported
+        let mut carried = None;
+        if config.carry_over_todos {
+            match carry_over_todos(config, today, &mut content) {
+                Ok(previous) => carried = previous,
+                Err(e) => eprintln!("Couldn't carry over the open tasks: {}", e),
+            }
+        }
+        let scripts = hooks::scripts("note_created");
+        if !scripts.is_empty() {
+            let info = hooks::HookInfo {
+                event: "note_created",
+                date: today.format("%Y-%m-%d").to_string(),
+                file: path.to_string_lossy().to_string(),
+                words: 0,
+                goal: config.daily_word_goal,
+                minutes: 0,
+            };
+            let (printed, error) = hooks::run_scripts(&scripts, &info);
+            if let Some(e) = error {
+                eprintln!("{}", e);
+            }
+            content.push_str(&printed);
+        }
+        // Templates and hook scripts may end lines either way; the note
+        // gets the line endings a new file should have (see buffer.rs)
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        let endings = LineEndings { final_newline: content.ends_with('\n'), ..LineEndings::default() };
+        crypto::write(&path, endings.with_overrides(&config.line_endings, &config.final_newline).join(&lines))?;
+        // Only now are the tasks in today's note, so they can be marked
+        // as moved in the one they came from
+        if let Some((previous, text)) = carried {
+            if let Err(e) = crypto::write(&previous, text) {
+                eprintln!("Couldn't mark the carried tasks in {}: {}", previous.display(), e);
+            }
+        }
     }
-    
-    Ok(notes_dir.join(filename))
+    Ok(path)
 }
 
-fn create_daily_note_content() -> String {
-    let today = Local::now();
-    let date_str = today.format("%A, %B %d, %Y").to_string();
-    format!("# {}\n\n", date_str)
+// A point in time for :diff: "2024-05-01 14:00", "2024-05-01" (the end of
+// that day) or "14:00" (today)
+fn parse_timestamp(text: &str, today: NaiveDate) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    if let Ok(time) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M") {
+        return Some(time);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return date.and_hms_opt(23, 59, 59);
+    }
+    NaiveTime::parse_from_str(text, "%H:%M").ok().map(|time| today.and_time(time))
+}
+
+// review_days = ["sunday"]: full names or the first three letters
+fn is_review_day(config: &Config, date: NaiveDate) -> bool {
+    let weekday = date.format("%A").to_string().to_lowercase();
+    config.review_days.iter().any(|day| {
+        let day = day.trim().to_lowercase();
+        day.len() >= 3 && weekday.starts_with(&day)
+    })
+}
+
+// The weekly review: review_template (or a plain outline) with the last
+// seven days' numbers filled in
+fn create_review_note_content(config: &Config, date: NaiveDate) -> String {
+    const OUTLINE: &str = "# {{date}}\n\n\
+        ## Week in review ({{week_start}} - {{week_end}})\n\n\
+        - Words: {{week_words}}\n\
+        - Minutes writing: {{week_minutes}}\n\
+        - Days written: {{days_written}} of 7\n\
+        - Goal met: {{goal_days}} of 7\n\n\
+        ## What went well\n\n\
+        ## What to change\n\n";
+    // Like daily_note_template, a template that can't be read falls back
+    let template = match fs::read_to_string(&config.review_template) {
+        Ok(template) if !config.review_template.is_empty() => template,
+        _ => OUTLINE.to_string(),
+    };
+    let week = stats::week_totals(config, date);
+    template
+        .replace("{{date}}", &date.format("%A, %B %d, %Y").to_string())
+        .replace("{{iso_date}}", &date.format("%Y-%m-%d").to_string())
+        .replace("{{week_start}}", &week.first.format("%b %d").to_string())
+        .replace("{{week_end}}", &week.last.format("%b %d").to_string())
+        .replace("{{week_words}}", &week.words.to_string())
+        .replace("{{week_minutes}}", &week.minutes.to_string())
+        .replace("{{days_written}}", &week.days_written.to_string())
+        .replace("{{goal_days}}", &week.goal_days.to_string())
+}
+
+// Move the open tasks of the last note before 'today' into 'content'
+// (see carry.rs). Returns that note's path and its text with the tasks
+// marked as moved, to write once 'content' is safely saved.
+fn carry_over_todos(config: &Config, today: NaiveDate, content: &mut String) -> io::Result<Option<(PathBuf, String)>> {
+    // Not necessarily yesterday's: the last day anything was written
+    let previous = finder::scan_notes(Path::new(&config.daily_notes_dir))
+        .into_iter()
+        .find(|note| NaiveDate::parse_from_str(&note.name, "%Y-%m-%d").is_ok_and(|date| date < today));
+    let Some(previous) = previous else {
+        return Ok(None);
+    };
+    let Some(carried) = carry::carry_over(&crypto::read_to_string(&previous.path)?) else {
+        return Ok(None);
+    };
+    if !content.is_empty() && !content.ends_with("\n\n") {
+        content.push_str(if content.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    content.push_str(&carried.section);
+    Ok(Some((previous.path, carried.previous)))
+}
+
+fn create_daily_note_content(config: &Config, date: NaiveDate) -> String {
+    let date_str = date.format("%A, %B %d, %Y").to_string();
+    // A template that can't be read falls back to the plain heading
+    match fs::read_to_string(&config.daily_note_template) {
+        Ok(template) if !config.daily_note_template.is_empty() => template
+            .replace("{{date}}", &date_str)
+            .replace("{{iso_date}}", &date.format("%Y-%m-%d").to_string()),
+        _ => format!("# {}\n\n", date_str),
+    }
 }
 
 // Entry point of the program
 // main can return Result for error propagation
 fn main() -> io::Result<()> {
     // collect() transforms an iterator into a collection
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    
+    // Settings for this run only, over config.toml and RIVER_* variables.
+    // Like --profile they can go anywhere.
+    for (flag, key) in [("--notes-dir", "daily_notes_dir"), ("--goal", "daily_word_goal"), ("--set", "")] {
+        while let Some(i) = args.iter().position(|arg| arg == flag) {
+            let Some(value) = args.get(i + 1).cloned() else {
+                eprintln!("Usage: river {} <{}>", flag, if key.is_empty() { "setting=value" } else { "value" });
+                std::process::exit(1);
+            };
+            args.drain(i..=i + 1);
+            // --set names the setting itself
+            let (key, value) = match value.split_once('=') {
+                Some((set, value)) if key.is_empty() => (set.trim().to_string(), value.to_string()),
+                _ => (key.to_string(), value),
+            };
+            if key.is_empty() {
+                eprintln!("Usage: river --set <setting>=<value>");
+                std::process::exit(1);
+            }
+            Config::set_flag(flag, &key, &value);
+        }
+    }
+    for (flag, vim) in [("--vim", "true"), ("--no-vim", "false")] {
+        if let Some(i) = args.iter().position(|arg| arg == flag) {
+            args.remove(i);
+            Config::set_flag(flag, "vim_bindings", vim);
+        }
+    }
+    
+    // --profile <name> can go anywhere and applies to everything below
+    if let Some(i) = args.iter().position(|arg| arg == "--profile") {
+        let Some(name) = args.get(i + 1).cloned() else {
+            eprintln!("Usage: river --profile <name> [...]");
+            std::process::exit(1);
+        };
+        // drain removes a range of elements from the Vec
+        args.drain(i..=i + 1);
+        let profiles = Config::load().profiles;
+        if !profiles.contains_key(&name) {
+            let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            eprintln!("No profile '{}' in config.toml (profiles: {})", name, names.join(", "));
+            std::process::exit(1);
+        }
+        Config::select_profile(Some(name));
+    }
+    
+    // A config.toml with mistakes in it would run with settings nobody
+    // chose (notes going to the default folder, say), so nothing starts
+    // until it's fixed - except the commands for fixing it
+    if args.get(1).map(String::as_str) != Some("config") {
+        let problems = Config::check();
+        if !problems.is_empty() {
+            eprintln!("River can't start with these settings:");
+            for problem in &problems {
+                eprintln!("  {}", problem);
+            }
+            eprintln!("config.toml is {} - river config edit opens it", Config::config_path().display());
+            std::process::exit(1);
+        }
+    }
+    
+    // river encrypt / river decrypt - convert the whole journal at once
+    if args.len() > 1 && (args[1] == "encrypt" || args[1] == "decrypt") {
+        return convert_notes(args[1] == "encrypt");
+    }
+    
+    // river config path|check|set - everything but edit, which needs the editor.
+    // None of them read notes, so there's no passphrase to ask for
+    if args.len() > 1 && args[1] == "config" && args.get(2).map(String::as_str) != Some("edit") {
+        return config_command(&args[2..]);
+    }
+    
+    // With encrypt = true every note and stats file goes through the key,
+    // so it's unlocked before anything reads one
+    if Config::load().encrypt {
+        unlock_notes()?;
+    }
+    // Messages, the status bar and river stats in the language asked for
+    i18n::use_language(&Config::load());
     
     // Check for --stats flag
     // Array indexing with [] - will panic if out of bounds
-    if args.len() > 1 && args[1] == "--stats" {
-        show_stats()?;
+    if args.len() > 1 && (args[1] == "--stats" || args[1] == "stats") {
+        stats::show()?;
         return Ok(()); // Early return with unit value
     }
     
-    // Check for --generate-prompts flag
+    // Check for --generate-prompts flag (same as "river prompts generate")
     if args.len() > 1 && args[1] == "--generate-prompts" {
         generate_ai_prompts()?;
         return Ok(());
     }
     
+    // river prompts <generate|show|regenerate-today|clear-cache>
+    if args.len() > 1 && args[1] == "prompts" {
+        return prompts_command(&args[2..]);
+    }
+    
+    // river list - notes with their titles
+    if args.len() > 1 && args[1] == "list" {
+        list_notes();
+        return Ok(());
+    }
+    
+    // river remind [--at HH:MM] - nudge if today's goal isn't met
+    if args.len() > 1 && args[1] == "remind" {
+        return remind_command(&args[2..]);
+    }
+    
+    // river summarize --week - AI review of the last seven days
+    if args.len() > 1 && args[1] == "summarize" {
+        return summarize_command(&args[2..]);
+    }
+    
+    // river analyze [range] - word frequency report
+    if args.len() > 1 && args[1] == "analyze" {
+        return analyze_notes(args.get(2).map(String::as_str));
+    }
+    
+    // river compile 2024-05 - a month's notes as one markdown document
+    if args.len() > 1 && args[1] == "compile" {
+        return compile_command(&args[2..]);
+    }
+    
+    // river import dayone|jrnl <file> - entries from another journal app
+    if args.len() > 1 && args[1] == "import" {
+        return import_command(&args[2..]);
+    }
+    
+    // river publish - tagged notes as a static site
+    if args.len() > 1 && args[1] == "publish" {
+        return publish_command(&args[2..]);
+    }
+    
+    // river export pdf|dayone|jrnl [range] - the notes in another format
+    if args.len() > 1 && args[1] == "export" {
+        return export_command(&args[2..]);
+    }
+    
+    // river archive --before <date> [--zip] - old notes into archive/
+    if args.len() > 1 && args[1] == "archive" {
+        return archive_command(&args[2..]);
+    }
+    
     let mut editor = Editor::new()?;
     
-    if args.len() > 1 {
+    // river view <date|path> - open a note read-only
+    if args.len() > 1 && args[1] == "view" {
+        let Some(target) = args.get(2) else {
+            eprintln!("Usage: river view <date|path>");
+            std::process::exit(1);
+        };
+        let path = resolve_note_path(&editor.config, target);
+        if !path.exists() {
+            eprintln!("No such note: {}", path.display());
+            std::process::exit(1);
+        }
+        editor.read_only = true;
+        editor.load_file(&path.to_string_lossy())?;
+    } else if args.len() > 1 && args[1] == "tutor" {
+        // river tutor - a fresh copy of the lessons every time
+        let path = tutor::write(&editor.config)?;
+        editor.tutor = true;
+        editor.load_file(&path.to_string_lossy())?;
+    } else if args.len() > 1 && args[1] == "config" {
+        // river config edit - saving it reloads the settings straight away
+        editor.load_file(&Config::config_path().to_string_lossy())?;
+    } else if args.len() > 1 {
         // If a file is specified, open it
         editor.load_file(&args[1])?;
     } else {
         // Otherwise, open today's daily note
-        let daily_note_path = get_daily_note_path(&editor.config)?;
-        
-        if !daily_note_path.exists() {
-            // Create new daily note with date header
-            let content = create_daily_note_content();
-            fs::write(&daily_note_path, &content)?;
-        }
-        
+        let daily_note_path = create_daily_note(&editor.config)?;
         editor.load_file(&daily_note_path.to_string_lossy())?;
     }
     
@@ -1499,6 +6343,251 @@ fn main() -> io::Result<()> {
     editor.run()
 }
 
+// Ask for the passphrase and unlock the encryption key. A new key needs
+// the passphrase typed twice; a wrong one gets three tries.
+fn unlock_notes() -> io::Result<()> {
+    if !crypto::has_identity() {
+        println!("Notes will be encrypted. Choose a passphrase - without it they can't be read.");
+        let passphrase = crypto::ask_passphrase("New passphrase: ")?;
+        if passphrase.is_empty() {
+            eprintln!("The passphrase can't be empty");
+            std::process::exit(1);
+        }
+        if std::env::var("RIVER_PASSPHRASE").is_err() && crypto::ask_passphrase("Again: ")? != passphrase {
+            eprintln!("The passphrases don't match");
+            std::process::exit(1);
+        }
+        return crypto::unlock(&passphrase).map_err(io::Error::other);
+    }
+    for _ in 0..3 {
+        let passphrase = crypto::ask_passphrase("Passphrase: ")?;
+        match crypto::unlock(&passphrase) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                // Asking again won't change the environment variable
+                if std::env::var("RIVER_PASSPHRASE").is_ok() {
+                    break;
+                }
+            }
+        }
+    }
+    std::process::exit(1);
+}
+
+// Rewrite every note and stats file encrypted (or back as plain text).
+// Files already in the wanted form are left alone.
+fn convert_notes(encrypt: bool) -> io::Result<()> {
+    let config = Config::load();
+    unlock_notes()?;
+    let mut converted = 0;
+    for entry in fs::read_dir(&config.daily_notes_dir)?.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_journal_file = name.ends_with(".md") || (name.starts_with(".stats-") && name.ends_with(".toml"));
+        if !is_journal_file || !path.is_file() {
+            continue;
+        }
+        if crypto::is_encrypted(&fs::read(&path)?) == encrypt {
+            continue;
+        }
+        let contents = crypto::read_to_string(&path)?;
+        if encrypt {
+            crypto::write(&path, contents)?;
+        } else {
+            fs::write(&path, contents)?;
+        }
+        converted += 1;
+    }
+    println!("{} {} file{}", if encrypt { "Encrypted" } else { "Decrypted" }, converted, if converted == 1 { "" } else { "s" });
+    if encrypt != config.encrypt {
+        println!("Set encrypt = {} in config.toml to match", encrypt);
+    }
+    Ok(())
+}
+
+// river config <path|edit|check|set <key> <value>>
+fn config_command(args: &[String]) -> io::Result<()> {
+    match args.first().map(String::as_str) {
+        Some("path") => println!("{}", Config::config_path().display()),
+        Some("check") => {
+            let problems = Config::check();
+            if problems.is_empty() {
+                println!("No problems in {}", Config::config_path().display());
+            } else {
+                for problem in &problems {
+                    println!("{}", problem);
+                }
+                std::process::exit(1);
+            }
+        }
+        Some("set") if args.len() >= 3 => {
+            // Anything after the key is the value, so lists needn't be quoted
+            let value = args[2..].join(" ");
+            if let Err(e) = Config::set_in_file(&args[1], &value) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("Usage: river config <path|edit|check|set <key> <value>>");
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+// river prompts <generate|show [date]|regenerate-today|clear-cache>
+fn prompts_command(args: &[String]) -> io::Result<()> {
+    let config = Config::load();
+    // map(String::as_str) turns Option<&String> into Option<&str> so it can be matched
+    match args.first().map(String::as_str) {
+        Some("generate") => generate_ai_prompts(),
+        Some("show") => {
+            let date = match args.get(1) {
+                Some(arg) => parse_date(arg),
+                None => Some(Local::now().date_naive()),
+            };
+            let Some(date) = date else {
+                eprintln!("Usage: river prompts show [today|yesterday|YYYY-MM-DD]");
+                std::process::exit(1);
+            };
+            match ai::cached_prompt(&config, &date) {
+                Some(prompt) => {
+                    println!("{}", prompt.prompt);
+                    println!("Theme: {}", prompt.theme);
+                    if let Some(context) = prompt.context {
+                        println!("Context: {}", context);
+                    }
+                }
+                None => {
+                    println!("{}", prompts::for_date(&date));
+                    println!("(from the prompt library - no AI prompt is cached for {})", date);
+                }
+            }
+            Ok(())
+        }
+        Some("regenerate-today") => {
+            let result = ai::PromptGenerator::new(&config).and_then(|generator| generator.regenerate_today());
+            match result {
+                Ok(prompt) => println!("Today's prompt: {}", prompt.prompt),
+                Err(e) => {
+                    eprintln!("Error generating prompt: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Ok(())
+        }
+        Some("clear-cache") => {
+            if ai::clear_cache(&config)? {
+                println!("Prompt cache cleared");
+            } else {
+                println!("No prompt cache to clear");
+            }
+            Ok(())
+        }
+        _ => {
+            eprintln!("Usage: river prompts <generate|show [date]|regenerate-today|clear-cache>");
+            std::process::exit(1);
+        }
+    }
+}
+
+// river remind checks today's note once and sends a desktop notification
+// if the goal hasn't been met. With --at 21:00 it keeps running and checks
+// at that time every day, e.g. started from a login script.
+fn remind_command(args: &[String]) -> io::Result<()> {
+    let at = match args {
+        [] => None,
+        [flag, time] if flag == "--at" => match remind::parse_time(time) {
+            Some(time) => Some(time),
+            None => {
+                eprintln!("Couldn't read the time {:?}; use HH:MM, e.g. 21:00", time);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("Usage: river remind [--at HH:MM]");
+            std::process::exit(1);
+        }
+    };
+    
+    let Some(at) = at else {
+        remind_if_behind(&Config::load())?;
+        return Ok(());
+    };
+    
+    println!("Reminding at {} each day if the goal isn't met (Ctrl+C to stop)", at.format("%H:%M"));
+    loop {
+        let next = remind::next_occurrence(at, Local::now());
+        // to_std fails for negative durations, which just means "now"
+        std::thread::sleep((next - Local::now()).to_std().unwrap_or_default());
+        // Loaded each time so config changes are picked up
+        remind_if_behind(&Config::load())?;
+    }
+}
+
+fn remind_if_behind(config: &Config) -> io::Result<()> {
+    let path = get_daily_note_path(config)?;
+    let (words, goal) = match crypto::read_to_string(&path) {
+        Ok(content) => {
+            let lines: Vec<Vec<char>> = content.lines().map(|line| line.chars().collect()).collect();
+            (text_stats::count_words(&content, config.markdown_word_count), note_goal(&lines, config))
+        }
+        // No note yet today
+        Err(_) => (0, config.daily_word_goal),
+    };
+    if words >= goal {
+        return Ok(());
+    }
+    
+    let body = if words == 0 {
+        format!("Nothing written yet today - {} words to go", goal)
+    } else {
+        format!("{} of {} words so far today", words, goal)
+    };
+    if !remind::notify("River", &body) {
+        // No notification service, so the terminal will have to do
+        println!("\x07River: {}", body);
+    }
+    Ok(())
+}
+
+// Every note, newest first, with its :title (or its heading if it has none)
+fn list_notes() {
+    let config = Config::load();
+    for entry in finder::scan_notes(Path::new(&config.daily_notes_dir)) {
+        println!("{}  {}", entry.name, entry.label());
+    }
+}
+
+// river summarize --week: an AI review of the last seven days, saved as a
+// note of its own named after the ISO week (2024-W20-summary.md)
+fn summarize_command(args: &[String]) -> io::Result<()> {
+    if args.first().map(String::as_str) != Some("--week") {
+        eprintln!("Usage: river summarize --week");
+        std::process::exit(1);
+    }
+    
+    let config = Config::load();
+    let summary = match ai::PromptGenerator::new(&config).and_then(|generator| generator.summarize_week()) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("Error summarizing the week: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    let week = Local::now().date_naive().iso_week();
+    let name = format!("{}-W{:02}", week.year(), week.week());
+    let notes_dir = PathBuf::from(&config.daily_notes_dir);
+    fs::create_dir_all(&notes_dir)?;
+    let path = notes_dir.join(format!("{}-summary.md", name));
+    crypto::write(&path, format!("# Week {} summary\n\n{}\n", name, summary))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
 // Function to generate AI prompts using the AI module
 fn generate_ai_prompts() -> io::Result<()> {
     let config = Config::load();
@@ -0,0 +1,213 @@
+// Markdown helpers for the editor: recognising list items so Enter can
+// continue a list, and flipping task checkboxes.
+//
+// Lines are Vec<char> like the rest of the editor, so positions here are
+// character indices rather than byte offsets.
+
+use crate::front_matter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub indent: usize,        // Leading whitespace before the marker
+    pub marker: String,       // "- ", "* ", "3. ", ...
+    pub checkbox: bool,       // Followed by "[ ] " or "[x] "
+    pub content_start: usize, // Where the item's text begins
+}
+
+impl ListItem {
+    // The prefix for the item after this one: ordered lists count up and
+    // a new task always starts unchecked
+    pub fn next_prefix(&self, line: &[char]) -> Vec<char> {
+        let mut prefix: Vec<char> = line[..self.indent].to_vec();
+        let marker = self.marker.trim_end();
+        // Ordered markers are digits followed by '.' or ')'
+        let digits: String = marker.chars().take_while(|c| c.is_ascii_digit()).collect();
+        match digits.parse::<u64>() {
+            Ok(n) => prefix.extend(format!("{}{} ", n + 1, &marker[digits.len()..]).chars()),
+            Err(_) => prefix.extend(self.marker.chars()),
+        }
+        if self.checkbox {
+            prefix.extend("[ ] ".chars());
+        }
+        prefix
+    }
+}
+
+// Recognises "- ", "* ", "+ ", "1. " and "1) " items, with an optional
+// "[ ]"/"[x]" checkbox after the marker
+pub fn list_item(line: &[char]) -> Option<ListItem> {
+    let indent = line.iter().take_while(|c| **c == ' ' || **c == '\t').count();
+    let rest = &line[indent..];
+
+    let marker_len = match rest.first()? {
+        '-' | '*' | '+' => 1,
+        c if c.is_ascii_digit() => {
+            let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+            match rest.get(digits) {
+                Some('.' | ')') => digits + 1,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    // The marker must be followed by a space (or end the line, for an
+    // item that's still empty)
+    match rest.get(marker_len) {
+        Some(' ') | None => {}
+        _ => return None,
+    }
+
+    let marker: String = rest[..marker_len].iter().collect::<String>() + " ";
+    let mut content_start = (indent + marker_len + 1).min(line.len());
+    let checkbox = checkbox_at(line, content_start).is_some();
+    if checkbox {
+        content_start = (content_start + 4).min(line.len());
+    }
+    Some(ListItem { indent, marker, checkbox, content_start })
+}
+
+// Index of the 'x' / ' ' inside a "[ ] " checkbox starting at 'start'.
+// "[>]" is a task carried over to a later day (see carry.rs).
+fn checkbox_at(line: &[char], start: usize) -> Option<usize> {
+    match line.get(start..start + 3)? {
+        ['[', ' ' | 'x' | 'X' | '>', ']'] if matches!(line.get(start + 3), Some(' ') | None) => Some(start + 1),
+        _ => None,
+    }
+}
+
+// Where the ' ' of an unchecked task's "[ ]" is, if the line is one
+pub fn open_task(line: &[char]) -> Option<usize> {
+    let item = list_item(line)?;
+    let i = checkbox_at(line, item.indent + item.marker.chars().count())?;
+    (item.checkbox && line[i] == ' ').then_some(i)
+}
+
+// Flip "- [ ]" to "- [x]" and back. Returns false if the line has no checkbox.
+pub fn toggle_checkbox(line: &mut [char]) -> bool {
+    let Some(item) = list_item(line) else {
+        return false;
+    };
+    if !item.checkbox {
+        return false;
+    }
+    let Some(i) = checkbox_at(line, item.indent + item.marker.chars().count()) else {
+        return false;
+    };
+    line[i] = if line[i] == ' ' { 'x' } else { ' ' };
+    true
+}
+
+// The prose in a markdown document with the syntax taken out, for word
+// counting: front matter, heading/quote/list markers, checkboxes, code
+// fence lines, link and image URLs, bare URLs and HTML tags all go.
+pub fn strip_markup(text: &str) -> String {
+    let mut prose = String::new();
+    for line in front_matter::strip(text).lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            continue;
+        }
+        // Headings and block quotes
+        let mut rest = trimmed.trim_start_matches(['#', '>']).trim_start();
+        // List markers and checkboxes
+        let chars: Vec<char> = rest.chars().collect();
+        if let Some(item) = list_item(&chars) {
+            let skip: usize = chars[..item.content_start].iter().map(|c| c.len_utf8()).sum();
+            rest = &rest[skip..];
+        }
+        prose.push_str(&strip_inline(rest));
+        prose.push('\n');
+    }
+    prose
+}
+
+// Links keep their text but lose the URL, images keep their alt text,
+// and bare URLs and <tags> disappear
+fn strip_inline(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        if c == ']' && rest.starts_with("](") {
+            // End of link text: skip the (url)
+            match rest.find(')') {
+                Some(close) => rest = &rest[close + 1..],
+                None => rest = "",
+            }
+            continue;
+        }
+        // Only something that looks like a tag: <b>, </p>, <!-- ... -->
+        let tag_start = rest[c.len_utf8()..].starts_with(|n: char| n.is_ascii_alphabetic() || n == '/' || n == '!');
+        if c == '<' && tag_start {
+            if let Some(close) = rest.find('>') {
+                rest = &rest[close + 1..];
+                continue;
+            }
+        }
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = &rest[end..];
+            continue;
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// A ``` or ~~~ line opening or closing a fenced code block
+pub fn is_fence(line: &[char]) -> bool {
+    let trimmed: String = line.iter().collect::<String>().trim_start().chars().take(3).collect();
+    trimmed == "```" || trimmed == "~~~"
+}
+
+// Whether column x of line y is code: inside a ``` or ~~~ fenced block, or
+// an inline `code span` (an odd number of backticks before it on the line)
+pub fn in_code(lines: &[Vec<char>], y: usize, x: usize) -> bool {
+    let fences_above = lines[..y].iter().filter(|line| is_fence(line)).count();
+    if fences_above % 2 == 1 || is_fence(&lines[y]) {
+        return true;
+    }
+    let x = x.min(lines[y].len());
+    lines[y][..x].iter().filter(|c| **c == '`').count() % 2 == 1
+}
+
+// The level of a markdown heading ("## Notes" is 2), or None if the line
+// isn't one. The #s need a space (or nothing) after them: #tag isn't a
+// heading.
+pub fn heading_level(line: &[char]) -> Option<usize> {
+    let level = line.iter().take_while(|c| **c == '#').count();
+    let after = line.get(level);
+    if (1..=6).contains(&level) && after.is_none_or(|c| *c == ' ') {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+// Lines that are headings, with their level - leaving out "# comments"
+// in fenced code blocks
+pub fn headings(lines: &[Vec<char>]) -> Vec<(usize, usize)> {
+    let mut in_fence = false;
+    let mut headings = Vec::new();
+    for (y, line) in lines.iter().enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if let Some(level) = heading_level(line) {
+                headings.push((y, level));
+            }
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_tags_around_non_ascii_text() {
+        assert_eq!(strip_markup("- Café <em>très</em> bien, voilà\n"), "Café très bien, voilà\n");
+    }
+}
@@ -0,0 +1,100 @@
+// Mood and theme tracking: at the end of a session today's note gets a
+// mood on a five point scale plus a few themes, stored in the day's stats
+// and drawn as a timeline by river stats.
+//
+// The local classifier just weighs positive and negative words, which is
+// crude but needs no network; mood_tracking = "ai" asks the AI provider
+// instead.
+
+use crate::analyze;
+
+// Worst to best. Anything stored is one of these.
+pub const MOODS: [&str; 5] = ["rough", "low", "okay", "good", "great"];
+
+// How many themes to keep
+const THEMES: usize = 3;
+
+const POSITIVE: &[&str] = &[
+    "calm", "celebrate", "cheerful", "comfortable", "confident", "content", "delighted", "easy",
+    "energized", "enjoy", "enjoyed", "excited", "fantastic", "fun", "glad", "good", "grateful",
+    "great", "happy", "hopeful", "inspired", "joy", "laugh", "laughed", "love", "loved", "lovely",
+    "lucky", "nice", "peaceful", "proud", "relaxed", "relieved", "rested", "satisfied", "smile",
+    "smiled", "success", "thankful", "thrilled", "wonderful",
+];
+
+const NEGATIVE: &[&str] = &[
+    "afraid", "angry", "annoyed", "anxious", "awful", "bad", "bored", "broke", "cried", "cry",
+    "depressed", "difficult", "disappointed", "drained", "exhausted", "fail", "failed", "frustrated",
+    "guilty", "hard", "hate", "hurt", "lonely", "lost", "miserable", "nervous", "overwhelmed",
+    "pain", "regret", "sad", "scared", "sick", "stressed", "stuck", "terrible", "tired", "upset",
+    "worried", "worse", "worst",
+];
+
+// Words that flip the next sentiment word: "not happy", "never relaxed"
+const NEGATIONS: &[&str] = &["not", "never", "no", "hardly", "isn't", "wasn't", "don't", "didn't", "can't"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoodReading {
+    pub mood: String,
+    pub themes: Vec<String>,
+}
+
+// Classify without the network
+pub fn classify(text: &str) -> MoodReading {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+
+    let (mut positive, mut negative) = (0i32, 0i32);
+    for (i, word) in words.iter().enumerate() {
+        let negated = i > 0 && NEGATIONS.contains(&words[i - 1].as_str());
+        let sign = if POSITIVE.contains(&word.as_str()) {
+            1
+        } else if NEGATIVE.contains(&word.as_str()) {
+            -1
+        } else {
+            continue;
+        };
+        if (sign > 0) != negated {
+            positive += 1;
+        } else {
+            negative += 1;
+        }
+    }
+
+    MoodReading { mood: mood_for(positive, negative).to_string(), themes: themes(text) }
+}
+
+// The balance of positive to negative words, from -1.0 to 1.0, in five bands
+fn mood_for(positive: i32, negative: i32) -> &'static str {
+    if positive + negative == 0 {
+        return "okay";
+    }
+    let balance = (positive - negative) as f32 / (positive + negative) as f32;
+    match balance {
+        b if b >= 0.6 => "great",
+        b if b >= 0.2 => "good",
+        b if b > -0.2 => "okay",
+        b if b > -0.6 => "low",
+        _ => "rough",
+    }
+}
+
+// The most used words that aren't common ones, if they come up more than once
+pub fn themes(text: &str) -> Vec<String> {
+    let report = analyze::analyze(&[(String::new(), text.to_string())]);
+    report
+        .top
+        .into_iter()
+        .filter(|(word, count)| *count > 1 && word.chars().count() > 3)
+        .take(THEMES)
+        .map(|(word, _)| word)
+        .collect()
+}
+
+// Where a mood sits on the scale, 0 (rough) to 4 (great)
+pub fn rank(mood: &str) -> Option<usize> {
+    MOODS.iter().position(|m| *m == mood)
+}
@@ -0,0 +1,70 @@
+// Outline panel for the current note (:outline)
+//
+// Lists the note's markdown headings, indented by level. Typing filters
+// them with the same fuzzy matching as the note finder, and Enter jumps to
+// the selected one.
+
+use crate::finder::fuzzy_score;
+use crate::markdown;
+
+pub struct Heading {
+    pub line: usize,
+    pub level: usize,
+    pub text: String, // Without the #s
+}
+
+pub struct Outline {
+    pub query: String,
+    headings: Vec<Heading>,
+    pub matches: Vec<usize>, // Indices into headings, in note order
+    pub selected: usize,     // Index into matches
+}
+
+impl Outline {
+    // Starts on the heading of the section the cursor is in
+    pub fn new(lines: &[Vec<char>], cursor_y: usize) -> Self {
+        let headings: Vec<Heading> = markdown::headings(lines)
+            .into_iter()
+            .map(|(line, level)| Heading {
+                line,
+                level,
+                text: lines[line][level..].iter().collect::<String>().trim().to_string(),
+            })
+            .collect();
+        let selected = headings.iter().rposition(|heading| heading.line <= cursor_y).unwrap_or(0);
+        let mut outline = Outline { query: String::new(), headings, matches: Vec::new(), selected: 0 };
+        outline.update();
+        outline.selected = selected;
+        outline
+    }
+
+    // Re-filter after the query changes. Headings stay in note order - an
+    // outline sorted by score would be hard to read.
+    pub fn update(&mut self) {
+        self.matches = self.headings
+            .iter()
+            .enumerate()
+            .filter(|(_, heading)| fuzzy_score(&self.query, &heading.text).is_some())
+            .map(|(i, _)| i)
+            .collect();
+        self.selected = 0;
+    }
+
+    pub fn entry(&self, index: usize) -> Option<&Heading> {
+        self.matches.get(index).map(|&i| &self.headings[i])
+    }
+
+    pub fn selected_heading(&self) -> Option<&Heading> {
+        self.entry(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
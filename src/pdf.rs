@@ -0,0 +1,464 @@
+// Printable PDF of a run of daily notes (river export pdf)
+//
+// The text is set in one of the fonts every PDF reader has built in
+// (Times, Helvetica or Courier), so nothing needs embedding. Those fonts
+// only cover Western European characters; anything else prints as '?'.
+//
+// Layout is deliberately simple: each note gets a heading, paragraphs are
+// wrapped to the page width, list items hang under their bullet, code
+// blocks are set in Courier, and every page is numbered at the bottom.
+
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use crate::front_matter;
+use crate::markdown;
+
+// Points (1/72 inch) per millimetre
+const MM: f32 = 72.0 / 25.4;
+// Line height as a multiple of the font size
+const LEADING: f32 = 1.4;
+// Built-in fonts have no bold width tables here, so bold text is measured
+// as this much wider than regular - a little generous so lines never
+// overrun the margin
+const BOLD_WIDTH: f32 = 1.1;
+// Width of characters outside the tables, in thousandths of the font size
+const OTHER_WIDTH: u16 = 600;
+
+// Character widths of printable ASCII (' ' to '~'), in thousandths of the
+// font size, from the standard Adobe font metrics
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778,
+    722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278,
+    278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+const TIMES_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611, 556, 722,
+    722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333,
+    278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Font {
+    Times,
+    Helvetica,
+    Courier,
+}
+
+impl Font {
+    pub fn from_name(name: &str) -> Option<Font> {
+        match name.to_lowercase().as_str() {
+            "times" | "serif" => Some(Font::Times),
+            "helvetica" | "sans" | "sans-serif" => Some(Font::Helvetica),
+            "courier" | "mono" | "monospace" => Some(Font::Courier),
+            _ => None,
+        }
+    }
+
+    // PostScript names of the regular and bold faces
+    fn base_names(self) -> (&'static [u8], &'static [u8]) {
+        match self {
+            Font::Times => (b"Times-Roman", b"Times-Bold"),
+            Font::Helvetica => (b"Helvetica", b"Helvetica-Bold"),
+            Font::Courier => (b"Courier", b"Courier-Bold"),
+        }
+    }
+
+    fn char_width(self, c: char) -> u16 {
+        let table = match self {
+            Font::Times => &TIMES_WIDTHS,
+            Font::Helvetica => &HELVETICA_WIDTHS,
+            // Every Courier character is the same width
+            Font::Courier => return 600,
+        };
+        match c {
+            ' '..='~' => table[c as usize - ' ' as usize],
+            _ => OTHER_WIDTH,
+        }
+    }
+}
+
+// Width and height in points of a named paper size
+pub fn page_size(name: &str) -> Option<(f32, f32)> {
+    match name.to_lowercase().as_str() {
+        "a4" => Some((210.0 * MM, 297.0 * MM)),
+        "a5" => Some((148.0 * MM, 210.0 * MM)),
+        "letter" => Some((612.0, 792.0)),
+        "legal" => Some((612.0, 1008.0)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PageSetup {
+    pub width: f32, // Points
+    pub height: f32,
+    pub margin: f32,
+    pub font: Font,
+    pub font_size: f32,
+}
+
+impl PageSetup {
+    // From the pdf_* config settings, or a message saying which is wrong
+    pub fn new(page: &str, margin_mm: f32, font: &str, font_size: f32) -> Result<PageSetup, String> {
+        let (width, height) = page_size(page)
+            .ok_or_else(|| format!("Unknown page size '{}' (try a4, a5, letter or legal)", page))?;
+        let font = Font::from_name(font)
+            .ok_or_else(|| format!("Unknown font '{}' (try times, helvetica or courier)", font))?;
+        let margin = margin_mm * MM;
+        if !(4.0..=200.0).contains(&font_size) {
+            return Err(format!("Font size {} is out of range", font_size));
+        }
+        if margin < 0.0 || width - 2.0 * margin < font_size * 10.0 {
+            return Err(format!("A {}mm margin leaves no room for text", margin_mm));
+        }
+        Ok(PageSetup { width, height, margin, font, font_size })
+    }
+}
+
+// Which of the three fonts in the document a run of text uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Face {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Face {
+    fn resource(self) -> Name<'static> {
+        match self {
+            Face::Regular => Name(b"F1"),
+            Face::Bold => Name(b"F2"),
+            Face::Mono => Name(b"F3"),
+        }
+    }
+}
+
+// Lays lines out top to bottom, starting a new page when one fills up
+struct Writer {
+    setup: PageSetup,
+    pages: Vec<Content>,
+    y: f32, // Baseline of the next line
+}
+
+impl Writer {
+    fn new(setup: PageSetup) -> Self {
+        let mut writer = Writer { setup, pages: Vec::new(), y: 0.0 };
+        writer.new_page();
+        writer
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Content::new());
+        self.y = self.setup.height - self.setup.margin;
+    }
+
+    // Space left above the bottom margin (and the page number below it)
+    fn room(&self) -> f32 {
+        self.y - self.setup.margin
+    }
+
+    fn text_width(&self) -> f32 {
+        self.setup.width - 2.0 * self.setup.margin
+    }
+
+    fn measure(&self, text: &str, face: Face, size: f32) -> f32 {
+        let font = if face == Face::Mono { Font::Courier } else { self.setup.font };
+        let thousandths: u32 = text.chars().map(|c| font.char_width(c) as u32).sum();
+        let width = thousandths as f32 * size / 1000.0;
+        if face == Face::Bold { width * BOLD_WIDTH } else { width }
+    }
+
+    fn space(&mut self, points: f32) {
+        // Space at the top of a page is wasted, so it's dropped
+        if self.y < self.setup.height - self.setup.margin {
+            self.y -= points;
+        }
+    }
+
+    // One line of text at an indent from the left margin
+    fn line(&mut self, text: &str, indent: f32, face: Face, size: f32) {
+        let height = size * LEADING;
+        if self.room() < height {
+            self.new_page();
+        }
+        self.y -= size;
+        let x = self.setup.margin + indent;
+        let y = self.y;
+        let content = self.pages.last_mut().expect("there is always a page");
+        content
+            .begin_text()
+            .set_font(face.resource(), size)
+            .next_line(x, y)
+            .show(Str(&encode(text)))
+            .end_text();
+        self.y -= height - size;
+    }
+
+    // A paragraph wrapped to the page. 'first' starts the first line (a
+    // list bullet, say) and later lines hang at 'indent'.
+    fn paragraph(&mut self, first: &str, text: &str, indent: f32, face: Face, size: f32) {
+        let width = self.text_width() - indent;
+        let first_width = self.measure(first, face, size);
+        let mut lines: Vec<String> = Vec::new();
+        let mut current = String::new();
+
+        for word in text.split_whitespace() {
+            let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+            if self.measure(&candidate, face, size) <= width || current.is_empty() {
+                current = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut current, word.to_string()));
+            }
+            // A word too long for a line on its own is broken wherever it
+            // runs out of room
+            while self.measure(&current, face, size) > width {
+                let mut split = current.chars().count() - 1;
+                while split > 1 && self.measure(&current.chars().take(split).collect::<String>(), face, size) > width {
+                    split -= 1;
+                }
+                let rest: String = current.chars().skip(split).collect();
+                lines.push(current.chars().take(split).collect());
+                current = rest;
+            }
+        }
+        if !current.is_empty() || lines.is_empty() {
+            lines.push(current);
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            if i == 0 && !first.is_empty() {
+                // Keep the bullet and its first line together
+                if self.room() < size * LEADING {
+                    self.new_page();
+                }
+                let y = self.y;
+                self.line(line, indent, face, size);
+                let x = self.setup.margin + indent - first_width;
+                let content = self.pages.last_mut().expect("there is always a page");
+                content
+                    .begin_text()
+                    .set_font(face.resource(), size)
+                    .next_line(x, y - size)
+                    .show(Str(&encode(first)))
+                    .end_text();
+            } else {
+                self.line(line, indent, face, size);
+            }
+        }
+    }
+
+    // A note's markdown, block by block
+    fn note(&mut self, heading: &str, text: &str) {
+        let size = self.setup.font_size;
+        let body = front_matter::strip(text);
+        let mut lines = body.lines().peekable();
+
+        // The note's own first heading stands in for the generated one
+        let mut heading = heading.to_string();
+        while lines.peek().is_some_and(|line| line.trim().is_empty()) {
+            lines.next();
+        }
+        if let Some(title) = lines.peek().and_then(|line| line.strip_prefix("# ")) {
+            heading = title.trim().to_string();
+            lines.next();
+        }
+        // Don't leave a heading alone at the bottom of a page
+        if self.room() < size * 1.5 * LEADING + size * LEADING * 2.0 {
+            self.new_page();
+        }
+        self.space(size * 1.5);
+        self.paragraph("", &plain(&heading), 0.0, Face::Bold, size * 1.5);
+        self.space(size * 0.5);
+
+        let mut paragraph: Vec<String> = Vec::new();
+        let mut in_code = false;
+        for line in lines {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                self.flush(&mut paragraph);
+                in_code = !in_code;
+                continue;
+            }
+            if in_code {
+                self.code_line(&line.replace('\t', "    "));
+                continue;
+            }
+            if trimmed.is_empty() {
+                self.flush(&mut paragraph);
+                self.space(size * 0.5);
+                continue;
+            }
+
+            let level = trimmed.chars().take_while(|&c| c == '#').count();
+            if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+                self.flush(&mut paragraph);
+                let heading_size = match level {
+                    1 => size * 1.3,
+                    2 => size * 1.15,
+                    _ => size,
+                };
+                self.space(size * 0.5);
+                self.paragraph("", &plain(&trimmed[level..]), 0.0, Face::Bold, heading_size);
+                continue;
+            }
+
+            let chars: Vec<char> = line.chars().collect();
+            if let Some(item) = markdown::list_item(&chars) {
+                self.flush(&mut paragraph);
+                let content: String = chars[item.content_start..].iter().collect();
+                let bullet = match (item.checkbox, item.marker.trim()) {
+                    (true, _) => {
+                        // The character inside "[ ]"
+                        let checked = chars[item.indent + item.marker.chars().count() + 1] != ' ';
+                        if checked { "[x] ".to_string() } else { "[  ] ".to_string() }
+                    }
+                    (false, "-" | "*" | "+") => "\u{2022} ".to_string(),
+                    (false, marker) => format!("{} ", marker),
+                };
+                let indent = size * 1.5 * (1.0 + item.indent as f32 / 2.0);
+                self.paragraph(&bullet, &plain(&content), indent, Face::Regular, size);
+                continue;
+            }
+
+            if let Some(quote) = trimmed.strip_prefix('>') {
+                self.flush(&mut paragraph);
+                self.paragraph("", &plain(quote), size * 1.5, Face::Regular, size);
+                continue;
+            }
+
+            paragraph.push(plain(trimmed));
+        }
+        self.flush(&mut paragraph);
+    }
+
+    // Code keeps its spacing, so long lines are cut rather than wrapped at
+    // spaces. Courier makes every character the same width.
+    fn code_line(&mut self, line: &str) {
+        let size = self.setup.font_size * 0.9;
+        let indent = self.setup.font_size;
+        let fits = ((self.text_width() - indent) / (size * 0.6)).max(1.0) as usize;
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            self.line("", indent, Face::Mono, size);
+        }
+        for chunk in chars.chunks(fits) {
+            self.line(&chunk.iter().collect::<String>(), indent, Face::Mono, size);
+        }
+    }
+
+    // Print the lines gathered so far as one wrapped paragraph
+    fn flush(&mut self, paragraph: &mut Vec<String>) {
+        if !paragraph.is_empty() {
+            let text = paragraph.join(" ");
+            self.paragraph("", &text, 0.0, Face::Regular, self.setup.font_size);
+            paragraph.clear();
+        }
+    }
+}
+
+// Render notes, each given as (heading, markdown text), into a PDF.
+// Returns the file's bytes and its page count.
+pub fn render(title: &str, notes: &[(String, String)], setup: PageSetup) -> (Vec<u8>, usize) {
+    let mut writer = Writer::new(setup);
+    for (heading, text) in notes {
+        writer.note(heading, text);
+    }
+
+    let catalog_id = Ref::new(1);
+    let tree_id = Ref::new(2);
+    let info_id = Ref::new(3);
+    let font_ids = [Ref::new(4), Ref::new(5), Ref::new(6)];
+    let page_count = writer.pages.len();
+    // Each page needs a page object and a content stream
+    let page_ids: Vec<Ref> = (0..page_count).map(|i| Ref::new(7 + 2 * i as i32)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id).kids(page_ids.iter().copied()).count(page_count as i32);
+    pdf.document_info(info_id).title(TextStr(title)).creator(TextStr("river"));
+
+    let (regular, bold) = setup.font.base_names();
+    for (id, name) in font_ids.iter().zip([regular, bold, b"Courier".as_slice()]) {
+        pdf.type1_font(*id).base_font(Name(name)).encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+
+    for (number, (content, page_id)) in writer.pages.into_iter().zip(&page_ids).enumerate() {
+        let mut content = content;
+        // Page number centred in the bottom margin
+        let label = (number + 1).to_string();
+        let size = setup.font_size * 0.8;
+        let label_width = label.len() as f32 * setup.font.char_width('0') as f32 * size / 1000.0;
+        content
+            .begin_text()
+            .set_font(Face::Regular.resource(), size)
+            .next_line((setup.width - label_width) / 2.0, setup.margin / 2.0)
+            .show(Str(label.as_bytes()))
+            .end_text();
+
+        let content_id = Ref::new(page_id.get() + 1);
+        // The writers pdf-writer hands out finish their object when dropped,
+        // hence the block
+        {
+            let mut page = pdf.page(*page_id);
+            page.media_box(Rect::new(0.0, 0.0, setup.width, setup.height))
+                .parent(tree_id)
+                .contents(content_id);
+            let mut resources = page.resources();
+            let mut fonts = resources.fonts();
+            for (face, id) in [Face::Regular, Face::Bold, Face::Mono].iter().zip(font_ids) {
+                fonts.pair(face.resource(), id);
+            }
+        }
+        pdf.stream(content_id, &content.finish());
+    }
+
+    (pdf.finish(), page_count)
+}
+
+// Inline markdown reduced to the words that get printed: links keep their
+// text, images their alt text, and emphasis and code markers go
+fn plain(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text.trim();
+    while let Some(c) = rest.chars().next() {
+        if c == ']' && rest.starts_with("](") {
+            rest = rest.find(')').map_or("", |close| &rest[close + 1..]);
+            continue;
+        }
+        if rest.starts_with("__") {
+            rest = &rest[2..];
+            continue;
+        }
+        if !matches!(c, '*' | '`' | '[') && !rest.starts_with("![") {
+            out.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// Text in the WinAnsi encoding the built-in fonts use: Latin-1 plus a few
+// typographic characters, with '?' for anything else
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '\u{20ac}' => 0x80,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\u{2122}' => 0x99,
+            '\t' => b' ',
+            _ => b'?',
+        })
+        .collect()
+}
@@ -0,0 +1,97 @@
+// The offline prompt library: writing prompts used when AI prompts are
+// turned off, nothing is cached for today, or the API can't be reached.
+//
+// A curated set is built in. To use your own, put them in prompts.toml
+// next to config.toml:
+//
+//     prompts = [
+//         "What did you notice on your walk today?",
+//         "Which conversation is still on your mind?",
+//     ]
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+const BUILT_IN: &[&str] = &[
+    // The day itself
+    "What moment from today do you want to remember?",
+    "What surprised you today?",
+    "What made you smile or laugh today?",
+    "What small victory did you achieve today?",
+    "What did today ask of you, and what did you give?",
+    "Describe one ordinary thing from today in more detail than it deserves.",
+    "Where did your attention go today when nobody was asking for it?",
+    "What would you do differently if you could live today again?",
+    // Gratitude
+    "What are you grateful for today?",
+    "Who made your life a little easier recently, and do they know?",
+    "What do you have now that you once wished for?",
+    "Which small comfort would you miss most if it disappeared?",
+    // Growth
+    "What challenged you today and how did you handle it?",
+    "What did you learn about yourself today?",
+    "How did you grow as a person today?",
+    "What are you avoiding, and what would it take to face it?",
+    "Which habit is quietly shaping your weeks?",
+    "What advice would you give yourself from a year ago?",
+    "What is something you've changed your mind about?",
+    "Where are you being too hard on yourself?",
+    // People
+    "Who do you wish you had spent more time with this week?",
+    "What conversation is still on your mind?",
+    "Who taught you something without meaning to?",
+    "Write a letter you don't intend to send.",
+    // Looking ahead
+    "What would you tell your future self about today?",
+    "What intention do you want to set for tomorrow?",
+    "What are you looking forward to, however small?",
+    "If this week had a theme, what would you want it to be?",
+    "What is one thing you could let go of before the week ends?",
+    // Looking inward
+    "How are you really feeling right now? Name it as precisely as you can.",
+    "What has been taking up space in your head lately?",
+    "When did you last feel completely at ease?",
+    "What does a good day look like for you at the moment?",
+    "What question do you keep coming back to?",
+    "Describe where you are sitting as if seeing it for the first time.",
+    "What are you proud of that no one else knows about?",
+];
+
+#[derive(Deserialize)]
+struct PromptFile {
+    #[serde(default)]
+    prompts: Vec<String>,
+}
+
+// ~/.config/river/prompts.toml (or the platform's config directory)
+pub fn library_path() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("prompts.toml");
+    path
+}
+
+// The user's prompts if they've written any, otherwise the built-in set
+pub fn library() -> Vec<String> {
+    let from_file = fs::read_to_string(library_path())
+        .ok()
+        .and_then(|contents| toml::from_str::<PromptFile>(&contents).ok())
+        .map(|file| file.prompts.into_iter().filter(|p| !p.trim().is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    if from_file.is_empty() {
+        BUILT_IN.iter().map(|p| p.to_string()).collect()
+    } else {
+        from_file
+    }
+}
+
+// The prompt for a date. Counting days rather than using the day of the
+// year walks through the whole library before any prompt comes back.
+pub fn for_date(date: &NaiveDate) -> String {
+    let prompts = library();
+    let index = date.num_days_from_ce().unsigned_abs() as usize % prompts.len();
+    prompts[index].clone()
+}
@@ -0,0 +1,202 @@
+// Turning selected notes into a small static site (river publish)
+//
+// A note is published when it contains the tag #public, or when its name
+// is listed in publish.txt next to config.toml, one per line:
+//
+//     2024-05-01
+//     trip-to-lisbon
+//
+// The site is plain HTML with no scripts: index.html lists the entries
+// newest first, each entry gets a page named after its note, and feed.xml
+// is an RSS feed of the lot. Files river didn't write are left alone.
+
+use chrono::{NaiveDate, NaiveTime};
+use pulldown_cmark::{html, Options, Parser};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::front_matter;
+
+const TAG: &str = "#public";
+// How many entries the feed carries
+const FEED_LENGTH: usize = 20;
+
+const STYLE: &str = "body{max-width:40em;margin:2em auto;padding:0 1em;font:18px/1.6 Georgia,serif;color:#222}\
+a{color:#2a5db0}h1,h2{line-height:1.2}.date{color:#777}ul.entries{list-style:none;padding:0}\
+ul.entries li{margin:.5em 0}pre{overflow-x:auto;background:#f4f4f4;padding:.5em}\
+blockquote{border-left:3px solid #ccc;margin-left:0;padding-left:1em;color:#555}";
+
+pub struct Post {
+    pub slug: String, // Note name, used for the page's file name
+    pub title: String,
+    pub date: Option<NaiveDate>, // From a YYYY-MM-DD note name
+    pub html: String,
+}
+
+// ~/.config/river/publish.txt (or the platform's config directory)
+pub fn manifest_path() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("publish.txt");
+    path
+}
+
+// Note names listed in publish.txt; "#" starts a comment
+pub fn manifest() -> Vec<String> {
+    fs::read_to_string(manifest_path())
+        .map(|contents| {
+            contents
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or("").trim())
+                .filter(|line| !line.is_empty())
+                .map(|line| line.trim_end_matches(".md").to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Whether the note carries the #public tag (and not, say, #publicity)
+pub fn is_tagged(text: &str) -> bool {
+    text.match_indices(TAG).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + TAG.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric() || c == '#')
+            && !after.is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+// A note rendered for the site. The title is the note's :title, its first
+// heading or its date, in that order of preference. The heading is left
+// out of the body so it doesn't show twice, and the #public tag goes too.
+pub fn post(name: &str, text: &str, saved_title: Option<&str>) -> Post {
+    let body = front_matter::strip(text);
+    let mut lines: Vec<&str> = body.lines().skip_while(|line| line.trim().is_empty()).collect();
+
+    let heading = lines.first().and_then(|line| line.strip_prefix("# ")).map(|h| h.trim().to_string());
+    if heading.is_some() {
+        lines.remove(0);
+    }
+    let date = NaiveDate::parse_from_str(name, "%Y-%m-%d").ok();
+    // New daily notes start with the date as their heading, which the
+    // date line on the page already covers
+    let heading = heading.filter(|heading| NaiveDate::parse_from_str(heading, "%A, %B %d, %Y").ok() != date);
+    let title = saved_title
+        .map(str::to_string)
+        .or(heading)
+        .unwrap_or_else(|| match date {
+            Some(date) => long_date(date),
+            None => name.to_string(),
+        });
+
+    let markdown: Vec<String> = lines.iter().map(|line| remove_tag(line)).collect();
+    let markdown = markdown.join("\n");
+    let mut html = String::new();
+    html::push_html(&mut html, Parser::new_ext(&markdown, Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH));
+    Post { slug: slug(name), title, date, html }
+}
+
+fn remove_tag(line: &str) -> String {
+    if !is_tagged(line) {
+        return line.to_string();
+    }
+    line.split(' ')
+        .filter(|word| *word != TAG)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// A file name that's safe in a URL
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+// Write the site into 'dir'. 'base_url' makes the feed's links absolute
+// (feed readers need that); without it they're relative.
+pub fn write_site(dir: &Path, site_title: &str, base_url: &str, posts: &[Post]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut list = String::from("<ul class=\"entries\">\n");
+    for post in posts {
+        list.push_str(&format!(
+            "<li><span class=\"date\">{}</span> <a href=\"{}.html\">{}</a></li>\n",
+            post.date.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+            post.slug,
+            escape(&post.title)
+        ));
+    }
+    list.push_str("</ul>\n");
+    let index = page(site_title, site_title, &format!("<h1>{}</h1>\n{}", escape(site_title), list));
+    fs::write(dir.join("index.html"), index)?;
+
+    for post in posts {
+        let date = post
+            .date
+            .map(long_date)
+            .filter(|date| *date != post.title)
+            .map(|date| format!("<p class=\"date\">{}</p>\n", date))
+            .unwrap_or_default();
+        let body = format!(
+            "<p><a href=\"index.html\">&larr; {}</a></p>\n<h1>{}</h1>\n{}{}",
+            escape(site_title),
+            escape(&post.title),
+            date,
+            post.html
+        );
+        fs::write(dir.join(format!("{}.html", post.slug)), page(&post.title, site_title, &body))?;
+    }
+
+    fs::write(dir.join("feed.xml"), feed(site_title, base_url, posts))
+}
+
+fn page(title: &str, site_title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n\
+         <link rel=\"alternate\" type=\"application/rss+xml\" title=\"{}\" href=\"feed.xml\">\n\
+         <style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        escape(site_title),
+        STYLE,
+        body
+    )
+}
+
+// RSS 2.0, newest entries first
+fn feed(site_title: &str, base_url: &str, posts: &[Post]) -> String {
+    let base = if base_url.is_empty() { String::new() } else { format!("{}/", base_url.trim_end_matches('/')) };
+    let mut items = String::new();
+    for post in posts.iter().take(FEED_LENGTH) {
+        let link = format!("{}{}.html", base, post.slug);
+        items.push_str("<item>\n");
+        items.push_str(&format!("<title>{}</title>\n", escape(&post.title)));
+        items.push_str(&format!("<link>{}</link>\n<guid>{}</guid>\n", escape(&link), escape(&link)));
+        if let Some(date) = post.date {
+            // RSS wants RFC 822 dates; midnight UTC stands in for "that day"
+            let time = date.and_time(NaiveTime::MIN).and_utc();
+            items.push_str(&format!("<pubDate>{}</pubDate>\n", time.to_rfc2822()));
+        }
+        items.push_str(&format!("<description>{}</description>\n", escape(&post.html)));
+        items.push_str("</item>\n");
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n<channel>\n\
+         <title>{}</title>\n<link>{}</link>\n<description>{}</description>\n{}</channel>\n</rss>\n",
+        escape(site_title),
+        escape(if base.is_empty() { "index.html" } else { &base }),
+        escape(site_title),
+        items
+    )
+}
+
+fn long_date(date: NaiveDate) -> String {
+    date.format("%A, %B %-d, %Y").to_string()
+}
+
+// Make text safe to put in HTML or XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,58 @@
+// Desktop reminders for river remind: a nudge when today's word goal
+// hasn't been met yet.
+//
+// Notifications go through whatever the platform already has rather than
+// a library: notify-send on Linux and the BSDs, osascript on macOS and a
+// PowerShell balloon on Windows. If none of those work the reminder is
+// printed to the terminal instead.
+
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+use std::process::Command;
+
+// "21:00" or "9:30"
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+// The next time the clock reads 'at': later today, or tomorrow if that's
+// already gone
+pub fn next_occurrence(at: NaiveTime, now: DateTime<Local>) -> DateTime<Local> {
+    let today = now.date_naive().and_time(at);
+    // earliest() copes with clocks changing, when a time can happen twice
+    // or not at all
+    match Local.from_local_datetime(&today).earliest() {
+        Some(time) if time > now => time,
+        _ => Local
+            .from_local_datetime(&(today + Duration::days(1)))
+            .earliest()
+            .unwrap_or(now + Duration::days(1)),
+    }
+}
+
+// Show a desktop notification. Returns false if it couldn't be shown.
+pub fn notify(title: &str, body: &str) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!("display notification {:?} with title {:?}", body, title);
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else if cfg!(windows) {
+        // Quotes are doubled to escape them inside a PowerShell string
+        let script = format!(
+            "[reflection.assembly]::loadwithpartialname('System.Windows.Forms') | Out-Null; \
+             $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(10000, '{}', '{}', 'Info'); Start-Sleep -Seconds 10",
+            title.replace('\'', "''"),
+            body.replace('\'', "''")
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &script]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args([title, body]);
+        command
+    };
+    command.status().map(|status| status.success()).unwrap_or(false)
+}
@@ -0,0 +1,153 @@
+// Autosaves and stats files are written on a worker thread
+//
+// Writing a large note to a slow disk or a network mount can take long
+// enough to drop keystrokes if the editor waits for it. Instead the text is
+// handed to a worker over a channel and the editor carries on; the result
+// comes back on another channel and is picked up by poll(). Only one write
+// is in flight at a time: anything asked for meanwhile waits, and a newer
+// version of the same file replaces a waiting older one.
+//
+// Saves the user asks for (:w, quitting, switching buffers) still happen
+// right away, after wait() has let the worker finish, so an older
+// background write can never land on top of them.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::crypto;
+use crate::history;
+
+pub struct Write {
+    pub path: PathBuf,
+    pub content: String,
+    pub snapshot: bool,            // Keep the version on disk for :diff first (notes, not stats)
+    pub fallback: Option<PathBuf>, // Where to write instead if 'path' can't be written
+}
+
+// How a write went
+pub struct Written {
+    pub path: PathBuf,
+    pub result: io::Result<()>,
+    pub moved_to: Option<PathBuf>, // Written to the fallback instead
+}
+
+// Write a file now, creating its folder if it's missing
+pub fn write_now(write: Write) -> Written {
+    if write.snapshot {
+        // Losing the earlier version isn't worth failing the save over
+        let _ = history::snapshot(&write.path.to_string_lossy());
+    }
+    let result = write_file(&write.path, &write.content);
+    match (result, write.fallback) {
+        (Err(_), Some(fallback)) if write_file(&fallback, &write.content).is_ok() => {
+            Written { path: write.path, result: Ok(()), moved_to: Some(fallback) }
+        }
+        (result, _) => Written { path: write.path, result, moved_to: None },
+    }
+}
+
+fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    crypto::write(path, content)
+}
+
+pub struct Saver {
+    sender: Sender<Write>,
+    done: Receiver<Written>,
+    in_flight: bool,
+    waiting: Vec<Write>,
+    failed: Vec<Written>, // Writes the worker never got, for the next poll
+}
+
+impl Saver {
+    pub fn new() -> Self {
+        let (sender, jobs) = mpsc::channel::<Write>();
+        let (finished, done) = mpsc::channel();
+        thread::spawn(move || {
+            // Ends when the Saver (and so the sender) is dropped
+            for write in jobs {
+                if finished.send(write_now(write)).is_err() {
+                    break;
+                }
+            }
+        });
+        Saver { sender, done, in_flight: false, waiting: Vec::new(), failed: Vec::new() }
+    }
+
+    pub fn write(&mut self, write: Write) {
+        if self.in_flight {
+            self.waiting.retain(|waiting| waiting.path != write.path);
+            self.waiting.push(write);
+        } else {
+            self.send(write);
+        }
+    }
+
+    fn send(&mut self, write: Write) {
+        match self.sender.send(write) {
+            Ok(()) => self.in_flight = true,
+            // The worker is gone (it panicked); report it like a failed write
+            Err(mpsc::SendError(write)) => self.failed.push(stopped(write.path)),
+        }
+    }
+
+    pub fn busy(&self) -> bool {
+        self.in_flight
+    }
+
+    // Writes finished since last time, starting the next waiting one
+    pub fn poll(&mut self) -> Vec<Written> {
+        let mut finished = Vec::new();
+        while self.in_flight {
+            match self.done.try_recv() {
+                Ok(written) => {
+                    finished.push(written);
+                    self.next();
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    finished.push(stopped(PathBuf::new()));
+                    self.in_flight = false;
+                }
+            }
+        }
+        finished.append(&mut self.failed);
+        finished
+    }
+
+    // Block until every write asked for so far is done
+    pub fn wait(&mut self) -> Vec<Written> {
+        let mut finished = Vec::new();
+        while self.in_flight {
+            match self.done.recv() {
+                Ok(written) => {
+                    finished.push(written);
+                    self.next();
+                }
+                Err(_) => {
+                    finished.push(stopped(PathBuf::new()));
+                    self.in_flight = false;
+                }
+            }
+        }
+        finished.append(&mut self.failed);
+        finished
+    }
+
+    fn next(&mut self) {
+        self.in_flight = false;
+        if !self.waiting.is_empty() {
+            let write = self.waiting.remove(0);
+            self.send(write);
+        }
+    }
+}
+
+fn stopped(path: PathBuf) -> Written {
+    Written { path, result: Err(io::Error::other("the save thread stopped")), moved_to: None }
+}
@@ -0,0 +1,195 @@
+// Scripts: your own commands written in Rhai (https://rhai.rs), a small
+// scripting language made for embedding in Rust programs. Each file in
+// ~/.config/river/scripts is a command named after it: gratitude.rhai is
+// run by :gratitude, and like any command can be bound to a key in [keys].
+//
+// A script works on the open note through these functions (lines and
+// columns count from 0):
+//   lines()                 every line, as an array of strings
+//   line(n), set_line(n, text)
+//   insert_lines(n, lines)  add an array of lines before line n
+//                           (line_count() adds them at the end)
+//   remove_line(n)
+//   line_count()
+//   cursor()                [line, column]
+//   set_cursor(line, column)
+//   insert(text)            type text at the cursor ("\n" starts a line)
+//   note(date)              another day's note ("2024-05-01"), "" if none
+//   today(), yesterday()    dates in that form
+//   command(text)           run a river command after the script ("w")
+//   print(text)             show text on the message line
+//
+// For example, moving yesterday's open tasks into today's note:
+//
+//   let open = note(yesterday()).split("\n").filter(|l| l.starts_with("- [ ]"));
+//   insert_lines(line_count(), open);
+//
+// Nothing the script does reaches the note until it finishes without an
+// error, so a failed script leaves the note as it was.
+
+use chrono::{Duration, Local};
+use rhai::{Array, Dynamic, Engine, EvalAltResult};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::archive;
+use crate::crypto;
+use crate::i18n::t;
+
+// Enough for any sensible script, and stops one stuck in a loop from
+// freezing the editor
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+// The note a script runs on, and what it leaves behind
+#[derive(Debug, Clone, Default)]
+pub struct Note {
+    pub lines: Vec<String>,
+    pub cursor: (usize, usize), // (line, column)
+    pub message: Option<String>,
+    pub commands: Vec<String>,
+}
+
+// ~/.config/river/scripts (or the platform's config directory)
+pub fn dir() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("scripts");
+    path
+}
+
+fn path(name: &str) -> PathBuf {
+    dir().join(format!("{}.rhai", name))
+}
+
+// Names of the scripts there is a file for, sorted
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn exists(name: &str) -> bool {
+    !name.is_empty() && path(name).is_file()
+}
+
+// Run the script called 'name' on 'note' and return the note as the
+// script left it. 'notes_dir' is where note(date) looks.
+pub fn run(name: &str, note: Note, notes_dir: &Path) -> Result<Note, String> {
+    let path = path(name);
+    let source = fs::read_to_string(&path).map_err(|e| t!("Can't read {path}: {error}", path = path.display(), error = e))?;
+
+    // The functions below all change the same note, so it's shared
+    // between them: Rc gives each its own handle, RefCell lets them
+    // borrow it mutably one at a time
+    let state = Rc::new(RefCell::new(note));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let s = state.clone();
+    engine.on_print(move |text| s.borrow_mut().message = Some(text.to_string()));
+
+    let s = state.clone();
+    engine.register_fn("lines", move || -> Array { s.borrow().lines.iter().cloned().map(Dynamic::from).collect() });
+    let s = state.clone();
+    engine.register_fn("line_count", move || s.borrow().lines.len() as i64);
+    let s = state.clone();
+    engine.register_fn("line", move |n: i64| -> Result<String, Box<EvalAltResult>> {
+        let note = s.borrow();
+        Ok(note.lines[index(n, note.lines.len())?].clone())
+    });
+    let s = state.clone();
+    engine.register_fn("set_line", move |n: i64, text: &str| -> Result<(), Box<EvalAltResult>> {
+        let mut note = s.borrow_mut();
+        let y = index(n, note.lines.len())?;
+        note.lines[y] = text.to_string();
+        Ok(())
+    });
+    let s = state.clone();
+    engine.register_fn("insert_lines", move |n: i64, lines: Array| -> Result<(), Box<EvalAltResult>> {
+        let mut note = s.borrow_mut();
+        let y = index(n, note.lines.len() + 1)?;
+        note.lines.splice(y..y, lines.iter().map(|line| line.to_string()));
+        Ok(())
+    });
+    let s = state.clone();
+    engine.register_fn("remove_line", move |n: i64| -> Result<(), Box<EvalAltResult>> {
+        let mut note = s.borrow_mut();
+        let y = index(n, note.lines.len())?;
+        note.lines.remove(y);
+        // A note always has a line, if only an empty one
+        if note.lines.is_empty() {
+            note.lines.push(String::new());
+        }
+        Ok(())
+    });
+    let s = state.clone();
+    engine.register_fn("cursor", move || -> Array {
+        let (y, x) = s.borrow().cursor;
+        vec![Dynamic::from(y as i64), Dynamic::from(x as i64)]
+    });
+    let s = state.clone();
+    engine.register_fn("set_cursor", move |y: i64, x: i64| {
+        s.borrow_mut().cursor = (y.max(0) as usize, x.max(0) as usize);
+    });
+    let s = state.clone();
+    engine.register_fn("insert", move |text: &str| insert(&mut s.borrow_mut(), text));
+    let s = state.clone();
+    engine.register_fn("command", move |text: &str| {
+        s.borrow_mut().commands.push(text.trim().trim_start_matches(':').to_string());
+    });
+    let notes_dir = notes_dir.to_path_buf();
+    engine.register_fn("note", move |date: &str| read_note(&notes_dir, date));
+    engine.register_fn("today", || Local::now().format("%Y-%m-%d").to_string());
+    engine.register_fn("yesterday", || (Local::now() - Duration::days(1)).format("%Y-%m-%d").to_string());
+
+    engine
+        .run(&source)
+        .map_err(|e| format!("{}.rhai: {}", name, e))?;
+    let note = state.borrow().clone();
+    Ok(note)
+}
+
+// A line number from a script, checked against the number of lines
+fn index(n: i64, len: usize) -> Result<usize, Box<EvalAltResult>> {
+    usize::try_from(n)
+        .ok()
+        .filter(|&y| y < len)
+        .ok_or_else(|| format!("line {} isn't in the note ({} lines)", n, len).into())
+}
+
+// Put 'text' in at the cursor, leaving the cursor after it
+fn insert(note: &mut Note, text: &str) {
+    let (y, x) = note.cursor;
+    let y = y.min(note.lines.len() - 1);
+    let line = &note.lines[y];
+    // The cursor counts characters; the String needs a byte offset
+    let at = line.char_indices().nth(x).map_or(line.len(), |(i, _)| i);
+    let after = line[at..].to_string();
+    let before = line[..at].to_string();
+
+    let mut new: Vec<String> = text.split('\n').map(str::to_string).collect();
+    let last = new.len() - 1;
+    let column = if last == 0 { before.chars().count() } else { 0 } + new[last].chars().count();
+    new[0].insert_str(0, &before);
+    new[last].push_str(&after);
+    note.lines.splice(y..=y, new);
+    note.cursor = (y + last, column);
+}
+
+// The note for a YYYY-MM-DD date, archived or not
+fn read_note(notes_dir: &Path, date: &str) -> String {
+    let name = format!("{}.md", date);
+    [notes_dir.join(&name), notes_dir.join(archive::DIR).join(&name)]
+        .iter()
+        .find_map(|path| crypto::read_to_string(path).ok())
+        .unwrap_or_default()
+}
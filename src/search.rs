@@ -0,0 +1,91 @@
+// / and ? searches, with vim's offsets after a second separator:
+//
+//   /term/e      the last character of the match (e+1, e-2: counted from it)
+//   /term/s+2    two characters after its start (b is the same as s)
+//   /term/+1     the start of the line below the match (-1: above)
+//
+// An ending that isn't an offset is part of the term, so /and/or finds
+// "and/or". n and N keep the offset.
+
+use crate::vim::Pos;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Offset {
+    #[default]
+    None,
+    Start(isize), // s, b
+    End(isize),   // e
+    Lines(isize), // +N, -N
+}
+
+// Split what was typed after / (or ?) into the term and its offset
+pub fn parse(input: &str, separator: char) -> (String, Offset) {
+    if let Some((term, offset)) = input.rsplit_once(separator) {
+        if !term.is_empty() && !term.ends_with('\\') {
+            if let Some(offset) = parse_offset(offset) {
+                return (unescape(term, separator), offset);
+            }
+        }
+    }
+    (unescape(input, separator), Offset::None)
+}
+
+// \/ in a / search (\? in a ? one) is the separator itself
+fn unescape(term: &str, separator: char) -> String {
+    term.replace(&format!("\\{}", separator), &separator.to_string())
+}
+
+fn parse_offset(text: &str) -> Option<Offset> {
+    // "/term/" is just the term
+    if text.is_empty() {
+        return Some(Offset::None);
+    }
+    let number = |text: &str| -> Option<isize> {
+        match text {
+            "" => Some(0),
+            "+" => Some(1),
+            "-" => Some(-1),
+            _ if text.starts_with(['+', '-']) => text.parse().ok(),
+            _ => None,
+        }
+    };
+    match text.chars().next()? {
+        'e' => number(&text[1..]).map(Offset::End),
+        's' | 'b' => number(&text[1..]).map(Offset::Start),
+        // A bare number counts lines down too
+        '+' | '-' => number(text).map(Offset::Lines),
+        '0'..='9' => text.parse().ok().map(Offset::Lines),
+        _ => None,
+    }
+}
+
+// Where every occurrence of 'term' starts, in order
+pub fn find_all(lines: &[Vec<char>], term: &str) -> Vec<Pos> {
+    let needle: Vec<char> = term.chars().collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let mut found = Vec::new();
+    for (y, line) in lines.iter().enumerate() {
+        for x in 0..line.len() {
+            if line[x..].starts_with(&needle) {
+                found.push(Pos::new(x, y));
+            }
+        }
+    }
+    found
+}
+
+// Where the cursor goes for a match of 'len' characters at 'start'
+pub fn target(lines: &[Vec<char>], start: Pos, len: usize, offset: Offset) -> Pos {
+    let within_line = |x: isize, y: usize| Pos::new(x.clamp(0, lines[y].len().saturating_sub(1) as isize) as usize, y);
+    match offset {
+        Offset::None => start,
+        Offset::Start(n) => within_line(start.x as isize + n, start.y),
+        Offset::End(n) => within_line(start.x as isize + len as isize - 1 + n, start.y),
+        Offset::Lines(n) => {
+            let y = (start.y as isize + n).clamp(0, lines.len() as isize - 1);
+            Pos::new(0, y as usize)
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// Remembers where you were in each file between runs: cursor position,
+// scroll offset, editor mode and marks, stored as JSON in the data directory.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config;
+use crate::vim::Pos;
+use crate::Mode;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileSession {
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub offset_x: usize,
+    pub offset_y: usize,
+    #[serde(default)]
+    pub mode: Option<Mode>,
+    #[serde(default)]
+    pub marks: HashMap<char, Pos>, // Set with m{a-z}
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionStore {
+    // Keyed by absolute file path
+    #[serde(default)]
+    files: HashMap<String, FileSession>,
+}
+
+impl SessionStore {
+    // A missing or unreadable store just means nothing is remembered yet
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    pub fn get(&self, filename: &str) -> Option<&FileSession> {
+        self.files.get(&Self::key(filename))
+    }
+
+    pub fn set(&mut self, filename: &str, session: FileSession) {
+        self.files.insert(Self::key(filename), session);
+    }
+
+    // The same file can be opened as "notes/a.md" or "/home/me/notes/a.md"
+    fn key(filename: &str) -> String {
+        fs::canonicalize(filename)
+            .unwrap_or_else(|_| PathBuf::from(filename))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn path() -> PathBuf {
+        config::data_dir().join("sessions.json")
+    }
+}
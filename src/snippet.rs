@@ -0,0 +1,92 @@
+// Snippets: blocks of text you insert often, one per file in
+// ~/.config/river/snippets (review.md is inserted by :snippet review)
+//
+// $1, $2, ... in a snippet are tab stops. The cursor starts at $1 and Tab
+// moves on to the next; $0, if there is one, is where it ends up last.
+//
+//   ## Highlights
+//   - $1
+//
+//   ## Lowlights
+//   - $2
+//
+//   ## Tomorrow
+//   - $0
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::i18n::t;
+
+pub struct Snippet {
+    pub lines: Vec<Vec<char>>,
+    pub stops: Vec<(usize, usize)>, // (line, column) in visiting order
+}
+
+// ~/.config/river/snippets (or the platform's config directory)
+pub fn dir() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("snippets");
+    path
+}
+
+// Names of the snippets there is a file for, sorted
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// The snippet called 'name': snippets/name.md, or any other file of that
+// name (name.txt, plain name)
+pub fn load(name: &str) -> Result<Snippet, String> {
+    let path = fs::read_dir(dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.file_stem().is_some_and(|stem| stem == name))
+        .min_by_key(|path| path.extension().is_none_or(|ext| ext != "md"))
+        .ok_or_else(|| t!("No snippet \"{name}\" in {dir}", name = name, dir = dir().display()))?;
+    let text = fs::read_to_string(&path).map_err(|e| t!("Can't read {path}: {error}", path = path.display(), error = e))?;
+    Ok(parse(&text))
+}
+
+// Take the $N markers out of 'text', remembering where they were
+pub fn parse(text: &str) -> Snippet {
+    // The file's last newline isn't part of the snippet
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let text = text.strip_suffix('\r').unwrap_or(text);
+
+    let mut lines = Vec::new();
+    let mut numbered: Vec<(u32, usize, usize)> = Vec::new();
+    for (y, line) in text.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut chars = Vec::new();
+        let mut rest = line.chars().peekable();
+        while let Some(c) = rest.next() {
+            match rest.peek().and_then(|next| next.to_digit(10)) {
+                Some(number) if c == '$' => {
+                    rest.next();
+                    numbered.push((number, y, chars.len()));
+                }
+                _ => chars.push(c),
+            }
+        }
+        lines.push(chars);
+    }
+
+    // $1, $2, ... in order, then $0
+    numbered.sort_by_key(|&(number, _, _)| if number == 0 { u32::MAX } else { number });
+    let stops = numbered.into_iter().map(|(_, y, x)| (y, x)).collect();
+    Snippet { lines, stops }
+}
@@ -0,0 +1,224 @@
+// Spell checking (:spell, or spell_check = true in config.toml)
+//
+// Words are checked by hunspell, or aspell if that's what's installed,
+// against the dictionaries in spell_lang - a word is only misspelled if
+// none of them knows it, so a journal in two languages can use both. A
+// note can choose its own with "lang: de_DE" (or a list) in its front
+// matter. Words added with zg go into a personal list, words.txt in the
+// config directory unless spell_file says otherwise.
+//
+// Each word is only sent to the checker once; the answer is remembered
+// until the languages change.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::front_matter;
+use crate::i18n::t;
+use crate::markdown;
+use crate::vim::Pos;
+
+pub struct Spell {
+    langs: RefCell<Vec<String>>,
+    known: RefCell<HashMap<String, bool>>, // Word -> spelled right
+    personal: HashSet<String>,
+    pub error: RefCell<Option<String>>, // Why the checker couldn't run
+}
+
+impl Spell {
+    pub fn new(config: &Config) -> Self {
+        let personal = fs::read_to_string(personal_file(config))
+            .map(|text| text.lines().map(|word| word.trim().to_string()).filter(|word| !word.is_empty()).collect())
+            .unwrap_or_default();
+        Spell {
+            langs: RefCell::new(Vec::new()),
+            known: RefCell::new(HashMap::new()),
+            personal,
+            error: RefCell::new(None),
+        }
+    }
+
+    // The misspelled words in 'lines' as (start, end) ranges. 'skip' is
+    // the word being typed, left alone until it's finished.
+    pub fn misspelled(&self, lines: &[Vec<char>], config: &Config, skip: Option<Pos>) -> Vec<(Pos, Pos)> {
+        self.set_langs(note_langs(lines).unwrap_or_else(|| config.spell_lang.clone()));
+        let words: Vec<(Pos, Pos, String)> = words(lines)
+            .into_iter()
+            .filter(|(start, end, _)| !skip.is_some_and(|at| at.y == start.y && start.x <= at.x && at.x <= end.x))
+            .filter(|(_, _, word)| !self.personal.contains(word))
+            .collect();
+
+        // Ask about the words not seen before, all at once
+        let unknown: Vec<String> = {
+            let known = self.known.borrow();
+            let mut unknown: Vec<String> = words.iter().map(|(_, _, word)| word.clone()).filter(|word| !known.contains_key(word)).collect();
+            unknown.sort();
+            unknown.dedup();
+            unknown
+        };
+        if !unknown.is_empty() && self.error.borrow().is_none() {
+            match check(&self.langs.borrow(), &unknown) {
+                Ok(wrong) => {
+                    let mut known = self.known.borrow_mut();
+                    for word in unknown {
+                        let right = !wrong.contains(&word);
+                        known.insert(word, right);
+                    }
+                }
+                Err(e) => *self.error.borrow_mut() = Some(e),
+            }
+        }
+
+        let known = self.known.borrow();
+        words
+            .into_iter()
+            .filter(|(_, _, word)| known.get(word) == Some(&false))
+            .map(|(start, end, _)| (start, end))
+            .collect()
+    }
+
+    fn set_langs(&self, langs: Vec<String>) {
+        if *self.langs.borrow() != langs {
+            *self.langs.borrow_mut() = langs;
+            self.known.borrow_mut().clear();
+            *self.error.borrow_mut() = None;
+        }
+    }
+
+    // zg - never flag 'word' again
+    pub fn add_word(&mut self, config: &Config, word: &str) -> io::Result<()> {
+        let path = personal_file(config);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", word)?;
+        self.personal.insert(word.to_string());
+        Ok(())
+    }
+}
+
+pub fn personal_file(config: &Config) -> PathBuf {
+    if !config.spell_file.is_empty() {
+        return PathBuf::from(&config.spell_file);
+    }
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("river");
+    path.push("words.txt");
+    path
+}
+
+// "lang: de_DE" or "lang: [en_US, de_DE]" in the front matter. Pandoc's
+// "de-DE" spelling works too.
+pub fn note_langs(lines: &[Vec<char>]) -> Option<Vec<String>> {
+    let value = front_matter::get(lines, "lang")?;
+    let langs: Vec<String> = value
+        .trim_matches(|c| c == '[' || c == ']')
+        .split(',')
+        .map(|lang| lang.trim().trim_matches(|c| c == '"' || c == '\'').replace('-', "_"))
+        .filter(|lang| !lang.is_empty())
+        .collect();
+    (!langs.is_empty()).then_some(langs)
+}
+
+// The words worth checking, with where they are: letters and apostrophes
+// inside them, outside front matter and code blocks, and not part of a
+// URL or a word with digits in it
+fn words(lines: &[Vec<char>]) -> Vec<(Pos, Pos, String)> {
+    let start = front_matter::find(lines).map_or(0, |block| block.end + 1);
+    let mut words = Vec::new();
+    let mut in_fence = false;
+    for (y, line) in lines.iter().enumerate().skip(start) {
+        if markdown::is_fence(line) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let mut x = 0;
+        while x < line.len() {
+            // One whitespace-separated chunk at a time, so URLs can be skipped
+            let chunk_end = (x..line.len()).find(|&i| line[i].is_whitespace()).unwrap_or(line.len());
+            let chunk: String = line[x..chunk_end].iter().collect();
+            if !chunk.contains("://") && !chunk.starts_with("www.") && !chunk.contains('@') {
+                let mut i = x;
+                while i < chunk_end {
+                    if !line[i].is_alphanumeric() {
+                        i += 1;
+                        continue;
+                    }
+                    let begin = i;
+                    while i < chunk_end && (line[i].is_alphanumeric() || (line[i] == '\'' && i + 1 < chunk_end && line[i + 1].is_alphabetic())) {
+                        i += 1;
+                    }
+                    let word: String = line[begin..i].iter().collect();
+                    if !word.chars().any(|c| c.is_ascii_digit()) && word.chars().count() > 1 {
+                        words.push((Pos::new(begin, y), Pos::new(i, y), word));
+                    }
+                }
+            }
+            x = chunk_end + 1;
+        }
+    }
+    words
+}
+
+// The words in 'words' no dictionary in 'langs' knows
+fn check(langs: &[String], words: &[String]) -> Result<HashSet<String>, String> {
+    let input = words.join("\n") + "\n";
+    // hunspell takes several dictionaries at once
+    let dictionaries = langs.join(",");
+    if let Some(output) = run("hunspell", &["-d", &dictionaries, "-l"], &input)? {
+        return Ok(output.lines().map(str::to_string).collect());
+    }
+    // aspell doesn't, so a word is wrong if every language says so
+    let mut wrong: Option<HashSet<String>> = None;
+    for lang in langs {
+        let Some(output) = run("aspell", &[&format!("--lang={}", lang), "list"], &input)? else {
+            return Err(t!("Spell checking needs hunspell or aspell installed"));
+        };
+        let here: HashSet<String> = output.lines().map(str::to_string).collect();
+        wrong = Some(match wrong {
+            Some(wrong) => wrong.intersection(&here).cloned().collect(),
+            None => here,
+        });
+    }
+    Ok(wrong.unwrap_or_default())
+}
+
+// Run a checker with 'input' on its standard input. Ok(None) if it isn't
+// installed; an error if it ran but failed (a missing dictionary, say).
+fn run(program: &str, args: &[&str], input: &str) -> Result<Option<String>, String> {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(t!("Couldn't run {command}: {error}", command = program, error = e)),
+    };
+    // Written from another thread so a long list can't block on a full pipe
+    let mut stdin = child.stdin.take();
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+    let output = child.wait_with_output().map_err(|e| format!("{}: {}", program, e))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}: {}", program, errors.lines().next().unwrap_or("failed").trim()));
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+}
@@ -0,0 +1,762 @@
+// river stats: writing statistics on an interactive screen
+//
+// The top half is a summary - today, the streak, averages, the hours and
+// weekdays you write on, and the mood timeline. Below it is a chart you
+// can move around in:
+//   ←/→   a period back or forward
+//   ↑/↓   switch between a week and a month
+//   Tab   switch between time, words, streak and the goal calendar
+//   e     save what the chart shows as a CSV file
+//   q     quit
+
+use chrono::{Datelike, Duration, Local, Months, NaiveDate};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    style::{Color, Print, ResetColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Stdout, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::term::{self, Foreground};
+use crate::theme::Theme;
+use crate::i18n::{self, t, tn};
+use crate::{archive, count_words_in_file, crypto, mood, width, DailyStats};
+
+// Width of the chart's bars
+const BAR_WIDTH: u64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chart {
+    Time,
+    Words,
+    Streak,
+    Calendar,
+}
+
+impl Chart {
+    fn next(self) -> Self {
+        match self {
+            Chart::Time => Chart::Words,
+            Chart::Words => Chart::Streak,
+            Chart::Streak => Chart::Calendar,
+            Chart::Calendar => Chart::Time,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Chart::Time => "time",
+            Chart::Words => "words",
+            Chart::Streak => "streak",
+            Chart::Calendar => "calendar",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Period {
+    Week,
+    Month,
+}
+
+// What the chart is showing: which chart, and how many weeks or months
+// back from the current one
+struct View {
+    chart: Chart,
+    period: Period,
+    offset: u32,
+}
+
+// One line of the chart: a day, or in the month view a week
+struct Row {
+    label: String,
+    dates: Vec<NaiveDate>,
+}
+
+pub fn show() -> io::Result<()> {
+    let config = Config::load();
+    let days = load_days(&config);
+    let today = Local::now().date_naive();
+    let mut view = View { chart: Chart::Time, period: Period::Week, offset: 0 };
+    let mut message: Option<String> = None;
+
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+    terminal::enable_raw_mode()?;
+
+    // Keep going until q, whatever happens, so the terminal is put back
+    let result = (|| -> io::Result<()> {
+        loop {
+            draw(&mut stdout, &config, &days, today, &view, message.as_deref())?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            message = None;
+            match key.code {
+                KeyCode::Left => view.offset += 1,
+                KeyCode::Right => view.offset = view.offset.saturating_sub(1),
+                KeyCode::Up | KeyCode::Down => {
+                    view.period = match view.period {
+                        Period::Week => Period::Month,
+                        Period::Month => Period::Week,
+                    };
+                    view.offset = 0;
+                }
+                KeyCode::Tab => view.chart = view.chart.next(),
+                KeyCode::Char('e') => {
+                    message = Some(match export(&config, &days, today, &view) {
+                        Ok(filename) => t!("Saved {file}", file = filename),
+                        Err(e) => t!("Export failed: {error}", error = e),
+                    });
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                _ => {}
+            }
+        }
+    })();
+
+    terminal::disable_raw_mode()?;
+    execute!(stdout, Show, LeaveAlternateScreen)?;
+    result
+}
+
+// Where stats go when the notes folder can't be written to: river's data
+// directory, in a folder named after the notes folder so profiles don't mix
+pub fn fallback_dir(config: &Config) -> PathBuf {
+    let notes_dir = fs::canonicalize(&config.daily_notes_dir).unwrap_or_else(|_| PathBuf::from(&config.daily_notes_dir));
+    let name: String = notes_dir
+        .to_string_lossy()
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':') { '%' } else { c })
+        .collect();
+    config::data_dir().join("stats").join(name)
+}
+
+// Every day with a stats file, oldest first, including those moved to
+// archive/ by river archive. Stats from before word counts were saved get
+// one from the day's note.
+fn load_days(config: &Config) -> BTreeMap<NaiveDate, DailyStats> {
+    let notes_dir = Path::new(&config.daily_notes_dir);
+    let archived = fs::read_dir(notes_dir.join(archive::DIR)).into_iter().flatten();
+    // Last, so a day saved there because the notes folder was read-only wins
+    let fallback = fs::read_dir(fallback_dir(config)).into_iter().flatten();
+    let mut days = BTreeMap::new();
+    for entry in fs::read_dir(notes_dir).into_iter().flatten().chain(archived).chain(fallback).flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(date) = name
+            .strip_prefix(".stats-")
+            .and_then(|rest| rest.strip_suffix(".toml"))
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        let Some(mut stats) = crypto::read_to_string(entry.path())
+            .ok()
+            .and_then(|contents| toml::from_str::<DailyStats>(&contents).ok())
+        else {
+            continue;
+        };
+        if stats.typing_seconds > 0 && stats.word_count == 0 {
+            let note = notes_dir.join(format!("{}.md", date.format("%Y-%m-%d")));
+            if let Ok(words) = count_words_in_file(&note, config) {
+                stats.word_count = words as u64;
+            }
+        }
+        days.insert(date, stats);
+    }
+    days
+}
+
+fn wrote_on(days: &BTreeMap<NaiveDate, DailyStats>, date: NaiveDate) -> bool {
+    days.get(&date).is_some_and(|stats| stats.typing_seconds > 0)
+}
+
+// Days in a row with some typing, counting back from 'end'
+fn streak_ending(days: &BTreeMap<NaiveDate, DailyStats>, end: NaiveDate) -> u32 {
+    let mut streak = 0;
+    let mut date = end;
+    while wrote_on(days, date) {
+        streak += 1;
+        date -= Duration::days(1);
+    }
+    streak
+}
+
+fn longest_streak(days: &BTreeMap<NaiveDate, DailyStats>) -> u32 {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for (&date, stats) in days {
+        if stats.typing_seconds == 0 {
+            current = 0;
+        } else if previous.is_some_and(|previous| previous + Duration::days(1) == date) && current > 0 {
+            current += 1;
+        } else {
+            current = 1;
+        }
+        longest = longest.max(current);
+        previous = Some(date);
+    }
+    longest
+}
+
+fn goal_for(config: &Config, stats: &DailyStats) -> u64 {
+    if stats.goal > 0 {
+        stats.goal
+    } else {
+        config.daily_word_goal as u64
+    }
+}
+
+// How a day went against its word goal, for the calendar
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GoalStatus {
+    Met,
+    Partial,
+    Missed,
+}
+
+fn goal_status(config: &Config, days: &BTreeMap<NaiveDate, DailyStats>, date: NaiveDate) -> GoalStatus {
+    match days.get(&date) {
+        Some(stats) if stats.word_count >= goal_for(config, stats) => GoalStatus::Met,
+        Some(stats) if stats.word_count > 0 || stats.typing_seconds > 0 => GoalStatus::Partial,
+        _ => GoalStatus::Missed,
+    }
+}
+
+// Totals for the seven days before 'end', for the weekly review note
+pub struct WeekTotals {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    pub words: u64,
+    pub minutes: u64,
+    pub days_written: usize,
+    pub goal_days: usize, // Days the word goal was met
+}
+
+pub fn week_totals(config: &Config, end: NaiveDate) -> WeekTotals {
+    let days = load_days(config);
+    let dates: Vec<NaiveDate> = (1..=7).rev().map(|back| end - Duration::days(back)).collect();
+    let week: Vec<&DailyStats> = dates.iter().filter_map(|date| days.get(date)).collect();
+    WeekTotals {
+        first: dates[0],
+        last: dates[6],
+        words: week.iter().map(|stats| stats.word_count).sum(),
+        minutes: week.iter().map(|stats| stats.typing_seconds).sum::<u64>() / 60,
+        days_written: dates.iter().filter(|date| wrote_on(&days, **date)).count(),
+        goal_days: dates.iter().filter(|date| goal_status(config, &days, **date) == GoalStatus::Met).count(),
+    }
+}
+
+// The first of the month the calendar shows: the month being paged
+// through, or in the week view the month the week ends in
+fn calendar_month(view: &View, today: NaiveDate) -> NaiveDate {
+    let first_of_this_month = today.with_day(1).unwrap_or(today);
+    match view.period {
+        Period::Week => {
+            let end = today - Duration::days(7 * view.offset as i64);
+            end.with_day(1).unwrap_or(end)
+        }
+        Period::Month => first_of_this_month - Months::new(view.offset),
+    }
+}
+
+// The chart's title and rows, newest first
+fn rows(view: &View, today: NaiveDate) -> (String, Vec<Row>) {
+    if view.chart == Chart::Calendar {
+        // One row per Monday-Sunday week of the month, oldest first
+        let first = calendar_month(view, today);
+        let last = first + Months::new(1) - Duration::days(1);
+        let mut rows: Vec<Row> = Vec::new();
+        for date in first.iter_days().take_while(|date| *date <= last) {
+            match rows.last_mut() {
+                Some(row) if date.weekday() != chrono::Weekday::Mon => row.dates.push(date),
+                _ => rows.push(Row { label: String::new(), dates: vec![date] }),
+            }
+        }
+        return (i18n::month_year(first), rows);
+    }
+
+    match view.period {
+        Period::Week => {
+            let end = today - Duration::days(7 * view.offset as i64);
+            let title = if view.offset == 0 {
+                t!("Last 7 Days")
+            } else {
+                t!("7 Days to {date}", date = i18n::short_date(end))
+            };
+            let rows = (0..7)
+                .map(|i| end - Duration::days(i))
+                .map(|date| Row { label: format!("{} {}", t!(&date.format("%a").to_string()), date.day()), dates: vec![date] })
+                .collect();
+            (title, rows)
+        }
+        Period::Month => {
+            let first_of_this_month = today.with_day(1).unwrap_or(today);
+            let first = first_of_this_month - Months::new(view.offset);
+            let last = (first + Months::new(1) - Duration::days(1)).min(today);
+            // Split the month into Monday-Sunday weeks
+            let mut rows: Vec<Row> = Vec::new();
+            for date in first.iter_days().take_while(|date| *date <= last) {
+                match rows.last_mut() {
+                    Some(row) if date.weekday() != chrono::Weekday::Mon => row.dates.push(date),
+                    _ => rows.push(Row { label: String::new(), dates: vec![date] }),
+                }
+            }
+            for row in &mut rows {
+                let (start, end) = (row.dates[0], row.dates[row.dates.len() - 1]);
+                row.label = format!("{} {}-{}", t!(&start.format("%b").to_string()), start.day(), end.day());
+            }
+            rows.reverse();
+            (i18n::month_year(first), rows)
+        }
+    }
+}
+
+fn draw(
+    stdout: &mut Stdout,
+    config: &Config,
+    days: &BTreeMap<NaiveDate, DailyStats>,
+    today: NaiveDate,
+    view: &View,
+    message: Option<&str>,
+) -> io::Result<()> {
+    execute!(stdout, Clear(ClearType::All))?;
+    let colors = config.colors();
+
+    // Header
+    execute!(
+        stdout,
+        MoveTo(2, 1),
+        Foreground(colors.heading),
+        Print(t!("River Writing Statistics")),
+        ResetColor
+    )?;
+
+    let last_30_days: Vec<NaiveDate> = (0..30).map(|i| today - Duration::days(i)).collect();
+    let today_typing = days.get(&today).map_or(0, |stats| stats.typing_seconds);
+    let streak = streak_ending(days, today);
+    let weekly_avg = last_30_days[..7]
+        .iter()
+        .filter_map(|date| days.get(date))
+        .map(|stats| stats.typing_seconds)
+        .sum::<u64>()
+        / 7;
+    let notes_dir = Path::new(&config.daily_notes_dir);
+    let total_files = last_30_days
+        .iter()
+        .filter(|date| notes_dir.join(format!("{}.md", date.format("%Y-%m-%d"))).exists())
+        .count();
+    let minutes_to_goal: Vec<u64> = last_30_days
+        .iter()
+        .filter_map(|date| days.get(date).and_then(|stats| stats.goal_minutes))
+        .collect();
+
+    let summary = [
+        (t!("Today:"), t!("{minutes} min", minutes = today_typing / 60), colors.time),
+        (t!("Current Streak:"), tn!(streak, "{n} day", "{n} days"), if streak > 0 { colors.streak } else { colors.dim }),
+        (t!("Weekly Average:"), t!("{minutes} min/day", minutes = weekly_avg / 60), colors.average),
+        (t!("Total Notes:"), format!("{}", total_files), colors.words),
+    ];
+    // The values line up after the longest label, which depends on the language
+    let labels = [t!("Time to Goal:"), t!("Mood (30 days):"), t!("Themes:")];
+    let longest = summary.iter().map(|(label, _, _)| label).chain(&labels).map(|label| width::str_width(label)).max().unwrap_or(0);
+    let value_x = (longest as u16 + 4).max(20);
+    for (i, (label, value, color)) in summary.iter().enumerate() {
+        execute!(
+            stdout,
+            MoveTo(2, 3 + i as u16),
+            Print(label),
+            MoveTo(value_x, 3 + i as u16),
+            Foreground(*color),
+            Print(value),
+            ResetColor
+        )?;
+    }
+
+    // Average time to goal, once there's a day it was reached
+    if !minutes_to_goal.is_empty() {
+        let average = minutes_to_goal.iter().sum::<u64>() / minutes_to_goal.len() as u64;
+        execute!(
+            stdout,
+            MoveTo(2, 7),
+            Print(&labels[0]),
+            MoveTo(value_x, 7),
+            Foreground(colors.time),
+            Print(tn!(minutes_to_goal.len(), "{minutes} min avg ({n} day)", "{minutes} min avg ({n} days)", minutes = average)),
+            ResetColor
+        )?;
+    }
+
+    draw_hours(stdout, colors, days, &last_30_days)?;
+    draw_weekdays(stdout, colors, days, today)?;
+    draw_chart(stdout, config, days, today, view)?;
+    draw_moods(stdout, colors, days, &last_30_days, value_x)?;
+
+    // Footer
+    execute!(
+        stdout,
+        MoveTo(2, 21),
+        Foreground(colors.dim),
+        Print(t!("←/→ page  ↑/↓ week/month  Tab time/words/streak/goal  e export  q quit")),
+        ResetColor
+    )?;
+    if let Some(message) = message {
+        execute!(stdout, MoveTo(2, 22), Print(message))?;
+    }
+
+    stdout.flush()
+}
+
+// When do I write: one cell per hour, darker for more keystrokes
+fn draw_hours(stdout: &mut Stdout, colors: &Theme, days: &BTreeMap<NaiveDate, DailyStats>, dates: &[NaiveDate]) -> io::Result<()> {
+    // Keystrokes in each hour of the day, added up over the 30 days
+    let mut hour_totals = [0u64; 24];
+    for stats in dates.iter().filter_map(|date| days.get(date)) {
+        for (total, count) in hour_totals.iter_mut().zip(&stats.hours) {
+            *total += count;
+        }
+    }
+    let glyphs = term::glyphs();
+    let busiest_hour = hour_totals.iter().copied().max().unwrap_or(0);
+    if busiest_hour == 0 {
+        return Ok(());
+    }
+    execute!(
+        stdout,
+        MoveTo(50, 3),
+        Foreground(colors.heading),
+        Print(t!("When You Write (30 days):")),
+        ResetColor,
+        MoveTo(50, 4),
+    )?;
+    for &count in &hour_totals {
+        let shade = if count == 0 {
+            glyphs.dot
+        } else {
+            glyphs.shades[(count * 4 / busiest_hour.max(1)) as usize]
+        };
+        execute!(stdout, Foreground(if count == 0 { colors.dim } else { colors.time }), Print(shade))?;
+    }
+    execute!(
+        stdout,
+        Foreground(colors.dim),
+        MoveTo(50, 5),
+        Print("0     6     12    18   23"),
+        ResetColor
+    )
+}
+
+// Average minutes for each day of the week, over all history
+fn draw_weekdays(stdout: &mut Stdout, colors: &Theme, days: &BTreeMap<NaiveDate, DailyStats>, today: NaiveDate) -> io::Result<()> {
+    let glyphs = term::glyphs();
+    let weekday_minutes = weekday_averages(days, today);
+    let busiest_weekday = weekday_minutes.iter().copied().max().unwrap_or(0).max(1);
+    execute!(
+        stdout,
+        MoveTo(50, 8),
+        Foreground(colors.heading),
+        Print(t!("By Weekday (avg):")),
+        ResetColor
+    )?;
+    for (i, minutes) in weekday_minutes.iter().enumerate() {
+        let weekday = chrono::Weekday::try_from(i as u8).unwrap_or(chrono::Weekday::Mon);
+        let bar_width = (minutes * 12 / busiest_weekday) as usize;
+        execute!(
+            stdout,
+            MoveTo(50, 10 + i as u16),
+            Print(format!("{:>3} ", t!(&weekday.to_string()))),
+            Foreground(colors.average),
+            Print(glyphs.full.repeat(bar_width)),
+            Foreground(colors.dim),
+            Print(glyphs.empty.repeat(12 - bar_width)),
+            Foreground(colors.heading),
+            Print(format!(" {}", t!("{minutes} min", minutes = format!("{:>3}", minutes)))),
+            ResetColor
+        )?;
+    }
+    Ok(())
+}
+
+// Average typing minutes for Monday..Sunday, from the first day with stats
+// up to today. Days with no stats count as 0 minutes, so a weekday you
+// usually skip shows up as a short bar.
+fn weekday_averages(days: &BTreeMap<NaiveDate, DailyStats>, today: NaiveDate) -> [u64; 7] {
+    let mut seconds = [0u64; 7];
+    for (date, stats) in days.range(..=today) {
+        seconds[date.weekday().num_days_from_monday() as usize] += stats.typing_seconds;
+    }
+    let Some(&first) = days.keys().next() else {
+        return [0; 7];
+    };
+    // How many of each weekday there have been since the first day
+    let mut count = [0u64; 7];
+    for date in first.iter_days().take_while(|date| *date <= today) {
+        count[date.weekday().num_days_from_monday() as usize] += 1;
+    }
+    let mut minutes = [0u64; 7];
+    for (i, minutes) in minutes.iter_mut().enumerate() {
+        *minutes = seconds[i] / count[i].max(1) / 60;
+    }
+    minutes
+}
+
+// The chart the arrow keys and Tab move around
+fn draw_chart(
+    stdout: &mut Stdout,
+    config: &Config,
+    days: &BTreeMap<NaiveDate, DailyStats>,
+    today: NaiveDate,
+    view: &View,
+) -> io::Result<()> {
+    let colors = config.colors();
+    let glyphs = term::glyphs();
+    let (title, rows) = rows(view, today);
+    let heading = match view.chart {
+        Chart::Time => t!("{title} - time", title = title),
+        Chart::Words => t!("{title} - words", title = title),
+        Chart::Streak => tn!(longest_streak(days), "{title} - streak (longest {n} day)", "{title} - streak (longest {n} days)", title = title),
+        Chart::Calendar => t!("{title} - goal", title = title),
+    };
+    execute!(
+        stdout,
+        MoveTo(2, 8),
+        Foreground(colors.heading),
+        Print(heading),
+        ResetColor
+    )?;
+
+    if view.chart == Chart::Calendar {
+        return draw_calendar(stdout, config, days, today, &rows);
+    }
+
+    // Totals for each row, and the largest for scaling the bars
+    let totals: Vec<(u64, u64)> = rows
+        .iter()
+        .map(|row| {
+            row.dates.iter().filter_map(|date| days.get(date)).fold((0, 0), |(minutes, words), stats| {
+                (minutes + stats.typing_seconds / 60, words + stats.word_count)
+            })
+        })
+        .collect();
+    let max = totals
+        .iter()
+        .map(|&(minutes, words)| if view.chart == Chart::Words { words } else { minutes })
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    for (i, (row, &(minutes, words))) in rows.iter().zip(&totals).enumerate() {
+        let y = 10 + i as u16;
+        execute!(stdout, MoveTo(2, y), Print(&row.label), MoveTo(12, y))?;
+
+        if view.chart == Chart::Streak {
+            // A dot per day you wrote, a speck for the others
+            let mut wrote = 0;
+            for date in &row.dates {
+                if wrote_on(days, *date) {
+                    wrote += 1;
+                    execute!(stdout, Foreground(colors.time), Print(glyphs.wrote))?;
+                } else {
+                    execute!(stdout, Foreground(colors.dim), Print(glyphs.dot))?;
+                }
+            }
+            execute!(
+                stdout,
+                Foreground(colors.heading),
+                MoveTo(28, y),
+                Print(tn!(row.dates.len(), "{wrote}/{n} day", "{wrote}/{n} days", wrote = wrote)),
+                ResetColor
+            )?;
+            continue;
+        }
+
+        let value = if view.chart == Chart::Words { words } else { minutes };
+        if value > 0 {
+            let bar_width = (value * BAR_WIDTH / max).min(BAR_WIDTH);
+            execute!(
+                stdout,
+                Foreground(if view.chart == Chart::Words { colors.words } else { colors.time }),
+                Print(glyphs.full.repeat(bar_width as usize)),
+                Foreground(colors.dim),
+                Print(glyphs.empty.repeat((BAR_WIDTH - bar_width) as usize)),
+                ResetColor
+            )?;
+        } else {
+            // A mark of its own for no writing at all
+            execute!(
+                stdout,
+                Foreground(colors.nothing),
+                Print(glyphs.nothing),
+                Foreground(colors.dim),
+                Print(glyphs.empty.repeat(BAR_WIDTH as usize - 1)),
+                ResetColor
+            )?;
+        }
+
+        // Both time and words in a compact format
+        execute!(
+            stdout,
+            MoveTo(28, y),
+            Foreground(colors.heading),
+            Print(t!("{minutes} min", minutes = format!("{:>3}", minutes))),
+            Foreground(colors.dim),
+            Print(format!(" {} ", glyphs.vertical)),
+            Foreground(colors.words),
+            Print(t!("{words} words", words = format!("{:>4}", words))),
+            ResetColor
+        )?;
+        // A tick for a day the goal was met
+        let met_goal = row.dates.len() == 1
+            && days.get(&row.dates[0]).is_some_and(|stats| stats.typing_seconds > 0 && words >= goal_for(config, stats));
+        if met_goal {
+            execute!(stdout, Foreground(colors.goal[0]), Print(format!(" {}", glyphs.tick)), ResetColor)?;
+        }
+    }
+    Ok(())
+}
+
+// A month grid, Monday first, each day marked (and coloured) by how it
+// went: ✓ for the goal met, ~ for some writing, nothing for the rest
+fn draw_calendar(
+    stdout: &mut Stdout,
+    config: &Config,
+    days: &BTreeMap<NaiveDate, DailyStats>,
+    today: NaiveDate,
+    weeks: &[Row],
+) -> io::Result<()> {
+    let colors = config.colors();
+    let glyphs = term::glyphs();
+    // The mark goes in the gap after the day's number
+    let marks = [(colors.goal[0], glyphs.tick), (colors.goal[1], '~'), (colors.goal[2], ' ')];
+    execute!(stdout, MoveTo(12, 9), Foreground(colors.dim), Print(t!("Mo Tu We Th Fr Sa Su")), ResetColor)?;
+    for (i, week) in weeks.iter().enumerate() {
+        for date in &week.dates {
+            let (color, mark) = if *date > today {
+                (Color::Reset, ' ')
+            } else {
+                match goal_status(config, days, *date) {
+                    GoalStatus::Met => marks[0],
+                    GoalStatus::Partial => marks[1],
+                    GoalStatus::Missed => marks[2],
+                }
+            };
+            let x = 12 + 3 * date.weekday().num_days_from_monday() as u16;
+            execute!(
+                stdout,
+                MoveTo(x, 10 + i as u16),
+                Foreground(color),
+                Print(format!("{:>2}{}", date.day(), mark)),
+                ResetColor
+            )?;
+        }
+    }
+
+    let legend = [t!("goal met"), t!("partial"), t!("missed")];
+    for (i, (label, (color, mark))) in legend.iter().zip(marks).enumerate() {
+        execute!(
+            stdout,
+            MoveTo(35, 10 + i as u16),
+            Foreground(color),
+            Print(format!("{}{} ", glyphs.square, mark)),
+            ResetColor,
+            Print(label)
+        )?;
+    }
+    Ok(())
+}
+
+// Mood timeline: one bar per day, oldest on the left, taller (and
+// coloured from red to green) the better the day went. Only shown once
+// moods are being recorded. 'value_x' is the column the summary's values
+// start at
+fn draw_moods(stdout: &mut Stdout, colors: &Theme, days: &BTreeMap<NaiveDate, DailyStats>, dates: &[NaiveDate], value_x: u16) -> io::Result<()> {
+    let moods: Vec<Option<&str>> = dates.iter().rev().map(|date| days.get(date).and_then(|stats| stats.mood.as_deref())).collect();
+    if moods.iter().all(Option::is_none) {
+        return Ok(());
+    }
+    let glyphs = term::glyphs();
+    execute!(stdout, MoveTo(2, 18), Print(t!("Mood (30 days):")), MoveTo(value_x, 18))?;
+    for mood in moods {
+        let (symbol, color) = match mood.and_then(mood::rank) {
+            Some(rank) => (glyphs.moods[rank], colors.moods[rank]),
+            None => (glyphs.dot, colors.dim),
+        };
+        execute!(stdout, Foreground(color), Print(symbol))?;
+    }
+    execute!(stdout, ResetColor)?;
+
+    // How often each theme came up this week: most common first, then
+    // alphabetical so the order is stable
+    let mut theme_counts: HashMap<&str, usize> = HashMap::new();
+    for stats in dates[..7].iter().filter_map(|date| days.get(date)) {
+        for theme in &stats.themes {
+            *theme_counts.entry(theme).or_insert(0) += 1;
+        }
+    }
+    let mut themes: Vec<(&str, usize)> = theme_counts.into_iter().collect();
+    themes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let themes: Vec<&str> = themes.into_iter().take(5).map(|(theme, _)| theme).collect();
+    if !themes.is_empty() {
+        execute!(
+            stdout,
+            MoveTo(2, 19),
+            Print(t!("Themes:")),
+            MoveTo(value_x, 19),
+            Foreground(colors.words),
+            Print(themes.join(", ")),
+            ResetColor
+        )?;
+    }
+    Ok(())
+}
+
+// Save the chart's days as CSV in the current directory, oldest first.
+// Returns the file name.
+fn export(config: &Config, days: &BTreeMap<NaiveDate, DailyStats>, today: NaiveDate, view: &View) -> io::Result<String> {
+    let (_, rows) = rows(view, today);
+    let mut dates: Vec<NaiveDate> = rows.into_iter().flat_map(|row| row.dates).collect();
+    dates.retain(|date| *date <= today);
+    dates.sort();
+    let (Some(first), Some(last)) = (dates.first(), dates.last()) else {
+        return Err(io::Error::other(t!("nothing to export")));
+    };
+
+    let mut csv = match view.chart {
+        Chart::Time => "date,minutes\n".to_string(),
+        Chart::Words => "date,words,goal\n".to_string(),
+        Chart::Streak => "date,wrote\n".to_string(),
+        Chart::Calendar => "date,words,goal,status\n".to_string(),
+    };
+    for date in &dates {
+        let stats = days.get(date).cloned().unwrap_or_default();
+        let line = match view.chart {
+            Chart::Time => format!("{},{}\n", date, stats.typing_seconds / 60),
+            Chart::Words => format!("{},{},{}\n", date, stats.word_count, goal_for(config, &stats)),
+            Chart::Streak => format!("{},{}\n", date, if stats.typing_seconds > 0 { "yes" } else { "no" }),
+            Chart::Calendar => {
+                let status = match goal_status(config, days, *date) {
+                    GoalStatus::Met => "met",
+                    GoalStatus::Partial => "partial",
+                    GoalStatus::Missed => "missed",
+                };
+                format!("{},{},{},{}\n", date, stats.word_count, goal_for(config, &stats), status)
+            }
+        };
+        csv.push_str(&line);
+    }
+
+    let filename = format!("river-stats-{}-{}-{}.csv", view.chart.name(), first, last);
+    fs::write(&filename, csv)?;
+    Ok(filename)
+}
@@ -0,0 +1,113 @@
+// :s - replace text, as in vim:
+//
+//   :s/teh/the/       the first "teh" on the cursor line
+//   :%s/teh/the/g     every one in the note (g: all on each line, not just the first)
+//   :5,9s/teh/the/gc  lines 5 to 9, asking y/n/a/q/l at each one
+//   :%s/Teh/the/gi    ignoring case
+//
+// Terms are plain text like / searches, not patterns, and an empty one
+// reuses the last search. Any punctuation can stand in for the slashes
+// (:s#a/b#c#), and \/ is a slash inside a term.
+
+use crate::filter;
+use crate::i18n::t;
+use crate::vim::Pos;
+
+pub struct Substitute {
+    pub pattern: Vec<char>,
+    pub replacement: Vec<char>,
+    pub global: bool,      // g
+    pub confirm: bool,     // c
+    pub ignore_case: bool, // i
+}
+
+// A :s command with its range (first and last line, 0-based). None if
+// 'cmd' isn't one; Err if it is, but can't be carried out.
+pub fn parse(cmd: &str, current: usize, last: usize, last_search: Option<&str>) -> Option<Result<(usize, usize, Substitute), String>> {
+    // The range is everything before the s
+    let s = cmd.find(|c: char| !(c.is_ascii_digit() || matches!(c, '%' | '.' | '$' | ',' | ' ')))?;
+    let rest = cmd[s..].strip_prefix('s')?;
+    let delimiter = rest.chars().next().filter(|c| c.is_ascii_punctuation() && !matches!(c, '\\' | '"' | '|'))?;
+    let range = &cmd[..s];
+    let (first, end) = if range.trim().is_empty() {
+        (current, current)
+    } else {
+        match filter::range(range, current, last) {
+            Some(lines) => lines,
+            None => return Some(Err(t!("Invalid range: {range}", range = range))),
+        }
+    };
+
+    let parts = split(&rest[delimiter.len_utf8()..], delimiter);
+    let pattern = match parts.first().filter(|pattern| !pattern.is_empty()) {
+        Some(pattern) => pattern.clone(),
+        None => match last_search {
+            Some(term) => term.chars().collect(),
+            None => return Some(Err(t!("No previous search to substitute"))),
+        },
+    };
+    let mut sub = Substitute {
+        pattern,
+        replacement: parts.get(1).cloned().unwrap_or_default(),
+        global: false,
+        confirm: false,
+        ignore_case: false,
+    };
+    for flag in parts.get(2).into_iter().flatten() {
+        match flag {
+            'g' => sub.global = true,
+            'c' => sub.confirm = true,
+            'i' => sub.ignore_case = true,
+            other => return Some(Err(t!("Unknown flag: {flag}", flag = other))),
+        }
+    }
+    Some(Ok((first, end, sub)))
+}
+
+// "teh/the/g" into its parts, with \/ kept as a /
+fn split(text: &str, delimiter: char) -> Vec<Vec<char>> {
+    let mut parts = vec![Vec::new()];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&delimiter) {
+            parts.last_mut().unwrap().push(delimiter);
+            chars.next();
+        } else if c == delimiter {
+            parts.push(Vec::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+impl Substitute {
+    fn matches_at(&self, line: &[char], x: usize) -> bool {
+        let Some(text) = line.get(x..x + self.pattern.len()) else {
+            return false;
+        };
+        if self.ignore_case {
+            text.iter().zip(&self.pattern).all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+        } else {
+            text == self.pattern.as_slice()
+        }
+    }
+
+    // The first match at or after 'from', up to the end of line 'last'
+    pub fn find(&self, lines: &[Vec<char>], from: Pos, last: usize) -> Option<Pos> {
+        (from.y..=last.min(lines.len().saturating_sub(1))).find_map(|y| {
+            let start = if y == from.y { from.x } else { 0 };
+            (start..lines[y].len()).find(|&x| self.matches_at(&lines[y], x)).map(|x| Pos::new(x, y))
+        })
+    }
+
+    // Where to look next after the match at 'at', once it's been replaced
+    // (or not): further along the line with g, otherwise the next line
+    pub fn after(&self, at: Pos, replaced: bool) -> Pos {
+        if !self.global {
+            return Pos::new(0, at.y + 1);
+        }
+        let len = if replaced { self.replacement.len() } else { self.pattern.len() };
+        Pos::new(at.x + len, at.y)
+    }
+}
@@ -0,0 +1,197 @@
+// What the terminal can show, and drawing within it
+//
+// river draws in the 16 named terminal colours (see theme.rs), which
+// crossterm sends as 256-colour codes, and uses box drawing and symbols
+// for bars and dividers. Not every terminal takes those:
+//   - NO_COLOR set to anything (see no-color.org): no colour
+//   - TERM=dumb, or no TERM at all outside Windows: no colour, and plain
+//     ASCII in place of the symbols
+//   - a TERM without "256color" or "direct" in its name, and no COLORTERM:
+//     the classic 16-colour codes, which every colour terminal knows
+// Without colour, a selection or highlight is drawn in reverse video, and
+// anything else colour shows - the goal, a misspelling - is in the text
+// or underlined already.
+
+use crossterm::style::{Color, SetBackgroundColor, SetForegroundColor};
+#[cfg(windows)]
+use crossterm::style::{Attribute, SetAttribute};
+use crossterm::Command;
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Colors {
+    None,
+    Basic, // The 16 colours' own codes
+    Full,  // 256 colours or more
+}
+
+pub struct Caps {
+    pub colors: Colors,
+    pub ascii: bool,
+}
+
+static CAPS: OnceLock<Caps> = OnceLock::new();
+
+pub fn caps() -> &'static Caps {
+    CAPS.get_or_init(detect)
+}
+
+fn detect() -> Caps {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    // The Windows console doesn't set TERM, and does colour
+    let dumb = term == "dumb" || (term.is_empty() && !cfg!(windows));
+    let colors = if dumb || !var("NO_COLOR").is_empty() {
+        Colors::None
+    } else if cfg!(windows) || !var("COLORTERM").is_empty() || term.contains("256color") || term.contains("direct") {
+        Colors::Full
+    } else {
+        Colors::Basic
+    };
+    Caps { colors, ascii: dumb }
+}
+
+// The symbols river draws with, or stand-ins for them
+pub struct Glyphs {
+    pub vertical: &'static str,   // Dividers
+    pub horizontal: &'static str,
+    pub dot: &'static str,        // Between status bar items, a day with no writing
+    pub spinner: &'static [char], // While a prompt is on its way
+    pub full: &'static str,       // Bars
+    pub empty: &'static str,
+    pub shades: [&'static str; 5], // The hours heatmap, lightest first
+    pub nothing: &'static str,    // A day with no writing on a bar chart
+    pub wrote: &'static str,      // A day written on the streak chart
+    pub tick: char,               // The goal met
+    pub square: &'static str,     // The calendar's legend
+    pub moods: [&'static str; 5], // Rough to great
+}
+
+const UNICODE: Glyphs = Glyphs {
+    vertical: "│",
+    horizontal: "─",
+    dot: "·",
+    spinner: &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'],
+    full: "█",
+    empty: "░",
+    shades: ["░", "▒", "▓", "▓", "█"],
+    nothing: "▬",
+    wrote: "●",
+    tick: '✓',
+    square: "■",
+    moods: ["▁", "▂", "▄", "▆", "█"],
+};
+
+const ASCII: Glyphs = Glyphs {
+    vertical: "|",
+    horizontal: "-",
+    dot: ".",
+    spinner: &['|', '/', '-', '\\'],
+    full: "#",
+    empty: ".",
+    shades: [":", "+", "*", "*", "#"],
+    nothing: "-",
+    wrote: "o",
+    tick: '*',
+    square: "#",
+    moods: ["1", "2", "3", "4", "5"],
+};
+
+pub fn glyphs() -> &'static Glyphs {
+    if caps().ascii { &ASCII } else { &UNICODE }
+}
+
+// In place of crossterm's SetForegroundColor
+pub struct Foreground(pub Color);
+
+// In place of SetBackgroundColor, for a background that's only decoration
+pub struct Background(pub Color);
+
+// Text and background colours for something that has to stand out from
+// the text around it - the selected entry, a highlight - which is reverse
+// video without colour. A Reset background is just the text colour.
+pub struct Standout(pub (Color, Color));
+
+impl Command for Foreground {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        match caps().colors {
+            Colors::None => Ok(()),
+            Colors::Basic => write!(f, "\x1b[{}m", basic_code(self.0)),
+            Colors::Full => SetForegroundColor(self.0).write_ansi(f),
+        }
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        match caps().colors {
+            Colors::None => Ok(()),
+            _ => SetForegroundColor(self.0).execute_winapi(),
+        }
+    }
+}
+
+impl Command for Background {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        match caps().colors {
+            Colors::None => Ok(()),
+            // Background codes are the text ones plus 10
+            Colors::Basic => write!(f, "\x1b[{}m", basic_code(self.0) + 10),
+            Colors::Full => SetBackgroundColor(self.0).write_ansi(f),
+        }
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        match caps().colors {
+            Colors::None => Ok(()),
+            _ => SetBackgroundColor(self.0).execute_winapi(),
+        }
+    }
+}
+
+impl Command for Standout {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let (text, background) = self.0;
+        if caps().colors == Colors::None {
+            return if background == Color::Reset { Ok(()) } else { f.write_str("\x1b[7m") };
+        }
+        Foreground(text).write_ansi(f)?;
+        Background(background).write_ansi(f)
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        let (text, background) = self.0;
+        if caps().colors == Colors::None {
+            return if background == Color::Reset { Ok(()) } else { SetAttribute(Attribute::Reverse).execute_winapi() };
+        }
+        Foreground(text).execute_winapi()?;
+        Background(background).execute_winapi()
+    }
+}
+
+// The colour's code from the original ANSI set (30-37) or the bright one
+// (90-97); river doesn't use any other colours
+fn basic_code(color: Color) -> u8 {
+    match color {
+        Color::Black => 30,
+        Color::DarkRed => 31,
+        Color::DarkGreen => 32,
+        Color::DarkYellow => 33,
+        Color::DarkBlue => 34,
+        Color::DarkMagenta => 35,
+        Color::DarkCyan => 36,
+        Color::Grey => 37,
+        Color::DarkGrey => 90,
+        Color::Red => 91,
+        Color::Green => 92,
+        Color::Yellow => 93,
+        Color::Blue => 94,
+        Color::Magenta => 95,
+        Color::Cyan => 96,
+        Color::White => 97,
+        _ => 39, // The terminal's own
+    }
+}
@@ -0,0 +1,188 @@
+// Counting words, sentences and so on in text. A word is a run of letters
+// or digits, so punctuation and whitespace of any kind separate words.
+
+use crate::front_matter;
+use crate::markdown;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextStats {
+    pub words: usize,
+    pub characters: usize,
+    pub characters_no_spaces: usize,
+    pub sentences: usize,
+    pub paragraphs: usize,
+}
+
+// The text that gets counted: with 'markdown' set the syntax is stripped
+// first so "# Title" or "- [ ] item" only count their words (see
+// markdown::strip_markup). Front matter never counts.
+fn prose(text: &str, markdown: bool) -> String {
+    if markdown {
+        markdown::strip_markup(text)
+    } else {
+        front_matter::strip(text).to_string()
+    }
+}
+
+pub fn count_words(text: &str, markdown: bool) -> usize {
+    count_plain_words(&prose(text, markdown))
+}
+
+// Words in one line of a note, for counting line by line (see lines.rs).
+// Adding these up for every line outside the front matter gives the same
+// total as count_words on the whole text.
+pub fn count_line_words(line: &[char], markdown: bool) -> usize {
+    let text: String = line.iter().collect();
+    if markdown {
+        count_plain_words(&markdown::strip_markup(&text))
+    } else {
+        count_plain_words(&text)
+    }
+}
+
+pub fn analyze(text: &str, markdown: bool) -> TextStats {
+    let prose = prose(text, markdown);
+    // Newlines separate lines rather than being part of the text
+    let characters = prose.chars().filter(|&c| c != '\n').count();
+    let spaces = prose.chars().filter(|&c| c != '\n' && c.is_whitespace()).count();
+
+    TextStats {
+        words: count_plain_words(&prose),
+        characters,
+        characters_no_spaces: characters - spaces,
+        sentences: count_sentences(&prose),
+        paragraphs: count_paragraphs(&prose),
+    }
+}
+
+// Whole minutes to read 'words' at 'wpm' words per minute, rounded up so
+// a short note still reads as "1 min"
+pub fn reading_minutes(words: usize, wpm: usize) -> usize {
+    words.div_ceil(wpm.max(1))
+}
+
+fn count_plain_words(text: &str) -> usize {
+    let mut word_count = 0;
+    let mut in_word = false;
+
+    for ch in text.chars() {
+        if ch.is_alphanumeric() {
+            if !in_word {
+                word_count += 1;
+                in_word = true;
+            }
+        } else {
+            in_word = false;
+        }
+    }
+    word_count
+}
+
+fn count_sentences(text: &str) -> usize {
+    sentences(text).len()
+}
+
+// Each sentence's text. A sentence is some words ended by '.', '!' or
+// '?', or by the end of its paragraph. "Wait..." or "?!" only end one.
+fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    for paragraph in paragraphs(text) {
+        let mut current = String::new();
+        let mut has_words = false;
+        for ch in paragraph.chars() {
+            current.push(ch);
+            if ch.is_alphanumeric() {
+                has_words = true;
+            } else if matches!(ch, '.' | '!' | '?') && has_words {
+                sentences.push(std::mem::take(&mut current));
+                has_words = false;
+            }
+        }
+        // A trailing fragment without a full stop still counts
+        if has_words {
+            sentences.push(current);
+        }
+    }
+    sentences
+}
+
+fn count_paragraphs(text: &str) -> usize {
+    paragraphs(text).count()
+}
+
+// Blocks of text separated by blank lines
+fn paragraphs(text: &str) -> impl Iterator<Item = String> + '_ {
+    let mut lines = text.lines().peekable();
+    std::iter::from_fn(move || {
+        // Skip blank lines before the paragraph
+        while lines.peek().is_some_and(|line| line.trim().is_empty()) {
+            lines.next();
+        }
+        let mut paragraph = String::new();
+        while let Some(line) = lines.next_if(|line| !line.trim().is_empty()) {
+            paragraph.push_str(line);
+            paragraph.push('\n');
+        }
+        (!paragraph.is_empty()).then_some(paragraph)
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Readability {
+    pub grade: f64,                 // Flesch-Kincaid grade level
+    pub words_per_sentence: f64,
+    pub long_sentences: usize,      // Sentences over the length threshold
+    pub long_sentence_percent: f64,
+}
+
+// Flesch-Kincaid grade level and sentence length figures. 'long_words' is
+// how many words make a sentence count as long.
+pub fn readability(text: &str, markdown: bool, long_words: usize) -> Readability {
+    let prose = prose(text, markdown);
+    let sentences = sentences(&prose);
+    let words: Vec<String> = sentences.iter().flat_map(|s| words(s)).collect();
+    if sentences.is_empty() || words.is_empty() {
+        return Readability::default();
+    }
+
+    let sentence_count = sentences.len() as f64;
+    let word_count = words.len() as f64;
+    let syllables: usize = words.iter().map(|w| count_syllables(w)).sum();
+    let long_sentences = sentences.iter().filter(|s| count_plain_words(s) > long_words).count();
+
+    let words_per_sentence = word_count / sentence_count;
+    Readability {
+        grade: 0.39 * words_per_sentence + 11.8 * (syllables as f64 / word_count) - 15.59,
+        words_per_sentence,
+        long_sentences,
+        long_sentence_percent: long_sentences as f64 * 100.0 / sentence_count,
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+// A rough English syllable count: groups of vowels, less a silent final
+// 'e', and never less than one
+pub fn count_syllables(word: &str) -> usize {
+    let chars: Vec<char> = word.to_lowercase().chars().collect();
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for &c in &chars {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    // "make", "stone" - but not "the" or "table"
+    if chars.len() > 2 && chars.ends_with(&['e']) && !chars.ends_with(&['l', 'e']) && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
@@ -0,0 +1,141 @@
+// Colour themes: which colour each part of the screen is drawn in
+//
+// The default theme is river's original look. The others are for eyes
+// and screens it doesn't suit:
+//   - "high-contrast" uses only bright colours, nothing dim grey on black
+//   - "deuteranopia" never tells two things apart by red against green,
+//     using blue and yellow instead
+//   - "monochrome" uses only black, white and greys
+// Colour is never the only sign of anything that matters - the goal shows
+// as GOAL! in the status bar and ✓ in the stats, a misspelling is
+// underlined - so a theme only changes how things look.
+
+use crossterm::style::Color;
+
+pub struct Theme {
+    // The editor
+    pub dim: Color,                // Line numbers, dividers, front matter, folds, the ghost prompt
+    pub line_number: Color,        // The cursor's line number
+    pub selected: (Color, Color),  // Text and background of the chosen entry in a list
+    pub menu: Color,               // Background of the other entries in a popup menu
+    pub highlight: (Color, Color), // Long sentences, :s///c matches and the like
+    pub misspelled: Color,         // Underlined, as are grammar issues
+    pub grammar: Color,
+    pub added: Color,              // Diff lines, which also start with + or -
+    pub removed: Color,
+    pub progress: [Color; 3],      // The status bar: short of 3/4 of the goal, past it, goal reached
+    pub celebrate: (Color, Color), // The whole status bar just after reaching the goal
+
+    // The stats screen
+    pub heading: Color,
+    pub time: Color,
+    pub words: Color,
+    pub streak: Color,
+    pub average: Color,
+    pub nothing: Color,            // The mark for a day with no writing at all
+    pub goal: [Color; 3],          // The calendar: goal met, partial, missed
+    pub moods: [Color; 5],         // Rough to great
+}
+
+pub const DEFAULT: Theme = Theme {
+    dim: Color::DarkGrey,
+    line_number: Color::Yellow,
+    selected: (Color::Black, Color::Cyan),
+    menu: Color::DarkGrey,
+    highlight: (Color::Reset, Color::DarkYellow),
+    misspelled: Color::Red,
+    grammar: Color::Blue,
+    added: Color::Green,
+    removed: Color::Red,
+    progress: [Color::White, Color::Yellow, Color::Green],
+    celebrate: (Color::Black, Color::Green),
+
+    heading: Color::Cyan,
+    time: Color::Green,
+    words: Color::Magenta,
+    streak: Color::Yellow,
+    average: Color::Blue,
+    nothing: Color::Red,
+    goal: [Color::Green, Color::Yellow, Color::DarkGrey],
+    moods: [Color::Red, Color::DarkYellow, Color::Yellow, Color::DarkGreen, Color::Green],
+};
+
+const HIGH_CONTRAST: Theme = Theme {
+    dim: Color::Grey,
+    line_number: Color::Yellow,
+    selected: (Color::Black, Color::Yellow),
+    menu: Color::Blue,
+    highlight: (Color::Black, Color::Cyan),
+    misspelled: Color::Red,
+    grammar: Color::Cyan,
+    added: Color::Green,
+    removed: Color::Red,
+    progress: [Color::White, Color::Yellow, Color::Green],
+    celebrate: (Color::Black, Color::Green),
+
+    heading: Color::White,
+    time: Color::Green,
+    words: Color::Magenta,
+    streak: Color::Yellow,
+    average: Color::Cyan,
+    nothing: Color::Red,
+    goal: [Color::Green, Color::Yellow, Color::Grey],
+    moods: [Color::Red, Color::Yellow, Color::White, Color::Cyan, Color::Green],
+};
+
+const DEUTERANOPIA: Theme = Theme {
+    dim: Color::DarkGrey,
+    line_number: Color::Yellow,
+    selected: (Color::Black, Color::Cyan),
+    menu: Color::DarkGrey,
+    highlight: (Color::Reset, Color::DarkBlue),
+    misspelled: Color::Yellow,
+    grammar: Color::Blue,
+    added: Color::Blue,
+    removed: Color::Yellow,
+    progress: [Color::White, Color::Yellow, Color::Cyan],
+    celebrate: (Color::Black, Color::Cyan),
+
+    heading: Color::Cyan,
+    time: Color::Blue,
+    words: Color::Magenta,
+    streak: Color::Yellow,
+    average: Color::Cyan,
+    nothing: Color::DarkYellow,
+    goal: [Color::Blue, Color::Yellow, Color::DarkGrey],
+    moods: [Color::DarkYellow, Color::Yellow, Color::White, Color::Cyan, Color::Blue],
+};
+
+const MONOCHROME: Theme = Theme {
+    dim: Color::DarkGrey,
+    line_number: Color::White,
+    selected: (Color::Black, Color::White),
+    menu: Color::DarkGrey,
+    highlight: (Color::Black, Color::Grey),
+    misspelled: Color::White,
+    grammar: Color::Grey,
+    added: Color::White,
+    removed: Color::DarkGrey,
+    progress: [Color::Grey, Color::White, Color::White],
+    celebrate: (Color::Black, Color::White),
+
+    heading: Color::White,
+    time: Color::Grey,
+    words: Color::Grey,
+    streak: Color::White,
+    average: Color::Grey,
+    nothing: Color::White,
+    goal: [Color::White, Color::Grey, Color::DarkGrey],
+    moods: [Color::DarkGrey, Color::DarkGrey, Color::Grey, Color::White, Color::White],
+};
+
+// The theme called 'name' in config.toml, if there is one
+pub fn named(name: &str) -> Option<&'static Theme> {
+    match name {
+        "default" => Some(&DEFAULT),
+        "high-contrast" => Some(&HIGH_CONTRAST),
+        "deuteranopia" => Some(&DEUTERANOPIA),
+        "monochrome" => Some(&MONOCHROME),
+        _ => None,
+    }
+}
@@ -0,0 +1,126 @@
+// Synonyms for the word under the cursor (:syn, zs in vim mode, Shift-F7
+// without), from a thesaurus file on disk - nothing goes online
+//
+// Two kinds of file work:
+//   - a plain list, one word per line followed by its synonyms, separated
+//     by commas: Moby's mthesaur.txt, or the files vim's 'thesaurus' uses
+//   - the MyThes .dat files LibreOffice installs (/usr/share/mythes),
+//     built from WordNet
+// thesaurus_file in config.toml picks one; otherwise river looks for
+// thesaurus.txt in its data directory, then for an English MyThes file.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::vim::Pos;
+
+// The popup: synonyms for the word between 'start' and 'end'
+pub struct Synonyms {
+    pub start: Pos,
+    pub end: Pos,
+    pub list: Vec<String>,
+    pub selected: usize,
+}
+
+impl Synonyms {
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.list.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+// The thesaurus to use, if there is one
+pub fn find_file(config: &Config) -> Option<PathBuf> {
+    if !config.thesaurus_file.is_empty() {
+        return Some(PathBuf::from(&config.thesaurus_file));
+    }
+    let own = config::data_dir().join("thesaurus.txt");
+    if own.is_file() {
+        return Some(own);
+    }
+    let mut mythes: Vec<PathBuf> = fs::read_dir("/usr/share/mythes")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("th_en")))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "dat"))
+        .collect();
+    mythes.sort();
+    mythes.pop()
+}
+
+// Synonyms for 'word', in the file's order, each once. Capitalised like
+// 'word' so they can go straight into the text.
+pub fn lookup(path: &Path, word: &str) -> io::Result<Vec<String>> {
+    // Read as bytes: older MyThes files are Latin-1, not UTF-8
+    let text = String::from_utf8_lossy(&fs::read(path)?).to_string();
+    let key = word.to_lowercase();
+    let found = if path.extension().is_some_and(|ext| ext == "dat") { mythes(&text, &key) } else { plain(&text, &key) };
+
+    let mut list: Vec<String> = Vec::new();
+    for synonym in found {
+        let synonym = match_case(&synonym, word);
+        if synonym.to_lowercase() != key && !list.contains(&synonym) {
+            list.push(synonym);
+        }
+    }
+    Ok(list)
+}
+
+// "word,synonym,synonym,..."
+fn plain(text: &str, key: &str) -> Vec<String> {
+    text.lines()
+        .find(|line| line.split(',').next().is_some_and(|head| head.trim().to_lowercase() == key))
+        .map(|line| line.split(',').skip(1).map(|w| w.trim().to_string()).filter(|w| !w.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+// MyThes: "word|2" then that many meanings, "(noun)|synonym|synonym (generic term)|..."
+fn mythes(text: &str, key: &str) -> Vec<String> {
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some((head, count)) = line.split_once('|') else {
+            continue;
+        };
+        let count: usize = count.trim().parse().unwrap_or(0);
+        if head.to_lowercase() != key {
+            // Skip the meanings, which could look like entries
+            for _ in 0..count {
+                lines.next();
+            }
+            continue;
+        }
+        return lines
+            .take(count)
+            .flat_map(|meaning| meaning.split('|').skip(1))
+            // "(generic term)" and the like aren't part of the word
+            .map(|synonym| synonym.split(" (").next().unwrap_or(synonym).trim().to_string())
+            .filter(|synonym| !synonym.is_empty())
+            .collect();
+    }
+    Vec::new()
+}
+
+// "Happy" -> "Glad", "HAPPY" -> "GLAD"
+fn match_case(synonym: &str, word: &str) -> String {
+    let mut chars = word.chars();
+    let first_upper = chars.next().is_some_and(char::is_uppercase);
+    if first_upper && word.chars().count() > 1 && chars.all(char::is_uppercase) {
+        return synonym.to_uppercase();
+    }
+    if first_upper {
+        let mut synonym_chars = synonym.chars();
+        if let Some(first) = synonym_chars.next() {
+            return first.to_uppercase().chain(synonym_chars).collect();
+        }
+    }
+    synonym.to_string()
+}
@@ -0,0 +1,62 @@
+// One-line AI summaries of notes (:title, or on quit with auto_title),
+// shown by river list and the fuzzy finder instead of just the date.
+// Stored as JSON in the data directory, like sessions.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteTitle {
+    pub title: String,
+    pub words: usize, // Word count when titled, to tell if the note has changed since
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TitleStore {
+    // Keyed by absolute file path
+    #[serde(default)]
+    notes: HashMap<String, NoteTitle>,
+}
+
+impl TitleStore {
+    // A missing or unreadable store just means nothing has a title yet
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&NoteTitle> {
+        self.notes.get(&Self::key(path))
+    }
+
+    pub fn set(&mut self, path: &Path, title: NoteTitle) {
+        self.notes.insert(Self::key(path), title);
+    }
+
+    fn key(path: &Path) -> String {
+        fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn path() -> PathBuf {
+        config::data_dir().join("titles.json")
+    }
+}
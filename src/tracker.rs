@@ -0,0 +1,180 @@
+// Typing time: how long you've spent writing today.
+//
+// Time counts in stretches. A keystroke within typing_timeout_seconds of
+// the one before carries the stretch on; a longer pause ends it at the
+// last keystroke, so time spent thinking, reading or away from the desk
+// isn't counted. Today's total is the finished stretches plus the one in
+// progress, worked out from the keystroke times whenever it's asked for -
+// nothing has to tick in the background for it to be right.
+//
+// Callers pass in the current time, which keeps this easy to test.
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct SessionTracker {
+    timeout: Duration,
+    banked: Duration, // Finished stretches, plus earlier runs of river today
+    // First and latest keystroke of the stretch in progress
+    stretch: Option<(Instant, Instant)>,
+}
+
+impl SessionTracker {
+    // 'banked' is time already recorded today, from the stats file
+    pub fn new(timeout: Duration, banked: Duration) -> Self {
+        SessionTracker { timeout, banked, stretch: None }
+    }
+
+    pub fn keystroke(&mut self, now: Instant) {
+        self.stretch = match self.stretch {
+            Some((start, last)) if now.saturating_duration_since(last) <= self.timeout => Some((start, now)),
+            Some((start, last)) => {
+                // The pause was too long: the old stretch ended at its
+                // last keystroke and this one starts a new stretch
+                self.banked += last - start;
+                Some((now, now))
+            }
+            None => Some((now, now)),
+        };
+    }
+
+    // Today's typing time so far
+    pub fn total(&self) -> Duration {
+        match self.stretch {
+            Some((start, last)) => self.banked + (last - start),
+            None => self.banked,
+        }
+    }
+
+    // True when nothing has been typed for longer than the timeout, so
+    // the clock isn't running
+    pub fn is_idle(&self, now: Instant) -> bool {
+        match self.stretch {
+            Some((_, last)) => now.saturating_duration_since(last) > self.timeout,
+            None => true,
+        }
+    }
+
+    // Start again from 'banked', e.g. on a new day or in another profile
+    pub fn reset(&mut self, banked: Duration) {
+        self.banked = banked;
+        self.stretch = None;
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn nothing_typed_is_no_time() {
+        let tracker = SessionTracker::new(secs(5), secs(0));
+        assert_eq!(tracker.total(), secs(0));
+        assert!(tracker.is_idle(Instant::now()));
+    }
+
+    #[test]
+    fn starts_from_banked_time() {
+        let tracker = SessionTracker::new(secs(5), secs(600));
+        assert_eq!(tracker.total(), secs(600));
+    }
+
+    #[test]
+    fn a_single_keystroke_adds_nothing_yet() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.keystroke(t0);
+        assert_eq!(tracker.total(), secs(0));
+    }
+
+    #[test]
+    fn keystrokes_within_the_timeout_count_the_time_between() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.keystroke(t0);
+        tracker.keystroke(t0 + secs(3));
+        tracker.keystroke(t0 + secs(7));
+        assert_eq!(tracker.total(), secs(7));
+    }
+
+    #[test]
+    fn a_pause_longer_than_the_timeout_is_not_counted() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.keystroke(t0);
+        tracker.keystroke(t0 + secs(4));
+        // 60 second pause
+        tracker.keystroke(t0 + secs(64));
+        tracker.keystroke(t0 + secs(66));
+        assert_eq!(tracker.total(), secs(4 + 2));
+    }
+
+    #[test]
+    fn a_pause_of_exactly_the_timeout_still_counts() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.keystroke(t0);
+        tracker.keystroke(t0 + secs(5));
+        assert_eq!(tracker.total(), secs(5));
+    }
+
+    #[test]
+    fn total_does_not_grow_while_idle() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.keystroke(t0);
+        tracker.keystroke(t0 + secs(2));
+        let before = tracker.total();
+        assert!(tracker.is_idle(t0 + secs(30)));
+        assert_eq!(tracker.total(), before);
+    }
+
+    #[test]
+    fn total_is_the_same_however_often_it_is_asked_for() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        for i in 0..10 {
+            tracker.keystroke(t0 + secs(i));
+            let _ = tracker.total();
+        }
+        assert_eq!(tracker.total(), secs(9));
+    }
+
+    #[test]
+    fn idle_only_after_the_timeout() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.keystroke(t0);
+        assert!(!tracker.is_idle(t0 + secs(5)));
+        assert!(tracker.is_idle(t0 + secs(6)));
+    }
+
+    #[test]
+    fn reset_drops_the_stretch_in_progress() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(100));
+        tracker.keystroke(t0);
+        tracker.keystroke(t0 + secs(3));
+        tracker.reset(secs(40));
+        assert_eq!(tracker.total(), secs(40));
+        assert!(tracker.is_idle(t0 + secs(3)));
+    }
+
+    #[test]
+    fn a_longer_timeout_joins_stretches() {
+        let t0 = Instant::now();
+        let mut tracker = SessionTracker::new(secs(5), secs(0));
+        tracker.set_timeout(secs(60));
+        tracker.keystroke(t0);
+        tracker.keystroke(t0 + secs(30));
+        assert_eq!(tracker.total(), secs(30));
+    }
+}
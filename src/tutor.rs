@@ -0,0 +1,178 @@
+// river tutor - a lesson note to work through in river itself, like
+// vimtutor. It's written fresh each time (tutor.md in the data directory)
+// so the exercises can be done again, and it follows config.toml: the vim
+// lessons with vim_bindings on, the plain keys without, and the goal and
+// notes folder actually in use.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::{self, Config};
+
+// Write the tutorial and return where it is
+pub fn write(config: &Config) -> io::Result<PathBuf> {
+    let path = config::data_dir().join("tutor.md");
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, text(config))?;
+    Ok(path)
+}
+
+fn text(config: &Config) -> String {
+    let mut text = String::from(INTRO);
+    text.push_str(if config.vim_bindings { VIM } else { STANDARD });
+    text.push_str(GOALS);
+    text.push_str(if config.vim_bindings { PROMPTS_VIM } else { PROMPTS_STANDARD });
+    text.push_str(OUTRO);
+    text.replace("{{goal}}", &config.daily_word_goal.to_string())
+        .replace("{{notes_dir}}", &config.daily_notes_dir)
+        .replace("{{config}}", &Config::config_path().to_string_lossy())
+}
+
+const INTRO: &str = "# Welcome to the river tutor
+
+River is a quiet place to write every day. This note is a practice
+copy: change anything you like, nothing here counts towards your
+writing time, and `river tutor` gives you a fresh one next time.
+
+Lines starting with ---> are exercises. Do what the lesson says to them.
+
+";
+
+const VIM: &str = "## Lesson 1: Modes
+
+River starts in NORMAL mode, where keys are commands rather than
+text. INSERT mode is for typing; Esc always goes back to NORMAL.
+
+  1. Move the cursor with h (left), j (down), k (up) and l (right).
+     The arrow keys work too. Move down to the line marked --->.
+  2. Press i to INSERT before the cursor, type the missing word, and
+     press Esc to go back to NORMAL mode.
+
+---> The river flowed past the old stone .
+
+  3. A (append) types at the end of the line instead. Try it:
+
+---> Every morning I write a few
+
+  4. R is REPLACE mode: what you type goes over the text that's there.
+     -- REPLACE -- shows at the bottom while it's on. Make it 2026:
+
+---> River was first written in 1999.
+
+## Lesson 2: Moving faster
+
+  w and b jump a word forwards and back, e to the end of a word.
+  ( and ) go by sentence, { and } by paragraph, gg and G to the first
+  and last line. ]] and [[ go from heading to heading - try it now to
+  visit each lesson.
+
+  Put a number first to repeat a motion: 3w is three words.
+
+## Lesson 3: Changing text
+
+  x deletes the character under the cursor. dw deletes a word, dd the
+  whole line. cw changes a word: it deletes it and leaves you in
+  INSERT mode. After any change, . does it again.
+
+---> The the river is is very cold today.
+---> Delete this whole line with dd.
+---> Change this word: apple. (Put the cursor on apple, type cw, then pear, Esc)
+
+  p puts back what was deleted last, after the cursor.
+
+## Lesson 4: The command line
+
+  : opens the command line at the bottom. Type a command and press
+  Enter; Esc gives up on it. Tab completes command names, and Up and
+  Down bring back earlier ones.
+
+  Try these:
+    :count       words, sentences and reading time for this note
+    :w           save now (river also saves by itself as you write)
+    :help        every key and command - F1 does the same anywhere
+
+  / searches forward: type /stone and Enter, then n for the next one.
+
+";
+
+const STANDARD: &str = "## Lesson 1: Just type
+
+Without vim bindings river works like most editors: what you type goes
+in at the cursor.
+
+  1. Use the arrow keys to move to the line marked --->.
+  2. Type the missing word.
+
+---> The river flowed past the old stone .
+
+  Home and End go to the start and end of a line. Ctrl-Left and
+  Ctrl-Right go a word at a time.
+
+## Lesson 2: Deleting
+
+  Backspace and Delete remove a character. Ctrl-Backspace (or Ctrl-W)
+  removes the word before the cursor, and Ctrl-K the rest of the line.
+
+---> The the river is is very cold today.
+---> Remove the end of this line with Ctrl-K: everything after here
+
+## Lesson 3: Saving and help
+
+  River saves by itself as you write. Ctrl-S saves straight away, if
+  you'd like to see it happen.
+
+  F1 lists every key. Press it now, read for a moment, then press q
+  to come back.
+
+  Ctrl-T ticks a checkbox on the line the cursor is on:
+
+---> - [ ] Learn river
+
+";
+
+const GOALS: &str = "## Goals
+
+The bar at the bottom fills up as you write. Today's note is done at
+{{goal}} words - daily_word_goal in config.toml changes that for every
+day. When you get there the bar celebrates.
+
+A single note can have its own goal in its front matter, the block
+between --- lines at the very top:
+
+    ---
+    goal: 750
+    ---
+
+";
+
+const PROMPTS_VIM: &str = "  :goal shows the goal for this note, and :goal 2000 gives it one.
+  Try :goal 2000 now and watch the bar at the bottom drop.
+
+## Prompts
+
+When today's note is still empty, a writing prompt appears faintly
+where you'd start typing. It goes away as soon as you write. Lost it?
+:prompt brings it back. Try it now.
+
+";
+
+const PROMPTS_STANDARD: &str = "## Prompts
+
+When today's note is still empty, a writing prompt appears faintly
+where you'd start typing. It goes away as soon as you write.
+
+";
+
+const OUTRO: &str = "## Where things are
+
+  Your notes:   {{notes_dir}}, one file per day
+  Settings:     {{config}}
+                (river config edit opens it, river config check tests it)
+  Stats:        river stats shows your writing time and streak
+
+That's it. Quit with Ctrl-Q, then run river on its own to start
+today's note.
+";
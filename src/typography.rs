@@ -0,0 +1,32 @@
+// Smart typography (smart_typography = true in config.toml)
+//
+// As you type, straight quotes become curly ones, -- an en dash, a third
+// hyphen an em dash and ... an ellipsis. Code - inline `spans` and fenced
+// blocks - is left alone (see Editor::insert_char), and so are lines of
+// nothing but hyphens, which are markdown rules and front matter fences.
+
+// What typing 'c' after 'before' (the line up to the cursor) should do
+// instead of inserting it: how many characters before the cursor to
+// replace, and the character to put there. None means type 'c' as usual.
+pub fn substitute(before: &[char], c: char) -> Option<(usize, char)> {
+    let previous = before.last().copied();
+    match c {
+        '"' => Some((0, if opens_quote(previous) { '“' } else { '”' })),
+        // Also the apostrophe in "don't", which takes the closing form
+        '\'' => Some((0, if opens_quote(previous) { '‘' } else { '’' })),
+        '-' if before.iter().all(|c| matches!(c, '-' | '–')) => None,
+        '-' if previous == Some('-') => Some((1, '–')),
+        '-' if previous == Some('–') => Some((1, '—')),
+        '.' if before.ends_with(&['.', '.']) => Some((2, '…')),
+        _ => None,
+    }
+}
+
+// A quote opens at the start of a line and after a space or bracket;
+// anywhere else it closes
+fn opens_quote(previous: Option<char>) -> bool {
+    match previous {
+        None => true,
+        Some(c) => c.is_whitespace() || matches!(c, '(' | '[' | '{' | '—' | '–' | '“' | '‘'),
+    }
+}
@@ -0,0 +1,829 @@
+// Multi-key normal mode commands (vim bindings)
+//
+// Most normal mode keys act immediately, but some need more keys before
+// they mean anything: "f" waits for the character to find, "d" waits for
+// a motion, and so on. The Editor collects those keys in a small pending
+// string and asks parse() what it has so far:
+// - Incomplete: keep waiting for more keys
+// - Invalid: the sequence can't become a command, throw it away
+// - Complete: run the resulting Action
+//
+// The motion helpers below are plain functions over the text so they can
+// be used both to move the cursor and to find the far end of an operator.
+
+use serde::{Deserialize, Serialize};
+
+use crate::markdown;
+
+// A position in the buffer. Fields are ordered y-then-x so the derived
+// comparison (<, >, min, max) matches reading order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Pos {
+    pub y: usize,
+    pub x: usize,
+}
+
+impl Pos {
+    pub fn new(x: usize, y: usize) -> Self {
+        Pos { y, x }
+    }
+}
+
+// Which way a character search goes, and whether it stops on the
+// character (f/F) or just before it (t/T)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FindKind {
+    Forward,      // f
+    TillForward,  // t
+    Backward,     // F
+    TillBackward, // T
+}
+
+impl FindKind {
+    fn from_key(key: char) -> Option<Self> {
+        match key {
+            'f' => Some(FindKind::Forward),
+            't' => Some(FindKind::TillForward),
+            'F' => Some(FindKind::Backward),
+            'T' => Some(FindKind::TillBackward),
+            _ => None,
+        }
+    }
+
+    // ',' repeats a search in the opposite direction
+    pub fn reversed(self) -> Self {
+        match self {
+            FindKind::Forward => FindKind::Backward,
+            FindKind::TillForward => FindKind::TillBackward,
+            FindKind::Backward => FindKind::Forward,
+            FindKind::TillBackward => FindKind::TillForward,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharFind {
+    pub kind: FindKind,
+    pub ch: char,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    Left,              // h
+    Right,             // l
+    Up,                // k
+    Down,              // j
+    LineStart,         // 0
+    FirstNonBlank,     // ^
+    LineEnd,           // $
+    WordForward,       // w
+    WordBackward,      // b
+    WordEnd,           // e
+    FirstLine,         // gg
+    LastLine,          // G
+    SentenceForward,   // )
+    SentenceBackward,  // (
+    ParagraphForward,  // }
+    ParagraphBackward, // {
+    HeadingForward,    // ]]
+    HeadingBackward,   // [[
+    FindChar(CharFind),
+}
+
+// How an operator treats the text between the cursor and a motion's end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotionKind {
+    Exclusive, // Up to but not including the end (w, b, h, ...)
+    Inclusive, // Including the character at the end (e, $, f, t)
+    Linewise,  // Whole lines (j, k, G)
+}
+
+impl Motion {
+    pub fn kind(&self) -> MotionKind {
+        match self {
+            Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine => MotionKind::Linewise,
+            Motion::LineEnd | Motion::WordEnd => MotionKind::Inclusive,
+            Motion::FindChar(find) if matches!(find.kind, FindKind::Forward | FindKind::TillForward) => {
+                MotionKind::Inclusive
+            }
+            _ => MotionKind::Exclusive,
+        }
+    }
+
+    fn from_key(key: char) -> Option<Self> {
+        match key {
+            'h' => Some(Motion::Left),
+            'l' => Some(Motion::Right),
+            'k' => Some(Motion::Up),
+            'j' => Some(Motion::Down),
+            '0' => Some(Motion::LineStart),
+            '^' => Some(Motion::FirstNonBlank),
+            '$' => Some(Motion::LineEnd),
+            'w' => Some(Motion::WordForward),
+            'b' => Some(Motion::WordBackward),
+            'e' => Some(Motion::WordEnd),
+            'G' => Some(Motion::LastLine),
+            ')' => Some(Motion::SentenceForward),
+            '(' => Some(Motion::SentenceBackward),
+            '}' => Some(Motion::ParagraphForward),
+            '{' => Some(Motion::ParagraphBackward),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Delete,     // d
+    Yank,       // y
+    Change,     // c
+    Indent,     // >
+    Outdent,    // <
+    Lowercase,  // gu
+    Uppercase,  // gU
+    ToggleCase, // g~
+    Link,       // gl - make the text a markdown link (see link.rs)
+    Filter,     // ! - run the lines through a shell command (see filter.rs)
+}
+
+impl Operator {
+    fn from_key(key: char) -> Option<Self> {
+        match key {
+            'd' => Some(Operator::Delete),
+            'y' => Some(Operator::Yank),
+            'c' => Some(Operator::Change),
+            '>' => Some(Operator::Indent),
+            '<' => Some(Operator::Outdent),
+            '!' => Some(Operator::Filter),
+            _ => None,
+        }
+    }
+
+    // Operators are one key (d) or two (gu). Returns the operator and how
+    // many keys it took.
+    fn from_keys(keys: &[char]) -> Option<(Self, usize)> {
+        match keys {
+            ['g', 'u', ..] => Some((Operator::Lowercase, 2)),
+            ['g', 'U', ..] => Some((Operator::Uppercase, 2)),
+            ['g', '~', ..] => Some((Operator::ToggleCase, 2)),
+            ['g', 'l', ..] => Some((Operator::Link, 2)),
+            [key, ..] => Operator::from_key(*key).map(|op| (op, 1)),
+            [] => None,
+        }
+    }
+}
+
+// Text objects select a piece of text around the cursor rather than
+// moving it: "ip" is the paragraph the cursor is in, "a\"" a quoted
+// string including its quotes. 'i' (inner) leaves out the surrounding
+// whitespace or delimiters, 'a' (around) takes them too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+    Word,      // w
+    Sentence,  // s
+    Paragraph, // p
+    Quote,     // " (also matches curly “ ” quotes)
+    Parens,    // ( ) b
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextObject {
+    pub kind: ObjectKind,
+    pub around: bool,
+}
+
+impl ObjectKind {
+    fn from_key(key: char) -> Option<Self> {
+        match key {
+            'w' => Some(ObjectKind::Word),
+            's' => Some(ObjectKind::Sentence),
+            'p' => Some(ObjectKind::Paragraph),
+            '"' => Some(ObjectKind::Quote),
+            '(' | ')' | 'b' => Some(ObjectKind::Parens),
+            _ => None,
+        }
+    }
+}
+
+// The text an operator works on: characters from start up to (not
+// including) end, or whole lines first..=last
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Span {
+    Chars(Pos, Pos),
+    Lines(usize, usize),
+}
+
+// v / V - the selection runs from where it was started (the anchor) to
+// the cursor, by character or by whole lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub anchor: Pos,
+    pub linewise: bool,
+}
+
+impl Selection {
+    // The selected text. Both ends are part of it, unlike a motion's end.
+    pub fn span(&self, cursor: Pos, lines: &[Vec<char>]) -> Span {
+        // A key binding's command can change the text under the anchor
+        let y = self.anchor.y.min(lines.len() - 1);
+        let anchor = Pos::new(self.anchor.x.min(lines[y].len()), y);
+        let start = anchor.min(cursor);
+        let end = anchor.max(cursor);
+        if self.linewise {
+            Span::Lines(start.y, end.y)
+        } else {
+            Span::Chars(start, Pos::new((end.x + 1).min(lines[end.y].len()), end.y))
+        }
+    }
+}
+
+// zt / zz / zb - where to put the cursor line in the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollTo {
+    Top,
+    Center,
+    Bottom,
+}
+
+// za / zR / zM - section folding (see fold.rs)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FoldCommand {
+    Toggle,   // za - fold the cursor's section, or open it
+    OpenAll,  // zR
+    CloseAll, // zM
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Move(Motion),
+    Operate(Operator, Motion),           // d{motion}, y{motion}, c{motion}
+    OperateLine(Operator),               // dd, yy, cc
+    OperateObject(Operator, TextObject), // diw, cip, ya", ...
+    OperateSelection(Operator),          // d, y, c, >, ... on a v/V selection
+    SelectObject(TextObject),            // viw, vap, ...: select a text object
+    ReplaceChar(char),                   // r{char}
+    Scroll(ScrollTo),                    // zt, zz, zb
+    Fold(FoldCommand),                   // za, zR, zM
+    SetMark(char),                       // m{a-z}
+    JumpToMark(char, bool),              // '{a-z} (line) or `{a-z} (exact position)
+    AddWord,                             // zg
+    Synonyms,                            // zs
+    GrammarIssue(bool),                  // ]g (true) or [g: the next or previous grammar issue
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Parse {
+    Incomplete,
+    Invalid,
+    Complete(Action),
+}
+
+// Does this key begin a multi-key command?
+pub fn starts_sequence(key: char) -> bool {
+    matches!(key, 'r' | 'z' | 'm' | '\'' | '`' | 'g' | ']' | '[') || FindKind::from_key(key).is_some() || Operator::from_key(key).is_some()
+}
+
+pub fn parse(keys: &str) -> Parse {
+    let chars: Vec<char> = keys.chars().collect();
+    // split_first gives the first key and a slice of the rest
+    let Some((&first, rest)) = chars.split_first() else {
+        return Parse::Incomplete;
+    };
+
+    if first == 'r' {
+        return match rest {
+            [] => Parse::Incomplete,
+            [ch] => Parse::Complete(Action::ReplaceChar(*ch)),
+            _ => Parse::Invalid,
+        };
+    }
+
+    if first == 'z' {
+        return match rest {
+            [] => Parse::Incomplete,
+            ['t'] => Parse::Complete(Action::Scroll(ScrollTo::Top)),
+            ['z'] => Parse::Complete(Action::Scroll(ScrollTo::Center)),
+            ['b'] => Parse::Complete(Action::Scroll(ScrollTo::Bottom)),
+            ['a'] => Parse::Complete(Action::Fold(FoldCommand::Toggle)),
+            ['R'] => Parse::Complete(Action::Fold(FoldCommand::OpenAll)),
+            ['M'] => Parse::Complete(Action::Fold(FoldCommand::CloseAll)),
+            ['g'] => Parse::Complete(Action::AddWord),
+            ['s'] => Parse::Complete(Action::Synonyms),
+            _ => Parse::Invalid,
+        };
+    }
+
+    if matches!(first, 'm' | '\'' | '`') {
+        return match rest {
+            [] => Parse::Incomplete,
+            [name] if name.is_ascii_lowercase() => Parse::Complete(match first {
+                'm' => Action::SetMark(*name),
+                _ => Action::JumpToMark(*name, first == '`'),
+            }),
+            _ => Parse::Invalid,
+        };
+    }
+
+    if let [']' | '[', 'g'] = chars.as_slice() {
+        return Parse::Complete(Action::GrammarIssue(first == ']'));
+    }
+
+    match Operator::from_keys(&chars) {
+        // The keys after the operator
+        Some((op, len)) => match &chars[len..] {
+            [] => Parse::Incomplete,
+            // Doubling the operator (dd, yy, >>) works on the whole line;
+            // for two-key operators repeating the last key is enough (guu)
+            [key] if *key == chars[len - 1] => Parse::Complete(Action::OperateLine(op)),
+            keys if keys == &chars[..len] => Parse::Complete(Action::OperateLine(op)),
+            ['i' | 'a'] => Parse::Incomplete,
+            [scope @ ('i' | 'a'), key] => match ObjectKind::from_key(*key) {
+                Some(kind) => Parse::Complete(Action::OperateObject(op, TextObject { kind, around: *scope == 'a' })),
+                None => Parse::Invalid,
+            },
+            keys => match parse_motion(keys) {
+                Parse::Complete(Action::Move(motion)) => Parse::Complete(Action::Operate(op, motion)),
+                other => other,
+            },
+        },
+        None => parse_motion(&chars),
+    }
+}
+
+// Keys in visual mode (v, V). An operator acts on the selection straight
+// away, with x, s, u, U and ~ as one-key stand-ins for d, c, gu, gU and
+// g~; a text object (iw, ap) becomes the selection; anything else is a
+// motion that moves the cursor end of it.
+pub fn parse_visual(keys: &str) -> Parse {
+    let chars: Vec<char> = keys.chars().collect();
+    match chars.as_slice() {
+        ['i' | 'a'] => return Parse::Incomplete,
+        [scope @ ('i' | 'a'), key] => {
+            return match ObjectKind::from_key(*key) {
+                Some(kind) => Parse::Complete(Action::SelectObject(TextObject { kind, around: *scope == 'a' })),
+                None => Parse::Invalid,
+            };
+        }
+        _ => {}
+    }
+    let op = match chars.as_slice() {
+        ['x'] => Some(Operator::Delete),
+        ['s'] => Some(Operator::Change),
+        ['u'] => Some(Operator::Lowercase),
+        ['U'] => Some(Operator::Uppercase),
+        ['~'] => Some(Operator::ToggleCase),
+        keys => Operator::from_keys(keys).filter(|&(_, len)| len == keys.len()).map(|(op, _)| op),
+    };
+    match op {
+        Some(op) => Parse::Complete(Action::OperateSelection(op)),
+        None => parse_motion(&chars),
+    }
+}
+
+// Does this complete normal mode command change the text? Used to pick
+// out what '.' should repeat. Leading count digits are skipped.
+pub fn is_change(keys: &str) -> bool {
+    let command = keys.trim_start_matches(|c: char| c.is_ascii_digit());
+    match command.chars().next() {
+        Some('x' | 'p' | 'P' | 'J' | 'r' | 's' | 'S' | 'C' | 'R' | 'i' | 'I' | 'a' | 'A' | 'o' | 'O') => true,
+        Some('~') => true,
+        Some(_) => {
+            let chars: Vec<char> = command.chars().collect();
+            Operator::from_keys(&chars).is_some_and(|(op, _)| op != Operator::Yank)
+        }
+        None => false,
+    }
+}
+
+// A motion on its own: a single key, gg, or f/t/F/T plus a character
+fn parse_motion(chars: &[char]) -> Parse {
+    match chars {
+        ['g'] => Parse::Incomplete,
+        ['g', 'g'] => Parse::Complete(Action::Move(Motion::FirstLine)),
+        [']'] | ['['] => Parse::Incomplete,
+        [']', ']'] => Parse::Complete(Action::Move(Motion::HeadingForward)),
+        ['[', '['] => Parse::Complete(Action::Move(Motion::HeadingBackward)),
+        [key] if FindKind::from_key(*key).is_some() => Parse::Incomplete,
+        [key] => match Motion::from_key(*key) {
+            Some(motion) => Parse::Complete(Action::Move(motion)),
+            None => Parse::Invalid,
+        },
+        [key, ch] => match FindKind::from_key(*key) {
+            Some(kind) => Parse::Complete(Action::Move(Motion::FindChar(CharFind { kind, ch: *ch }))),
+            None => Parse::Invalid,
+        },
+        _ => Parse::Invalid,
+    }
+}
+
+// Where a character search from column x lands on 'line', if anywhere.
+// When 'repeat' is set (; and ,) a till search that is already parked
+// next to its character looks past it instead of standing still.
+pub fn find_char(line: &[char], x: usize, find: CharFind, repeat: bool) -> Option<usize> {
+    match find.kind {
+        FindKind::Forward | FindKind::TillForward => {
+            let till = find.kind == FindKind::TillForward;
+            let start = x + if till && repeat { 2 } else { 1 };
+            let found = (start..line.len()).find(|&i| line[i] == find.ch)?;
+            Some(if till { found - 1 } else { found })
+        }
+        FindKind::Backward | FindKind::TillBackward => {
+            let till = find.kind == FindKind::TillBackward;
+            let end = if till && repeat { x.saturating_sub(1) } else { x };
+            let found = (0..end).rev().find(|&i| line[i] == find.ch)?;
+            Some(if till { found + 1 } else { found })
+        }
+    }
+}
+
+// w - start of the next word, moving to the next line at the end of one
+pub fn word_forward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    let line = &lines[pos.y];
+    let mut x = pos.x;
+    // Skip current word
+    while x < line.len() && line[x].is_alphanumeric() {
+        x += 1;
+    }
+    // Skip spaces and punctuation
+    while x < line.len() && !line[x].is_alphanumeric() {
+        x += 1;
+    }
+
+    if x < line.len() {
+        Pos::new(x, pos.y)
+    } else if pos.y + 1 < lines.len() {
+        Pos::new(0, pos.y + 1)
+    } else {
+        pos
+    }
+}
+
+// b - start of the previous word, moving to the previous line at column 0
+pub fn word_backward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    if pos.x == 0 {
+        if pos.y == 0 {
+            return pos;
+        }
+        let above = lines[pos.y - 1].len();
+        return Pos::new(above.saturating_sub(1), pos.y - 1);
+    }
+
+    let line = &lines[pos.y];
+    let mut x = pos.x - 1;
+    // Skip spaces
+    while x > 0 && !line[x].is_alphanumeric() {
+        x -= 1;
+    }
+    // Skip word
+    while x > 0 && line[x - 1].is_alphanumeric() {
+        x -= 1;
+    }
+    Pos::new(x, pos.y)
+}
+
+// e - last character of the current or next word
+pub fn word_end(lines: &[Vec<char>], pos: Pos) -> Pos {
+    let line = &lines[pos.y];
+    let mut x = pos.x + 1;
+    // Skip whatever separates us from the next word
+    while x < line.len() && !line[x].is_alphanumeric() {
+        x += 1;
+    }
+    if x >= line.len() {
+        return if pos.y + 1 < lines.len() { Pos::new(0, pos.y + 1) } else { pos };
+    }
+    while x + 1 < line.len() && line[x + 1].is_alphanumeric() {
+        x += 1;
+    }
+    Pos::new(x, pos.y)
+}
+
+fn is_blank(line: &[char]) -> bool {
+    line.iter().all(|c| c.is_whitespace())
+}
+
+// Every position where a sentence begins, in order. A sentence starts:
+// - at the first non-blank character of the text or of a paragraph
+// - after '.', '!' or '?' (plus any closing quotes/brackets) and whitespace
+// - on an empty line (vim treats paragraph breaks as sentence boundaries)
+fn sentence_starts(lines: &[Vec<char>]) -> Vec<Pos> {
+    let mut starts = Vec::new();
+    let mut at_boundary = true; // Next non-blank character starts a sentence
+    let mut after_terminator = false; // Just saw . ! ? (and closers)
+
+    for (y, line) in lines.iter().enumerate() {
+        if is_blank(line) {
+            starts.push(Pos::new(0, y));
+            at_boundary = true;
+            after_terminator = false;
+            continue;
+        }
+        for (x, &c) in line.iter().enumerate() {
+            if c.is_whitespace() {
+                if after_terminator {
+                    at_boundary = true;
+                    after_terminator = false;
+                }
+                continue;
+            }
+            if at_boundary {
+                starts.push(Pos::new(x, y));
+                at_boundary = false;
+            }
+            if matches!(c, '.' | '!' | '?') {
+                after_terminator = true;
+            } else if !matches!(c, ')' | ']' | '"' | '\'' | '”' | '’') {
+                after_terminator = false;
+            }
+        }
+        // A line break counts as whitespace
+        if after_terminator {
+            at_boundary = true;
+            after_terminator = false;
+        }
+    }
+    starts
+}
+
+// Every sentence as a (start, end) pair, end exclusive and without the
+// trailing whitespace. Blank lines aren't sentences.
+pub fn sentences(lines: &[Vec<char>]) -> Vec<(Pos, Pos)> {
+    let starts = sentence_starts(lines);
+    starts
+        .iter()
+        .enumerate()
+        .filter(|(_, start)| !is_blank(&lines[start.y]))
+        .map(|(i, &start)| {
+            let next = starts.get(i + 1).copied().unwrap_or_else(|| end_of_text(lines));
+            (start, trim_end(lines, start, next))
+        })
+        .collect()
+}
+
+// ) - start of the next sentence, or the end of the text
+pub fn sentence_forward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    sentence_starts(lines)
+        .into_iter()
+        .find(|&start| start > pos)
+        .unwrap_or_else(|| end_of_text(lines))
+}
+
+// ( - start of this sentence, or the previous one if already there
+pub fn sentence_backward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    sentence_starts(lines)
+        .into_iter()
+        .rev()
+        .find(|&start| start < pos)
+        .unwrap_or(Pos::new(0, 0))
+}
+
+// } - the blank line after this paragraph (or the end of the text)
+pub fn paragraph_forward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    let mut y = pos.y;
+    // Blank lines we're already sitting on don't count
+    while y + 1 < lines.len() && is_blank(&lines[y + 1]) && is_blank(&lines[y]) {
+        y += 1;
+    }
+    for (i, line) in lines.iter().enumerate().skip(y + 1) {
+        if is_blank(line) {
+            return Pos::new(0, i);
+        }
+    }
+    end_of_text(lines)
+}
+
+// ]] - the next markdown heading (or the end of the text)
+pub fn heading_forward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    markdown::headings(lines)
+        .into_iter()
+        .find(|&(y, _)| y > pos.y)
+        .map(|(y, _)| Pos::new(0, y))
+        .unwrap_or_else(|| end_of_text(lines))
+}
+
+// [[ - the heading before the cursor's line (or the start of the text)
+pub fn heading_backward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    markdown::headings(lines)
+        .into_iter()
+        .rev()
+        .find(|&(y, _)| y < pos.y)
+        .map(|(y, _)| Pos::new(0, y))
+        .unwrap_or(Pos::new(0, 0))
+}
+
+// { - the blank line before this paragraph (or the start of the text)
+pub fn paragraph_backward(lines: &[Vec<char>], pos: Pos) -> Pos {
+    let mut y = pos.y;
+    while y > 0 && is_blank(&lines[y - 1]) && is_blank(&lines[y]) {
+        y -= 1;
+    }
+    (0..y)
+        .rev()
+        .find(|&i| is_blank(&lines[i]))
+        .map(|i| Pos::new(0, i))
+        .unwrap_or(Pos::new(0, 0))
+}
+
+// The span a text object covers around 'pos', if there is one
+pub fn select_object(lines: &[Vec<char>], pos: Pos, object: TextObject) -> Option<Span> {
+    match object.kind {
+        ObjectKind::Word => Some(word_object(&lines[pos.y], pos, object.around)),
+        ObjectKind::Sentence => Some(sentence_object(lines, pos, object.around)),
+        ObjectKind::Paragraph => Some(paragraph_object(lines, pos.y, object.around)),
+        ObjectKind::Quote => quote_object(lines, pos, object.around),
+        ObjectKind::Parens => delimited_object(lines, pos, '(', ')', object.around),
+    }
+}
+
+// Letters/digits, whitespace and punctuation each form their own runs
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() {
+        1
+    } else {
+        2
+    }
+}
+
+// iw is the run of similar characters under the cursor; aw adds the
+// whitespace after it (or before it, at the end of a line)
+fn word_object(line: &[char], pos: Pos, around: bool) -> Span {
+    if line.is_empty() {
+        return Span::Chars(pos, pos);
+    }
+    let x = pos.x.min(line.len() - 1);
+    let class = char_class(line[x]);
+    let mut start = x;
+    while start > 0 && char_class(line[start - 1]) == class {
+        start -= 1;
+    }
+    let mut end = x + 1;
+    while end < line.len() && char_class(line[end]) == class {
+        end += 1;
+    }
+
+    if around && class != 0 {
+        let trailing = end;
+        while end < line.len() && line[end].is_whitespace() {
+            end += 1;
+        }
+        if end == trailing {
+            while start > 0 && line[start - 1].is_whitespace() {
+                start -= 1;
+            }
+        }
+    }
+    Span::Chars(Pos::new(start, pos.y), Pos::new(end, pos.y))
+}
+
+// The position before 'pos', stepping back over line breaks
+fn step_back(lines: &[Vec<char>], pos: Pos) -> Option<Pos> {
+    if pos.x > 0 {
+        Some(Pos::new(pos.x - 1, pos.y))
+    } else if pos.y > 0 {
+        Some(Pos::new(lines[pos.y - 1].len(), pos.y - 1))
+    } else {
+        None
+    }
+}
+
+// Pull 'end' back over whitespace and line breaks, stopping at 'start'
+fn trim_end(lines: &[Vec<char>], start: Pos, mut end: Pos) -> Pos {
+    while end > start {
+        let Some(prev) = step_back(lines, end) else { break };
+        if prev.y == end.y && !lines[prev.y][prev.x].is_whitespace() {
+            break;
+        }
+        end = prev;
+    }
+    end
+}
+
+// is runs from the start of the sentence to its last non-blank character;
+// as also takes the spaces before the next sentence on the same paragraph
+fn sentence_object(lines: &[Vec<char>], pos: Pos, around: bool) -> Span {
+    let starts = sentence_starts(lines);
+    let start = starts.iter().rev().find(|&&s| s <= pos).copied().unwrap_or(Pos::new(0, 0));
+    let next = starts.iter().find(|&&s| s > pos).copied().unwrap_or_else(|| end_of_text(lines));
+
+    // A paragraph break isn't part of the sentence before it
+    let paragraph_break = next.x == 0 && is_blank(&lines[next.y]);
+    let end = if around && !paragraph_break { next } else { trim_end(lines, start, next) };
+    Span::Chars(start, end)
+}
+
+// ip is the block of non-blank lines around the cursor (or the block of
+// blank lines when on one); ap adds the blank lines that follow it
+fn paragraph_object(lines: &[Vec<char>], y: usize, around: bool) -> Span {
+    let blank = is_blank(&lines[y]);
+    let mut first = y;
+    while first > 0 && is_blank(&lines[first - 1]) == blank {
+        first -= 1;
+    }
+    let mut last = y;
+    while last + 1 < lines.len() && is_blank(&lines[last + 1]) == blank {
+        last += 1;
+    }
+
+    if around {
+        let before = last;
+        while last + 1 < lines.len() && is_blank(&lines[last + 1]) != blank {
+            last += 1;
+        }
+        // Nothing after the last paragraph, so take the blank lines before it
+        if last == before {
+            while first > 0 && is_blank(&lines[first - 1]) != blank {
+                first -= 1;
+            }
+        }
+    }
+    Span::Lines(first, last)
+}
+
+// i" / a" on the current line. Straight quotes pair up from the start of
+// the line; if the cursor isn't inside a pair the next pair is used.
+fn quote_object(lines: &[Vec<char>], pos: Pos, around: bool) -> Option<Span> {
+    let line = &lines[pos.y];
+    if let Some(span) = delimited_object(lines, pos, '“', '”', around) {
+        return Some(span);
+    }
+
+    let quotes: Vec<usize> = (0..line.len()).filter(|&i| line[i] == '"').collect();
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, close)| close >= pos.x)?;
+
+    if !around {
+        return Some(Span::Chars(Pos::new(open + 1, pos.y), Pos::new(close, pos.y)));
+    }
+    // Like aw, a" takes trailing whitespace, or leading if there is none
+    let mut start = open;
+    let mut end = close + 1;
+    while end < line.len() && line[end].is_whitespace() {
+        end += 1;
+    }
+    if end == close + 1 {
+        while start > 0 && line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+    }
+    Some(Span::Chars(Pos::new(start, pos.y), Pos::new(end, pos.y)))
+}
+
+// The next position after 'pos', stepping over line breaks
+fn step_forward(lines: &[Vec<char>], pos: Pos) -> Option<Pos> {
+    if pos.x < lines[pos.y].len() {
+        Some(Pos::new(pos.x + 1, pos.y))
+    } else if pos.y + 1 < lines.len() {
+        Some(Pos::new(0, pos.y + 1))
+    } else {
+        None
+    }
+}
+
+// i( / a( and curly quotes: the innermost open/close pair around the
+// cursor, which may span several lines
+fn delimited_object(lines: &[Vec<char>], pos: Pos, open: char, close: char, around: bool) -> Option<Span> {
+    let char_at = |p: Pos| lines[p.y].get(p.x).copied();
+
+    // Walk back to the unmatched opening delimiter
+    let mut depth = 0;
+    let mut start = pos;
+    loop {
+        match char_at(start) {
+            Some(c) if c == open && depth == 0 => break,
+            Some(c) if c == open => depth -= 1,
+            // The closing delimiter under the cursor belongs to the pair we want
+            Some(c) if c == close && start != pos => depth += 1,
+            _ => {}
+        }
+        start = step_back(lines, start)?;
+    }
+
+    // Then forward to its partner
+    let mut depth = 0;
+    let mut end = step_forward(lines, start)?;
+    loop {
+        match char_at(end) {
+            Some(c) if c == close && depth == 0 => break,
+            Some(c) if c == close => depth -= 1,
+            Some(c) if c == open => depth += 1,
+            _ => {}
+        }
+        end = step_forward(lines, end)?;
+    }
+
+    if around {
+        Some(Span::Chars(start, Pos::new(end.x + 1, end.y)))
+    } else {
+        Some(Span::Chars(step_forward(lines, start)?, end))
+    }
+}
+
+// The position just after the last character
+pub fn end_of_text(lines: &[Vec<char>]) -> Pos {
+    let y = lines.len() - 1;
+    Pos::new(lines[y].len(), y)
+}
@@ -0,0 +1,184 @@
+// How many terminal columns text takes up
+//
+// Most characters take one column, but Chinese, Japanese and Korean
+// characters (and most emoji) take two, and combining marks - the accent
+// in an "e" followed by U+0301, Hebrew and Arabic vowel points - take none,
+// sitting on the character before. Layout that counted characters would
+// put the cursor in the wrong place and run lines off the edge.
+//
+// The tables are the common ranges rather than all of Unicode's East
+// Asian Width data, which is near enough for text people write.
+
+// (first, last) code points, in order
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),   // Hangul Jamo initial consonants
+    (0x231A, 0x231B),   // Watch, hourglass
+    (0x2329, 0x232A),   // Angle brackets
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),   // Zodiac signs
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),   // CJK radicals, punctuation
+    (0x3041, 0x33FF),   // Hiragana, Katakana, Bopomofo, compatibility
+    (0x3400, 0x4DBF),   // CJK extension A
+    (0x4E00, 0x9FFF),   // CJK unified ideographs
+    (0xA000, 0xA4CF),   // Yi
+    (0xA960, 0xA97F),   // Hangul Jamo extended
+    (0xAC00, 0xD7A3),   // Hangul syllables
+    (0xF900, 0xFAFF),   // CJK compatibility ideographs
+    (0xFE10, 0xFE19),   // Vertical forms
+    (0xFE30, 0xFE6F),   // CJK compatibility forms, small forms
+    (0xFF00, 0xFF60),   // Fullwidth forms
+    (0xFFE0, 0xFFE6),
+    (0x16FE0, 0x18CFF), // Tangut
+    (0x1B000, 0x1B2FF), // Kana supplement
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F200, 0x1F251),
+    (0x1F300, 0x1F64F), // Emoji: pictographs, emoticons
+    (0x1F680, 0x1F6FF), // Transport and map symbols
+    (0x1F7E0, 0x1F7EB),
+    (0x1F900, 0x1F9FF), // Supplemental symbols and pictographs
+    (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD), // CJK extensions B onwards
+    (0x30000, 0x3FFFD),
+];
+
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036F),   // Combining diacritical marks
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),   // Hebrew points
+    (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2),
+    (0x05C4, 0x05C5),
+    (0x05C7, 0x05C7),
+    (0x0610, 0x061A),   // Arabic marks
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06DC),
+    (0x06DF, 0x06E4),
+    (0x06E7, 0x06E8),
+    (0x06EA, 0x06ED),
+    (0x0900, 0x0902),   // Devanagari signs
+    (0x093A, 0x093A),
+    (0x093C, 0x093C),
+    (0x0941, 0x0948),
+    (0x094D, 0x094D),
+    (0x0951, 0x0957),
+    (0x0E31, 0x0E31),   // Thai vowels and tone marks
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1160, 0x11FF),   // Hangul Jamo vowels and finals
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),   // Zero-width space and joiners, direction marks
+    (0x202A, 0x202E),
+    (0x2060, 0x2064),
+    (0x20D0, 0x20FF),   // Combining marks for symbols
+    (0x302A, 0x302D),
+    (0x3099, 0x309A),   // Kana voicing marks
+    (0xFE00, 0xFE0F),   // Variation selectors
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    (0xE0100, 0xE01EF),
+];
+
+pub fn char_width(c: char) -> usize {
+    if in_table(ZERO, c) {
+        0
+    } else if in_table(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+pub fn str_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+// As much of 'text' as fits in 'columns'
+pub fn truncate(text: &str, columns: usize) -> String {
+    let mut used = 0;
+    let mut fitted = String::new();
+    for c in text.chars() {
+        used += char_width(c);
+        if used > columns {
+            break;
+        }
+        fitted.push(c);
+    }
+    fitted
+}
+
+// 'text' cut or padded with spaces to exactly 'columns' - what
+// format!("{:<width$}") does when every character is one column
+pub fn fit(text: &str, columns: usize) -> String {
+    let mut fitted = truncate(text, columns);
+    let used = str_width(&fitted);
+    fitted.push_str(&" ".repeat(columns - used));
+    fitted
+}
+
+// 'text' in pieces no wider than 'columns' (at least one character each)
+pub fn chunks(text: &[char], columns: usize) -> Vec<&[char]> {
+    let mut chunks = Vec::new();
+    let (mut start, mut used) = (0, 0);
+    for (i, &c) in text.iter().enumerate() {
+        let w = char_width(c);
+        if used + w > columns && i > start {
+            chunks.push(&text[start..i]);
+            start = i;
+            used = 0;
+        }
+        used += w;
+    }
+    if start < text.len() {
+        chunks.push(&text[start..]);
+    }
+    chunks
+}
+
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    // Everything in the tables is past plain ASCII and Latin-1
+    c >= 0x300 && table.binary_search_by(|&(first, last)| {
+        if last < c {
+            std::cmp::Ordering::Less
+        } else if first > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }).is_ok()
+}
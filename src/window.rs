@@ -0,0 +1,156 @@
+// Split windows: several views onto open buffers, arranged on screen by
+// a small layout tree.
+//
+// - Window: which buffer a view shows, plus its own cursor and scroll offset
+// - Layout: a tree of splits whose leaves are window indices
+// - Rect: a rectangle of terminal cells handed to each window when rendering
+//
+// As with buffers, the active window's cursor/offset live in the Editor's
+// own fields while it is focused and are copied back here on switch.
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Window {
+    pub buffer: usize, // Index into Editor::buffers
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub offset_x: usize,
+    pub offset_y: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitDirection {
+    Horizontal, // :split - windows stacked top to bottom
+    Vertical,   // :vsplit - windows side by side
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+// A window index with the area it was given, and a divider between windows
+pub type Placement = (usize, Rect);
+pub type Divider = (Rect, SplitDirection);
+
+// Box isn't needed here: a Vec already stores its items on the heap,
+// which is what lets an enum contain itself recursively
+#[derive(Debug, Clone)]
+pub enum Layout {
+    Window(usize),
+    Split(SplitDirection, Vec<Layout>),
+}
+
+impl Layout {
+    // Split the leaf showing 'target', placing 'new' after it.
+    // Returns false if 'target' isn't in this tree.
+    pub fn split(&mut self, target: usize, new: usize, direction: SplitDirection) -> bool {
+        match self {
+            Layout::Window(id) if *id == target => {
+                *self = Layout::Split(direction, vec![Layout::Window(target), Layout::Window(new)]);
+                true
+            }
+            Layout::Window(_) => false,
+            Layout::Split(dir, children) => {
+                // Splitting in the same direction adds a sibling instead of nesting
+                if *dir == direction {
+                    if let Some(pos) = children.iter().position(|c| matches!(c, Layout::Window(id) if *id == target)) {
+                        children.insert(pos + 1, Layout::Window(new));
+                        return true;
+                    }
+                }
+                children.iter_mut().any(|child| child.split(target, new, direction))
+            }
+        }
+    }
+
+    // Remove a window from the tree and renumber the ones after it
+    // (window indices shift down when the Vec entry is removed).
+    // Each leaf is renumbered once, when it's reached as a Window: a split
+    // collapsing into its only child hands back a child already renumbered.
+    pub fn remove(&mut self, target: usize) {
+        match self {
+            Layout::Window(id) => {
+                if *id > target {
+                    *id -= 1;
+                }
+            }
+            Layout::Split(_, children) => {
+                children.retain(|c| !matches!(c, Layout::Window(id) if *id == target));
+                for child in children.iter_mut() {
+                    child.remove(target);
+                }
+                // A split with a single child is just that child
+                if children.len() == 1 {
+                    *self = children.remove(0);
+                }
+            }
+        }
+    }
+
+    // Window indices in screen order (used for Ctrl-W cycling)
+    pub fn windows(&self) -> Vec<usize> {
+        match self {
+            Layout::Window(id) => vec![*id],
+            Layout::Split(_, children) => children.iter().flat_map(|c| c.windows()).collect(),
+        }
+    }
+
+    // Divide 'area' between the windows. Each split reserves one row or
+    // column between neighbours for a divider, reported in 'dividers'.
+    pub fn arrange(&self, area: Rect, windows: &mut Vec<Placement>, dividers: &mut Vec<Divider>) {
+        match self {
+            Layout::Window(id) => windows.push((*id, area)),
+            Layout::Split(direction, children) => {
+                let count = children.len() as u16;
+                let total = match direction {
+                    SplitDirection::Horizontal => area.height,
+                    SplitDirection::Vertical => area.width,
+                };
+                // Space left once the dividers are taken out, shared evenly;
+                // the last child absorbs any remainder
+                let usable = total.saturating_sub(count - 1);
+                let each = usable / count;
+                let mut start = 0;
+
+                for (i, child) in children.iter().enumerate() {
+                    let last = i as u16 == count - 1;
+                    let size = if last { usable - each * (count - 1) } else { each };
+                    let (child_area, divider) = match direction {
+                        SplitDirection::Horizontal => (
+                            Rect { x: area.x, y: area.y + start, width: area.width, height: size },
+                            Rect { x: area.x, y: area.y + start + size, width: area.width, height: 1 },
+                        ),
+                        SplitDirection::Vertical => (
+                            Rect { x: area.x + start, y: area.y, width: size, height: area.height },
+                            Rect { x: area.x + start + size, y: area.y, width: 1, height: area.height },
+                        ),
+                    };
+                    child.arrange(child_area, windows, dividers);
+                    if !last {
+                        dividers.push((divider, *direction));
+                    }
+                    start += size + 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closing_a_window_in_a_nested_split_renumbers_each_once() {
+        // Window 0 above window 2 on the left, window 1 on the right
+        let mut layout = Layout::Split(
+            SplitDirection::Vertical,
+            vec![Layout::Split(SplitDirection::Horizontal, vec![Layout::Window(0), Layout::Window(2)]), Layout::Window(1)],
+        );
+        layout.remove(0);
+        assert_eq!(layout.windows(), vec![1, 0]);
+    }
+}